@@ -0,0 +1,103 @@
+//! Checks a large generated songbook sequentially and in parallel.
+//!
+//! Run with `cargo bench -p chamber_analyzer --features parallel` so that
+//! `Analyzer::analyze_all` uses the thread pool.
+
+use chamber_analyzer::Analyzer;
+use chamber_parser::{parse_collection, TuneSource, TuneSources};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rayon::prelude::*;
+
+const TUNE_COUNT: usize = 2_000;
+
+/// Builds a collection of `count` reels with a few deliberate problems.
+fn songbook(count: usize) -> String {
+    const PHRASES: [&str; 6] = [
+        "GABc dedB | dedB dedB | c2ec B2dB | c2A2 A2BA |",
+        "GABc dedB | dedB dedB | c2ec B2dB | AGFG A2 :|",
+        "!trill!g2 ga gfed | (3efg fe d2 BA | [GBd]2 gf edcB | A4 G4 |]",
+        "\"G\"d2 Bd \"C\"c2 Ac | \"G\"B2 GB \"D\"A2 FA | G,4 {A}G4 | z8 |",
+        "e2 ef gfed | !trilx!B2 GB c'2 BA | d>e d<B A2 GA | B8 |",
+        "|: D2 FA dAFA | DFAF G2 FE | D2 FA d2 cB | ABAG FED2 :|",
+    ];
+
+    let mut out = String::new();
+    for i in 0..count {
+        out.push_str(&format!(
+            "X:{}\nT:Reel No. {}\nM:4/4\nL:1/8\nK:{}\n",
+            i + 1,
+            i + 1,
+            ["G", "D", "Ador", "Em"][i % 4]
+        ));
+        for line in 0..8 {
+            out.push_str(PHRASES[(i + line) % PHRASES.len()]);
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn check_sequential(analyzer: &Analyzer, sources: &[TuneSource]) -> usize {
+    sources
+        .iter()
+        .map(|source| {
+            let tune = source.clone().parse();
+            tune.diagnostics.len() + analyzer.analyze(&tune.tune).diagnostics.len()
+        })
+        .sum()
+}
+
+fn check_parallel(analyzer: &Analyzer, sources: &[TuneSource]) -> usize {
+    sources
+        .par_iter()
+        .map(|source| {
+            let tune = source.clone().parse();
+            tune.diagnostics.len() + analyzer.analyze(&tune.tune).diagnostics.len()
+        })
+        .sum()
+}
+
+fn bench_check(c: &mut Criterion) {
+    let text = songbook(TUNE_COUNT);
+    let sources: Vec<_> = TuneSources::new(text.as_bytes())
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let analyzer = Analyzer::new();
+
+    let mut group = c.benchmark_group("check");
+    group.throughput(Throughput::Bytes(text.len() as u64));
+    group.sample_size(10);
+    group.bench_function(BenchmarkId::new("sequential", TUNE_COUNT), |b| {
+        b.iter(|| check_sequential(&analyzer, &sources))
+    });
+    group.bench_function(BenchmarkId::new("parallel", TUNE_COUNT), |b| {
+        b.iter(|| check_parallel(&analyzer, &sources))
+    });
+    group.finish();
+}
+
+fn bench_analyze(c: &mut Criterion) {
+    let text = songbook(TUNE_COUNT);
+    let tunes: Vec<_> = parse_collection(&text).map(|t| t.tune).collect();
+    let analyzer = Analyzer::new();
+
+    let mut group = c.benchmark_group("analyze");
+    group.throughput(Throughput::Elements(tunes.len() as u64));
+    group.sample_size(10);
+    group.bench_function(BenchmarkId::new("each", TUNE_COUNT), |b| {
+        b.iter(|| {
+            tunes
+                .iter()
+                .map(|tune| analyzer.analyze(tune))
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function(BenchmarkId::new("analyze_all", TUNE_COUNT), |b| {
+        b.iter(|| analyzer.analyze_all(&tunes))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_check, bench_analyze);
+criterion_main!(benches);
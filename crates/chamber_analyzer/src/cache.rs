@@ -0,0 +1,264 @@
+//! Caching of per-tune results.
+//!
+//! A tune's diagnostics only depend on its text and on the analyzer's
+//! configuration, so they are cached under a hash of both. A cache is tied
+//! to the analyzer it was created for; results of other configurations (or
+//! other Chamber versions) are never returned.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::Hasher;
+use std::io::{Read, Write};
+use std::sync::{Mutex, MutexGuard};
+
+use chamber_diagnostics::Diagnostic;
+use serde::{Deserialize, Serialize};
+
+use crate::Analyzer;
+
+/// Per-tune diagnostics of an [`Analyzer`], keyed by the tune's text.
+///
+/// Shared between threads; lookups and inserts take `&self`.
+#[derive(Debug)]
+pub struct AnalysisCache {
+    /// Hash of the analyzer's configuration.
+    config: u64,
+    entries: Mutex<HashMap<u64, Entry>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    diagnostics: Vec<Diagnostic>,
+    /// Whether the entry was looked up or inserted since the last
+    /// [`AnalysisCache::prune`].
+    used: bool,
+}
+
+/// The cache as it is saved.
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    /// Diagnostics keyed by the hex hash of tune and configuration.
+    entries: BTreeMap<String, Vec<Diagnostic>>,
+}
+
+impl AnalysisCache {
+    /// Creates an empty cache for the results of `analyzer`.
+    ///
+    /// Create a new cache if the analyzer's configuration changes.
+    pub fn new(analyzer: &Analyzer) -> Self {
+        Self {
+            config: config_hash(analyzer),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads a cache written by [`AnalysisCache::save`].
+    ///
+    /// Entries of other configurations are kept but never match; they are
+    /// dropped by [`AnalysisCache::prune`].
+    pub fn load(analyzer: &Analyzer, reader: impl Read) -> Result<Self, CacheError> {
+        let file: CacheFile = serde_json::from_reader(reader).map_err(CacheError)?;
+        let mut entries = HashMap::new();
+        for (key, diagnostics) in file.entries {
+            let key = u64::from_str_radix(&key, 16)
+                .map_err(|_| CacheError(serde::de::Error::custom("invalid cache key")))?;
+            let used = false;
+            entries.insert(key, Entry { diagnostics, used });
+        }
+        Ok(Self {
+            config: config_hash(analyzer),
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Writes the cache as JSON.
+    pub fn save(&self, mut writer: impl Write) -> Result<(), CacheError> {
+        let entries = self
+            .lock()
+            .iter()
+            .map(|(key, entry)| (format!("{:016x}", key), entry.diagnostics.clone()))
+            .collect();
+        serde_json::to_writer(&mut writer, &CacheFile { entries }).map_err(CacheError)?;
+        writer
+            .flush()
+            .map_err(|e| CacheError(serde_json::Error::io(e)))
+    }
+
+    /// Returns the cached diagnostics of the tune with text `source`.
+    pub fn get(&self, source: &str) -> Option<Vec<Diagnostic>> {
+        let key = self.key(source);
+        let mut entries = self.lock();
+        let entry = entries.get_mut(&key)?;
+        entry.used = true;
+        Some(entry.diagnostics.clone())
+    }
+
+    /// Caches the diagnostics of the tune with text `source`.
+    pub fn insert(&self, source: &str, diagnostics: Vec<Diagnostic>) {
+        let key = self.key(source);
+        let used = true;
+        self.lock().insert(key, Entry { diagnostics, used });
+    }
+
+    /// Drops the entries that were not looked up or inserted since the last
+    /// prune (or since the cache was created or loaded), such as those of
+    /// tunes that have changed.
+    pub fn prune(&self) {
+        self.lock()
+            .retain(|_, entry| std::mem::take(&mut entry.used));
+    }
+
+    /// Returns the number of cached tunes.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns true if no tunes are cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn key(&self, source: &str) -> u64 {
+        let mut hasher = StableHasher::new();
+        hasher.write_u64(self.config);
+        hasher.write(source.as_bytes());
+        hasher.finish()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<u64, Entry>> {
+        // Entries are replaced whole, so a panic elsewhere cannot leave one
+        // half-written
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Hashes what the diagnostics of a tune depend on besides its text: the
+/// Chamber version and the analyzer's rules, levels and options.
+///
+/// The code of rules registered with [`Analyzer::with_rule`] is not covered;
+/// rename such a rule (or start a new cache) when its behavior changes.
+fn config_hash(analyzer: &Analyzer) -> u64 {
+    let mut hasher = StableHasher::new();
+    write_str(&mut hasher, env!("CARGO_PKG_VERSION"));
+    for rule in analyzer.registry().iter() {
+        let name = rule.info.name;
+        write_str(&mut hasher, name);
+        write_str(&mut hasher, rule.info.code.code());
+        if let Some(level) = analyzer.rule_level(name) {
+            write_str(&mut hasher, level.as_str());
+        }
+        if let Some(options) = analyzer.rule_options(name) {
+            write_str(&mut hasher, &options.to_string());
+        }
+    }
+    hasher.finish()
+}
+
+fn write_str(hasher: &mut StableHasher, s: &str) {
+    hasher.write(s.as_bytes());
+    hasher.write_u8(0xff);
+}
+
+/// 64-bit FNV-1a.
+///
+/// Unlike `DefaultHasher`, its output is the same in every Rust release, so
+/// keys stay valid in a cache on disk.
+struct StableHasher(u64);
+
+impl StableHasher {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// An error reading or writing an [`AnalysisCache`].
+#[derive(Debug)]
+pub struct CacheError(serde_json::Error);
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid analysis cache: {}", self.0)
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AnalyzerConfig, RuleLevel};
+    use chamber_diagnostics::DiagnosticCode;
+
+    fn warning() -> Diagnostic {
+        let range = chamber_text_size::TextRange::default();
+        Diagnostic::warning(DiagnosticCode::BarLengthMismatch, range, "cached")
+    }
+
+    #[test]
+    fn test_get_and_insert() {
+        let cache = AnalysisCache::new(&Analyzer::new());
+        assert!(cache.get("X:1\nK:C\nCDEF|\n").is_none());
+        cache.insert("X:1\nK:C\nCDEF|\n", vec![warning()]);
+        assert_eq!(cache.get("X:1\nK:C\nCDEF|\n"), Some(vec![warning()]));
+        assert!(cache.get("X:1\nK:C\nCDEG|\n").is_none());
+    }
+
+    #[test]
+    fn test_config_changes_key() {
+        let config = AnalyzerConfig::new().with_rule("barLength", RuleLevel::Off);
+        let analyzer = Analyzer::new().with_config(config).unwrap();
+        let mut saved = Vec::new();
+        let cache = AnalysisCache::new(&Analyzer::new());
+        cache.insert("X:1\nK:C\n", vec![warning()]);
+        cache.save(&mut saved).unwrap();
+
+        let same = AnalysisCache::load(&Analyzer::new(), saved.as_slice()).unwrap();
+        assert_eq!(same.get("X:1\nK:C\n"), Some(vec![warning()]));
+        let other = AnalysisCache::load(&analyzer, saved.as_slice()).unwrap();
+        assert!(other.get("X:1\nK:C\n").is_none());
+
+        // Lint rules off is another configuration too
+        let cache = AnalysisCache::load(&Analyzer::new().without_lint(), saved.as_slice()).unwrap();
+        assert!(cache.get("X:1\nK:C\n").is_none());
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut saved = Vec::new();
+        let cache = AnalysisCache::new(&Analyzer::new());
+        cache.insert("X:1\nK:C\n", Vec::new());
+        cache.insert("X:2\nK:C\n", Vec::new());
+        cache.save(&mut saved).unwrap();
+
+        let cache = AnalysisCache::load(&Analyzer::new(), saved.as_slice()).unwrap();
+        assert!(cache.get("X:2\nK:C\n").is_some());
+        cache.prune();
+        assert_eq!(cache.len(), 1);
+        cache.prune();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_load_invalid() {
+        assert!(AnalysisCache::load(&Analyzer::new(), "[]".as_bytes()).is_err());
+        let bad_key = r#"{ "entries": { "xyz": [] } }"#;
+        assert!(AnalysisCache::load(&Analyzer::new(), bad_key.as_bytes()).is_err());
+    }
+}
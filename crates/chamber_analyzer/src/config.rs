@@ -0,0 +1,160 @@
+//! Analyzer configuration: per-rule levels and options.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chamber_diagnostics::Severity;
+use serde::{Deserialize, Serialize};
+
+use crate::registry::{ResolvedOptions, RuleRegistry};
+
+/// How a rule is reported: turned off, or at a fixed severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleLevel {
+    /// The rule does not run.
+    Off,
+    /// Report as info.
+    Info,
+    /// Report as a warning.
+    #[serde(alias = "warning")]
+    Warn,
+    /// Report as an error.
+    Error,
+}
+
+impl RuleLevel {
+    /// Returns the name used in configuration files (e.g., "warn").
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RuleLevel::Off => "off",
+            RuleLevel::Info => "info",
+            RuleLevel::Warn => "warn",
+            RuleLevel::Error => "error",
+        }
+    }
+
+    /// Returns the severity to report at, or `None` if the rule is off.
+    pub fn severity(self) -> Option<Severity> {
+        match self {
+            RuleLevel::Off => None,
+            RuleLevel::Info => Some(Severity::Info),
+            RuleLevel::Warn => Some(Severity::Warning),
+            RuleLevel::Error => Some(Severity::Error),
+        }
+    }
+}
+
+/// Per-rule configuration, keyed by rule name.
+///
+/// Rules without an entry run at their default severity, and rules without
+/// options use their defaults.
+///
+/// ```json
+/// {
+///   "rules": { "unusualOctave": "off", "barLength": "error" },
+///   "options": { "suspiciousDuration": { "threshold": 32 } }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalyzerConfig {
+    /// Level overrides, keyed by rule name (e.g., "unknownDecoration").
+    pub rules: BTreeMap<String, RuleLevel>,
+    /// Rule options, keyed by rule name. Each value must match the rule's
+    /// options type (see [`Rule::Options`](crate::Rule::Options)).
+    pub options: BTreeMap<String, serde_json::Value>,
+}
+
+impl AnalyzerConfig {
+    /// Creates an empty configuration (all rules at their defaults).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the level of a rule.
+    pub fn with_rule(mut self, name: impl Into<String>, level: RuleLevel) -> Self {
+        self.rules.insert(name.into(), level);
+        self
+    }
+
+    /// Sets the options of a rule.
+    ///
+    /// ```
+    /// use chamber_analyzer::{AnalyzerConfig, UnusualOctaveOptions};
+    ///
+    /// let config = AnalyzerConfig::new().with_options(
+    ///     "unusualOctave",
+    ///     UnusualOctaveOptions { lowest: -4, highest: 3 },
+    /// );
+    /// assert_eq!(config.options["unusualOctave"]["lowest"], -4);
+    /// ```
+    pub fn with_options(mut self, name: impl Into<String>, options: impl Serialize) -> Self {
+        let options = serde_json::to_value(options).expect("rule options serialize to JSON");
+        self.options.insert(name.into(), options);
+        self
+    }
+
+    /// Returns the configured level of a rule, if any.
+    pub fn level(&self, name: &str) -> Option<RuleLevel> {
+        self.rules.get(name).copied()
+    }
+
+    /// Checks that every configured rule exists in `registry` and that its
+    /// options are valid.
+    pub fn validate(&self, registry: &RuleRegistry) -> Result<(), ConfigError> {
+        self.resolve_options(registry).map(|_| ())
+    }
+
+    /// Reads the options of each configured rule into the rule's options type.
+    pub(crate) fn resolve_options(
+        &self,
+        registry: &RuleRegistry,
+    ) -> Result<BTreeMap<&'static str, ResolvedOptions>, ConfigError> {
+        if let Some(name) = self.rules.keys().find(|name| registry.get(name).is_none()) {
+            return Err(ConfigError::UnknownRule(name.clone()));
+        }
+
+        let mut resolved = BTreeMap::new();
+        for (name, value) in &self.options {
+            let rule = registry
+                .get(name)
+                .ok_or_else(|| ConfigError::UnknownRule(name.clone()))?;
+            let options =
+                rule.resolve_options(value)
+                    .map_err(|error| ConfigError::InvalidOptions {
+                        rule: name.clone(),
+                        message: error.to_string(),
+                    })?;
+            resolved.insert(rule.info.name, options);
+        }
+        Ok(resolved)
+    }
+}
+
+/// Error in an analyzer configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// A rule name that is not in the registry.
+    UnknownRule(String),
+    /// Options that do not match the rule's options type.
+    InvalidOptions {
+        /// The rule name.
+        rule: String,
+        /// What is wrong with the options.
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::UnknownRule(name) => write!(f, "unknown rule '{}'", name),
+            ConfigError::InvalidOptions { rule, message } => {
+                write!(f, "invalid options for rule '{}': {}", rule, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
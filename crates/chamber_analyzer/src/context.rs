@@ -0,0 +1,222 @@
+//! What a rule knows about the tune it checks.
+
+use std::cell::OnceCell;
+
+use chamber_ast::{Header, HeaderFieldKind, Tune};
+use chamber_cst::CstNode;
+use chamber_diagnostics::LineIndex;
+use chamber_text_size::TextSize;
+
+use crate::model::{PitchModel, TimingModel};
+
+/// The values of the tune's header fields, as written.
+///
+/// A field given more than once takes its last value, except for the title,
+/// which is the first `T:` field (later ones are subtitles).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderValues<'a> {
+    /// `X:` reference number.
+    pub reference_number: Option<&'a str>,
+    /// First `T:` title.
+    pub title: Option<&'a str>,
+    /// `C:` composer.
+    pub composer: Option<&'a str>,
+    /// `M:` meter.
+    pub meter: Option<&'a str>,
+    /// `L:` unit note length.
+    pub unit_note_length: Option<&'a str>,
+    /// `Q:` tempo.
+    pub tempo: Option<&'a str>,
+    /// `K:` key.
+    pub key: Option<&'a str>,
+}
+
+impl<'a> HeaderValues<'a> {
+    /// Reads the values of `header`.
+    pub fn new(header: &'a Header) -> Self {
+        let mut values = Self::default();
+        for field in &header.fields {
+            let value = Some(field.value.trim());
+            match field.kind {
+                HeaderFieldKind::ReferenceNumber => values.reference_number = value,
+                HeaderFieldKind::Title => values.title = values.title.or(value),
+                HeaderFieldKind::Composer => values.composer = value,
+                HeaderFieldKind::Meter => values.meter = value,
+                HeaderFieldKind::UnitNoteLength => values.unit_note_length = value,
+                HeaderFieldKind::Tempo => values.tempo = value,
+                HeaderFieldKind::Key => values.key = value,
+                HeaderFieldKind::Other(_) => {}
+            }
+        }
+        values
+    }
+}
+
+/// What the analyzer knows about a tune, shared by all rules.
+#[derive(Debug)]
+pub(crate) struct TuneContext<'a> {
+    tune: &'a Tune,
+    source: Option<&'a str>,
+    /// Where the tune starts in its collection.
+    offset: TextSize,
+    /// Built on first use; most rules never need it.
+    line_index: OnceCell<LineIndex>,
+    /// Built on first use, once for all rules that need it.
+    cst: OnceCell<CstNode>,
+    header: HeaderValues<'a>,
+    timing: TimingModel,
+    pitch: PitchModel,
+}
+
+impl<'a> TuneContext<'a> {
+    /// Resolves the header of `tune`. `source` is the text it was parsed
+    /// from, if known.
+    pub(crate) fn new(tune: &'a Tune, source: Option<&'a str>) -> Self {
+        let header = HeaderValues::new(&tune.header);
+        Self {
+            tune,
+            source,
+            offset: TextSize::new(0),
+            line_index: OnceCell::new(),
+            cst: OnceCell::new(),
+            timing: TimingModel::new(header.meter, header.unit_note_length),
+            pitch: PitchModel::new(header.key),
+            header,
+        }
+    }
+
+    /// Sets where the tune starts in its collection.
+    pub(crate) fn with_offset(mut self, offset: TextSize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Returns the tune.
+    pub(crate) fn tune(&self) -> &'a Tune {
+        self.tune
+    }
+}
+
+/// The context a rule is created and finished with: the tune, its source,
+/// the resolved header, and the rule's options.
+///
+/// The models describe the header; changes made by inline fields in the
+/// body are seen while visiting (see
+/// [`TimingModel::apply_field`] and [`PitchModel::apply_field`]).
+#[derive(Debug)]
+pub struct RuleContext<'a, O> {
+    tune: &'a TuneContext<'a>,
+    options: &'a O,
+}
+
+impl<'a, O> RuleContext<'a, O> {
+    pub(crate) fn new(tune: &'a TuneContext<'a>, options: &'a O) -> Self {
+        Self { tune, options }
+    }
+
+    /// Returns the tune being checked.
+    pub fn tune(&self) -> &'a Tune {
+        self.tune.tune()
+    }
+
+    /// Returns the source text of the tune, if the analyzer was given it
+    /// (see [`Analyzer::analyze_source`](crate::Analyzer::analyze_source)).
+    pub fn source(&self) -> Option<&'a str> {
+        self.tune.source
+    }
+
+    /// Returns where the tune starts in its collection (0 for a tune
+    /// analyzed on its own).
+    ///
+    /// Ranges in the tune are relative to its own source; add the offset to
+    /// get a range in the collection, as collection rules report.
+    pub fn offset(&self) -> TextSize {
+        self.tune.offset
+    }
+
+    /// Returns the line index of the source text, if known.
+    pub fn line_index(&self) -> Option<&'a LineIndex> {
+        let source = self.tune.source?;
+        Some(self.tune.line_index.get_or_init(|| LineIndex::new(source)))
+    }
+
+    /// Returns the concrete syntax tree of the source text, if known, for
+    /// rules that need its whitespace and comments.
+    pub fn cst(&self) -> Option<&'a CstNode> {
+        let source = self.tune.source?;
+        Some(
+            self.tune
+                .cst
+                .get_or_init(|| chamber_parser::parse_cst(source)),
+        )
+    }
+
+    /// Returns the values of the header fields.
+    pub fn header(&self) -> &'a HeaderValues<'a> {
+        &self.tune.header
+    }
+
+    /// Returns the meter and unit note length set by the header.
+    pub fn timing(&self) -> &'a TimingModel {
+        &self.tune.timing
+    }
+
+    /// Returns the key set by the header.
+    pub fn pitch(&self) -> &'a PitchModel {
+        &self.tune.pitch
+    }
+
+    /// Returns the rule's options.
+    pub fn options(&self) -> &'a O {
+        self.options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Fraction;
+    use crate::rule::NoOptions;
+    use chamber_ast::{Accidental, Pitch};
+    use chamber_parser::parse;
+    use chamber_text_size::TextSize;
+
+    #[test]
+    fn test_header_values() {
+        let tune = parse("X:3\nT:Title\nT:Subtitle\nM:6/8\nM:9/8\nK:D\nABc");
+        let header = HeaderValues::new(&tune.header);
+        assert_eq!(header.reference_number, Some("3"));
+        assert_eq!(header.title, Some("Title"));
+        assert_eq!(header.meter, Some("9/8"));
+        assert_eq!(header.unit_note_length, None);
+        assert_eq!(header.key, Some("D"));
+    }
+
+    #[test]
+    fn test_rule_context() {
+        let source = "X:1\nM:2/4\nK:D\nABc";
+        let tune = parse(source);
+        let tune = TuneContext::new(&tune, Some(source));
+        let cx = RuleContext::new(&tune, &NoOptions {});
+
+        assert_eq!(cx.timing().meter(), Some(Fraction::new(2, 4)));
+        assert_eq!(cx.timing().unit_note_length(), Fraction::new(1, 16));
+        assert_eq!(cx.pitch().signature(Pitch::C), Some(Accidental::Sharp));
+        assert_eq!(cx.source(), Some(source));
+
+        let line_index = cx.line_index().unwrap();
+        assert_eq!(line_index.line_col(TextSize::new(14)).line, 3);
+        let cst = cx.cst().unwrap();
+        assert_eq!(cst.full_range().len(), TextSize::new(source.len() as u32));
+    }
+
+    #[test]
+    fn test_no_source() {
+        let tune = parse("X:1\nK:C\nC");
+        let tune = TuneContext::new(&tune, None);
+        let cx = RuleContext::new(&tune, &NoOptions {});
+        assert!(cx.source().is_none());
+        assert!(cx.line_index().is_none());
+        assert!(cx.cst().is_none());
+    }
+}
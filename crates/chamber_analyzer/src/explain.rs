@@ -0,0 +1,126 @@
+//! The `explain` catalogue: each diagnostic code's explanation together with
+//! the metadata of the rules that report it.
+
+use std::fmt::{self, Write};
+
+use chamber_diagnostics::DiagnosticCode;
+
+use crate::{Analyzer, Category, RuleLevel, Scope};
+
+/// Renders the explanation of `code` as Markdown.
+pub(crate) fn render(analyzer: &Analyzer, code: DiagnosticCode) -> String {
+    let mut out = String::new();
+    write_explanation(&mut out, analyzer, code).expect("writing to a String cannot fail");
+    out
+}
+
+fn write_explanation(out: &mut String, analyzer: &Analyzer, code: DiagnosticCode) -> fmt::Result {
+    let explanation = code.explanation();
+    writeln!(out, "# {}: {}", code, code.message_template())?;
+    writeln!(out)?;
+
+    let rules: Vec<_> = analyzer
+        .registry()
+        .iter()
+        .filter(|rule| rule.info.code == code)
+        .collect();
+    if rules.is_empty() {
+        writeln!(out, "Reported by the parser ({}).", code.default_severity())?;
+    }
+    for rule in rules {
+        let info = rule.info;
+        let category = match info.category {
+            Category::Lint => "lint",
+            Category::Style => "style",
+        };
+        let scope = match info.scope {
+            Scope::Tune => "",
+            Scope::Collection => ", collection",
+        };
+        let level = analyzer.rule_level(info.name).unwrap_or(RuleLevel::Error);
+        writeln!(
+            out,
+            "Reported by the `{}` rule ({}{}, {}): {}",
+            info.name,
+            category,
+            scope,
+            level.as_str(),
+            info.docs
+        )?;
+        if let Some(options) = analyzer
+            .rule_options(info.name)
+            .filter(|options| options.as_object().is_some_and(|o| !o.is_empty()))
+        {
+            writeln!(out, "Options: `{}`", options)?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{}", explanation.rationale)?;
+    writeln!(out)?;
+    writeln!(out, "Example:")?;
+    writeln!(out)?;
+    write_abc(out, explanation.bad)?;
+    writeln!(out)?;
+    writeln!(out, "Corrected:")?;
+    writeln!(out)?;
+    write_abc(out, explanation.good)?;
+    writeln!(out)?;
+    writeln!(out, "To fix: {}", explanation.fix)?;
+
+    if !explanation.related.is_empty() {
+        let related: Vec<_> = explanation
+            .related
+            .iter()
+            .map(|code| format!("{} ({})", code, code.message_template()))
+            .collect();
+        writeln!(out)?;
+        writeln!(out, "Related: {}", related.join(", "))?;
+    }
+    Ok(())
+}
+
+/// Writes `source` as a fenced ABC block.
+fn write_abc(out: &mut String, source: &str) -> fmt::Result {
+    writeln!(out, "```abc")?;
+    if source.is_empty() {
+        writeln!(out, "(empty file)")?;
+    } else {
+        write!(out, "{}", source)?;
+    }
+    writeln!(out, "```")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AnalyzerConfig;
+
+    #[test]
+    fn test_rule_code() {
+        let text = render(&Analyzer::new(), DiagnosticCode::BarLengthMismatch);
+        assert!(text.starts_with("# W003: bar length mismatch\n"));
+        assert!(text.contains("Reported by the `barLength` rule (lint, warn)"));
+        assert!(text.contains("```abc\nX:1\n"));
+        assert!(text.contains("Related: M012"));
+    }
+
+    #[test]
+    fn test_parser_code() {
+        let text = render(&Analyzer::new(), DiagnosticCode::UnclosedChord);
+        assert!(text.contains("Reported by the parser (error)."));
+    }
+
+    #[test]
+    fn test_configured_level_and_options() {
+        let config = AnalyzerConfig::new()
+            .with_rule("duplicateTitle", RuleLevel::Off)
+            .with_options("duplicateMelody", serde_json::json!({ "minNotes": 4 }));
+        let analyzer = Analyzer::new().with_config(config).unwrap();
+
+        let text = render(&analyzer, DiagnosticCode::DuplicateTitle);
+        assert!(text.contains("(lint, collection, off)"));
+        let text = render(&analyzer, DiagnosticCode::DuplicateMelody);
+        assert!(text.contains("Options: `{\"minNotes\":4}`"));
+    }
+}
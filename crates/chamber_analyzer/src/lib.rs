@@ -7,9 +7,22 @@
 //!
 //! The analyzer follows a rule-based architecture inspired by Biome:
 //!
-//! - Each rule implements the `Rule` trait
-//! - Rules are registered in the `Analyzer`
-//! - The analyzer runs all rules and collects diagnostics
+//! - Each rule implements the `Rule` trait and the AST `Visitor` methods it
+//!   needs
+//! - Rules are registered by name in a `RuleRegistry`
+//! - An `AnalyzerConfig` turns rules off, overrides their severity and
+//!   sets their options
+//! - Rules get a `RuleContext` with their options, the resolved header
+//!   (`TimingModel`, `PitchModel`) and, via `Analyzer::analyze_source`, the
+//!   source text
+//! - The analyzer walks each tune once, feeding every enabled rule, and
+//!   collects their diagnostics
+//! - An `AnalysisCache` keeps the diagnostics of each tune under a hash of
+//!   its text and the configuration, so unchanged tunes are not analyzed
+//!   again (see `Analyzer::analyze_cached`)
+//! - `CollectionRule`s compare the tunes of a multi-tune file; they see each
+//!   tune in turn through `Analyzer::collection` and report with labels at
+//!   the other occurrence
 //!
 //! # Example
 //!
@@ -24,16 +37,131 @@
 //!     println!("{}: {}", diagnostic.code, diagnostic.message);
 //! }
 //! ```
+//!
+//! # Configuration
+//!
+//! ```
+//! use chamber_analyzer::{Analyzer, AnalyzerConfig, RuleLevel};
+//! use chamber_parser::parse;
+//!
+//! let config = AnalyzerConfig::new()
+//!     .with_rule("unknownDecoration", RuleLevel::Warn)
+//!     .with_rule("unusualOctave", RuleLevel::Off);
+//! let analyzer = Analyzer::new().with_config(config).unwrap();
+//!
+//! let result = analyzer.analyze(&parse("X:1\nK:C\n!trillx!C''''"));
+//! assert!(!result.has_errors());
+//! assert_eq!(result.diagnostics.len(), 1);
+//! ```
+//!
+//! Rules with thresholds take typed options:
+//!
+//! ```
+//! use chamber_analyzer::{Analyzer, AnalyzerConfig, SuspiciousDurationOptions};
+//! use chamber_parser::parse;
+//!
+//! let config = AnalyzerConfig::new()
+//!     .with_options("suspiciousDuration", SuspiciousDurationOptions { threshold: 32.0 });
+//! let analyzer = Analyzer::new().with_config(config).unwrap();
+//!
+//! // A slow air with very long notes
+//! assert!(analyzer.analyze(&parse("X:1\nL:1/16\nK:C\nC16|")).diagnostics.is_empty());
+//! ```
+//!
+//! # Custom rules
+//!
+//! Simple house rules can be declared in the configuration with the
+//! `customRules` rule (see [`CustomRulesOptions`]). Anything else can be
+//! written as a [`Rule`] and registered with [`Analyzer::with_rule`]:
+//!
+//! ```
+//! use chamber_analyzer::{Analyzer, Category, NoOptions, Rule, RuleContext, RuleMeta};
+//! use chamber_ast::visit::{VisitContext, Visitor};
+//! use chamber_ast::Rest;
+//! use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+//! use chamber_parser::parse;
+//!
+//! /// Our band's parts write out every bar.
+//! struct NoMultiMeasureRests(Vec<Diagnostic>);
+//!
+//! impl RuleMeta for NoMultiMeasureRests {
+//!     const NAME: &'static str = "noMultiMeasureRests";
+//!     const CODE: DiagnosticCode = DiagnosticCode::CustomRule;
+//!     const SEVERITY: Severity = Severity::Warning;
+//!     const CATEGORY: Category = Category::Style;
+//!     const DOCS: &'static str = "Disallows multi-measure rests (Z).";
+//! }
+//!
+//! impl Rule for NoMultiMeasureRests {
+//!     type Options = NoOptions;
+//!
+//!     fn new(_cx: &RuleContext<NoOptions>) -> Self {
+//!         Self(Vec::new())
+//!     }
+//!
+//!     fn finish(self, _cx: &RuleContext<NoOptions>) -> Vec<Diagnostic> {
+//!         self.0
+//!     }
+//! }
+//!
+//! impl Visitor for NoMultiMeasureRests {
+//!     fn visit_rest(&mut self, rest: &Rest, _cx: &VisitContext) {
+//!         if rest.multi_measure {
+//!             let message = "write out the bars of this rest";
+//!             self.0.push(Diagnostic::warning(Self::CODE, rest.range, message));
+//!         }
+//!     }
+//! }
+//!
+//! let analyzer = Analyzer::new().with_rule::<NoMultiMeasureRests>();
+//! let result = analyzer.analyze(&parse("X:1\nK:C\nC Z D"));
+//! assert_eq!(result.diagnostics[0].code, DiagnosticCode::CustomRule);
+//! ```
 
+mod cache;
+mod config;
+mod context;
+mod explain;
+mod model;
+mod registry;
 mod rule;
 pub mod rules;
+mod suppression;
+
+use std::collections::BTreeMap;
 
+use chamber_ast::visit::walk_tune;
 use chamber_ast::Tune;
-use chamber_diagnostics::Diagnostic;
+use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use chamber_text_size::TextSize;
 use serde::{Deserialize, Serialize};
 
-pub use rule::{Category, Rule, RuleExt, RuleMeta};
-pub use rules::{BarLength, SuspiciousDuration, UnknownDecoration, UnusualOctave};
+pub use cache::{AnalysisCache, CacheError};
+pub use config::{AnalyzerConfig, ConfigError, RuleLevel};
+pub use context::{HeaderValues, RuleContext};
+pub use model::{
+    BarAccidentals, BeatStructure, ChordQuality, ChordSymbol, Fraction, Mode, PitchModel, Repeat,
+    RepeatEnding, RepeatStart, RepeatStructure, TimingModel, VoiceProperties,
+};
+pub use registry::{RegisteredRule, RuleInfo, RuleRegistry, Scope};
+pub use rule::{Category, CollectionRule, NoOptions, Rule, RuleExt, RuleMeta, RuleOptions};
+
+use context::TuneContext;
+use registry::{CollectionState, ResolvedOptions};
+pub use rules::{
+    AccidentalMode, AugmentedSpelling, BarLength, BarLengthMode, BarLengthOptions, BeamGrouping,
+    ChordMelodyClash, ChordSpelling, ChordSpellingOptions, CustomRuleDefinition, CustomRules,
+    CustomRulesOptions, DiminishedSpelling, DuplicateMelody, DuplicateMelodyOptions,
+    DuplicateTitle, DuplicateTuneNumber, FieldLabel, HeaderPattern, InconsistentDirectives,
+    InconsistentDirectivesOptions, InstrumentRange, InstrumentRangeDefinition,
+    InstrumentRangeOptions, InvalidChordSymbol, InvalidTie, LyricsAlignment, MajorSpelling,
+    MinorSpelling, PitchName, RedundantAccidental, RedundantAccidentalOptions, RequiredField,
+    RequiredFields, RequiredFieldsOptions, RhythmMeter, RhythmMeterOptions, SuspiciousDuration,
+    SuspiciousDurationOptions, TuneNumbering, TuneNumberingOptions, UnbalancedRepeat,
+    UnitNoteLength, UnknownDecoration, UnknownDecorationOptions, UnusualOctave,
+    UnusualOctaveOptions, VoiceConsistency,
+};
+use suppression::Suppressions;
 
 /// Result of semantic analysis.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -63,6 +191,10 @@ pub struct Analyzer {
     pub lint: bool,
     /// Whether to run style rules.
     pub style: bool,
+    registry: RuleRegistry,
+    config: AnalyzerConfig,
+    /// Options from `config`, keyed by rule name.
+    options: BTreeMap<&'static str, ResolvedOptions>,
 }
 
 impl Analyzer {
@@ -71,9 +203,91 @@ impl Analyzer {
         Self {
             lint: true,
             style: true,
+            registry: RuleRegistry::builtin(),
+            config: AnalyzerConfig::default(),
+            options: BTreeMap::new(),
         }
     }
 
+    /// Applies per-rule levels and options.
+    ///
+    /// Fails if the configuration names a rule that is not registered, or
+    /// has options that do not fit the rule.
+    pub fn with_config(mut self, config: AnalyzerConfig) -> Result<Self, ConfigError> {
+        self.options = config.resolve_options(&self.registry)?;
+        self.config = config;
+        Ok(self)
+    }
+
+    /// Registers a rule of your own, replacing any rule with the same name.
+    ///
+    /// Use [`Analyzer::with_collection_rule`] for a [`CollectionRule`].
+    ///
+    /// Register rules before calling [`Analyzer::with_config`], so that the
+    /// configuration can refer to them. Rules outside this crate report
+    /// [`DiagnosticCode::CustomRule`].
+    pub fn with_rule<R: Rule>(mut self) -> Self {
+        self.registry.register::<R>();
+        self
+    }
+
+    /// Registers a collection rule of your own, like [`Analyzer::with_rule`].
+    pub fn with_collection_rule<R: CollectionRule>(mut self) -> Self {
+        self.registry.register_collection::<R>();
+        self
+    }
+
+    /// Returns the registered rules.
+    pub fn registry(&self) -> &RuleRegistry {
+        &self.registry
+    }
+
+    /// Returns the effective level of a rule, or `None` if it is not registered.
+    ///
+    /// Rules in a disabled category (see [`Analyzer::without_lint`]) are off,
+    /// and so are rules not enabled by default until they are given a level.
+    pub fn rule_level(&self, name: &str) -> Option<RuleLevel> {
+        let info = self.registry.get(name)?.info;
+        let enabled = match info.category {
+            Category::Lint => self.lint,
+            Category::Style => self.style,
+        };
+        if !enabled {
+            return Some(RuleLevel::Off);
+        }
+        let default = match info.severity {
+            _ if !info.enabled => RuleLevel::Off,
+            Severity::Info => RuleLevel::Info,
+            Severity::Warning => RuleLevel::Warn,
+            Severity::Error => RuleLevel::Error,
+        };
+        Some(self.config.level(name).unwrap_or(default))
+    }
+
+    /// Returns the options a rule runs with, as JSON, or `None` if it is not
+    /// registered.
+    pub fn rule_options(&self, name: &str) -> Option<serde_json::Value> {
+        let rule = self.registry.get(name)?;
+        Some(rule.options_to_json(self.options.get(rule.info.name)))
+    }
+
+    /// Explains a diagnostic code at length, as Markdown: what it means,
+    /// the rules that report it with their current level and options, and
+    /// examples (see [`DiagnosticCode::explanation`]).
+    ///
+    /// ```
+    /// use chamber_analyzer::Analyzer;
+    /// use chamber_diagnostics::DiagnosticCode;
+    ///
+    /// let analyzer = Analyzer::new();
+    /// let code = analyzer.registry().resolve_code("barLength").unwrap();
+    /// assert_eq!(code, DiagnosticCode::BarLengthMismatch);
+    /// assert!(analyzer.explain(code).starts_with("# W003: bar length mismatch"));
+    /// ```
+    pub fn explain(&self, code: DiagnosticCode) -> String {
+        explain::render(self, code)
+    }
+
     /// Disables lint rules.
     pub fn without_lint(mut self) -> Self {
         self.lint = false;
@@ -87,15 +301,45 @@ impl Analyzer {
     }
 
     /// Analyzes a tune and returns the result.
+    ///
+    /// Rules don't see the source text; use [`Analyzer::analyze_source`]
+    /// when it is available.
     pub fn analyze(&self, tune: &Tune) -> AnalysisResult {
-        let mut diagnostics = Vec::new();
+        self.run_rules(&TuneContext::new(tune, None))
+    }
 
-        if self.lint {
-            // Run lint rules
-            UnknownDecoration::run(tune, &mut diagnostics);
-            UnusualOctave::run(tune, &mut diagnostics);
-            SuspiciousDuration::run(tune, &mut diagnostics);
-            BarLength::run(tune, &mut diagnostics);
+    /// Analyzes a tune parsed from `source` and returns the result.
+    pub fn analyze_source(&self, source: &str, tune: &Tune) -> AnalysisResult {
+        self.run_rules(&TuneContext::new(tune, Some(source)))
+    }
+
+    fn run_rules(&self, tune: &TuneContext) -> AnalysisResult {
+        let mut enabled = Vec::new();
+        let mut states = Vec::new();
+        for rule in self.registry.iter() {
+            let Some(severity) = self
+                .rule_level(rule.info.name)
+                .and_then(RuleLevel::severity)
+            else {
+                continue;
+            };
+            let options = self.options.get(rule.info.name);
+            if let Some(state) = rule.start(tune, options) {
+                enabled.push((options, severity));
+                states.push(state);
+            }
+        }
+
+        // One walk over the tune drives every enabled rule
+        walk_tune(states.as_mut_slice(), tune.tune());
+
+        // Report at the configured severity
+        let mut diagnostics = Vec::new();
+        for (state, (options, severity)) in states.into_iter().zip(enabled) {
+            for mut diagnostic in state.finish(tune, options) {
+                diagnostic.severity = severity;
+                diagnostics.push(diagnostic);
+            }
         }
 
         // Sort diagnostics by position for consistent output
@@ -103,6 +347,191 @@ impl Analyzer {
 
         AnalysisResult { diagnostics }
     }
+
+    /// Applies the suppression comments in `source` to `diagnostics`.
+    ///
+    /// `diagnostics` are those of the tune parsed from `source`, from the
+    /// parser and from [`Analyzer::analyze`]. Suppressed diagnostics are
+    /// removed, and suppressions that match nothing are reported (W004),
+    /// except those for rules that are turned off.
+    ///
+    /// ```
+    /// use chamber_analyzer::Analyzer;
+    /// use chamber_parser::parse;
+    ///
+    /// let source = "X:1\nT:Pickup\nM:4/4\nL:1/4\nK:C\nC | % chamber-ignore barLength\nCDEF|]";
+    /// let analyzer = Analyzer::new();
+    /// let diagnostics = analyzer.analyze(&parse(source)).diagnostics;
+    /// assert_eq!(diagnostics.len(), 1);
+    /// assert!(analyzer.apply_suppressions(source, diagnostics).is_empty());
+    /// ```
+    pub fn apply_suppressions(
+        &self,
+        source: &str,
+        diagnostics: Vec<Diagnostic>,
+    ) -> Vec<Diagnostic> {
+        let inactive: Vec<_> = self
+            .registry
+            .iter()
+            .filter(|rule| self.rule_level(rule.info.name) == Some(RuleLevel::Off))
+            .map(|rule| rule.info.code)
+            .collect();
+        Suppressions::parse(source, &self.registry).apply(diagnostics, &inactive)
+    }
+
+    /// Returns the diagnostics of a tune parsed from `source`, like
+    /// [`Analyzer::analyze_source`] followed by
+    /// [`Analyzer::apply_suppressions`], unless `cache` has them already.
+    ///
+    /// `parser_diagnostics` are those of parsing `source`; they are part of
+    /// the cached result. `cache` must have been created for this analyzer.
+    /// Collection rules are not cached, since they depend on every tune.
+    ///
+    /// ```
+    /// use chamber_analyzer::{AnalysisCache, Analyzer};
+    /// use chamber_parser::parse_with_diagnostics;
+    ///
+    /// let analyzer = Analyzer::new();
+    /// let cache = AnalysisCache::new(&analyzer);
+    /// let source = "X:1\nT:Scale\nK:C\nCDEF GABc|CDE|\n";
+    /// let parsed = parse_with_diagnostics(source);
+    ///
+    /// let diagnostics = analyzer.analyze_cached(&cache, source, &parsed.tune, parsed.diagnostics);
+    /// assert_eq!(diagnostics.len(), 1);
+    /// assert_eq!(cache.get(source), Some(diagnostics));
+    /// ```
+    pub fn analyze_cached(
+        &self,
+        cache: &AnalysisCache,
+        source: &str,
+        tune: &Tune,
+        mut parser_diagnostics: Vec<Diagnostic>,
+    ) -> Vec<Diagnostic> {
+        if let Some(diagnostics) = cache.get(source) {
+            return diagnostics;
+        }
+        parser_diagnostics.extend(self.analyze_source(source, tune).diagnostics);
+        let diagnostics = self.apply_suppressions(source, parser_diagnostics);
+        cache.insert(source, diagnostics.clone());
+        diagnostics
+    }
+
+    /// Starts running the collection rules over the tunes of a collection.
+    ///
+    /// Feed it every tune of the collection in order with
+    /// [`CollectionAnalysis::add_tune`], then call
+    /// [`CollectionAnalysis::finish`]. To show diagnostics while their tune
+    /// is at hand, call [`CollectionAnalysis::take_diagnostics`] after each
+    /// tune; `finish` then returns the rest. Per-tune rules are not run; use
+    /// [`Analyzer::analyze_source`] for each tune as well.
+    ///
+    /// ```
+    /// use chamber_analyzer::Analyzer;
+    /// use chamber_diagnostics::DiagnosticCode;
+    /// use chamber_parser::TuneStream;
+    /// use chamber_text_size::TextSize;
+    ///
+    /// let source = "X:1\nT:Reel\nK:D\nDFAd|\n\nX:1\nT:Jig\nK:G\nGBd|\n";
+    /// let analyzer = Analyzer::new();
+    /// let mut collection = analyzer.collection();
+    /// for tune in TuneStream::new(source.as_bytes()) {
+    ///     let tune = tune.unwrap();
+    ///     collection.add_tune(&tune.source, &tune.tune, TextSize::new(tune.offset as u32));
+    /// }
+    ///
+    /// let result = collection.finish();
+    /// assert_eq!(result.diagnostics[0].code, DiagnosticCode::DuplicateTuneNumber);
+    /// // The range of the second `X:1`, with a label at the first
+    /// assert_eq!(result.diagnostics[0].range.start(), TextSize::new(22));
+    /// assert_eq!(result.diagnostics[0].labels[0].range.start(), TextSize::new(0));
+    /// ```
+    pub fn collection(&self) -> CollectionAnalysis<'_> {
+        let mut rules = Vec::new();
+        for rule in self.registry.iter() {
+            let Some(severity) = self
+                .rule_level(rule.info.name)
+                .and_then(RuleLevel::severity)
+            else {
+                continue;
+            };
+            let options = self.options.get(rule.info.name);
+            if let Some(state) = rule.start_collection(options) {
+                rules.push((state, options, severity));
+            }
+        }
+        CollectionAnalysis { rules }
+    }
+
+    /// Analyzes several independent tunes, such as the tunes of a collection.
+    ///
+    /// Results are in the same order as `tunes`. With the `parallel` feature
+    /// the tunes are analyzed on the rayon thread pool.
+    pub fn analyze_all(&self, tunes: &[Tune]) -> Vec<AnalysisResult> {
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            tunes.par_iter().map(|tune| self.analyze(tune)).collect()
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            tunes.iter().map(|tune| self.analyze(tune)).collect()
+        }
+    }
+}
+
+/// The collection rules of an [`Analyzer`], reading the tunes of one
+/// collection (see [`Analyzer::collection`]).
+pub struct CollectionAnalysis<'a> {
+    rules: Vec<(
+        Box<dyn CollectionState>,
+        Option<&'a ResolvedOptions>,
+        Severity,
+    )>,
+}
+
+impl CollectionAnalysis<'_> {
+    /// Shows the collection rules the next tune. `tune` was parsed from
+    /// `source`, which starts at `offset` in the collection.
+    pub fn add_tune(&mut self, source: &str, tune: &Tune, offset: TextSize) {
+        let tune = TuneContext::new(tune, Some(source)).with_offset(offset);
+        for (state, options, _) in &mut self.rules {
+            state.visit_tune(&tune, *options);
+        }
+    }
+
+    /// Returns the diagnostics the collection rules found so far and haven't
+    /// returned yet, usually about the tune just added.
+    ///
+    /// Their ranges are offsets in the whole collection, like those of
+    /// [`CollectionAnalysis::finish`].
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for (state, _, severity) in &mut self.rules {
+            for mut diagnostic in state.take_diagnostics() {
+                diagnostic.severity = *severity;
+                diagnostics.push(diagnostic);
+            }
+        }
+        diagnostics.sort_by_key(|d| d.range.start());
+        diagnostics
+    }
+
+    /// Returns the diagnostics of the collection rules not returned by
+    /// [`CollectionAnalysis::take_diagnostics`].
+    ///
+    /// Their ranges, and those of their labels, are offsets in the whole
+    /// collection.
+    pub fn finish(self) -> AnalysisResult {
+        let mut diagnostics = Vec::new();
+        for (state, options, severity) in self.rules {
+            for mut diagnostic in state.finish(options) {
+                diagnostic.severity = severity;
+                diagnostics.push(diagnostic);
+            }
+        }
+        diagnostics.sort_by_key(|d| d.range.start());
+        AnalysisResult { diagnostics }
+    }
 }
 
 /// Convenience function to analyze a tune with default settings.
@@ -113,6 +542,8 @@ pub fn analyze(tune: &Tune) -> Vec<Diagnostic> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chamber_ast::visit::{VisitContext, Visitor};
+    use chamber_ast::{Note, Pitch};
     use chamber_parser::parse;
 
     #[test]
@@ -144,4 +575,329 @@ mod tests {
             assert!(result.diagnostics[i - 1].range.start() <= result.diagnostics[i].range.start());
         }
     }
+
+    #[test]
+    fn test_rule_off() {
+        let tune = parse("X:1\nK:C\n!trillx!C''''");
+        let config = AnalyzerConfig::new().with_rule(UnusualOctave::NAME, RuleLevel::Off);
+        let result = Analyzer::new().with_config(config).unwrap().analyze(&tune);
+
+        let codes: Vec<_> = result.diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(codes, [UnknownDecoration::CODE]);
+    }
+
+    #[test]
+    fn test_severity_override() {
+        let tune = parse("X:1\nK:C\n!trillx!C''''");
+        let config = AnalyzerConfig::new()
+            .with_rule(UnknownDecoration::NAME, RuleLevel::Info)
+            .with_rule(UnusualOctave::NAME, RuleLevel::Error);
+        let result = Analyzer::new().with_config(config).unwrap().analyze(&tune);
+
+        assert_eq!(result.diagnostics.len(), 2);
+        for diagnostic in &result.diagnostics {
+            let expected = if diagnostic.code == UnknownDecoration::CODE {
+                Severity::Info
+            } else {
+                Severity::Error
+            };
+            assert_eq!(diagnostic.severity, expected);
+        }
+    }
+
+    #[test]
+    fn test_unknown_rule_rejected() {
+        let config = AnalyzerConfig::new().with_rule("noSuchRule", RuleLevel::Off);
+        let error = Analyzer::new().with_config(config).unwrap_err();
+        assert_eq!(error, ConfigError::UnknownRule("noSuchRule".to_string()));
+    }
+
+    #[test]
+    fn test_rule_level() {
+        let analyzer = Analyzer::new();
+        assert_eq!(
+            analyzer.rule_level("unknownDecoration"),
+            Some(RuleLevel::Error)
+        );
+        assert_eq!(analyzer.rule_level("barLength"), Some(RuleLevel::Warn));
+        assert_eq!(analyzer.rule_level("noSuchRule"), None);
+        // Off until configured
+        assert_eq!(analyzer.rule_level("beamGrouping"), Some(RuleLevel::Off));
+        let config = AnalyzerConfig::new().with_rule("beamGrouping", RuleLevel::Warn);
+        let analyzer = Analyzer::new().with_config(config).unwrap();
+        assert_eq!(analyzer.rule_level("beamGrouping"), Some(RuleLevel::Warn));
+
+        let analyzer = Analyzer::new().without_lint();
+        assert_eq!(analyzer.rule_level("barLength"), Some(RuleLevel::Off));
+    }
+
+    #[test]
+    fn test_config_from_json() {
+        let json = r#"{ "rules": { "barLength": "error", "unusualOctave": "warning" } }"#;
+        let config: AnalyzerConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.level("barLength"), Some(RuleLevel::Error));
+        assert_eq!(config.level("unusualOctave"), Some(RuleLevel::Warn));
+    }
+
+    #[test]
+    fn test_rule_options_from_json() {
+        let json = r#"{
+            "options": {
+                "unusualOctave": { "lowest": -4 },
+                "unknownDecoration": { "allowed": ["bend"] }
+            }
+        }"#;
+        let config: AnalyzerConfig = serde_json::from_str(json).unwrap();
+        let analyzer = Analyzer::new().with_config(config).unwrap();
+
+        let result = analyzer.analyze(&parse("X:1\nK:C\n!bend!C,,, c''''"));
+        // Only the high note is left; `highest` keeps its default
+        assert_eq!(result.diagnostics.len(), 1);
+        assert!(result.diagnostics[0].message.contains("high"));
+    }
+
+    #[test]
+    fn test_invalid_rule_options_rejected() {
+        let config = AnalyzerConfig {
+            options: [(
+                "unusualOctave".to_string(),
+                serde_json::json!({ "highest": "very" }),
+            )]
+            .into(),
+            ..AnalyzerConfig::default()
+        };
+        let error = Analyzer::new().with_config(config).unwrap_err();
+        assert!(
+            matches!(error, ConfigError::InvalidOptions { ref rule, .. } if rule == "unusualOctave")
+        );
+
+        // Unknown fields are typos, not ignored
+        let config =
+            AnalyzerConfig::new().with_options("barLength", serde_json::json!({ "strict": true }));
+        assert!(Analyzer::new().with_config(config).is_err());
+
+        let config = AnalyzerConfig::new().with_options("noSuchRule", serde_json::json!({}));
+        assert_eq!(
+            Analyzer::new().with_config(config).unwrap_err(),
+            ConfigError::UnknownRule("noSuchRule".to_string())
+        );
+    }
+
+    #[test]
+    fn test_default_options() {
+        let registry = RuleRegistry::builtin();
+        let options = registry
+            .get("suspiciousDuration")
+            .unwrap()
+            .default_options();
+        assert_eq!(options, serde_json::json!({ "threshold": 16.0 }));
+
+        let config = AnalyzerConfig::new().with_options(
+            "barLength",
+            BarLengthOptions {
+                mode: BarLengthMode::Strict,
+            },
+        );
+        let analyzer = Analyzer::new().with_config(config).unwrap();
+        assert_eq!(
+            analyzer.rule_options("barLength"),
+            Some(serde_json::json!({ "mode": "strict" }))
+        );
+        assert_eq!(
+            analyzer.rule_options("unusualOctave"),
+            Some(serde_json::json!({ "lowest": -2, "highest": 3 }))
+        );
+    }
+
+    /// A rule from outside the crate: flags every `C`.
+    struct NoCs(Vec<Diagnostic>);
+
+    impl RuleMeta for NoCs {
+        const NAME: &'static str = "noCs";
+        const CODE: DiagnosticCode = DiagnosticCode::CustomRule;
+        const SEVERITY: Severity = Severity::Warning;
+        const CATEGORY: Category = Category::Style;
+        const DOCS: &'static str = "Disallows C.";
+    }
+
+    impl Rule for NoCs {
+        type Options = NoOptions;
+
+        fn new(_cx: &RuleContext<NoOptions>) -> Self {
+            Self(Vec::new())
+        }
+
+        fn finish(self, _cx: &RuleContext<NoOptions>) -> Vec<Diagnostic> {
+            self.0
+        }
+    }
+
+    impl Visitor for NoCs {
+        fn visit_note(&mut self, note: &Note, _cx: &VisitContext) {
+            if note.pitch == Pitch::C {
+                self.0
+                    .push(Diagnostic::warning(Self::CODE, note.range, "C"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_with_rule() {
+        let tune = parse("X:1\nK:C\nCDEc");
+        let analyzer = Analyzer::new().with_rule::<NoCs>();
+        assert_eq!(analyzer.rule_level("noCs"), Some(RuleLevel::Warn));
+        assert_eq!(analyzer.analyze(&tune).diagnostics.len(), 2);
+
+        // Configured like any built-in rule
+        let config = AnalyzerConfig::new().with_rule("noCs", RuleLevel::Error);
+        let analyzer = Analyzer::new()
+            .with_rule::<NoCs>()
+            .with_config(config)
+            .unwrap();
+        assert!(analyzer.analyze(&tune).has_errors());
+        let analyzer = Analyzer::new().without_style().with_rule::<NoCs>();
+        assert!(analyzer.analyze(&tune).diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_custom_rules_from_json() {
+        let json = r#"{
+            "rules": { "customRules": "error" },
+            "options": {
+                "customRules": {
+                    "rules": [
+                        { "name": "requireRhythm", "requiredFields": ["R:"] },
+                        { "name": "noFermata", "forbiddenDecorations": ["fermata"] }
+                    ]
+                }
+            }
+        }"#;
+        let config: AnalyzerConfig = serde_json::from_str(json).unwrap();
+        let analyzer = Analyzer::new().with_config(config).unwrap();
+
+        let result = analyzer.analyze(&parse("X:1\nT:Air\nK:C\nCD!fermata!E"));
+        let messages: Vec<_> = result
+            .diagnostics
+            .iter()
+            .map(|d| d.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "requireRhythm: missing 'R:' field",
+                "noFermata: decoration 'fermata' is not allowed"
+            ]
+        );
+        assert!(result.has_errors());
+
+        let config = AnalyzerConfig::new().with_options(
+            "customRules",
+            serde_json::json!({ "rules": [{ "name": "x", "headerPatterns": { "T": "[" } }] }),
+        );
+        let error = Analyzer::new().with_config(config).unwrap_err();
+        assert!(
+            matches!(error, ConfigError::InvalidOptions { ref rule, .. } if rule == "customRules")
+        );
+    }
+
+    /// Runs the collection rules over the tunes of `source`.
+    fn analyze_collection(analyzer: &Analyzer, source: &str) -> AnalysisResult {
+        let mut collection = analyzer.collection();
+        for tune in chamber_parser::TuneStream::new(source.as_bytes()) {
+            let tune = tune.unwrap();
+            let offset = TextSize::new(tune.offset as u32);
+            collection.add_tune(&tune.source, &tune.tune, offset);
+        }
+        collection.finish()
+    }
+
+    #[test]
+    fn test_collection_rules() {
+        let source = "X:1\nT:Reel\nK:D\nDFAd|\n\nX:1\nT:Reel\nK:G\nGBdg|\n";
+        let result = analyze_collection(&Analyzer::new(), source);
+        let codes: Vec<_> = result.diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(
+            codes,
+            [
+                DiagnosticCode::DuplicateTuneNumber,
+                DiagnosticCode::DuplicateTitle
+            ]
+        );
+
+        // Configured like per-tune rules
+        let config = AnalyzerConfig::new()
+            .with_rule(DuplicateTuneNumber::NAME, RuleLevel::Error)
+            .with_rule(DuplicateTitle::NAME, RuleLevel::Off);
+        let analyzer = Analyzer::new().with_config(config).unwrap();
+        let result = analyze_collection(&analyzer, source);
+        assert_eq!(result.diagnostics.len(), 1);
+        assert!(result.has_errors());
+    }
+
+    #[test]
+    fn test_collection_diagnostics_taken_per_tune() {
+        let source = "X:1\nT:Reel\nK:D\nDFAd|\n\nX:1\nT:Jig\nK:G\nGBd|\n";
+        let analyzer = Analyzer::new();
+        let mut collection = analyzer.collection();
+        let mut taken = Vec::new();
+        for tune in chamber_parser::TuneStream::new(source.as_bytes()) {
+            let tune = tune.unwrap();
+            let offset = TextSize::new(tune.offset as u32);
+            collection.add_tune(&tune.source, &tune.tune, offset);
+            taken.push(collection.take_diagnostics().len());
+        }
+        // Reported with the second tune, and not again at the end
+        assert_eq!(taken, [0, 1]);
+        assert!(collection.finish().diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_collection_rules_not_run_per_tune() {
+        let tune = parse("X:1\nT:Reel\nK:D\nDFAd AFDF|");
+        assert!(Analyzer::new().analyze(&tune).diagnostics.is_empty());
+        assert_eq!(
+            Analyzer::new().rule_level(DuplicateTuneNumber::NAME),
+            Some(RuleLevel::Warn)
+        );
+    }
+
+    #[test]
+    fn test_analyze_cached() {
+        let analyzer = Analyzer::new();
+        let cache = AnalysisCache::new(&analyzer);
+        let source = "X:1\nT:Scale\nK:C\n!trillx!C % chamber-ignore W003\n";
+        let diagnostics = analyzer.analyze_cached(&cache, source, &parse(source), Vec::new());
+        let codes: Vec<_> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(
+            codes,
+            [
+                DiagnosticCode::UnknownDecoration,
+                DiagnosticCode::UnusedSuppression
+            ]
+        );
+
+        // A hit returns the cached diagnostics without running the rules
+        cache.insert(source, Vec::new());
+        assert!(analyzer
+            .analyze_cached(&cache, source, &parse(source), Vec::new())
+            .is_empty());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_analyze_all_keeps_order() {
+        let tunes: Vec<_> = (0..64)
+            .map(|i| {
+                // Every third tune has an unknown decoration
+                let decoration = if i % 3 == 0 { "!trillx!" } else { "!trill!" };
+                parse(&format!("X:{}\nT:Tune\nK:C\n{}C", i + 1, decoration))
+            })
+            .collect();
+        let results = Analyzer::new().analyze_all(&tunes);
+
+        assert_eq!(results.len(), tunes.len());
+        for (i, result) in results.iter().enumerate() {
+            assert_eq!(result.has_errors(), i % 3 == 0, "tune {}", i);
+        }
+    }
 }
@@ -0,0 +1,1682 @@
+//! Resolved musical models of a tune: timing (meter, unit note length, and
+//! the beats of a bar), pitch (key signature, and accidentals carried over
+//! within a bar), chord symbols, voice properties, the repeat structure, and
+//! where each voice is in its bars and sections.
+//!
+//! Header values are plain text in the AST; these models parse them once and
+//! apply the ABC defaults, so rules don't each re-derive them.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Add, Mul, Range};
+
+use chamber_ast::visit::VisitContext;
+use chamber_ast::{Accidental, BarLine, BarLineKind, Duration, Ending, Note, Pitch};
+use chamber_text_size::TextRange;
+
+/// A non-negative fraction in lowest terms, for exact duration arithmetic.
+///
+/// Results whose reduced terms don't fit in `u32` (a bar of many unusual
+/// tuplets or broken durations) lose their lowest bits instead of
+/// overflowing, so they are close but no longer exact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fraction {
+    num: u32,
+    den: u32,
+}
+
+impl Fraction {
+    /// Creates `num/den`, reduced. `den` must not be zero.
+    pub fn new(num: u32, den: u32) -> Self {
+        Self::reduce(num.into(), den.into())
+    }
+
+    /// Returns `0/1`.
+    pub fn zero() -> Self {
+        Self { num: 0, den: 1 }
+    }
+
+    /// Returns the numerator.
+    pub fn numerator(self) -> u32 {
+        self.num
+    }
+
+    /// Returns the denominator.
+    pub fn denominator(self) -> u32 {
+        self.den
+    }
+
+    /// Returns true if this fraction is zero.
+    pub fn is_zero(self) -> bool {
+        self.num == 0
+    }
+
+    /// Returns `self - other`, or `None` if `other` is larger.
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        let lhs = u128::from(self.num) * u128::from(other.den);
+        let rhs = u128::from(other.num) * u128::from(self.den);
+        let num = lhs.checked_sub(rhs)?;
+        let den = u128::from(self.den) * u128::from(other.den);
+        Some(Self::reduce(num, den))
+    }
+
+    /// Parses `num/den` (e.g., "3/4").
+    fn parse(value: &str) -> Option<Self> {
+        let (num, den) = value.split_once('/')?;
+        let num = num.trim().parse::<u32>().ok()?;
+        let den = den.trim().parse::<u32>().ok()?;
+        (den > 0).then(|| Self::new(num, den))
+    }
+
+    fn reduce(num: u128, den: u128) -> Self {
+        if num == 0 {
+            return Self::zero();
+        }
+        let g = gcd(num, den);
+        let (num, den) = (num / g, den / g);
+
+        // Too fine for u32: drop the lowest bits of both terms
+        let bits = u128::BITS - num.max(den).leading_zeros();
+        let shift = bits.saturating_sub(u32::BITS);
+        if shift > 0 {
+            let num = (num >> shift).max(1);
+            let den = (den >> shift).max(1);
+            return Self::reduce(num, den);
+        }
+        Self {
+            num: num as u32,
+            den: den as u32,
+        }
+    }
+}
+
+impl Add for Fraction {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let num = u128::from(self.num) * u128::from(other.den)
+            + u128::from(other.num) * u128::from(self.den);
+        let den = u128::from(self.den) * u128::from(other.den);
+        Self::reduce(num, den)
+    }
+}
+
+impl Mul for Fraction {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let num = u128::from(self.num) * u128::from(other.num);
+        let den = u128::from(self.den) * u128::from(other.den);
+        Self::reduce(num, den)
+    }
+}
+
+impl Ord for Fraction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let lhs = self.num as u64 * other.den as u64;
+        let rhs = other.num as u64 * self.den as u64;
+        lhs.cmp(&rhs)
+    }
+}
+
+impl PartialOrd for Fraction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Fraction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.num, self.den)
+    }
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Meter and unit note length in effect.
+///
+/// Without an `M:` field, 4/4 is assumed. Without an `L:` field, the unit
+/// note length is derived from the meter as in ABC 2.1: 1/16 if the meter is
+/// less than 3/4, 1/8 otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimingModel {
+    meter: Option<Fraction>,
+    unit_note_length: Option<Fraction>,
+}
+
+impl TimingModel {
+    /// Resolves the values of the `M:` and `L:` fields.
+    pub fn new(meter: Option<&str>, unit_note_length: Option<&str>) -> Self {
+        let mut timing = Self {
+            meter: Some(Fraction::new(4, 4)),
+            unit_note_length: None,
+        };
+        if let Some(meter) = meter {
+            timing.set_meter(meter);
+        }
+        if let Some(unit_note_length) = unit_note_length {
+            timing.set_unit_note_length(unit_note_length);
+        }
+        timing
+    }
+
+    /// Returns the length of a bar as a fraction of a whole note, or `None`
+    /// for free meter (`M:none`).
+    pub fn meter(&self) -> Option<Fraction> {
+        self.meter
+    }
+
+    /// Returns the unit note length as a fraction of a whole note.
+    pub fn unit_note_length(&self) -> Fraction {
+        self.unit_note_length.unwrap_or_else(|| match self.meter {
+            Some(meter) if meter < Fraction::new(3, 4) => Fraction::new(1, 16),
+            _ => Fraction::new(1, 8),
+        })
+    }
+
+    /// Returns a note length (in unit note lengths, `None` for 1) as a
+    /// fraction of a whole note.
+    pub fn duration(&self, duration: Option<&Duration>) -> Fraction {
+        let length = duration.map_or(Fraction::new(1, 1), |d| {
+            Fraction::new(d.numerator, d.denominator)
+        });
+        length * self.unit_note_length()
+    }
+
+    /// Applies an inline field (`[M:3/4]`, `[L:1/16]`); other fields are
+    /// ignored.
+    ///
+    /// A meter change does not change a unit note length that was derived
+    /// from the header's meter.
+    pub fn apply_field(&mut self, label: char, value: &str) {
+        match label {
+            'M' => {
+                let unit_note_length = self.unit_note_length();
+                self.set_meter(value);
+                self.unit_note_length = Some(unit_note_length);
+            }
+            'L' => self.set_unit_note_length(value),
+            _ => {}
+        }
+    }
+
+    fn set_meter(&mut self, value: &str) {
+        self.meter = match value.trim() {
+            "C" => Some(Fraction::new(4, 4)),
+            "C|" => Some(Fraction::new(2, 2)),
+            value if value.eq_ignore_ascii_case("none") => None,
+            value => parse_meter(value).or(self.meter),
+        };
+    }
+
+    fn set_unit_note_length(&mut self, value: &str) {
+        if let Some(length) = Fraction::parse(value.trim()) {
+            self.unit_note_length = Some(length);
+        }
+    }
+}
+
+impl Default for TimingModel {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+/// Returns the value of an `M:` field as meters are compared: `C` and `C|`
+/// as 4/4 and 2/2, without spaces.
+pub fn normalize_meter(meter: &str) -> String {
+    match meter.trim() {
+        "C" => "4/4".to_string(),
+        "C|" => "2/2".to_string(),
+        meter => meter.chars().filter(|c| !c.is_whitespace()).collect(),
+    }
+}
+
+/// Parses a numeric meter, including additive ones like "2+3/8".
+fn parse_meter(value: &str) -> Option<Fraction> {
+    let (beats, den) = value.split_once('/')?;
+    let mut num = 0;
+    for beat in beats.split('+') {
+        num += beat.trim().parse::<u32>().ok()?;
+    }
+    Fraction::parse(&format!("{}/{}", num, den))
+}
+
+/// How the bars of a meter divide into beats, which beams follow.
+///
+/// A simple meter has one beat per count (`3/4` is three quarters), and a
+/// compound meter one per three counts (`6/8` is two dotted quarters). An
+/// additive meter lists its beats (`2+3/8`). Without an `M:` field, 4/4 is
+/// assumed; free meter (`M:none`) has no beats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BeatStructure {
+    beats: Vec<Fraction>,
+    beam_groups: Vec<Fraction>,
+}
+
+impl BeatStructure {
+    /// Resolves the value of the `M:` field.
+    pub fn new(meter: Option<&str>) -> Self {
+        let mut structure = Self {
+            beats: Vec::new(),
+            beam_groups: Vec::new(),
+        };
+        structure.set_meter(meter.unwrap_or("4/4"));
+        structure
+    }
+
+    /// Returns the beats of a bar, in order; none for free meter.
+    pub fn beats(&self) -> &[Fraction] {
+        &self.beats
+    }
+
+    /// Returns the longest stretches of a bar one beam may cover, in order.
+    ///
+    /// These are the beats, except in 4/4, where a beam may join the first
+    /// two beats or the last two.
+    pub fn beam_groups(&self) -> &[Fraction] {
+        &self.beam_groups
+    }
+
+    /// Returns true if `position` in the bar is a strong beat: the start of
+    /// the bar, or its middle if a beam group starts there (the third beat
+    /// of 4/4, the second of 6/8, but not the middle of 3/4).
+    pub fn is_strong(&self, position: Fraction) -> bool {
+        if position.is_zero() {
+            return true;
+        }
+        let mut start = Fraction::zero();
+        let mut starts = Vec::new();
+        for &group in &self.beam_groups {
+            starts.push(start);
+            start = start + group;
+        }
+        starts.contains(&position) && position + position == start
+    }
+
+    /// Applies an inline `[M:]` field; other fields are ignored.
+    pub fn apply_field(&mut self, label: char, value: &str) {
+        if label == 'M' {
+            self.set_meter(value);
+        }
+    }
+
+    fn set_meter(&mut self, value: &str) {
+        let value = match value.trim() {
+            "C" => "4/4",
+            "C|" => "2/2",
+            value => value,
+        };
+        if value.eq_ignore_ascii_case("none") {
+            self.beats.clear();
+            self.beam_groups.clear();
+            return;
+        }
+        let Some((counts, den)) = value.split_once('/') else {
+            return;
+        };
+        let Ok(den) = den.trim().parse::<u32>() else {
+            return;
+        };
+        let counts: Option<Vec<u32>> = counts
+            .trim()
+            .trim_start_matches('(')
+            .trim_end_matches(')')
+            .split('+')
+            .map(|count| count.trim().parse().ok().filter(|&count| count > 0))
+            .collect();
+        let Some(counts) = counts.filter(|_| den > 0) else {
+            return;
+        };
+
+        self.beats = match counts[..] {
+            [num] if den >= 8 && num % 3 == 0 => vec![Fraction::new(3, den); num as usize / 3],
+            [num] => vec![Fraction::new(1, den); num as usize],
+            _ => counts
+                .iter()
+                .map(|&count| Fraction::new(count, den))
+                .collect(),
+        };
+        self.beam_groups = match counts[..] {
+            [4] if den == 4 => vec![Fraction::new(1, 2); 2],
+            _ => self.beats.clone(),
+        };
+    }
+}
+
+impl Default for BeatStructure {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// A mode of a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Major,
+    Minor,
+    Mixolydian,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Locrian,
+}
+
+impl Mode {
+    /// Parses a mode name; only the first three letters count, and "m" is
+    /// minor.
+    fn parse(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        if name == "m" {
+            return Some(Self::Minor);
+        }
+        match name.get(..3)? {
+            "maj" | "ion" => Some(Self::Major),
+            "min" | "aeo" => Some(Self::Minor),
+            "mix" => Some(Self::Mixolydian),
+            "dor" => Some(Self::Dorian),
+            "phr" => Some(Self::Phrygian),
+            "lyd" => Some(Self::Lydian),
+            "loc" => Some(Self::Locrian),
+            _ => None,
+        }
+    }
+
+    /// Position on the circle of fifths relative to the major mode.
+    fn fifths(self) -> i8 {
+        match self {
+            Self::Lydian => 1,
+            Self::Major => 0,
+            Self::Mixolydian => -1,
+            Self::Dorian => -2,
+            Self::Minor => -3,
+            Self::Phrygian => -4,
+            Self::Locrian => -5,
+        }
+    }
+}
+
+/// Order in which key signatures add sharps; flats are added in reverse.
+const SHARPS: [Pitch; 7] = [
+    Pitch::F,
+    Pitch::C,
+    Pitch::G,
+    Pitch::D,
+    Pitch::A,
+    Pitch::E,
+    Pitch::B,
+];
+
+/// The key in effect and the accidentals of its key signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PitchModel {
+    tonic: Option<(Pitch, Option<Accidental>)>,
+    mode: Mode,
+    /// Accidental of each pitch from C to B.
+    signature: [Option<Accidental>; 7],
+}
+
+impl PitchModel {
+    /// Resolves the value of the `K:` field; no field means C major.
+    ///
+    /// Understands a tonic with a mode (`G`, `F#m`, `D dorian`), explicit
+    /// accidentals (`D ^g`, `D exp ^f ^c`), `none`, and the bagpipe keys
+    /// `HP` and `Hp`. Other words such as `clef=bass` are ignored.
+    pub fn new(key: Option<&str>) -> Self {
+        let mut pitch = Self {
+            tonic: Some((Pitch::C, None)),
+            mode: Mode::Major,
+            signature: [None; 7],
+        };
+        if let Some(key) = key {
+            pitch.set_key(key);
+        }
+        pitch
+    }
+
+    /// Returns the tonic, or `None` for `K:none` and bagpipe keys.
+    pub fn tonic(&self) -> Option<(Pitch, Option<Accidental>)> {
+        self.tonic
+    }
+
+    /// Returns the mode.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Returns the accidental the key signature gives `pitch`, if any.
+    pub fn signature(&self, pitch: Pitch) -> Option<Accidental> {
+        self.signature[pitch_index(pitch)]
+    }
+
+    /// Returns the accidental a note is played with, ignoring accidentals
+    /// carried over from earlier in the bar: its own, or the key
+    /// signature's.
+    pub fn accidental(&self, note: &Note) -> Option<Accidental> {
+        note.accidental.or_else(|| self.signature(note.pitch))
+    }
+
+    /// Applies an inline field (`[K:D]`); other fields are ignored.
+    pub fn apply_field(&mut self, label: char, value: &str) {
+        if label == 'K' {
+            self.set_key(value);
+        }
+    }
+
+    fn set_key(&mut self, value: &str) {
+        let value = value.trim();
+        // `[K:clef=bass]` and the like leave the key unchanged
+        let changes_key = value.split_whitespace().any(|word| {
+            word.eq_ignore_ascii_case("none")
+                || word.eq_ignore_ascii_case("exp")
+                || word.eq_ignore_ascii_case("hp")
+                || parse_tonic(word).is_some()
+                || parse_accidental(word).is_some()
+        });
+        if !changes_key {
+            return;
+        }
+
+        self.signature = [None; 7];
+        self.mode = Mode::Major;
+
+        let mut words = value.split_whitespace().peekable();
+        let first = words.peek().copied().unwrap_or("");
+        self.tonic = None;
+        if first.eq_ignore_ascii_case("none") || first == "HP" {
+            return;
+        }
+        if first == "Hp" {
+            // Highland pipes: F and C sharp, G natural
+            self.signature[pitch_index(Pitch::F)] = Some(Accidental::Sharp);
+            self.signature[pitch_index(Pitch::C)] = Some(Accidental::Sharp);
+            self.signature[pitch_index(Pitch::G)] = Some(Accidental::Natural);
+            return;
+        }
+
+        if let Some((tonic, accidental, mode)) = parse_tonic(first) {
+            words.next();
+            let mode = match mode {
+                // The mode may be a separate word ("D dorian")
+                "" => {
+                    let mode = words.peek().and_then(|word| Mode::parse(word));
+                    if mode.is_some() {
+                        words.next();
+                    }
+                    mode
+                }
+                mode => Mode::parse(mode),
+            };
+            self.tonic = Some((tonic, accidental));
+            self.mode = mode.unwrap_or(Mode::Major);
+            self.set_fifths(tonic_fifths(tonic, accidental) + self.mode.fifths());
+        }
+
+        for word in words {
+            if word.eq_ignore_ascii_case("exp") {
+                self.signature = [None; 7];
+            } else if let Some((pitch, accidental)) = parse_accidental(word) {
+                self.signature[pitch_index(pitch)] = Some(accidental);
+            }
+        }
+    }
+
+    fn set_fifths(&mut self, fifths: i8) {
+        let count = fifths.unsigned_abs().min(7) as usize;
+        if fifths > 0 {
+            for &pitch in &SHARPS[..count] {
+                self.signature[pitch_index(pitch)] = Some(Accidental::Sharp);
+            }
+        } else {
+            for &pitch in SHARPS.iter().rev().take(count) {
+                self.signature[pitch_index(pitch)] = Some(Accidental::Flat);
+            }
+        }
+    }
+}
+
+impl Default for PitchModel {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// Accidentals written earlier in the current bar.
+///
+/// In ABC an accidental applies to later notes of the same pitch and octave
+/// until the end of the bar. Notes are added as they are read, and the bar
+/// is cleared at each bar line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BarAccidentals {
+    written: HashMap<(Pitch, i8), Accidental>,
+}
+
+impl BarAccidentals {
+    /// Creates an empty bar.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the accidental carried over to `note` from an earlier note
+    /// of the bar, if any.
+    pub fn carried(&self, note: &Note) -> Option<Accidental> {
+        self.written.get(&(note.pitch, note.octave)).copied()
+    }
+
+    /// Returns the accidental `note` is played with: its own, one carried
+    /// over, or the key signature's.
+    pub fn accidental(&self, key: &PitchModel, note: &Note) -> Option<Accidental> {
+        note.accidental
+            .or_else(|| self.carried(note))
+            .or_else(|| key.signature(note.pitch))
+    }
+
+    /// Returns the pitch `note` sounds at, as a MIDI note number (`C` is
+    /// middle C, 60).
+    pub fn midi_pitch(&self, key: &PitchModel, note: &Note) -> i32 {
+        let alteration = match self.accidental(key, note) {
+            Some(Accidental::Sharp) => 1,
+            Some(Accidental::DoubleSharp) => 2,
+            Some(Accidental::Flat) => -1,
+            Some(Accidental::DoubleFlat) => -2,
+            Some(Accidental::Natural) | None => 0,
+        };
+        60 + 12 * i32::from(note.octave) + semitone(note.pitch) + alteration
+    }
+
+    /// Records the accidental of `note`, if it has one.
+    pub fn add(&mut self, note: &Note) {
+        if let Some(accidental) = note.accidental {
+            self.written.insert((note.pitch, note.octave), accidental);
+        }
+    }
+
+    /// Forgets every accidental, at the end of a bar.
+    pub fn clear(&mut self) {
+        self.written.clear();
+    }
+}
+
+/// State kept for each voice, by voice id ("" before any `V:` field), in
+/// the order the voices first appear.
+#[derive(Debug, Clone)]
+pub struct Voices<T> {
+    voices: Vec<(String, T)>,
+}
+
+impl<T> Default for Voices<T> {
+    fn default() -> Self {
+        Self { voices: Vec::new() }
+    }
+}
+
+impl<T: Default> Voices<T> {
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the state of voice `id`, adding it if it is new.
+    pub fn get_mut(&mut self, id: &str) -> &mut T {
+        self.get_or_insert_with(id, T::default)
+    }
+
+    /// Returns the state of the voice `cx` is in.
+    pub fn current(&mut self, cx: &VisitContext) -> &mut T {
+        self.get_mut(cx.voice.unwrap_or_default())
+    }
+}
+
+impl<T> Voices<T> {
+    /// Returns the state of voice `id`, adding it with the state `new`
+    /// returns if it is new.
+    pub fn get_or_insert_with(&mut self, id: &str, new: impl FnOnce() -> T) -> &mut T {
+        let index = match self.voices.iter().position(|(voice, _)| voice == id) {
+            Some(index) => index,
+            None => {
+                self.voices.push((id.to_string(), new()));
+                self.voices.len() - 1
+            }
+        };
+        &mut self.voices[index].1
+    }
+
+    /// Returns the voices and their state.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &T)> {
+        self.voices.iter().map(|(id, state)| (id.as_str(), state))
+    }
+
+    /// Returns the state of every voice, to change it.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.voices.iter_mut().map(|(_, state)| state)
+    }
+
+    /// Returns the state of every voice, dropping the map.
+    pub fn into_values(self) -> impl Iterator<Item = T> {
+        self.voices.into_iter().map(|(_, state)| state)
+    }
+}
+
+/// Where a voice is in its bars and sections, to tell a pickup from a
+/// short bar.
+///
+/// Every bar line but a single one ends a section, and the bar after it
+/// starts the next; an ending goes on with its section. Notes and rests,
+/// multi-measure ones included, are added as they are read, and the bar is
+/// ended at each bar line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BarTracker {
+    total: Fraction,
+    has_music: bool,
+    multi_measure: bool,
+    section_start: bool,
+}
+
+/// A bar ended by [`BarTracker::end_bar`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarEnd {
+    /// The time of its notes and rests, multi-measure rests aside.
+    pub total: Fraction,
+    /// The meter it is in, if any.
+    pub meter: Option<Fraction>,
+    /// Whether it starts a section.
+    pub section_start: bool,
+    /// Whether its bar line ends the section.
+    pub section_end: bool,
+    /// Whether it has a multi-measure rest (`Z4`).
+    pub multi_measure: bool,
+}
+
+impl Default for BarTracker {
+    fn default() -> Self {
+        Self {
+            total: Fraction::zero(),
+            has_music: false,
+            multi_measure: false,
+            section_start: true,
+        }
+    }
+}
+
+impl BarTracker {
+    /// Returns how far into the bar the next note starts.
+    pub fn position(&self) -> Fraction {
+        self.total
+    }
+
+    /// Adds a note, chord or rest of `duration` to the bar.
+    pub fn add(&mut self, duration: Fraction) {
+        self.total = self.total + duration;
+        self.has_music = true;
+    }
+
+    /// Adds a multi-measure rest, which stands for whole bars, to the bar.
+    pub fn add_multi_measure_rest(&mut self) {
+        self.has_music = true;
+        self.multi_measure = true;
+    }
+
+    /// Starts an ending, which goes on with its section.
+    pub fn start_ending(&mut self) {
+        if !self.has_music {
+            self.section_start = false;
+        }
+    }
+
+    /// Ends the bar at a bar line of `kind`, returning it unless it was
+    /// empty. `meter` is the meter the bar is in.
+    pub fn end_bar(&mut self, kind: BarLineKind, meter: Option<Fraction>) -> Option<BarEnd> {
+        let section_end = kind != BarLineKind::Single;
+        let bar = BarEnd {
+            total: self.total,
+            meter,
+            section_start: self.section_start,
+            section_end,
+            multi_measure: self.multi_measure,
+        };
+        let empty = !self.has_music;
+        self.section_start = section_end || (empty && self.section_start);
+        self.total = Fraction::zero();
+        self.has_music = false;
+        self.multi_measure = false;
+        (!empty).then_some(bar)
+    }
+}
+
+impl BarEnd {
+    /// Returns whether the bar fills its meter, or is a multi-measure rest.
+    pub fn is_full(&self) -> bool {
+        self.multi_measure || self.meter == Some(self.total)
+    }
+
+    /// Returns the time a pickup (anacrusis) lacks of a full bar, if this
+    /// bar is one: a short bar starting a section, followed by a full bar
+    /// (`next`). Without a full bar after it, the bar is just short.
+    pub fn pickup(&self, next: Option<&BarEnd>) -> Option<Fraction> {
+        if !self.section_start || self.multi_measure || !next.is_some_and(BarEnd::is_full) {
+            return None;
+        }
+        self.meter?
+            .checked_sub(self.total)
+            .filter(|missing| !missing.is_zero())
+    }
+}
+
+/// How a repeat starts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum RepeatStart {
+    /// No `|:`; the repeat goes back to the start of the tune.
+    #[default]
+    Tune,
+    /// No `|:`; the repeat goes back to the double bar line at this range.
+    Section(TextRange),
+    /// No `|:`, right after another repeat, which ends at this range.
+    AfterRepeat(TextRange),
+    /// A written `|:` or `::`.
+    Written(BarLine),
+}
+
+/// One repeat of a voice, from its start to its `:|`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Repeat {
+    pub start: RepeatStart,
+    /// The `:|` or `::` that ends the repeat, if any.
+    pub end: Option<BarLine>,
+    /// The endings taken on each time through, in order.
+    pub endings: Vec<RepeatEnding>,
+    /// Whether there are notes, chords or rests between start and end.
+    pub has_music: bool,
+}
+
+impl Repeat {
+    fn starting(start: RepeatStart) -> Self {
+        Self {
+            start,
+            ..Self::default()
+        }
+    }
+
+    /// Returns true if the repeat starts with a `|:` or `::`.
+    pub fn is_written(&self) -> bool {
+        matches!(self.start, RepeatStart::Written(_))
+    }
+}
+
+/// An ending of a [`Repeat`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepeatEnding {
+    pub numbers: Vec<u32>,
+    pub range: TextRange,
+    /// The `:|` that goes back from this ending for the next time through.
+    pub repeat_end: Option<TextRange>,
+}
+
+/// The repeats and endings of a voice.
+///
+/// Bar lines, endings and music are added as they are read; a `:|` with no
+/// `|:` repeats from the start of the tune, the last double bar line, or the
+/// end of the previous repeat. Implied repeats that are never closed are not
+/// repeats at all, and are dropped unless they have endings.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepeatStructure {
+    repeats: Vec<Repeat>,
+    current: Repeat,
+    /// Whether endings after the last repeat's `:|` still belong to it.
+    endings_open: bool,
+}
+
+impl RepeatStructure {
+    /// Creates the structure of a voice with no bar lines yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a bar line, which may start or end repeats.
+    pub fn add_bar_line(&mut self, bar_line: &BarLine) {
+        match bar_line.kind {
+            BarLineKind::Single => {}
+            BarLineKind::Double | BarLineKind::ThinThick | BarLineKind::ThickThin => {
+                self.endings_open = false;
+                if !self.current.is_written() {
+                    let start = RepeatStart::Section(bar_line.range);
+                    self.start(Repeat::starting(start));
+                }
+            }
+            BarLineKind::RepeatStart => {
+                self.start(Repeat::starting(RepeatStart::Written(bar_line.clone())))
+            }
+            BarLineKind::RepeatEnd => self.end(bar_line),
+            BarLineKind::RepeatBoth => {
+                self.end(bar_line);
+                self.start(Repeat::starting(RepeatStart::Written(bar_line.clone())));
+            }
+        }
+    }
+
+    /// Adds the start of an ending.
+    pub fn add_ending(&mut self, ending: &Ending) {
+        let ending = RepeatEnding {
+            numbers: ending.numbers.clone(),
+            range: ending.range,
+            repeat_end: None,
+        };
+        match self.repeats.last_mut() {
+            Some(last) if self.endings_open => last.endings.push(ending),
+            _ => self.current.endings.push(ending),
+        }
+    }
+
+    /// Adds a note, chord or rest.
+    pub fn add_music(&mut self) {
+        self.current.has_music = true;
+        // Music right after a `:|` with no endings is not in an ending
+        if self
+            .repeats
+            .last()
+            .is_some_and(|last| last.endings.is_empty())
+        {
+            self.endings_open = false;
+        }
+    }
+
+    /// Returns the repeats in order, including one left open at the end.
+    pub fn finish(mut self) -> Vec<Repeat> {
+        if self.current.is_written() || !self.current.endings.is_empty() {
+            self.repeats.push(self.current);
+        }
+        self.repeats
+    }
+
+    fn start(&mut self, repeat: Repeat) {
+        self.endings_open = false;
+        let previous = std::mem::replace(&mut self.current, repeat);
+        if previous.is_written() || !previous.endings.is_empty() {
+            self.repeats.push(previous);
+        }
+    }
+
+    fn end(&mut self, bar_line: &BarLine) {
+        let after = Repeat::starting(RepeatStart::AfterRepeat(bar_line.range));
+        // A later ending goes back for another time through (`:|2 ... :|3`)
+        if self.endings_open {
+            let last = self
+                .repeats
+                .last_mut()
+                .and_then(|last| last.endings.last_mut());
+            if let Some(ending) = last.filter(|ending| ending.repeat_end.is_none()) {
+                ending.repeat_end = Some(bar_line.range);
+                self.current = after;
+                return;
+            }
+        }
+        let mut repeat = std::mem::replace(&mut self.current, after);
+        repeat.end = Some(bar_line.clone());
+        if let Some(ending) = repeat.endings.last_mut() {
+            ending.repeat_end.get_or_insert(bar_line.range);
+        }
+        self.repeats.push(repeat);
+        self.endings_open = true;
+    }
+}
+
+/// The quality of a chord symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChordQuality {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    /// `ø`, a diminished triad with a minor seventh.
+    HalfDiminished,
+}
+
+impl ChordQuality {
+    /// Returns the name of the quality ("minor").
+    pub fn name(self) -> &'static str {
+        match self {
+            ChordQuality::Major => "major",
+            ChordQuality::Minor => "minor",
+            ChordQuality::Diminished => "diminished",
+            ChordQuality::Augmented => "augmented",
+            ChordQuality::HalfDiminished => "half-diminished",
+        }
+    }
+}
+
+/// Ways of writing each quality, longest first where one is a prefix of
+/// another.
+const MAJOR_WORDS: &[&str] = &["maj", "Maj", "M", "Δ"];
+const MINOR_WORDS: &[&str] = &["min", "mi", "m", "-"];
+const DIMINISHED_WORDS: &[&str] = &["dim", "o", "°"];
+const AUGMENTED_WORDS: &[&str] = &["aug", "+"];
+const HALF_DIMINISHED_WORDS: &[&str] = &["ø"];
+
+/// A chord symbol such as `Am7`, `F#m7b5` or `G/B`.
+///
+/// The root is an upper-case note name with an optional `#` or `b`,
+/// followed by the quality (`m`, `dim`, `aug`...), extensions and
+/// alterations (`7`, `maj7`, `sus4`, `add9`, `b9`), and an optional bass
+/// note after `/`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChordSymbol {
+    pub root: Pitch,
+    pub root_accidental: Option<Accidental>,
+    pub quality: ChordQuality,
+    /// The bass note of a slash chord (`B` in `G/B`).
+    pub bass: Option<(Pitch, Option<Accidental>)>,
+    /// The quality words as written (`m` in `Am7`, `maj` in `Cmaj7`) and
+    /// where they are in the text.
+    pub spellings: Vec<(ChordQuality, Range<usize>)>,
+    /// The chord's notes, as semitones above the root.
+    intervals: Vec<u8>,
+}
+
+impl ChordSymbol {
+    /// Returns true if the text of an annotation is meant as a chord symbol:
+    /// not text placed with `^`, `_`, `<`, `>` or `@`, and not "N.C." (no
+    /// chord).
+    pub fn is_chord_text(text: &str) -> bool {
+        let text = text.trim();
+        !text.is_empty()
+            && !text.starts_with(['^', '_', '<', '>', '@'])
+            && !matches!(text, "N.C." | "N.C" | "NC")
+    }
+
+    /// Parses a chord symbol; `None` if `text` is not one.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (root, root_accidental, mut rest) = parse_chord_note(text)?;
+        let at = |rest: &str| text.len() - rest.len();
+        let root_end = at(rest);
+
+        let mut spellings = Vec::new();
+        let mut spell = |rest: &mut &str, quality, words: &[&str]| {
+            let Some(word) = words.iter().find(|word| rest.starts_with(**word)) else {
+                return false;
+            };
+            let start = at(rest);
+            *rest = &rest[word.len()..];
+            spellings.push((quality, start..start + word.len()));
+            true
+        };
+        // A major word is an extension (`maj7`), read below
+        let major = MAJOR_WORDS.iter().any(|word| rest.starts_with(word));
+        let mut quality = ChordQuality::Major;
+        if !major && spell(&mut rest, ChordQuality::Minor, MINOR_WORDS) {
+            quality = ChordQuality::Minor;
+        } else if spell(&mut rest, ChordQuality::Diminished, DIMINISHED_WORDS) {
+            quality = ChordQuality::Diminished;
+        } else if spell(&mut rest, ChordQuality::Augmented, AUGMENTED_WORDS) {
+            quality = ChordQuality::Augmented;
+        } else if spell(
+            &mut rest,
+            ChordQuality::HalfDiminished,
+            HALF_DIMINISHED_WORDS,
+        ) {
+            quality = ChordQuality::HalfDiminished;
+        }
+
+        let mut third = match quality {
+            ChordQuality::Major | ChordQuality::Augmented => Some(4),
+            _ => Some(3),
+        };
+        let mut fifth = match quality {
+            ChordQuality::Diminished | ChordQuality::HalfDiminished => 6,
+            ChordQuality::Augmented => 8,
+            _ => 7,
+        };
+        let mut seventh = quality == ChordQuality::HalfDiminished;
+        let mut major_seventh = false;
+        let mut added = Vec::new();
+        let mut bass = None;
+        while !rest.is_empty() {
+            let triangle = rest.starts_with('Δ');
+            if spell(&mut rest, ChordQuality::Major, MAJOR_WORDS) {
+                major_seventh = true;
+                // `Δ` alone is a major seventh chord
+                seventh |= triangle && !rest.starts_with(|c: char| c.is_ascii_digit());
+            } else if let Some(after) = rest.strip_prefix('/') {
+                if let Some((pitch, accidental, after)) = parse_chord_note(after) {
+                    bass = Some((pitch, accidental));
+                    rest = after;
+                    break;
+                }
+                // `6/9`
+                if !after.starts_with(|c: char| c.is_ascii_digit()) {
+                    return None;
+                }
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("sus") {
+                let (degree, after) = split_number(after);
+                third = Some(if degree == Some(2) { 2 } else { 5 });
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("add") {
+                let (degree, after) = split_number(after);
+                added.push(degree_interval(degree?)?);
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("alt") {
+                seventh = true;
+                added.extend([1, 3, 6, 8]);
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix(['b', '#']) {
+                let flat = rest.starts_with('b');
+                let (degree, after) = split_number(after);
+                match (degree?, flat) {
+                    (5, true) => fifth = 6,
+                    (5, false) => fifth = 8,
+                    (9, true) => added.push(1),
+                    (9, false) => added.push(3),
+                    (11, false) => added.push(6),
+                    (13, true) => added.push(8),
+                    _ => return None,
+                }
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix(['(', ')', ',']) {
+                rest = after;
+            } else {
+                let (degree, after) = split_number(rest);
+                match degree? {
+                    // A power chord (`C5`)
+                    5 if at(rest) == root_end => third = None,
+                    6 => added.push(9),
+                    69 => added.extend([9, 2]),
+                    7 => seventh = true,
+                    9 => {
+                        seventh = true;
+                        added.push(2);
+                    }
+                    11 => {
+                        seventh = true;
+                        added.extend([2, 5]);
+                    }
+                    13 => {
+                        seventh = true;
+                        added.extend([2, 5, 9]);
+                    }
+                    _ => return None,
+                }
+                rest = after;
+            }
+        }
+        if !rest.is_empty() {
+            return None;
+        }
+
+        let mut intervals = vec![0, fifth];
+        intervals.extend(third);
+        if seventh {
+            intervals.push(match quality {
+                _ if major_seventh => 11,
+                ChordQuality::Diminished => 9,
+                _ => 10,
+            });
+        }
+        intervals.extend(added);
+        Some(Self {
+            root,
+            root_accidental,
+            quality,
+            bass,
+            spellings,
+            intervals,
+        })
+    }
+
+    /// Returns the pitch class of the root (C is 0, C# is 1...).
+    pub fn root_pitch_class(&self) -> u8 {
+        pitch_class(self.root, self.root_accidental)
+    }
+
+    /// Returns the pitch classes of the chord's notes, bass included.
+    pub fn pitch_classes(&self) -> Vec<u8> {
+        let root = self.root_pitch_class();
+        let mut classes: Vec<_> = self
+            .intervals
+            .iter()
+            .map(|interval| (root + interval) % 12)
+            .collect();
+        classes.extend(
+            self.bass
+                .map(|(pitch, accidental)| pitch_class(pitch, accidental)),
+        );
+        classes
+    }
+}
+
+/// The properties of a voice, from the value of a `V:` field such as
+/// `T1 name="Tenor I" clef=treble-8 transpose=-12`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VoiceProperties {
+    pub id: String,
+    /// `name=` (or `nm=`), the name printed before the first staff.
+    pub name: Option<String>,
+    /// `subname=` (or `snm=`), the name printed before later staves.
+    pub subname: Option<String>,
+    /// `clef=`, or a clef name on its own (`V:2 bass`).
+    pub clef: Option<String>,
+    /// `transpose=`, the semitones the voice sounds above how it is written.
+    pub transpose: Option<i32>,
+}
+
+impl VoiceProperties {
+    /// Parses the value of a `V:` field.
+    pub fn parse(value: &str) -> Self {
+        let mut words = split_properties(value).into_iter();
+        let mut properties = Self {
+            id: words.next().unwrap_or_default(),
+            ..Self::default()
+        };
+        for word in words {
+            let Some((key, value)) = word.split_once('=') else {
+                if matches!(word.as_str(), "treble" | "alto" | "tenor" | "bass" | "perc") {
+                    properties.clef = Some(word);
+                }
+                continue;
+            };
+            let value = value.to_string();
+            match key.to_ascii_lowercase().as_str() {
+                "name" | "nm" => properties.name = Some(value),
+                "subname" | "snm" => properties.subname = Some(value),
+                "clef" => properties.clef = Some(value),
+                "transpose" => properties.transpose = value.parse().ok(),
+                _ => {}
+            }
+        }
+        properties
+    }
+}
+
+/// Splits the value of a `V:` field into words, keeping quoted values with
+/// spaces in one word and dropping the quotes.
+fn split_properties(value: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in value.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Splits a note name with an optional `#` or `b` off the start of `text`.
+fn parse_chord_note(text: &str) -> Option<(Pitch, Option<Accidental>, &str)> {
+    let mut chars = text.chars();
+    let (pitch, 0) = Pitch::from_char(chars.next()?)? else {
+        return None;
+    };
+    let rest = chars.as_str();
+    Some(if let Some(rest) = rest.strip_prefix('#') {
+        (pitch, Some(Accidental::Sharp), rest)
+    } else if let Some(rest) = rest.strip_prefix('b') {
+        (pitch, Some(Accidental::Flat), rest)
+    } else {
+        (pitch, None, rest)
+    })
+}
+
+/// Splits a number off the start of `text`.
+fn split_number(text: &str) -> (Option<u32>, &str) {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    (text[..end].parse().ok(), &text[end..])
+}
+
+/// Returns the semitones above the root of an added degree (`add9`).
+fn degree_interval(degree: u32) -> Option<u8> {
+    match degree {
+        2 | 9 => Some(2),
+        4 | 11 => Some(5),
+        6 | 13 => Some(9),
+        _ => None,
+    }
+}
+
+/// Returns the pitch class of a note (C is 0, C# is 1...).
+fn pitch_class(pitch: Pitch, accidental: Option<Accidental>) -> u8 {
+    let alteration = match accidental {
+        Some(Accidental::Sharp) => 1,
+        Some(Accidental::DoubleSharp) => 2,
+        Some(Accidental::Flat) => -1,
+        Some(Accidental::DoubleFlat) => -2,
+        Some(Accidental::Natural) | None => 0,
+    };
+    (semitone(pitch) + alteration).rem_euclid(12) as u8
+}
+
+/// Returns the semitones of a natural note above C.
+fn semitone(pitch: Pitch) -> i32 {
+    match pitch {
+        Pitch::C => 0,
+        Pitch::D => 2,
+        Pitch::E => 4,
+        Pitch::F => 5,
+        Pitch::G => 7,
+        Pitch::A => 9,
+        Pitch::B => 11,
+    }
+}
+
+fn pitch_index(pitch: Pitch) -> usize {
+    match pitch {
+        Pitch::C => 0,
+        Pitch::D => 1,
+        Pitch::E => 2,
+        Pitch::F => 3,
+        Pitch::G => 4,
+        Pitch::A => 5,
+        Pitch::B => 6,
+    }
+}
+
+/// Splits a word like "F#m" into tonic, accidental and the rest.
+fn parse_tonic(word: &str) -> Option<(Pitch, Option<Accidental>, &str)> {
+    let mut chars = word.chars();
+    let (pitch, 0) = Pitch::from_char(chars.next()?)? else {
+        return None;
+    };
+    let rest = chars.as_str();
+    let (accidental, rest) = if let Some(rest) = rest.strip_prefix('#') {
+        (Some(Accidental::Sharp), rest)
+    } else if let Some(rest) = rest.strip_prefix('b') {
+        (Some(Accidental::Flat), rest)
+    } else {
+        (None, rest)
+    };
+    // "Gclef=..." or similar is not a key
+    if !rest.is_empty() && Mode::parse(rest).is_none() {
+        return None;
+    }
+    Some((pitch, accidental, rest))
+}
+
+/// Position of a major key on the circle of fifths.
+fn tonic_fifths(tonic: Pitch, accidental: Option<Accidental>) -> i8 {
+    let natural = match tonic {
+        Pitch::F => -1,
+        Pitch::C => 0,
+        Pitch::G => 1,
+        Pitch::D => 2,
+        Pitch::A => 3,
+        Pitch::E => 4,
+        Pitch::B => 5,
+    };
+    match accidental {
+        Some(Accidental::Sharp) => natural + 7,
+        Some(Accidental::Flat) => natural - 7,
+        _ => natural,
+    }
+}
+
+/// Parses an explicit accidental like "^f", "_B" or "=c".
+fn parse_accidental(word: &str) -> Option<(Pitch, Accidental)> {
+    let (accidental, rest) = if let Some(rest) = word.strip_prefix("^^") {
+        (Accidental::DoubleSharp, rest)
+    } else if let Some(rest) = word.strip_prefix("__") {
+        (Accidental::DoubleFlat, rest)
+    } else if let Some(rest) = word.strip_prefix('^') {
+        (Accidental::Sharp, rest)
+    } else if let Some(rest) = word.strip_prefix('_') {
+        (Accidental::Flat, rest)
+    } else if let Some(rest) = word.strip_prefix('=') {
+        (Accidental::Natural, rest)
+    } else {
+        return None;
+    };
+    let mut chars = rest.chars();
+    let (pitch, _) = Pitch::from_char(chars.next()?)?;
+    chars.as_str().is_empty().then_some((pitch, accidental))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fraction_arithmetic() {
+        let sum = Fraction::new(1, 4) + Fraction::new(1, 8);
+        assert_eq!(sum, Fraction::new(3, 8));
+        assert_eq!(
+            Fraction::new(1, 2) * Fraction::new(2, 3),
+            Fraction::new(1, 3)
+        );
+        assert_eq!(Fraction::new(2, 4).to_string(), "1/2");
+        assert!(Fraction::new(1, 2) < Fraction::new(3, 4));
+        assert!(Fraction::new(0, 5).is_zero());
+    }
+
+    #[test]
+    fn test_fraction_overflow() {
+        // The exact sum has a denominator of 3 * 5 * ... * 31, past u32
+        let primes = [3, 5, 7, 11, 13, 17, 19, 23, 29, 31];
+        let sum = primes
+            .iter()
+            .fold(Fraction::zero(), |sum, &p| sum + Fraction::new(1, p));
+        assert!(sum > Fraction::new(1, 1) && sum < Fraction::new(11, 10));
+
+        let big = Fraction::new(u32::MAX, u32::MAX - 1);
+        assert!(big * big > Fraction::new(1, 1));
+        assert_eq!(big.checked_sub(big), Some(Fraction::zero()));
+        assert!(big.checked_sub(sum).is_none());
+    }
+
+    #[test]
+    fn test_meter() {
+        let meter = |value| TimingModel::new(Some(value), None).meter();
+        assert_eq!(meter("6/8"), Some(Fraction::new(6, 8)));
+        assert_eq!(meter("C"), Some(Fraction::new(4, 4)));
+        assert_eq!(meter("C|"), Some(Fraction::new(2, 2)));
+        assert_eq!(meter("2+3/8"), Some(Fraction::new(5, 8)));
+        assert_eq!(meter("none"), None);
+        assert_eq!(TimingModel::default().meter(), Some(Fraction::new(4, 4)));
+    }
+
+    #[test]
+    fn test_beat_structure() {
+        let beats = |value| BeatStructure::new(Some(value)).beats().to_vec();
+        assert_eq!(beats("3/4"), [Fraction::new(1, 4); 3]);
+        assert_eq!(beats("6/8"), [Fraction::new(3, 8); 2]);
+        assert_eq!(beats("3/8"), [Fraction::new(3, 8)]);
+        assert_eq!(beats("C|"), [Fraction::new(1, 2); 2]);
+        assert_eq!(beats("2+3/8"), [Fraction::new(2, 8), Fraction::new(3, 8)]);
+        assert!(beats("none").is_empty());
+
+        // Beams may join two beats in 4/4
+        let common = BeatStructure::default();
+        assert_eq!(common.beats(), [Fraction::new(1, 4); 4]);
+        assert_eq!(common.beam_groups(), [Fraction::new(1, 2); 2]);
+
+        let mut structure = BeatStructure::new(Some("9/8"));
+        structure.apply_field('M', "oops");
+        assert_eq!(structure.beats(), [Fraction::new(3, 8); 3]);
+        structure.apply_field('M', "(3+3+2)/8");
+        assert_eq!(structure.beam_groups().len(), 3);
+
+        // The middle of the bar is strong where a beam group starts there
+        assert!(common.is_strong(Fraction::zero()));
+        assert!(common.is_strong(Fraction::new(1, 2)));
+        assert!(!common.is_strong(Fraction::new(1, 4)));
+        assert!(BeatStructure::new(Some("6/8")).is_strong(Fraction::new(3, 8)));
+        assert!(!BeatStructure::new(Some("3/4")).is_strong(Fraction::new(3, 8)));
+    }
+
+    #[test]
+    fn test_default_unit_note_length() {
+        let unit = |meter| TimingModel::new(meter, None).unit_note_length();
+        assert_eq!(unit(None), Fraction::new(1, 8));
+        assert_eq!(unit(Some("3/4")), Fraction::new(1, 8));
+        assert_eq!(unit(Some("2/4")), Fraction::new(1, 16));
+        assert_eq!(unit(Some("none")), Fraction::new(1, 8));
+
+        let timing = TimingModel::new(Some("2/4"), Some("1/4"));
+        assert_eq!(timing.unit_note_length(), Fraction::new(1, 4));
+    }
+
+    #[test]
+    fn test_inline_fields() {
+        let mut timing = TimingModel::new(Some("2/4"), None);
+        timing.apply_field('M', "6/8");
+        assert_eq!(timing.meter(), Some(Fraction::new(6, 8)));
+        // Still derived from the header's meter
+        assert_eq!(timing.unit_note_length(), Fraction::new(1, 16));
+
+        timing.apply_field('L', "1/8");
+        let duration = Duration::new(3, 2);
+        assert_eq!(timing.duration(Some(&duration)), Fraction::new(3, 16));
+    }
+
+    fn signature(key: &str) -> String {
+        let pitch = PitchModel::new(Some(key));
+        [
+            Pitch::C,
+            Pitch::D,
+            Pitch::E,
+            Pitch::F,
+            Pitch::G,
+            Pitch::A,
+            Pitch::B,
+        ]
+        .iter()
+        .map(|&p| match pitch.signature(p) {
+            Some(Accidental::Sharp) => '#',
+            Some(Accidental::Flat) => 'b',
+            Some(Accidental::Natural) => '=',
+            Some(_) => '?',
+            None => '.',
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_key_signatures() {
+        assert_eq!(signature("C"), ".......");
+        assert_eq!(signature("G"), "...#...");
+        assert_eq!(signature("D"), "#..#...");
+        assert_eq!(signature("F"), "......b");
+        assert_eq!(signature("Bb"), "..b...b");
+        assert_eq!(signature("C#"), "#######");
+        assert_eq!(signature("Cb"), "bbbbbbb");
+    }
+
+    #[test]
+    fn test_key_modes() {
+        assert_eq!(signature("Am"), ".......");
+        assert_eq!(signature("Em"), "...#...");
+        assert_eq!(signature("D dorian"), ".......");
+        assert_eq!(signature("ADor"), "...#...");
+        assert_eq!(signature("G mix"), ".......");
+        assert_eq!(signature("F#m"), "#..##..");
+
+        let pitch = PitchModel::new(Some("E minor clef=treble"));
+        assert_eq!(pitch.tonic(), Some((Pitch::E, None)));
+        assert_eq!(pitch.mode(), Mode::Minor);
+    }
+
+    #[test]
+    fn test_explicit_accidentals() {
+        assert_eq!(signature("D ^g"), "#..##..");
+        assert_eq!(signature("D exp _b"), "......b");
+        assert_eq!(signature("none"), ".......");
+        assert_eq!(signature("HP"), ".......");
+        assert_eq!(signature("Hp"), "#..#=..");
+        assert_eq!(PitchModel::new(Some("none")).tonic(), None);
+    }
+
+    #[test]
+    fn test_inline_key_change() {
+        let mut pitch = PitchModel::new(Some("G"));
+        pitch.apply_field('K', "clef=bass");
+        assert_eq!(pitch.signature(Pitch::F), Some(Accidental::Sharp));
+
+        pitch.apply_field('K', "F");
+        assert_eq!(pitch.signature(Pitch::F), None);
+        assert_eq!(pitch.signature(Pitch::B), Some(Accidental::Flat));
+    }
+
+    #[test]
+    fn test_bar_accidentals() {
+        let key = PitchModel::new(Some("G"));
+        let notes = |source: &str| -> Vec<Note> {
+            chamber_parser::parse(&format!("X:1\nK:G\n{}", source))
+                .body
+                .elements
+                .into_iter()
+                .filter_map(|element| match element {
+                    chamber_ast::MusicElement::Note(note) => Some(note),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // =F carries over to the next F of the same octave only
+        let notes = notes("=F F f C");
+        let mut bar = BarAccidentals::new();
+        bar.add(&notes[0]);
+        assert_eq!(bar.accidental(&key, &notes[1]), Some(Accidental::Natural));
+        assert_eq!(bar.midi_pitch(&key, &notes[1]), 65);
+        assert_eq!(bar.midi_pitch(&key, &notes[2]), 78);
+        assert_eq!(bar.midi_pitch(&key, &notes[3]), 60);
+
+        bar.clear();
+        assert_eq!(bar.midi_pitch(&key, &notes[1]), 66);
+    }
+
+    fn repeats(source: &str) -> Vec<Repeat> {
+        let tune = chamber_parser::parse(&format!("X:1\nK:G\n{}", source));
+        let mut structure = RepeatStructure::new();
+        for element in &tune.body.elements {
+            match element {
+                chamber_ast::MusicElement::BarLine(bar_line) => structure.add_bar_line(bar_line),
+                chamber_ast::MusicElement::Ending(ending) => structure.add_ending(ending),
+                chamber_ast::MusicElement::Note(_) => structure.add_music(),
+                _ => {}
+            }
+        }
+        structure.finish()
+    }
+
+    #[test]
+    fn test_repeat_structure() {
+        // An implied start at the tune and after the first repeat
+        let found = repeats("A:|B:|");
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].start, RepeatStart::Tune);
+        assert!(matches!(found[1].start, RepeatStart::AfterRepeat(_)));
+
+        // Implied repeats that are never closed are dropped
+        assert!(repeats("A|B||C|]").is_empty());
+        let found = repeats("A||B:|");
+        assert!(matches!(found[0].start, RepeatStart::Section(_)));
+
+        // `::` ends one repeat and starts the next, left open here
+        let found = repeats("|:A::B");
+        assert_eq!(found.len(), 2);
+        assert!(found[0].end.is_some() && found[0].is_written());
+        assert!(found[1].end.is_none() && found[1].is_written());
+    }
+
+    #[test]
+    fn test_repeat_endings() {
+        let found = repeats("|:A|1B:|2C:|3D|]E|");
+        assert_eq!(found.len(), 1);
+        let numbers: Vec<_> = found[0].endings.iter().map(|e| e.numbers[0]).collect();
+        assert_eq!(numbers, [1, 2, 3]);
+        let closed: Vec<_> = found[0]
+            .endings
+            .iter()
+            .map(|e| e.repeat_end.is_some())
+            .collect();
+        assert_eq!(closed, [true, true, false]);
+
+        // An ending with no repeat is kept in an open, implied one
+        let found = repeats("A|B:|C|[2D|]");
+        assert_eq!(found.len(), 2);
+        assert!(!found[1].is_written() && found[1].end.is_none());
+        assert_eq!(found[1].endings.len(), 1);
+    }
+
+    #[test]
+    fn test_chord_symbols() {
+        let classes = |text| {
+            let mut classes = ChordSymbol::parse(text).unwrap().pitch_classes();
+            classes.sort_unstable();
+            classes.dedup();
+            classes
+        };
+        assert_eq!(classes("C"), [0, 4, 7]);
+        assert_eq!(classes("Am7"), [0, 4, 7, 9]);
+        assert_eq!(classes("F#m7b5"), [0, 4, 6, 9]);
+        assert_eq!(classes("Bbmaj7"), [2, 5, 9, 10]);
+        assert_eq!(classes("CΔ"), [0, 4, 7, 11]);
+        assert_eq!(classes("Dsus4"), [2, 7, 9]);
+        assert_eq!(classes("G7/B"), [2, 5, 7, 11]);
+        assert_eq!(classes("C5"), [0, 7]);
+        assert_eq!(classes("Bdim7"), [2, 5, 8, 11]);
+
+        let chord = ChordSymbol::parse("AmM7").unwrap();
+        assert_eq!(chord.quality, ChordQuality::Minor);
+        assert_eq!(
+            chord.spellings,
+            [(ChordQuality::Minor, 1..2), (ChordQuality::Major, 2..3)]
+        );
+        let chord = ChordSymbol::parse("Ebmin(add9)/G").unwrap();
+        assert_eq!(chord.root_accidental, Some(Accidental::Flat));
+        assert_eq!(chord.bass, Some((Pitch::G, None)));
+
+        for text in ["H7", "Cx", "C7/", "Fine", "Cadd3"] {
+            assert!(ChordSymbol::parse(text).is_none(), "{}", text);
+        }
+        assert!(!ChordSymbol::is_chord_text("^Fine"));
+        assert!(!ChordSymbol::is_chord_text("N.C."));
+    }
+
+    #[test]
+    fn test_voice_properties() {
+        let voice = VoiceProperties::parse("T1 name=\"Tenor I\" snm=T clef=treble-8 transpose=-12");
+        assert_eq!(voice.id, "T1");
+        assert_eq!(voice.name.as_deref(), Some("Tenor I"));
+        assert_eq!(voice.subname.as_deref(), Some("T"));
+        assert_eq!(voice.clef.as_deref(), Some("treble-8"));
+        assert_eq!(voice.transpose, Some(-12));
+
+        let voice = VoiceProperties::parse("2 bass");
+        assert_eq!(voice.clef.as_deref(), Some("bass"));
+        assert_eq!(voice.name, None);
+        assert_eq!(voice.transpose, None);
+    }
+
+    #[test]
+    fn test_normalize_meter() {
+        assert_eq!(normalize_meter(" C "), "4/4");
+        assert_eq!(normalize_meter("C|"), "2/2");
+        assert_eq!(normalize_meter("2 + 3/8"), "2+3/8");
+    }
+
+    #[test]
+    fn test_bar_tracker() {
+        let meter = Some(Fraction::new(1, 1));
+        let mut bar = BarTracker::default();
+        // `|:GA|BcdB cBAG|1 ... :|2 ...|]`
+        let end = bar.end_bar(BarLineKind::RepeatStart, meter);
+        assert_eq!(end, None);
+        bar.add(Fraction::new(1, 4));
+        let pickup = bar.end_bar(BarLineKind::Single, meter).unwrap();
+        assert!(pickup.section_start && !pickup.section_end);
+        bar.add(Fraction::new(1, 1));
+        let full = bar.end_bar(BarLineKind::Single, meter).unwrap();
+        assert!(!full.section_start);
+        assert_eq!(pickup.pickup(Some(&full)), Some(Fraction::new(3, 4)));
+        assert_eq!(pickup.pickup(None), None);
+        assert_eq!(full.pickup(Some(&full)), None);
+        bar.add(Fraction::new(3, 4));
+        let closing = bar.end_bar(BarLineKind::RepeatEnd, meter).unwrap();
+        assert!(closing.section_end);
+        bar.start_ending();
+        bar.add(Fraction::new(3, 4));
+        let ending = bar.end_bar(BarLineKind::ThinThick, meter).unwrap();
+        assert!(!ending.section_start);
+
+        // A multi-measure rest is music
+        bar.add_multi_measure_rest();
+        let rest = bar.end_bar(BarLineKind::Single, meter).unwrap();
+        assert!(rest.section_start && rest.multi_measure);
+        assert_eq!(rest.total, Fraction::zero());
+    }
+}
@@ -0,0 +1,413 @@
+//! Registry of the rules the analyzer can run.
+
+use std::any::Any;
+use std::sync::Arc;
+
+use chamber_ast::visit::{walk_tune, Visitor};
+use chamber_ast::Tune;
+use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use serde::Serialize;
+
+use crate::context::{RuleContext, TuneContext};
+use crate::rule::{Category, CollectionRule, Rule, RuleOptions};
+use crate::rules::{
+    BarLength, BeamGrouping, ChordMelodyClash, ChordSpelling, CustomRules, DuplicateMelody,
+    DuplicateTitle, DuplicateTuneNumber, InconsistentDirectives, InstrumentRange,
+    InvalidChordSymbol, InvalidTie, LyricsAlignment, RedundantAccidental, RequiredFields,
+    RhythmMeter, SuspiciousDuration, TuneNumbering, UnbalancedRepeat, UnitNoteLength,
+    UnknownDecoration, UnusualOctave, VoiceConsistency,
+};
+
+/// A rule's metadata, as plain data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RuleInfo {
+    /// The unique name of the rule (e.g., "unknownDecoration").
+    pub name: &'static str,
+    /// The diagnostic code for this rule.
+    pub code: DiagnosticCode,
+    /// The default severity.
+    pub severity: Severity,
+    /// The category of this rule.
+    pub category: Category,
+    /// Whether the rule checks single tunes or whole collections.
+    pub scope: Scope,
+    /// A short description of what this rule checks.
+    pub docs: &'static str,
+    /// Whether the rule runs when the configuration doesn't set its level.
+    pub enabled: bool,
+}
+
+/// What a rule looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// One tune at a time (a [`Rule`]).
+    Tune,
+    /// All the tunes of a collection (a [`CollectionRule`]).
+    Collection,
+}
+
+/// A rule's options after they were read from the configuration.
+///
+/// Holds the rule's own [`Rule::Options`] type.
+pub(crate) type ResolvedOptions = Arc<dyn Any + Send + Sync>;
+
+/// A rule's state while a tune is walked, with its type erased.
+pub(crate) trait RuleState: Visitor {
+    /// Returns the diagnostics collected during the walk. `options` are
+    /// those the state was started with.
+    fn finish(
+        self: Box<Self>,
+        tune: &TuneContext,
+        options: Option<&ResolvedOptions>,
+    ) -> Vec<Diagnostic>;
+}
+
+impl<R: Rule> RuleState for R {
+    fn finish(
+        self: Box<Self>,
+        tune: &TuneContext,
+        options: Option<&ResolvedOptions>,
+    ) -> Vec<Diagnostic> {
+        with_options::<R::Options, _>(options, |options| {
+            Rule::finish(*self, &RuleContext::new(tune, options))
+        })
+    }
+}
+
+/// A collection rule's state while a collection is read, with its type
+/// erased.
+pub(crate) trait CollectionState {
+    /// Shows the rule the next tune of the collection.
+    fn visit_tune(&mut self, tune: &TuneContext, options: Option<&ResolvedOptions>);
+
+    /// Returns the diagnostics found so far and not yet returned.
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic>;
+
+    /// Returns the diagnostics collected over the collection.
+    fn finish(self: Box<Self>, options: Option<&ResolvedOptions>) -> Vec<Diagnostic>;
+}
+
+impl<R: CollectionRule> CollectionState for R {
+    fn visit_tune(&mut self, tune: &TuneContext, options: Option<&ResolvedOptions>) {
+        with_options::<R::Options, _>(options, |options| {
+            CollectionRule::visit_tune(self, &RuleContext::new(tune, options))
+        })
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        CollectionRule::take_diagnostics(self)
+    }
+
+    fn finish(self: Box<Self>, options: Option<&ResolvedOptions>) -> Vec<Diagnostic> {
+        with_options::<R::Options, _>(options, |options| CollectionRule::finish(*self, options))
+    }
+}
+
+/// How to start a rule, by scope.
+#[derive(Debug, Clone, Copy)]
+enum Start {
+    Tune(fn(&TuneContext, Option<&ResolvedOptions>) -> Box<dyn RuleState>),
+    Collection(fn(Option<&ResolvedOptions>) -> Box<dyn CollectionState>),
+}
+
+/// A rule in the registry: its metadata and how to run it.
+#[derive(Debug, Clone, Copy)]
+pub struct RegisteredRule {
+    /// The rule's metadata.
+    pub info: RuleInfo,
+    start: Start,
+    resolve_options: fn(&serde_json::Value) -> Result<ResolvedOptions, serde_json::Error>,
+    options_to_json: fn(Option<&ResolvedOptions>) -> serde_json::Value,
+}
+
+impl RegisteredRule {
+    /// Returns the registry entry for rule `R`.
+    pub fn of<R: Rule>() -> Self {
+        Self {
+            info: RuleInfo {
+                name: R::NAME,
+                code: R::CODE,
+                severity: R::SEVERITY,
+                category: R::CATEGORY,
+                scope: Scope::Tune,
+                docs: R::DOCS,
+                enabled: R::ENABLED,
+            },
+            start: Start::Tune(start_rule::<R>),
+            resolve_options: resolve_options::<R::Options>,
+            options_to_json: options_to_json::<R::Options>,
+        }
+    }
+
+    /// Returns the registry entry for collection rule `R`.
+    pub fn of_collection<R: CollectionRule>() -> Self {
+        Self {
+            info: RuleInfo {
+                name: R::NAME,
+                code: R::CODE,
+                severity: R::SEVERITY,
+                category: R::CATEGORY,
+                scope: Scope::Collection,
+                docs: R::DOCS,
+                enabled: R::ENABLED,
+            },
+            start: Start::Collection(start_collection_rule::<R>),
+            resolve_options: resolve_options::<R::Options>,
+            options_to_json: options_to_json::<R::Options>,
+        }
+    }
+
+    /// Runs the rule with its default options and collects diagnostics.
+    ///
+    /// Collection rules report nothing for a single tune.
+    pub fn run(&self, tune: &Tune, diagnostics: &mut Vec<Diagnostic>) {
+        let tune = TuneContext::new(tune, None);
+        if let Some(mut state) = self.start(&tune, None) {
+            walk_tune(&mut state, tune.tune());
+            diagnostics.extend(state.finish(&tune, None));
+        }
+    }
+
+    /// Creates the rule's state for walking one tune, with options from
+    /// [`RegisteredRule::resolve_options`] or the defaults if `None`.
+    ///
+    /// Returns `None` for collection rules.
+    pub(crate) fn start(
+        &self,
+        tune: &TuneContext,
+        options: Option<&ResolvedOptions>,
+    ) -> Option<Box<dyn RuleState>> {
+        match self.start {
+            Start::Tune(start) => Some(start(tune, options)),
+            Start::Collection(_) => None,
+        }
+    }
+
+    /// Creates the rule's state for reading one collection.
+    ///
+    /// Returns `None` for rules that check single tunes.
+    pub(crate) fn start_collection(
+        &self,
+        options: Option<&ResolvedOptions>,
+    ) -> Option<Box<dyn CollectionState>> {
+        match self.start {
+            Start::Tune(_) => None,
+            Start::Collection(start) => Some(start(options)),
+        }
+    }
+
+    /// Reads the rule's options from their JSON form.
+    pub(crate) fn resolve_options(
+        &self,
+        value: &serde_json::Value,
+    ) -> Result<ResolvedOptions, serde_json::Error> {
+        (self.resolve_options)(value)
+    }
+
+    /// Returns the rule's default options as JSON.
+    pub fn default_options(&self) -> serde_json::Value {
+        (self.options_to_json)(None)
+    }
+
+    /// Returns resolved options (or the defaults) as JSON.
+    pub(crate) fn options_to_json(&self, options: Option<&ResolvedOptions>) -> serde_json::Value {
+        (self.options_to_json)(options)
+    }
+}
+
+/// Calls `f` with the rule's resolved options, or its defaults.
+fn with_options<O: RuleOptions, T>(
+    options: Option<&ResolvedOptions>,
+    f: impl FnOnce(&O) -> T,
+) -> T {
+    match options.and_then(|options| options.downcast_ref::<O>()) {
+        Some(options) => f(options),
+        None => f(&O::default()),
+    }
+}
+
+fn start_rule<R: Rule>(
+    tune: &TuneContext,
+    options: Option<&ResolvedOptions>,
+) -> Box<dyn RuleState> {
+    with_options::<R::Options, _>(options, |options| {
+        Box::new(R::new(&RuleContext::new(tune, options)))
+    })
+}
+
+fn start_collection_rule<R: CollectionRule>(
+    options: Option<&ResolvedOptions>,
+) -> Box<dyn CollectionState> {
+    with_options::<R::Options, _>(options, |options| Box::new(R::new(options)))
+}
+
+fn resolve_options<O: RuleOptions>(
+    value: &serde_json::Value,
+) -> Result<ResolvedOptions, serde_json::Error> {
+    let options: O = serde_json::from_value(value.clone())?;
+    Ok(Arc::new(options))
+}
+
+fn options_to_json<O: RuleOptions>(options: Option<&ResolvedOptions>) -> serde_json::Value {
+    let value = match options.and_then(|options| options.downcast_ref::<O>()) {
+        Some(options) => serde_json::to_value(options),
+        None => serde_json::to_value(O::default()),
+    };
+    value.expect("rule options serialize to JSON")
+}
+
+/// The set of rules available to an [`Analyzer`](crate::Analyzer), keyed by name.
+#[derive(Debug, Clone)]
+pub struct RuleRegistry {
+    rules: Vec<RegisteredRule>,
+}
+
+impl RuleRegistry {
+    /// Creates a registry with no rules.
+    pub fn empty() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// Creates a registry with all built-in rules.
+    pub fn builtin() -> Self {
+        let mut registry = Self::empty();
+        registry.register::<UnknownDecoration>();
+        registry.register::<UnusualOctave>();
+        registry.register::<SuspiciousDuration>();
+        registry.register::<BarLength>();
+        registry.register::<CustomRules>();
+        registry.register::<InvalidTie>();
+        registry.register::<RedundantAccidental>();
+        registry.register::<UnbalancedRepeat>();
+        registry.register::<BeamGrouping>();
+        registry.register::<InvalidChordSymbol>();
+        registry.register::<ChordMelodyClash>();
+        registry.register::<ChordSpelling>();
+        registry.register::<InstrumentRange>();
+        registry.register::<VoiceConsistency>();
+        registry.register::<LyricsAlignment>();
+        registry.register::<RequiredFields>();
+        registry.register::<RhythmMeter>();
+        registry.register::<UnitNoteLength>();
+        registry.register_collection::<DuplicateTuneNumber>();
+        registry.register_collection::<DuplicateTitle>();
+        registry.register_collection::<DuplicateMelody>();
+        registry.register_collection::<InconsistentDirectives>();
+        registry.register_collection::<TuneNumbering>();
+        registry
+    }
+
+    /// Adds rule `R`, replacing any rule with the same name.
+    pub fn register<R: Rule>(&mut self) {
+        self.insert(RegisteredRule::of::<R>());
+    }
+
+    /// Adds collection rule `R`, replacing any rule with the same name.
+    pub fn register_collection<R: CollectionRule>(&mut self) {
+        self.insert(RegisteredRule::of_collection::<R>());
+    }
+
+    fn insert(&mut self, rule: RegisteredRule) {
+        match self
+            .rules
+            .iter_mut()
+            .find(|r| r.info.name == rule.info.name)
+        {
+            Some(existing) => *existing = rule,
+            None => self.rules.push(rule),
+        }
+    }
+
+    /// Looks up a rule by name.
+    pub fn get(&self, name: &str) -> Option<&RegisteredRule> {
+        self.rules.iter().find(|r| r.info.name == name)
+    }
+
+    /// Resolves a diagnostic code ("W003") or the name of a rule
+    /// ("barLength") to a code.
+    pub fn resolve_code(&self, target: &str) -> Option<DiagnosticCode> {
+        DiagnosticCode::from_code(target).or_else(|| self.get(target).map(|r| r.info.code))
+    }
+
+    /// Returns the rules in registration order.
+    pub fn iter(&self) -> impl Iterator<Item = &RegisteredRule> {
+        self.rules.iter()
+    }
+
+    /// Returns the metadata of every rule, for listing.
+    pub fn infos(&self) -> Vec<RuleInfo> {
+        self.rules.iter().map(|r| r.info).collect()
+    }
+}
+
+impl Default for RuleRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::RuleMeta;
+
+    #[test]
+    fn test_builtin_rules() {
+        let registry = RuleRegistry::builtin();
+        let names: Vec<_> = registry.iter().map(|r| r.info.name).collect();
+        assert_eq!(
+            names,
+            [
+                "unknownDecoration",
+                "unusualOctave",
+                "suspiciousDuration",
+                "barLength",
+                "customRules",
+                "invalidTie",
+                "redundantAccidental",
+                "unbalancedRepeat",
+                "beamGrouping",
+                "invalidChordSymbol",
+                "chordMelodyClash",
+                "chordSpelling",
+                "instrumentRange",
+                "voiceConsistency",
+                "lyricsAlignment",
+                "requiredFields",
+                "rhythmMeter",
+                "unitNoteLength",
+                "duplicateTuneNumber",
+                "duplicateTitle",
+                "duplicateMelody",
+                "inconsistentDirectives",
+                "tuneNumbering"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lookup_by_name() {
+        let registry = RuleRegistry::builtin();
+        let rule = registry.get(UnusualOctave::NAME).unwrap();
+        assert_eq!(rule.info.code, DiagnosticCode::UnusualOctave);
+        assert_eq!(rule.info.docs, UnusualOctave::DOCS);
+        assert!(registry.get("noSuchRule").is_none());
+    }
+
+    #[test]
+    fn test_scope() {
+        let registry = RuleRegistry::builtin();
+        assert_eq!(registry.get("barLength").unwrap().info.scope, Scope::Tune);
+        let rule = registry.get("duplicateTuneNumber").unwrap();
+        assert_eq!(rule.info.scope, Scope::Collection);
+        assert!(rule.start_collection(None).is_some());
+    }
+
+    #[test]
+    fn test_register_replaces_same_name() {
+        let mut registry = RuleRegistry::builtin();
+        registry.register::<UnusualOctave>();
+        assert_eq!(registry.iter().count(), 23);
+    }
+}
@@ -1,10 +1,15 @@
 //! Rule trait and infrastructure for semantic analysis.
 
+use chamber_ast::visit::{walk_tune, Visitor};
 use chamber_ast::Tune;
 use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::context::{RuleContext, TuneContext};
 
 /// Category of a rule.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Category {
     /// Lint rules check for potential errors and bad practices.
     Lint,
@@ -28,26 +33,137 @@ pub trait RuleMeta {
 
     /// A short description of what this rule checks.
     const DOCS: &'static str;
+
+    /// Whether the rule runs when the configuration doesn't set its level.
+    /// Rules that are a matter of taste are off until they are given one.
+    const ENABLED: bool = true;
 }
 
+/// Options of a rule, read from the `options` of an
+/// [`AnalyzerConfig`](crate::AnalyzerConfig).
+///
+/// Implemented for every type that can be deserialized and has a default;
+/// the default is used when the configuration has no options for the rule.
+pub trait RuleOptions: Default + Serialize + DeserializeOwned + Send + Sync + 'static {}
+
+impl<T: Default + Serialize + DeserializeOwned + Send + Sync + 'static> RuleOptions for T {}
+
+/// Options of a rule that has none.
+///
+/// Only an empty object (`{}`) is accepted in the configuration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NoOptions {}
+
 /// A semantic analysis rule.
 ///
 /// Rules are run on the parsed AST to check for semantic issues
 /// that cannot be detected during parsing.
-pub trait Rule: RuleMeta {
-    /// Run the rule on the given tune and collect diagnostics.
-    fn run(tune: &Tune, diagnostics: &mut Vec<Diagnostic>);
+///
+/// A rule is a [`Visitor`] that holds its state for one tune: it is created
+/// with [`Rule::new`], sees the tune's elements through the `visit_*` methods
+/// it implements, and returns its diagnostics from [`Rule::finish`]. The
+/// analyzer walks each tune once for all rules.
+///
+/// Both `new` and `finish` get a [`RuleContext`] with the rule's options,
+/// the resolved timing and pitch models, and the source text when known.
+/// A rule copies what it needs during the walk into its own state.
+pub trait Rule: RuleMeta + Visitor + Sized + 'static {
+    /// The rule's options; use [`NoOptions`] if it has none.
+    type Options: RuleOptions;
+
+    /// Creates the rule's state for checking one tune.
+    fn new(cx: &RuleContext<Self::Options>) -> Self;
+
+    /// Returns the diagnostics collected while the tune was visited.
+    fn finish(self, cx: &RuleContext<Self::Options>) -> Vec<Diagnostic>;
+}
+
+/// A rule that looks at all the tunes of a collection together.
+///
+/// Some problems only show up across a file, such as two tunes with the same
+/// `X:` number. A collection rule is created once per collection with
+/// [`CollectionRule::new`], sees each tune in turn through
+/// [`CollectionRule::visit_tune`] (after the per-tune rules ran on it), and
+/// returns its diagnostics from [`CollectionRule::finish`]. Diagnostics
+/// found while seeing a tune can be handed out as they are found through
+/// [`CollectionRule::take_diagnostics`], so callers can show them while the
+/// tune's source is still at hand.
+///
+/// Tunes are seen one at a time so that large collections can be streamed;
+/// a rule keeps only what it needs to compare later tunes with. Diagnostics
+/// have ranges in the whole collection (see [`RuleContext::offset`]) and
+/// point at the other occurrence with a label.
+pub trait CollectionRule: RuleMeta + Sized + 'static {
+    /// The rule's options; use [`NoOptions`] if it has none.
+    type Options: RuleOptions;
+
+    /// Creates the rule's state for checking one collection.
+    fn new(options: &Self::Options) -> Self;
+
+    /// Sees the next tune of the collection.
+    fn visit_tune(&mut self, cx: &RuleContext<Self::Options>);
+
+    /// Returns the diagnostics found so far and not yet returned. By default
+    /// everything is returned by [`CollectionRule::finish`].
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        Vec::new()
+    }
+
+    /// Returns the diagnostics collected over the collection, except those
+    /// already returned by [`CollectionRule::take_diagnostics`].
+    fn finish(self, options: &Self::Options) -> Vec<Diagnostic>;
 }
 
 /// Extension trait for running rules.
 pub trait RuleExt: Rule {
-    /// Run this rule and return the diagnostics.
+    /// Run this rule with its default options and return the diagnostics.
     fn check(tune: &Tune) -> Vec<Diagnostic> {
-        let mut diagnostics = Vec::new();
-        Self::run(tune, &mut diagnostics);
-        diagnostics
+        Self::check_with(tune, &Self::Options::default())
+    }
+
+    /// Run this rule with the given options and return the diagnostics.
+    fn check_with(tune: &Tune, options: &Self::Options) -> Vec<Diagnostic> {
+        let tune = TuneContext::new(tune, None);
+        let cx = RuleContext::new(&tune, options);
+        let mut rule = Self::new(&cx);
+        walk_tune(&mut rule, cx.tune());
+        rule.finish(&cx)
     }
 }
 
 // Blanket implementation
 impl<R: Rule> RuleExt for R {}
+
+/// Runs rule `R` over the tune parsed from `source`, for rules that read the
+/// source text.
+#[cfg(test)]
+pub(crate) fn check_source<R: Rule>(source: &str, options: &R::Options) -> Vec<Diagnostic> {
+    let tune = chamber_parser::parse(source);
+    let tune = TuneContext::new(&tune, Some(source));
+    let cx = RuleContext::new(&tune, options);
+    let mut rule = R::new(&cx);
+    walk_tune(&mut rule, cx.tune());
+    rule.finish(&cx)
+}
+
+/// Runs collection rule `R` over the tunes of `source`, split and parsed
+/// like the CLI does.
+#[cfg(test)]
+pub(crate) fn check_collection<R: CollectionRule>(
+    source: &str,
+    options: &R::Options,
+) -> Vec<Diagnostic> {
+    use chamber_parser::TuneSources;
+    use chamber_text_size::TextSize;
+
+    let mut rule = R::new(options);
+    for tune in TuneSources::new(source.as_bytes()) {
+        let tune = tune.unwrap();
+        let offset = TextSize::new(tune.offset as u32);
+        let streamed = tune.parse();
+        let tune = TuneContext::new(&streamed.tune, Some(&streamed.source)).with_offset(offset);
+        rule.visit_tune(&RuleContext::new(&tune, options));
+    }
+    rule.finish(options)
+}
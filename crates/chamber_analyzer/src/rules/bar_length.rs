@@ -1,71 +1,84 @@
 //! W003: Bar length mismatch warning.
 //!
 //! Warns when a bar's total duration does not match the meter.
+//!
+//! A short bar at the start of a section (a pickup, or anacrusis) is not
+//! reported on its own. Instead, the bar that closes the section, at a
+//! repeat, an ending's bar line or a double bar, must make up a full bar
+//! together with it:
+//!
+//! ```text
+//! |:GA|BcdB cBAG|...|1 BcdB c2:|2 BcdB cBAG|]
+//!   ^^ pickup        ^^^^^^^^^ closing bar, 2 + 6 eighths
+//! ```
+//!
+//! Each voice has its own pickups. In strict mode every short bar is
+//! reported.
 
-use chamber_ast::{Duration, HeaderFieldKind, MusicElement, Tune};
+use chamber_ast::visit::{VisitContext, Visitor};
+use chamber_ast::{BarLine, Chord, Ending, InlineField, Note, Rest, Slur, Tuplet};
 use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
 use chamber_text_size::TextRange;
+use serde::{Deserialize, Serialize};
 
+use crate::context::RuleContext;
+use crate::model::{BarEnd, BarTracker, Fraction, TimingModel, Voices};
 use crate::rule::{Category, Rule, RuleMeta};
 
 /// Rule that warns about bar length mismatches.
-pub struct BarLength;
-
-impl RuleMeta for BarLength {
-    const NAME: &'static str = "barLength";
-    const CODE: DiagnosticCode = DiagnosticCode::BarLengthMismatch;
-    const SEVERITY: Severity = Severity::Warning;
-    const CATEGORY: Category = Category::Lint;
-    const DOCS: &'static str =
-        "Warns when a bar's total duration does not match the time signature.";
+pub struct BarLength {
+    options: BarLengthOptions,
+    diagnostics: Vec<Diagnostic>,
+    timing: TimingModel,
+    voices: Voices<VoiceState>,
 }
 
-/// Fraction for exact arithmetic.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Fraction {
-    num: u32,
-    den: u32,
+#[derive(Debug, Default)]
+struct VoiceState {
+    bar: BarTracker,
+    /// Start of the current bar: the bar line before it, or its first element.
+    bar_start: Option<TextRange>,
+    /// A multi-measure rest (`Z4`) in the current bar.
+    bar_rest: Option<TextRange>,
+    /// The pickup of the current section.
+    pickup: Option<Pickup>,
 }
 
-impl Fraction {
-    fn new(num: u32, den: u32) -> Self {
-        Self::reduce(num, den)
-    }
-
-    fn zero() -> Self {
-        Self { num: 0, den: 1 }
-    }
-
-    fn add(self, other: Self) -> Self {
-        let num = self.num * other.den + other.num * self.den;
-        let den = self.den * other.den;
-        Self::reduce(num, den)
-    }
+#[derive(Debug, Clone, Copy)]
+struct Pickup {
+    range: TextRange,
+    total: Fraction,
+    /// Whether a closing bar made up the rest of it.
+    closed: bool,
+}
 
-    fn mul(self, other: Self) -> Self {
-        let num = self.num * other.num;
-        let den = self.den * other.den;
-        Self::reduce(num, den)
-    }
+/// Options for [`BarLength`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct BarLengthOptions {
+    /// How short bars at the start of a section are treated.
+    pub mode: BarLengthMode,
+}
 
-    fn reduce(num: u32, den: u32) -> Self {
-        if num == 0 {
-            return Self { num: 0, den: 1 };
-        }
-        let g = gcd(num, den);
-        Self {
-            num: num / g,
-            den: den / g,
-        }
-    }
+/// How [`BarLength`] treats pickups.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BarLengthMode {
+    /// A short bar at the start of a section (a pickup, or anacrusis) is
+    /// allowed when the bar closing the section makes up the rest.
+    #[default]
+    Pickup,
+    /// Every short bar is reported.
+    Strict,
 }
 
-fn gcd(a: u32, b: u32) -> u32 {
-    if b == 0 {
-        a
-    } else {
-        gcd(b, a % b)
-    }
+impl RuleMeta for BarLength {
+    const NAME: &'static str = "barLength";
+    const CODE: DiagnosticCode = DiagnosticCode::BarLengthMismatch;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Lint;
+    const DOCS: &'static str =
+        "Warns when a bar's total duration does not match the time signature.";
 }
 
 /// Get the time value for a tuplet ratio.
@@ -74,7 +87,7 @@ fn gcd(a: u32, b: u32) -> u32 {
 /// - (3 = 3 notes in the time of 2
 /// - (4 = 4 notes in the time of 3
 /// - (5+ = n notes in the time of (n-1)
-fn tuplet_time(ratio: u32) -> u32 {
+pub(crate) fn tuplet_time(ratio: u32) -> u32 {
     match ratio {
         2 => 3,
         3 | 4 => 2,
@@ -84,196 +97,193 @@ fn tuplet_time(ratio: u32) -> u32 {
 }
 
 impl Rule for BarLength {
-    fn run(tune: &Tune, diagnostics: &mut Vec<Diagnostic>) {
-        // Get meter from header (default: 4/4)
-        let meter = get_meter(tune);
-
-        // Get unit note length from header (default: 1/8)
-        let unit_length = get_unit_length(tune);
-
-        // Expected bar length = meter * unit_length factor
-        // For M:4/4 with L:1/8, we expect 4/4 of a whole note per bar
-        // Each note duration is multiplied by unit_length
-        let expected = meter;
-
-        // Track current bar
-        let mut bar_start: Option<TextRange> = None;
-        let mut bar_total = Fraction::zero();
-        let mut bar_end_pos = 0u32;
-
-        for element in &tune.body.elements {
-            match element {
-                MusicElement::BarLine(barline) => {
-                    // Check the completed bar
-                    if bar_start.is_some() && bar_total != expected && bar_total != Fraction::zero()
-                    {
-                        let range = TextRange::new(
-                            bar_start.unwrap().start(),
-                            barline.range.start(),
-                        );
-                        diagnostics.push(Diagnostic::warning(
-                            DiagnosticCode::BarLengthMismatch,
-                            range,
-                            format!(
-                                "bar has {}/{} beats, expected {}/{}",
-                                bar_total.num, bar_total.den, expected.num, expected.den
-                            ),
-                        ));
-                    }
-                    // Start new bar
-                    bar_start = Some(barline.range);
-                    bar_total = Fraction::zero();
-                    bar_end_pos = barline.range.end().into();
-                }
-                MusicElement::Note(note) => {
-                    let dur = note_duration(note.duration.as_ref(), unit_length);
-                    bar_total = bar_total.add(dur);
-                    if bar_start.is_none() {
-                        bar_start = Some(note.range);
-                    }
-                    bar_end_pos = note.range.end().into();
-                }
-                MusicElement::Rest(rest) => {
-                    if !rest.multi_measure {
-                        let dur = note_duration(rest.duration.as_ref(), unit_length);
-                        bar_total = bar_total.add(dur);
-                        if bar_start.is_none() {
-                            bar_start = Some(rest.range);
-                        }
-                        bar_end_pos = rest.range.end().into();
-                    }
-                }
-                MusicElement::Chord(chord) => {
-                    let dur = note_duration(chord.duration.as_ref(), unit_length);
-                    bar_total = bar_total.add(dur);
-                    if bar_start.is_none() {
-                        bar_start = Some(chord.range);
-                    }
-                    bar_end_pos = chord.range.end().into();
-                }
-                MusicElement::Tuplet(tuplet) => {
-                    let time = tuplet_time(tuplet.ratio);
-                    let ratio_frac = Fraction::new(time, tuplet.ratio);
-
-                    for note in &tuplet.notes {
-                        let note_dur = note_duration(note.duration.as_ref(), unit_length);
-                        let effective = note_dur.mul(ratio_frac);
-                        bar_total = bar_total.add(effective);
-                    }
-                    if bar_start.is_none() {
-                        bar_start = Some(tuplet.range);
-                    }
-                    bar_end_pos = tuplet.range.end().into();
-                }
-                MusicElement::Slur(slur) => {
-                    let dur = sum_slur_duration(slur, unit_length);
-                    bar_total = bar_total.add(dur);
-                    if bar_start.is_none() {
-                        bar_start = Some(slur.range);
-                    }
-                    bar_end_pos = slur.range.end().into();
-                }
-                MusicElement::GraceNotes(_) | MusicElement::Annotation(_) => {
-                    // Grace notes and annotations don't count toward bar length
-                }
-                MusicElement::InlineField(field) => {
-                    // TODO: Handle inline M: and L: changes
-                    let _ = field;
-                }
-                MusicElement::BrokenRhythm(_) | MusicElement::Tie(_) => {
-                    // These don't add duration directly
-                }
-            }
+    type Options = BarLengthOptions;
+
+    fn new(cx: &RuleContext<Self::Options>) -> Self {
+        Self {
+            options: *cx.options(),
+            diagnostics: Vec::new(),
+            timing: *cx.timing(),
+            voices: Voices::new(),
         }
+    }
 
-        // Check final bar (may be incomplete - pickup/anacrusis)
-        // We don't warn on the last bar if it's shorter (common for pickup measures)
-        let _ = bar_end_pos;
+    fn finish(mut self, _cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        // The final bar is not checked: it may be incomplete (the other
+        // half of a pickup measure)
+        self.diagnostics.sort_by_key(|d| d.range.start());
+        self.diagnostics
     }
 }
 
-fn get_meter(tune: &Tune) -> Fraction {
-    for field in &tune.header.fields {
-        if field.kind == HeaderFieldKind::Meter {
-            let value = field.value.trim();
-            if value == "C" {
-                return Fraction::new(4, 4);
+impl BarLength {
+    fn add(&mut self, duration: Fraction, range: TextRange, cx: &VisitContext) {
+        let voice = self.voices.current(cx);
+        voice.bar.add(duration);
+        voice.bar_start.get_or_insert(range);
+    }
+
+    /// Checks `bar` of voice `id`, at `range`, against the meter
+    /// `expected`. `rest` is the bar's multi-measure rest, if any.
+    fn check_bar(
+        &mut self,
+        id: &str,
+        range: TextRange,
+        bar: BarEnd,
+        rest: Option<TextRange>,
+        expected: Fraction,
+    ) {
+        let allow_pickup = self.options.mode == BarLengthMode::Pickup;
+        let voice = self.voices.get_mut(id);
+        let total = bar.total;
+        if let Some(rest) = rest {
+            if !total.is_zero() {
+                self.diagnostics.push(
+                    Diagnostic::warning(
+                        DiagnosticCode::BarLengthMismatch,
+                        range,
+                        "bar has a multi-measure rest and other notes",
+                    )
+                    .with_label(rest, "multi-measure rest here"),
+                );
             }
-            if value == "C|" {
-                return Fraction::new(2, 2);
+            return;
+        }
+        if total > expected || (total < expected && !allow_pickup) {
+            self.diagnostics.push(mismatch(range, total, expected));
+            return;
+        }
+
+        if total == expected {
+            if !allow_pickup {
+                return;
             }
-            if let Some((num, den)) = value.split_once('/') {
-                if let (Ok(n), Ok(d)) = (num.trim().parse::<u32>(), den.trim().parse::<u32>()) {
-                    if d > 0 {
-                        return Fraction::new(n, d);
-                    }
+            match voice.pickup {
+                // A section without a pickup after one that had it
+                Some(pickup) if bar.section_start && pickup.closed => voice.pickup = None,
+                // The section closes with a full bar, and the pickup is
+                // left incomplete
+                Some(pickup) if bar.section_end && !bar.section_start && !pickup.closed => {
+                    voice.pickup = None;
+                    self.diagnostics
+                        .push(mismatch(pickup.range, pickup.total, expected));
                 }
+                _ => {}
             }
+        } else if bar.section_start {
+            voice.pickup = Some(Pickup {
+                range,
+                total,
+                closed: false,
+            });
+        } else if !bar.section_end {
+            self.diagnostics.push(mismatch(range, total, expected));
+        } else if let Some(pickup) = &mut voice.pickup {
+            // The closing bar of a section makes up the pickup's bar
+            pickup.closed = true;
+            let sum = pickup.total + total;
+            if sum != expected {
+                self.diagnostics.push(
+                    Diagnostic::warning(
+                        DiagnosticCode::BarLengthMismatch,
+                        range,
+                        format!(
+                            "incomplete bars add up to {} beats, expected {}",
+                            sum, expected
+                        ),
+                    )
+                    .with_label(pickup.range, format!("first half: {} beats", pickup.total))
+                    .with_label(range, format!("second half: {} beats", total)),
+                );
+            }
+        } else {
+            self.diagnostics.push(mismatch(range, total, expected));
         }
     }
-    // Default: 4/4
-    Fraction::new(4, 4)
 }
 
-fn get_unit_length(tune: &Tune) -> Fraction {
-    for field in &tune.header.fields {
-        if field.kind == HeaderFieldKind::UnitNoteLength {
-            let value = field.value.trim();
-            if let Some((num, den)) = value.split_once('/') {
-                if let (Ok(n), Ok(d)) = (num.trim().parse::<u32>(), den.trim().parse::<u32>()) {
-                    if d > 0 {
-                        return Fraction::new(n, d);
-                    }
-                }
-            }
+fn mismatch(range: TextRange, total: Fraction, expected: Fraction) -> Diagnostic {
+    Diagnostic::warning(
+        DiagnosticCode::BarLengthMismatch,
+        range,
+        format!("bar has {} beats, expected {}", total, expected),
+    )
+}
+
+impl Visitor for BarLength {
+    fn visit_note(&mut self, note: &Note, cx: &VisitContext) {
+        // Chord notes count with their chord; grace notes take no time
+        if cx.in_chord || cx.in_grace_notes {
+            return;
+        }
+        let mut duration = self.timing.duration(note.duration.as_ref());
+        if let Some(ratio) = cx.tuplet {
+            duration = duration * Fraction::new(tuplet_time(ratio), ratio);
         }
+        self.add(duration, note.range, cx);
     }
-    // Default: 1/8
-    Fraction::new(1, 8)
-}
 
-fn note_duration(duration: Option<&Duration>, unit_length: Fraction) -> Fraction {
-    let dur = duration.map_or(Fraction::new(1, 1), |d| Fraction::new(d.numerator, d.denominator));
-    dur.mul(unit_length)
-}
+    fn visit_rest(&mut self, rest: &Rest, cx: &VisitContext) {
+        if rest.multi_measure {
+            // Stands for whole bars, however many
+            let voice = self.voices.current(cx);
+            voice.bar.add_multi_measure_rest();
+            voice.bar_rest.get_or_insert(rest.range);
+            voice.bar_start.get_or_insert(rest.range);
+        } else {
+            let duration = self.timing.duration(rest.duration.as_ref());
+            self.add(duration, rest.range, cx);
+        }
+    }
 
-fn sum_slur_duration(slur: &chamber_ast::Slur, unit_length: Fraction) -> Fraction {
-    let mut total = Fraction::zero();
-    for element in &slur.elements {
-        match element {
-            MusicElement::Note(note) => {
-                total = total.add(note_duration(note.duration.as_ref(), unit_length));
-            }
-            MusicElement::Rest(rest) => {
-                if !rest.multi_measure {
-                    total = total.add(note_duration(rest.duration.as_ref(), unit_length));
-                }
-            }
-            MusicElement::Chord(chord) => {
-                total = total.add(note_duration(chord.duration.as_ref(), unit_length));
-            }
-            MusicElement::Tuplet(tuplet) => {
-                let time = tuplet_time(tuplet.ratio);
-                let ratio_frac = Fraction::new(time, tuplet.ratio);
-                for note in &tuplet.notes {
-                    let note_dur = note_duration(note.duration.as_ref(), unit_length);
-                    total = total.add(note_dur.mul(ratio_frac));
-                }
-            }
-            MusicElement::Slur(inner) => {
-                total = total.add(sum_slur_duration(inner, unit_length));
-            }
-            MusicElement::GraceNotes(_) => {}
-            _ => {}
+    fn visit_chord(&mut self, chord: &Chord, cx: &VisitContext) {
+        let duration = self.timing.duration(chord.duration.as_ref());
+        self.add(duration, chord.range, cx);
+    }
+
+    fn visit_tuplet(&mut self, tuplet: &Tuplet, cx: &VisitContext) {
+        self.voices
+            .current(cx)
+            .bar_start
+            .get_or_insert(tuplet.range);
+    }
+
+    fn visit_slur(&mut self, slur: &Slur, cx: &VisitContext) {
+        self.voices.current(cx).bar_start.get_or_insert(slur.range);
+    }
+
+    fn visit_bar_line(&mut self, barline: &BarLine, cx: &VisitContext) {
+        let meter = self.timing.meter();
+        let voice = self.voices.current(cx);
+        let start = voice.bar_start.replace(barline.range);
+        let rest = voice.bar_rest.take();
+        let bar = voice.bar.end_bar(barline.kind, meter);
+
+        // Check the completed bar (free meter has no bar length)
+        if let (Some(bar), Some(expected), Some(start)) = (bar, meter, start) {
+            let range = TextRange::new(start.start(), barline.range.start());
+            self.check_bar(cx.voice.unwrap_or_default(), range, bar, rest, expected);
+        }
+    }
+
+    fn visit_ending(&mut self, _ending: &Ending, cx: &VisitContext) {
+        self.voices.current(cx).bar.start_ending();
+    }
+
+    fn visit_inline_field(&mut self, field: &InlineField, cx: &VisitContext) {
+        self.timing.apply_field(field.label, &field.value);
+        if field.label == 'M' {
+            // Bars in the new meter do not make up a pickup in the old one
+            self.voices.current(cx).pickup = None;
         }
     }
-    total
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::rule::RuleExt;
+    use chamber_ast::Tune;
     use chamber_parser::parse;
 
     #[test]
@@ -297,7 +307,7 @@ mod tests {
     fn test_bar_too_short() {
         // M:4/4 L:1/8, but only 4 eighth notes in bar
         let tune = parse("X:1\nM:4/4\nL:1/8\nK:C\nCDEF|GABCDEFG|");
-        let diagnostics = BarLength::check(&tune);
+        let diagnostics = check_strict(&tune);
         assert_eq!(diagnostics.len(), 1);
         // 4 eighth notes = 4/8 = 1/2 (reduced)
         assert!(diagnostics[0].message.contains("1/2"));
@@ -314,6 +324,13 @@ mod tests {
         assert!(diagnostics.is_empty(), "Triplet should be counted correctly");
     }
 
+    #[test]
+    fn test_many_tuplet_lengths() {
+        // Thirds, fifths, ... 31sts: the exact total does not fit in u32
+        let tune = parse("X:1\nM:4/4\nL:1/8\nK:C\nC8|C/3 D/5 E/7 F/11 G/13 A/17 B/19 c/23 d/29 e/31|C8|");
+        assert_eq!(BarLength::check(&tune).len(), 1);
+    }
+
     #[test]
     fn test_grace_notes_excluded() {
         // Grace notes should not count toward bar length
@@ -330,4 +347,181 @@ mod tests {
         let diagnostics = BarLength::check(&tune);
         assert!(diagnostics.is_empty(), "Rests should be counted");
     }
+
+    #[test]
+    fn test_pickup() {
+        let tune = parse("X:1\nM:4/4\nL:1/8\nK:C\n|:GA|BcdB cBAG|");
+        assert!(BarLength::check(&tune).is_empty());
+        assert_eq!(check_strict(&tune).len(), 1);
+    }
+
+    #[test]
+    fn test_pickup_only_first_bar() {
+        // A long first bar is not a pickup
+        let tune = parse("X:1\nM:4/4\nL:1/8\nK:C\nGABcdBcBA|");
+        assert_eq!(BarLength::check(&tune).len(), 1);
+
+        // Nor is a short bar later on
+        let tune = parse("X:1\nM:4/4\nL:1/8\nK:C\nGA|BcdB cBAG|AB|c8|");
+        assert_eq!(BarLength::check(&tune).len(), 1);
+    }
+
+    fn check_strict(tune: &Tune) -> Vec<Diagnostic> {
+        let options = BarLengthOptions {
+            mode: BarLengthMode::Strict,
+        };
+        BarLength::check_with(tune, &options)
+    }
+
+    fn check_pickups(source: &str) -> Vec<Diagnostic> {
+        BarLength::check(&parse(source))
+    }
+
+    #[test]
+    fn test_pickup_and_closing_bar() {
+        // 2 + 6 eighths around the repeat, in both endings
+        let source = "X:1\nM:4/4\nL:1/8\nK:G\n|:GA|BcdB cBAG|1 BcdB c2:|2 BcdB A2|]";
+        assert!(check_pickups(source).is_empty());
+
+        // A pickup outside the repeat
+        assert!(check_pickups("X:1\nM:4/4\nL:1/8\nK:G\nGA|:BcdB cBAG|BcdB c2:|").is_empty());
+
+        // Each part has its own pickup
+        let source = "X:1\nM:6/8\nL:1/8\nK:G\n|:G|ABc dBG|ABc d2:|:d|efg fed|efg f2:|";
+        assert!(check_pickups(source).is_empty());
+
+        // A part without a pickup after one with it
+        let source = "X:1\nM:6/8\nL:1/8\nK:G\n|:G|ABc dBG|ABc d2:||:efg fed|efg fed:|";
+        assert!(check_pickups(source).is_empty());
+    }
+
+    #[test]
+    fn test_closing_bar_mismatch() {
+        let source = "X:1\nM:4/4\nL:1/8\nK:G\n|:GA|BcdB cBAG|BcdB c3:|";
+        let diagnostics = check_pickups(source);
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(
+            diagnostic.message,
+            "incomplete bars add up to 9/8 beats, expected 1/1"
+        );
+        let labels: Vec<_> = diagnostic
+            .labels
+            .iter()
+            .map(|label| {
+                let range = label.range.start().raw() as usize..label.range.end().raw() as usize;
+                (&source[range], label.message.as_str())
+            })
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                ("|:GA", "first half: 1/4 beats"),
+                ("|BcdB c3", "second half: 7/8 beats")
+            ]
+        );
+
+        // Without a pickup, a short closing bar is just short
+        let diagnostics = check_pickups("X:1\nM:4/4\nL:1/8\nK:G\nBcdB cBAG|BcdB c2:|");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.starts_with("bar has 3/4 beats"));
+    }
+
+    #[test]
+    fn test_full_closing_bar() {
+        // The pickup is left without its other half
+        let source = "X:1\nM:4/4\nL:1/8\nK:G\n|:GA|BcdB cBAG|BcdB cBAG:|";
+        let diagnostics = check_pickups(source);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "bar has 1/4 beats, expected 1/1");
+        assert_eq!(diagnostics[0].range.start().raw(), 20);
+    }
+
+    #[test]
+    fn test_repeat_both_and_double_bar() {
+        let source = "X:1\nM:3/4\nL:1/4\nK:D\nA|d2:: B|e2||c|f2|]";
+        assert!(check_pickups(source).is_empty());
+
+        let source = "X:1\nM:3/4\nL:1/4\nK:D\nA|d2 A B:: B|e2|]";
+        assert_eq!(check_pickups(source).len(), 1);
+    }
+
+    #[test]
+    fn test_pickup_per_voice() {
+        // Only the melody has a pickup
+        let source = "X:1\nM:4/4\nL:1/8\nK:G\n\
+                      V:1\n|:GA|BcdB cBAG|BcdB c2:|\n\
+                      V:2\n|:z8|G,8|G,6 z2:|\n";
+        assert!(check_pickups(source).is_empty());
+
+        let source = "X:1\nM:4/4\nL:1/8\nK:G\n\
+                      V:1\n|:GA|BcdB cBAG|\n\
+                      V:2\n|:z8|G,8|\n\
+                      V:1\nBcdB c2:|\n\
+                      V:2\nG,6 z2:|\n";
+        assert!(check_pickups(source).is_empty());
+    }
+
+    #[test]
+    fn test_meter_change_drops_pickup() {
+        let source = "X:1\nM:4/4\nL:1/8\nK:C\nGA|BcdB cBAG|[M:3/4]cBA G2:|";
+        let diagnostics = check_pickups(source);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("expected 3/4"));
+    }
+
+    #[test]
+    fn test_multi_measure_rest() {
+        let tune = parse("X:1\nM:4/4\nL:1/8\nK:C\nZ4|CDEF GABc|Z|]");
+        assert!(BarLength::check(&tune).is_empty());
+
+        let tune = parse("X:1\nM:4/4\nL:1/8\nK:C\nCDEF GABc|Z2 CD|CDEF GABc|");
+        let diagnostics = BarLength::check(&tune);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "bar has a multi-measure rest and other notes"
+        );
+    }
+
+    #[test]
+    fn test_inline_meter_change() {
+        // Four bars of 4/4, then 3/4 from an inline field
+        let tune = parse("X:1\nM:4/4\nL:1/8\nK:C\nCDEF GABc|[M:3/4]CDE FGA|");
+        assert!(BarLength::check(&tune).is_empty());
+
+        let tune = parse("X:1\nM:4/4\nL:1/8\nK:C\nCDEF GABc|[M:3/4]CDEF GABc|");
+        let diagnostics = BarLength::check(&tune);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("expected 3/4"));
+    }
+
+    #[test]
+    fn test_inline_unit_length_change() {
+        let tune = parse("X:1\nM:4/4\nL:1/8\nK:C\nCDEF GABc|[L:1/4]CDEF|");
+        assert!(BarLength::check(&tune).is_empty());
+    }
+
+    #[test]
+    fn test_slur_and_chord() {
+        // (CD) [CEG]2 EF GA = 2 + 2 + 2 + 2 eighth notes
+        let tune = parse("X:1\nM:4/4\nL:1/8\nK:C\n(CD) [CEG]2 EF GA|");
+        assert!(BarLength::check(&tune).is_empty());
+    }
+
+    #[test]
+    fn test_default_unit_length_from_meter() {
+        // Without L:, M:2/4 makes the unit note length 1/16
+        let tune = parse("X:1\nM:2/4\nK:C\nCDEF GABc|");
+        assert!(BarLength::check(&tune).is_empty());
+
+        let tune = parse("X:1\nM:2/4\nK:C\nCDEF|");
+        assert_eq!(check_strict(&tune).len(), 1);
+    }
+
+    #[test]
+    fn test_free_meter() {
+        let tune = parse("X:1\nM:none\nL:1/8\nK:C\nCDE|CDEFGA|");
+        assert!(BarLength::check(&tune).is_empty());
+    }
 }
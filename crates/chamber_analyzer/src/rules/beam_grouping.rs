@@ -0,0 +1,414 @@
+//! W012: Beam grouping warning.
+//!
+//! In ABC, notes written together are beamed together and whitespace breaks
+//! the beam, so `GABcde` in 6/8 beams six eighths where `GAB cde` beams one
+//! group per beat. Warns when a beam crosses a beat of the meter (see
+//! [`BeatStructure`]), or notes of one beat are not beamed together, and
+//! re-groups the bar by inserting and removing spaces only.
+//!
+//! Only notes and chords shorter than a quarter note are beamed; rests and
+//! line breaks end a beam. A short bar at the start of a section, followed
+//! by a full bar, is a pickup and lines up with the end of the bar. Voices
+//! with lyrics are not checked, as vocal music is often written unbeamed.
+//!
+//! Beaming is a matter of taste as much as of the meter, so the rule is off
+//! until it is given a level, and the fix is only applied with unsafe fixes.
+
+use chamber_ast::visit::{VisitContext, Visitor};
+use chamber_ast::{BarLine, BarLineKind, Chord, Ending, InlineField, Lyrics, Note, Rest};
+use chamber_cst::{CstChild, CstNode};
+use chamber_diagnostics::{Applicability, Diagnostic, DiagnosticCode, Fix, Severity, TextEdit};
+use chamber_syntax::{SyntaxKind, Trivia};
+use chamber_text_size::{TextRange, TextSize};
+
+use crate::context::RuleContext;
+use crate::model::{BarEnd, BarTracker, BeatStructure, Fraction, TimingModel, Voices};
+use crate::rule::{Category, NoOptions, Rule, RuleMeta};
+use crate::rules::bar_length::tuplet_time;
+
+/// Rule that warns about notes not beamed by beat.
+pub struct BeamGrouping {
+    timing: TimingModel,
+    beats: BeatStructure,
+    voices: Voices<VoiceState>,
+}
+
+#[derive(Debug, Default)]
+struct VoiceState {
+    bar: BarTracker,
+    items: Vec<Item>,
+    /// The complete bars of the voice.
+    bars: Vec<Bar>,
+    /// Whether the voice has lyrics.
+    lyrics: bool,
+}
+
+/// A note, chord or rest, and where it starts in its bar.
+#[derive(Debug, Clone, Copy)]
+struct Item {
+    range: TextRange,
+    start: Fraction,
+    /// Whether it is short enough to be beamed.
+    beamed: bool,
+}
+
+#[derive(Debug)]
+struct Bar {
+    items: Vec<Item>,
+    end: BarEnd,
+    beats: BeatStructure,
+}
+
+impl RuleMeta for BeamGrouping {
+    const NAME: &'static str = "beamGrouping";
+    const CODE: DiagnosticCode = DiagnosticCode::BeamGrouping;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Style;
+    const DOCS: &'static str =
+        "Warns when beams (notes written without spaces) don't follow the beats of the meter.";
+    const ENABLED: bool = false;
+}
+
+impl Rule for BeamGrouping {
+    type Options = NoOptions;
+
+    fn new(cx: &RuleContext<Self::Options>) -> Self {
+        Self {
+            timing: *cx.timing(),
+            beats: BeatStructure::new(cx.header().meter),
+            voices: Voices::new(),
+        }
+    }
+
+    fn finish(mut self, cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        // Whitespace is only in the source
+        let (Some(source), Some(cst)) = (cx.source(), cx.cst()) else {
+            return Vec::new();
+        };
+        let meter = self.timing.meter();
+        for voice in self.voices.values_mut() {
+            voice.end_bar(BarLineKind::Single, meter, &self.beats);
+        }
+
+        let mut trivia = Vec::new();
+        collect_trivia(cst, &mut trivia);
+        trivia.sort_by_key(|trivia| trivia.range.start());
+
+        let mut diagnostics = Vec::new();
+        for voice in self.voices.into_values().filter(|voice| !voice.lyrics) {
+            for (i, bar) in voice.bars.iter().enumerate() {
+                let next = voice.bars.get(i + 1).map(|next| &next.end);
+                diagnostics.extend(check_bar(bar, next, source, &trivia));
+            }
+        }
+        diagnostics.sort_by_key(|d| d.range.start());
+        diagnostics
+    }
+}
+
+impl BeamGrouping {
+    fn add(&mut self, range: TextRange, duration: Fraction, beamed: bool, cx: &VisitContext) {
+        let voice = self.voices.current(cx);
+        voice.items.push(Item {
+            range,
+            start: voice.bar.position(),
+            beamed,
+        });
+        voice.bar.add(duration);
+    }
+}
+
+impl VoiceState {
+    /// Ends the current bar at a bar line of `kind`.
+    fn end_bar(&mut self, kind: BarLineKind, meter: Option<Fraction>, beats: &BeatStructure) {
+        let items = std::mem::take(&mut self.items);
+        if let Some(end) = self.bar.end_bar(kind, meter) {
+            self.bars.push(Bar {
+                items,
+                end,
+                beats: beats.clone(),
+            });
+        }
+    }
+}
+
+/// Collects the whitespace, line breaks and comments of `node`.
+fn collect_trivia(node: &CstNode, trivia: &mut Vec<Trivia>) {
+    for child in node.children() {
+        match child {
+            CstChild::Node(node) => collect_trivia(node, trivia),
+            CstChild::Token(token) => {
+                trivia.extend_from_slice(token.leading_trivia());
+                trivia.extend_from_slice(token.trailing_trivia());
+            }
+        }
+    }
+}
+
+/// Returns where each beat of `beats` but the first starts.
+fn boundaries(beats: &[Fraction]) -> Vec<Fraction> {
+    let mut position = Fraction::zero();
+    let mut starts = Vec::new();
+    for &beat in beats {
+        if !position.is_zero() {
+            starts.push(position);
+        }
+        position = position + beat;
+    }
+    starts
+}
+
+fn range_text(source: &str, range: TextRange) -> &str {
+    &source[range.start().raw() as usize..range.end().raw() as usize]
+}
+
+/// Re-groups the notes of `bar` by beat, if they aren't. `next` is the bar
+/// after it, which tells whether it is a pickup.
+fn check_bar(
+    bar: &Bar,
+    next: Option<&BarEnd>,
+    source: &str,
+    trivia: &[Trivia],
+) -> Option<Diagnostic> {
+    let beats = boundaries(bar.beats.beats());
+    let beam_groups = boundaries(bar.beats.beam_groups());
+    // Beats of an overfull bar are anyone's guess
+    let meter = bar.end.meter?;
+    if bar.beats.beats().is_empty() || meter.checked_sub(bar.end.total).is_none() {
+        return None;
+    }
+    // A pickup lines up with the end of the bar
+    let shift = bar.end.pickup(next).unwrap_or(Fraction::zero());
+
+    let mut edits = Vec::new();
+    let mut changed: Option<TextRange> = None;
+    let (mut split, mut joined) = (false, false);
+    for pair in bar.items.windows(2) {
+        let (prev, next) = (pair[0], pair[1]);
+        if !prev.beamed || !next.beamed {
+            continue;
+        }
+        let position = next.start + shift;
+        let gap = TextRange::new(prev.range.end(), next.range.start());
+        let first = trivia.partition_point(|trivia| trivia.range.start() < gap.start());
+        let between: Vec<_> = trivia[first..]
+            .iter()
+            .take_while(|trivia| trivia.range.end() <= gap.end())
+            .collect();
+        let text = range_text(source, gap);
+
+        if between.is_empty() && beam_groups.contains(&position) {
+            // `A>B` can't be split; the space goes after a tie (`A- B`)
+            if text.contains(['>', '<']) {
+                continue;
+            }
+            let ties = text.len() - text.trim_start_matches('-').len();
+            let at = gap.start() + TextSize::new(ties as u32);
+            edits.push(TextEdit::insert(at, " "));
+            split = true;
+        } else if !between.is_empty()
+            && !beats.contains(&position)
+            && between
+                .iter()
+                .all(|trivia| trivia.kind == SyntaxKind::WHITESPACE)
+        {
+            edits.extend(between.iter().map(|trivia| TextEdit::delete(trivia.range)));
+            joined = true;
+        } else {
+            continue;
+        }
+        let range = TextRange::new(prev.range.start(), next.range.end());
+        changed = Some(changed.map_or(range, |changed| changed.cover(range)));
+    }
+
+    let message = match (split, joined) {
+        (true, false) => "beam crosses a beat",
+        (false, true) => "notes of one beat are not beamed together",
+        _ => "notes are not grouped by beat",
+    };
+    let beats: Vec<_> = bar
+        .beats
+        .beats()
+        .iter()
+        .map(|beat| beat.to_string())
+        .collect();
+    Some(
+        Diagnostic::warning(DiagnosticCode::BeamGrouping, changed?, message)
+            .with_note(format!("the bar's beats are {}", beats.join(" + ")))
+            .with_fix(Fix::new(
+                "group notes by beat",
+                edits,
+                Applicability::MaybeIncorrect,
+            )),
+    )
+}
+
+impl Visitor for BeamGrouping {
+    fn visit_note(&mut self, note: &Note, cx: &VisitContext) {
+        // Chord notes count with their chord; grace notes take no time
+        if cx.in_chord || cx.in_grace_notes {
+            return;
+        }
+        let mut duration = self.timing.duration(note.duration.as_ref());
+        if let Some(ratio) = cx.tuplet {
+            duration = duration * Fraction::new(tuplet_time(ratio), ratio);
+        }
+        self.add(note.range, duration, duration < Fraction::new(1, 4), cx);
+    }
+
+    fn visit_chord(&mut self, chord: &Chord, cx: &VisitContext) {
+        let mut duration = self.timing.duration(chord.duration.as_ref());
+        if let Some(ratio) = cx.tuplet {
+            duration = duration * Fraction::new(tuplet_time(ratio), ratio);
+        }
+        self.add(chord.range, duration, duration < Fraction::new(1, 4), cx);
+    }
+
+    fn visit_rest(&mut self, rest: &Rest, cx: &VisitContext) {
+        // A multi-measure rest stands for whole bars
+        if rest.multi_measure {
+            let voice = self.voices.current(cx);
+            voice.items.push(Item {
+                range: rest.range,
+                start: voice.bar.position(),
+                beamed: false,
+            });
+            voice.bar.add_multi_measure_rest();
+            return;
+        }
+        let duration = self.timing.duration(rest.duration.as_ref());
+        self.add(rest.range, duration, false, cx);
+    }
+
+    fn visit_bar_line(&mut self, bar_line: &BarLine, cx: &VisitContext) {
+        let meter = self.timing.meter();
+        self.voices
+            .current(cx)
+            .end_bar(bar_line.kind, meter, &self.beats);
+    }
+
+    fn visit_ending(&mut self, _ending: &Ending, cx: &VisitContext) {
+        self.voices.current(cx).bar.start_ending();
+    }
+
+    fn visit_lyrics(&mut self, _lyrics: &Lyrics, cx: &VisitContext) {
+        // Vocal music is beamed by syllable, if at all
+        self.voices.current(cx).lyrics = true;
+    }
+
+    fn visit_inline_field(&mut self, field: &InlineField, _cx: &VisitContext) {
+        self.timing.apply_field(field.label, &field.value);
+        self.beats.apply_field(field.label, &field.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::{check_source, RuleExt};
+    use chamber_diagnostics::apply_fixes;
+    use chamber_parser::parse;
+
+    fn check(meter: &str, body: &str) -> Vec<Diagnostic> {
+        let source = format!("X:1\nM:{}\nL:1/8\nK:G\n{}", meter, body);
+        check_source::<BeamGrouping>(&source, &NoOptions::default())
+    }
+
+    fn fix(meter: &str, body: &str) -> String {
+        let header = format!("X:1\nM:{}\nL:1/8\nK:G\n", meter);
+        let source = format!("{}{}", header, body);
+        let diagnostics = check_source::<BeamGrouping>(&source, &NoOptions::default());
+        let fixed = apply_fixes(&source, &diagnostics, Applicability::MaybeIncorrect);
+        fixed.source[header.len()..].to_string()
+    }
+
+    #[test]
+    fn test_grouped_by_beat() {
+        assert!(check("6/8", "GAB cde|g2f e2d|").is_empty());
+        assert!(check("4/4", "GABc defg|GA Bc de fg|G2 AB c4|").is_empty());
+        assert!(check("3/4", "GA Bc de|").is_empty());
+        // Rests and line breaks end beams
+        assert!(check("6/8", "GAz cde|GAB\ncde|").is_empty());
+        // Free meter has no beats
+        assert!(check("none", "GABcdefg|").is_empty());
+    }
+
+    #[test]
+    fn test_beam_crosses_beat() {
+        let diagnostics = check("6/8", "GABcde|");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "beam crosses a beat");
+        assert_eq!(diagnostics[0].notes, ["the bar's beats are 3/8 + 3/8"]);
+
+        assert_eq!(fix("6/8", "GABcde|"), "GAB cde|");
+        assert_eq!(fix("4/4", "GABcdefg|"), "GABc defg|");
+        assert_eq!(fix("3/4", "GABcde|"), "GA Bc de|");
+        assert_eq!(fix("2+3/8", "GABcd|"), "GA Bcd|");
+
+        // Only applied with unsafe fixes
+        let source = "X:1\nM:6/8\nL:1/8\nK:G\nGABcde|";
+        let diagnostics = check_source::<BeamGrouping>(source, &NoOptions::default());
+        let fixed = apply_fixes(source, &diagnostics, Applicability::MachineApplicable);
+        assert_eq!(fixed.source, source);
+    }
+
+    #[test]
+    fn test_beat_not_beamed() {
+        let diagnostics = check("6/8", "G AB cde|");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "notes of one beat are not beamed together"
+        );
+        assert_eq!(fix("6/8", "G AB cde|"), "GAB cde|");
+        assert_eq!(fix("4/4", "G A B c d e f g|"), "GA Bc de fg|");
+        assert_eq!(fix("6/8", "GA  Bcde|"), "GAB cde|");
+    }
+
+    #[test]
+    fn test_only_whitespace_changes() {
+        // The space goes after a tie, and before a slur or a chord symbol
+        assert_eq!(fix("6/8", "GAB-Bcd|"), "GAB- Bcd|");
+        assert_eq!(fix("6/8", "GAB(cd)e|"), "GAB (cd)e|");
+        assert_eq!(fix("6/8", "GAB\"C\"cde|"), "GAB \"C\"cde|");
+        assert_eq!(fix("6/8", "[GB]AB[ce]de|"), "[GB]AB [ce]de|");
+        // A broken rhythm pair can't be split
+        assert_eq!(fix("3/4", "G>AB>cd>e|"), "G>A B>c d>e|");
+        assert!(check("3/4", "GA>Bc de|").is_empty());
+    }
+
+    #[test]
+    fn test_pickup_and_tuplets() {
+        // The pickup lines up with the end of the bar
+        assert_eq!(fix("6/8", "EFGA|BAG FED|"), "E FGA|BAG FED|");
+        assert!(check("6/8", "GA|BAG FED|").is_empty());
+        // Without a full bar after it, a short bar is not a pickup
+        assert!(check("4/4", "CDEF GAB|").is_empty());
+        assert!(check("4/4", "CDEF GAB|CDEF GA|").is_empty());
+        assert_eq!(fix("6/8", "EFGA|BAG FE|"), "EFG A|BAG FE|");
+        // A triplet of eighths takes one quarter beat
+        assert!(check("2/4", "(3GAB cd|").is_empty());
+        assert_eq!(fix("2/4", "(3GABcd|"), "(3GAB cd|");
+    }
+
+    #[test]
+    fn test_voices_and_meter_changes() {
+        assert!(check("6/8", "[V:1]GAB cde|[V:2]G,3 C3|").is_empty());
+        assert_eq!(
+            fix("6/8", "GAB cde|[M:3/4]GABcde|"),
+            "GAB cde|[M:3/4]GA Bc de|"
+        );
+    }
+
+    #[test]
+    fn test_lyrics_not_checked() {
+        assert!(check("6/8", "GABcde|\nw:sing-ing a song to you\n").is_empty());
+        let source = "[V:1]GAB cde|\nw:sing-ing a song to you\n[V:2]G,A,B,CDE|\n";
+        assert_eq!(check("6/8", source).len(), 1);
+    }
+
+    #[test]
+    fn test_needs_source() {
+        assert!(BeamGrouping::check(&parse("X:1\nM:6/8\nK:G\nGABcde|")).is_empty());
+    }
+}
@@ -0,0 +1,336 @@
+//! W014: Chord and melody clash warning.
+//!
+//! Warns when the melody note under a chord symbol is a semitone from the
+//! chord's root and not one of its notes (`"G"^F` or `"C"_D`), on a strong
+//! beat: the start of the bar, or its middle in meters that divide there
+//! (see [`BeatStructure::is_strong`]). Passing notes off the beat are
+//! fine. A short bar at the start of a section, followed by a full bar, is
+//! a pickup and lines up with the end of the bar.
+//!
+//! Pitches are compared as they sound, with the key signature and the
+//! accidentals carried over in the bar. A chord symbol over a chord (`[CEG]`)
+//! or a rest is not checked.
+
+use chamber_ast::visit::{VisitContext, Visitor};
+use chamber_ast::{
+    Accidental, Annotation, BarLine, BarLineKind, Chord, Ending, InlineField, Note, Pitch, Rest,
+};
+use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use chamber_text_size::TextRange;
+
+use crate::context::RuleContext;
+use crate::model::{
+    BarAccidentals, BarEnd, BarTracker, BeatStructure, ChordSymbol, Fraction, PitchModel,
+    TimingModel, Voices,
+};
+use crate::rule::{Category, NoOptions, Rule, RuleMeta};
+use crate::rules::bar_length::tuplet_time;
+
+/// Rule that warns about chord symbols that clash with the melody note on a
+/// strong beat.
+pub struct ChordMelodyClash {
+    diagnostics: Vec<Diagnostic>,
+    timing: TimingModel,
+    beats: BeatStructure,
+    key: PitchModel,
+    voices: Voices<VoiceState>,
+}
+
+#[derive(Debug, Default)]
+struct VoiceState {
+    accidentals: BarAccidentals,
+    bar: BarTracker,
+    /// A chord symbol waiting for its note, and where it is.
+    chord: Option<(ChordSymbol, TextRange)>,
+    /// Clashes in the bar, and where in the bar their notes start.
+    clashes: Vec<(Fraction, Diagnostic)>,
+    /// The last bar, if it had clashes, until the next bar tells whether it
+    /// is a pickup.
+    previous: Option<Bar>,
+}
+
+/// A complete bar with clashes.
+#[derive(Debug)]
+struct Bar {
+    end: BarEnd,
+    beats: BeatStructure,
+    clashes: Vec<(Fraction, Diagnostic)>,
+}
+
+impl Bar {
+    /// Returns the clashes on strong beats. `next` is the bar after this
+    /// one, which tells whether it is a pickup.
+    fn strong_clashes(self, next: Option<&BarEnd>) -> impl Iterator<Item = Diagnostic> {
+        // A pickup lines up with the end of the bar
+        let shift = self.end.pickup(next).unwrap_or(Fraction::zero());
+        let beats = self.beats;
+        self.clashes
+            .into_iter()
+            .filter(move |&(start, _)| beats.is_strong(start + shift))
+            .map(|(_, diagnostic)| diagnostic)
+    }
+}
+
+impl RuleMeta for ChordMelodyClash {
+    const NAME: &'static str = "chordMelodyClash";
+    const CODE: DiagnosticCode = DiagnosticCode::ChordMelodyClash;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Lint;
+    const DOCS: &'static str =
+        "Warns when the melody note under a chord symbol is a semitone from its root on a \
+         strong beat.";
+}
+
+impl Rule for ChordMelodyClash {
+    type Options = NoOptions;
+
+    fn new(cx: &RuleContext<Self::Options>) -> Self {
+        Self {
+            diagnostics: Vec::new(),
+            timing: *cx.timing(),
+            beats: BeatStructure::new(cx.header().meter),
+            key: cx.pitch().clone(),
+            voices: Voices::new(),
+        }
+    }
+
+    fn finish(mut self, _cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        let meter = self.timing.meter();
+        for voice in self.voices.values_mut() {
+            voice.end_bar(
+                BarLineKind::Single,
+                meter,
+                &self.beats,
+                &mut self.diagnostics,
+            );
+            if let Some(bar) = voice.previous.take() {
+                self.diagnostics.extend(bar.strong_clashes(None));
+            }
+        }
+        self.diagnostics.sort_by_key(|d| d.range.start());
+        self.diagnostics
+    }
+}
+
+impl ChordMelodyClash {
+    fn add_time(&mut self, duration: Fraction, cx: &VisitContext) {
+        let voice = self.voices.current(cx);
+        voice.bar.add(duration);
+        voice.chord = None;
+    }
+}
+
+impl VoiceState {
+    /// Ends the current bar at a bar line of `kind`, and reports the
+    /// clashes of the bar before it on strong beats. `meter` and `beats`
+    /// are those of the current bar.
+    fn end_bar(
+        &mut self,
+        kind: BarLineKind,
+        meter: Option<Fraction>,
+        beats: &BeatStructure,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        self.accidentals.clear();
+        self.chord = None;
+        let clashes = std::mem::take(&mut self.clashes);
+        let Some(end) = self.bar.end_bar(kind, meter) else {
+            return;
+        };
+        if let Some(bar) = self.previous.take() {
+            diagnostics.extend(bar.strong_clashes(Some(&end)));
+        }
+        if !clashes.is_empty() {
+            self.previous = Some(Bar {
+                end,
+                beats: beats.clone(),
+                clashes,
+            });
+        }
+    }
+}
+
+fn note_name(pitch: Pitch, accidental: Option<Accidental>) -> String {
+    let letter = match pitch {
+        Pitch::C => "C",
+        Pitch::D => "D",
+        Pitch::E => "E",
+        Pitch::F => "F",
+        Pitch::G => "G",
+        Pitch::A => "A",
+        Pitch::B => "B",
+    };
+    let accidental = match accidental {
+        Some(Accidental::Sharp) => "#",
+        Some(Accidental::DoubleSharp) => "##",
+        Some(Accidental::Flat) => "b",
+        Some(Accidental::DoubleFlat) => "bb",
+        Some(Accidental::Natural) | None => "",
+    };
+    format!("{}{}", letter, accidental)
+}
+
+impl Visitor for ChordMelodyClash {
+    fn visit_annotation(&mut self, annotation: &Annotation, cx: &VisitContext) {
+        if !ChordSymbol::is_chord_text(&annotation.text) {
+            return;
+        }
+        let chord = ChordSymbol::parse(annotation.text.trim());
+        self.voices.current(cx).chord = chord.map(|chord| (chord, annotation.range));
+    }
+
+    fn visit_note(&mut self, note: &Note, cx: &VisitContext) {
+        let key = &self.key;
+        let voice = self.voices.current(cx);
+        // Chord notes count with their chord; grace notes take no time, and
+        // the chord symbol is for the note after them
+        if cx.in_chord || cx.in_grace_notes {
+            voice.accidentals.add(note);
+            return;
+        }
+        let midi_pitch = voice.accidentals.midi_pitch(key, note);
+        let accidental = voice.accidentals.accidental(key, note);
+        voice.accidentals.add(note);
+
+        if let Some((chord, range)) = voice.chord.take() {
+            let pitch_class = midi_pitch.rem_euclid(12) as u8;
+            let from_root = (pitch_class + 12 - chord.root_pitch_class()) % 12;
+            if !chord.pitch_classes().contains(&pitch_class) && matches!(from_root, 1 | 11) {
+                let name = note_name(note.pitch, accidental);
+                let root = note_name(chord.root, chord.root_accidental);
+                let diagnostic = Diagnostic::warning(
+                    DiagnosticCode::ChordMelodyClash,
+                    note.range,
+                    format!("melody note {} clashes with the chord", name),
+                )
+                .with_label(range, "chord symbol")
+                .with_note(format!(
+                    "{} is a semitone from {}, the root of the chord, on a strong beat",
+                    name, root
+                ));
+                voice.clashes.push((voice.bar.position(), diagnostic));
+            }
+        }
+
+        let mut duration = self.timing.duration(note.duration.as_ref());
+        if let Some(ratio) = cx.tuplet {
+            duration = duration * Fraction::new(tuplet_time(ratio), ratio);
+        }
+        self.add_time(duration, cx);
+    }
+
+    fn visit_chord(&mut self, chord: &Chord, cx: &VisitContext) {
+        let mut duration = self.timing.duration(chord.duration.as_ref());
+        if let Some(ratio) = cx.tuplet {
+            duration = duration * Fraction::new(tuplet_time(ratio), ratio);
+        }
+        self.add_time(duration, cx);
+    }
+
+    fn visit_rest(&mut self, rest: &Rest, cx: &VisitContext) {
+        // A multi-measure rest stands for whole bars
+        if rest.multi_measure {
+            let voice = self.voices.current(cx);
+            voice.bar.add_multi_measure_rest();
+            voice.chord = None;
+            return;
+        }
+        let duration = self.timing.duration(rest.duration.as_ref());
+        self.add_time(duration, cx);
+    }
+
+    fn visit_bar_line(&mut self, bar_line: &BarLine, cx: &VisitContext) {
+        let meter = self.timing.meter();
+        self.voices
+            .current(cx)
+            .end_bar(bar_line.kind, meter, &self.beats, &mut self.diagnostics);
+    }
+
+    fn visit_ending(&mut self, _ending: &Ending, cx: &VisitContext) {
+        self.voices.current(cx).bar.start_ending();
+    }
+
+    fn visit_inline_field(&mut self, field: &InlineField, _cx: &VisitContext) {
+        self.timing.apply_field(field.label, &field.value);
+        self.beats.apply_field(field.label, &field.value);
+        self.key.apply_field(field.label, &field.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::RuleExt;
+    use chamber_parser::parse;
+
+    fn messages(meter: &str, body: &str) -> Vec<String> {
+        let source = format!("X:1\nM:{}\nL:1/8\nK:G\n{}", meter, body);
+        ChordMelodyClash::check(&parse(&source))
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_chord_tones() {
+        assert!(messages("4/4", "\"G\"GABc \"D7\"dcBA|\"Em\"E4 \"Cmaj7\"B4|").is_empty());
+        // The seventh of the chord
+        assert!(messages("4/4", "\"C7\"_B4 \"Cmaj7\"B4|").is_empty());
+    }
+
+    #[test]
+    fn test_clash_on_strong_beat() {
+        // F is sharp in G
+        assert_eq!(
+            messages("4/4", "\"G\"f4 \"C\"c4|"),
+            ["melody note F# clashes with the chord"]
+        );
+        assert_eq!(
+            messages("4/4", "\"G\"G4 \"C\"^c4|"),
+            ["melody note C# clashes with the chord"]
+        );
+        assert_eq!(
+            messages("6/8", "\"G\"GAB \"Am\"^GAB|"),
+            ["melody note G# clashes with the chord"]
+        );
+    }
+
+    #[test]
+    fn test_weak_beats() {
+        assert!(messages("4/4", "G2\"G\"f2 \"C\"c4|").is_empty());
+        assert!(messages("3/4", "G2 G \"C\"B c2|").is_empty());
+        // A pickup lines up with the end of the bar
+        assert!(messages("4/4", "\"G\"f2|\"G\"G8|").is_empty());
+        assert_eq!(
+            messages("4/4", "G2|\"G\"f2 G6|"),
+            ["melody note F# clashes with the chord"]
+        );
+        // Without a full bar after it, a short bar is not a pickup
+        assert_eq!(
+            messages("3/4", "\"G\"f2 G2|"),
+            ["melody note F# clashes with the chord"]
+        );
+        assert_eq!(
+            messages("3/4", "|:\"G\"f2 G2:|"),
+            ["melody note F# clashes with the chord"]
+        );
+    }
+
+    #[test]
+    fn test_voices() {
+        assert!(messages("4/4", "[V:1]G2|\"G\"G8|[V:2]\"G\"f2|G8|").is_empty());
+        assert_eq!(
+            messages("3/4", "[V:1]\"G\"f2 G2|[V:2]G6|"),
+            ["melody note F# clashes with the chord"]
+        );
+    }
+
+    #[test]
+    fn test_accidentals_carry_in_bar() {
+        assert!(messages("4/4", "=f4 \"G\"f4|").is_empty());
+        assert_eq!(
+            messages("4/4", "^G4 \"G\"G4|"),
+            ["melody note G# clashes with the chord"]
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! W015: Chord spelling warning.
+//!
+//! Warns about chord symbols that spell a quality differently from the house
+//! style: `m`, `min`, `mi` or `-` for minor, `maj`, `Maj`, `M` or `Δ` for
+//! major sevenths, `dim`, `o` or `°` for diminished, and `aug` or `+` for
+//! augmented. The style for each quality is set in the options; where it
+//! isn't, the first spelling in the tune is the style.
+//!
+//! `Δ` alone stands for `maj7`, so the fix adds or drops the `7` when
+//! switching between the two.
+
+use std::collections::HashMap;
+
+use chamber_ast::visit::{VisitContext, Visitor};
+use chamber_ast::Annotation;
+use chamber_diagnostics::{Applicability, Diagnostic, DiagnosticCode, Fix, Severity, TextEdit};
+use chamber_text_size::{TextRange, TextSize};
+use serde::{Deserialize, Serialize};
+
+use crate::context::RuleContext;
+use crate::model::{ChordQuality, ChordSymbol};
+use crate::rule::{Category, Rule, RuleMeta};
+
+/// Rule that warns about chord symbols not spelled in the house style.
+pub struct ChordSpelling {
+    options: ChordSpellingOptions,
+    diagnostics: Vec<Diagnostic>,
+    /// The first spelling of each quality with no style in the options, and
+    /// where it is.
+    first: HashMap<ChordQuality, (String, TextRange)>,
+}
+
+/// Options for [`ChordSpelling`].
+///
+/// ```json
+/// { "minor": "m", "major": "maj", "diminished": "dim", "augmented": "+" }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct ChordSpellingOptions {
+    /// How minor chords are spelled.
+    pub minor: Option<MinorSpelling>,
+    /// How major seventh chords are spelled.
+    pub major: Option<MajorSpelling>,
+    /// How diminished chords are spelled.
+    pub diminished: Option<DiminishedSpelling>,
+    /// How augmented chords are spelled.
+    pub augmented: Option<AugmentedSpelling>,
+}
+
+/// A spelling of minor chords.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MinorSpelling {
+    #[serde(rename = "m")]
+    M,
+    #[serde(rename = "min")]
+    Min,
+    #[serde(rename = "mi")]
+    Mi,
+    #[serde(rename = "-")]
+    Dash,
+}
+
+/// A spelling of major seventh chords.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MajorSpelling {
+    #[serde(rename = "maj")]
+    Maj,
+    #[serde(rename = "Maj")]
+    CapitalMaj,
+    #[serde(rename = "M")]
+    M,
+    #[serde(rename = "Δ")]
+    Delta,
+}
+
+/// A spelling of diminished chords.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiminishedSpelling {
+    #[serde(rename = "dim")]
+    Dim,
+    #[serde(rename = "o")]
+    O,
+    #[serde(rename = "°")]
+    Degree,
+}
+
+/// A spelling of augmented chords.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AugmentedSpelling {
+    #[serde(rename = "aug")]
+    Aug,
+    #[serde(rename = "+")]
+    Plus,
+}
+
+impl ChordSpellingOptions {
+    /// Returns the configured spelling of `quality`, if any.
+    fn spelling(&self, quality: ChordQuality) -> Option<&'static str> {
+        match quality {
+            ChordQuality::Minor => self.minor.map(|spelling| match spelling {
+                MinorSpelling::M => "m",
+                MinorSpelling::Min => "min",
+                MinorSpelling::Mi => "mi",
+                MinorSpelling::Dash => "-",
+            }),
+            ChordQuality::Major => self.major.map(|spelling| match spelling {
+                MajorSpelling::Maj => "maj",
+                MajorSpelling::CapitalMaj => "Maj",
+                MajorSpelling::M => "M",
+                MajorSpelling::Delta => "Δ",
+            }),
+            ChordQuality::Diminished => self.diminished.map(|spelling| match spelling {
+                DiminishedSpelling::Dim => "dim",
+                DiminishedSpelling::O => "o",
+                DiminishedSpelling::Degree => "°",
+            }),
+            ChordQuality::Augmented => self.augmented.map(|spelling| match spelling {
+                AugmentedSpelling::Aug => "aug",
+                AugmentedSpelling::Plus => "+",
+            }),
+            ChordQuality::HalfDiminished => None,
+        }
+    }
+}
+
+impl RuleMeta for ChordSpelling {
+    const NAME: &'static str = "chordSpelling";
+    const CODE: DiagnosticCode = DiagnosticCode::ChordSpelling;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Style;
+    const DOCS: &'static str =
+        "Warns about chord symbols that spell a quality differently from the house style.";
+}
+
+impl Rule for ChordSpelling {
+    type Options = ChordSpellingOptions;
+
+    fn new(cx: &RuleContext<Self::Options>) -> Self {
+        Self {
+            options: *cx.options(),
+            diagnostics: Vec::new(),
+            first: HashMap::new(),
+        }
+    }
+
+    fn finish(self, _cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+/// Returns the text replacing `word`, followed by `after`, to spell it
+/// `style` instead.
+fn replacement(word: &str, after: &str, style: &str) -> String {
+    let extended = after.starts_with(|c: char| c.is_ascii_digit());
+    match (word, style) {
+        // `Δ` is `maj7`, where `maj` alone is a major triad
+        ("Δ", _) if !extended => format!("{}7", style),
+        (_, "Δ") if !extended => String::new(),
+        _ => style.to_string(),
+    }
+}
+
+impl Visitor for ChordSpelling {
+    fn visit_annotation(&mut self, annotation: &Annotation, _cx: &VisitContext) {
+        let text = annotation.text.as_str();
+        if !ChordSymbol::is_chord_text(text) {
+            return;
+        }
+        let Some(chord) = ChordSymbol::parse(text.trim()) else {
+            return;
+        };
+        // Past the opening quote and any leading spaces
+        let offset = 1 + text.len() - text.trim_start().len();
+        let start = annotation.range.start() + TextSize::new(offset as u32);
+        let text = text.trim();
+
+        for (quality, span) in chord.spellings {
+            let word = &text[span.clone()];
+            let range = TextRange::new(
+                start + TextSize::new(span.start as u32),
+                start + TextSize::new(span.end as u32),
+            );
+            let (style, first) = match self.options.spelling(quality) {
+                Some(style) => (style.to_string(), None),
+                None => {
+                    let (style, first) = self
+                        .first
+                        .entry(quality)
+                        .or_insert_with(|| (word.to_string(), range));
+                    (style.clone(), Some(*first))
+                }
+            };
+            if word == style {
+                continue;
+            }
+
+            let mut diagnostic = Diagnostic::warning(
+                DiagnosticCode::ChordSpelling,
+                range,
+                format!(
+                    "{} chord spelled '{}', not '{}'",
+                    quality.name(),
+                    word,
+                    style
+                ),
+            );
+            if let Some(first) = first {
+                diagnostic = diagnostic.with_label(first, "first spelled here");
+            }
+            self.diagnostics.push(diagnostic.with_fix(Fix::edit(
+                format!("spell it '{}'", style),
+                TextEdit::replace(range, replacement(word, &text[span.end..], &style)),
+                Applicability::MachineApplicable,
+            )));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::check_source;
+    use chamber_diagnostics::apply_fixes;
+
+    fn fix(body: &str, options: ChordSpellingOptions) -> String {
+        let header = "X:1\nK:G\n";
+        let source = format!("{}{}", header, body);
+        let diagnostics = check_source::<ChordSpelling>(&source, &options);
+        let fixed = apply_fixes(&source, &diagnostics, Applicability::MachineApplicable).source;
+        fixed[header.len()..].to_string()
+    }
+
+    #[test]
+    fn test_first_spelling_is_the_style() {
+        let source = "X:1\nK:G\n\"Em\"E4 \"Amin7\"A4|\"Bm\"B4 \"Em7\"E4|";
+        let diagnostics = check_source::<ChordSpelling>(source, &Default::default());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "minor chord spelled 'min', not 'm'");
+        assert_eq!(diagnostics[0].labels[0].message, "first spelled here");
+
+        assert_eq!(
+            fix("\"Am\"A4 \"Dmin\"D4 \"D-7\"D4|", Default::default()),
+            "\"Am\"A4 \"Dm\"D4 \"Dm7\"D4|"
+        );
+    }
+
+    #[test]
+    fn test_configured_spelling() {
+        let options = ChordSpellingOptions {
+            minor: Some(MinorSpelling::Min),
+            major: Some(MajorSpelling::Maj),
+            diminished: Some(DiminishedSpelling::Dim),
+            augmented: Some(AugmentedSpelling::Plus),
+        };
+        assert_eq!(
+            fix("\"Am\"A4 \"CM7\"c4 \"Bo7\"B4 \"Daug\"d4|", options),
+            "\"Amin\"A4 \"Cmaj7\"c4 \"Bdim7\"B4 \"D+\"d4|"
+        );
+        // Dominant and half-diminished chords have one spelling
+        assert_eq!(fix("\"D7\"d4 \"Bø\"B4|", options), "\"D7\"d4 \"Bø\"B4|");
+    }
+
+    #[test]
+    fn test_triangle() {
+        let delta = ChordSpellingOptions {
+            major: Some(MajorSpelling::Delta),
+            ..Default::default()
+        };
+        let maj = ChordSpellingOptions {
+            major: Some(MajorSpelling::Maj),
+            ..Default::default()
+        };
+        assert_eq!(fix("\"CΔ\"c4 \"FΔ9\"f4|", maj), "\"Cmaj7\"c4 \"Fmaj9\"f4|");
+        assert_eq!(fix("\"Cmaj7\"c4 \"FM\"f4|", delta), "\"CΔ7\"c4 \"F\"f4|");
+    }
+
+    #[test]
+    fn test_options() {
+        let options: ChordSpellingOptions =
+            serde_json::from_value(serde_json::json!({ "minor": "-", "major": "Δ" })).unwrap();
+        assert_eq!(options.minor, Some(MinorSpelling::Dash));
+        assert_eq!(options.major, Some(MajorSpelling::Delta));
+        assert!(serde_json::from_value::<ChordSpellingOptions>(
+            serde_json::json!({ "minor": "min7" })
+        )
+        .is_err());
+    }
+}
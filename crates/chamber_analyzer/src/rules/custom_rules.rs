@@ -0,0 +1,328 @@
+//! C001: House rules declared in the configuration.
+//!
+//! Checks the rules listed in the options of `customRules`: header fields
+//! every tune must have, decorations that must not be used, and patterns
+//! that header values must match. Required fields are written and checked
+//! as for `requiredFields` (W019), so `"Z|S"` asks for either field.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chamber_ast::visit::{VisitContext, Visitor};
+use chamber_ast::{Decoration, HeaderField};
+use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use chamber_text_size::TextRange;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::context::RuleContext;
+use crate::rule::{Category, Rule, RuleMeta};
+use crate::rules::required_fields::{missing_fields, RequiredField};
+
+/// Rule that checks the house rules declared in its options.
+pub struct CustomRules {
+    rules: Vec<CustomRuleDefinition>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Options for [`CustomRules`].
+///
+/// ```json
+/// {
+///   "rules": [
+///     { "name": "requireOrigin", "requiredFields": ["R", "O"] },
+///     { "name": "noCrescendo", "forbiddenDecorations": ["crescendo(", "<("] },
+///     { "name": "titleCase", "headerPatterns": { "T": "^[A-Z]" } }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct CustomRulesOptions {
+    /// The house rules to check.
+    pub rules: Vec<CustomRuleDefinition>,
+}
+
+/// A house rule: any combination of checks, reported under one name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CustomRuleDefinition {
+    /// The rule's name, shown at the start of each message.
+    pub name: String,
+    /// Message to report instead of the default one for each check.
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Header fields every tune must have (e.g., "R", "O", or "Z|S" for
+    /// either), checked like those of
+    /// [`RequiredFields`](crate::rules::RequiredFields).
+    #[serde(default)]
+    pub required_fields: Vec<RequiredField>,
+    /// Decoration names that must not be used (case-insensitive).
+    #[serde(default)]
+    pub forbidden_decorations: Vec<String>,
+    /// Patterns the values of header fields must match, by field label.
+    #[serde(default)]
+    pub header_patterns: BTreeMap<FieldLabel, HeaderPattern>,
+}
+
+impl CustomRuleDefinition {
+    fn diagnostic(&self, range: TextRange, default: String) -> Diagnostic {
+        let message = self.message.clone().unwrap_or(default);
+        Diagnostic::warning(
+            DiagnosticCode::CustomRule,
+            range,
+            format!("{}: {}", self.name, message),
+        )
+    }
+}
+
+/// The letter of a header field, written "R" or "R:" in the configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FieldLabel(char);
+
+impl FieldLabel {
+    /// Returns the label for an ASCII letter.
+    pub fn new(label: char) -> Option<Self> {
+        label.is_ascii_alphabetic().then_some(Self(label))
+    }
+
+    /// Returns the letter.
+    pub fn as_char(self) -> char {
+        self.0
+    }
+}
+
+impl fmt::Display for FieldLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.0)
+    }
+}
+
+impl Serialize for FieldLabel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for FieldLabel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        let mut chars = text.strip_suffix(':').unwrap_or(&text).chars();
+        match (chars.next().and_then(FieldLabel::new), chars.next()) {
+            (Some(label), None) => Ok(label),
+            _ => Err(serde::de::Error::custom(format!(
+                "invalid field label '{}', expected a letter like \"R\"",
+                text
+            ))),
+        }
+    }
+}
+
+/// A regular expression for header values, compiled when the configuration
+/// is read.
+#[derive(Debug, Clone)]
+pub struct HeaderPattern(Regex);
+
+impl HeaderPattern {
+    /// Compiles a pattern.
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Regex::new(pattern).map(Self)
+    }
+
+    /// Returns true if `value` matches anywhere (use `^...$` to match all
+    /// of it).
+    pub fn is_match(&self, value: &str) -> bool {
+        self.0.is_match(value)
+    }
+
+    /// Returns the pattern's source text.
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Serialize for HeaderPattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for HeaderPattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        HeaderPattern::new(&pattern).map_err(serde::de::Error::custom)
+    }
+}
+
+impl RuleMeta for CustomRules {
+    const NAME: &'static str = "customRules";
+    const CODE: DiagnosticCode = DiagnosticCode::CustomRule;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Lint;
+    const DOCS: &'static str =
+        "Checks house rules declared in the configuration (required fields, forbidden decorations, header patterns).";
+}
+
+impl Rule for CustomRules {
+    type Options = CustomRulesOptions;
+
+    fn new(cx: &RuleContext<Self::Options>) -> Self {
+        Self {
+            rules: cx.options().rules.clone(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn finish(mut self, cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        let header = &cx.tune().header;
+        // Point at the first header line rather than the whole header
+        let range = header.fields.first().map_or(header.range, |f| f.range);
+        for rule in &self.rules {
+            for (required, empty) in missing_fields(header, &rule.required_fields) {
+                let diagnostic = match empty {
+                    Some(field) => {
+                        rule.diagnostic(field.range, format!("empty '{}' field", required))
+                    }
+                    None => rule.diagnostic(range, format!("missing '{}' field", required)),
+                };
+                self.diagnostics.push(diagnostic);
+            }
+        }
+        self.diagnostics
+    }
+}
+
+impl Visitor for CustomRules {
+    fn visit_header_field(&mut self, field: &HeaderField, _cx: &VisitContext) {
+        let Some(label) = FieldLabel::new(field.kind.to_char()) else {
+            return;
+        };
+        let value = field.value.trim();
+        for rule in &self.rules {
+            let Some(pattern) = rule.header_patterns.get(&label) else {
+                continue;
+            };
+            if !pattern.is_match(value) {
+                self.diagnostics.push(rule.diagnostic(
+                    field.range,
+                    format!(
+                        "'{}' value '{}' does not match /{}/",
+                        label,
+                        value,
+                        pattern.as_str()
+                    ),
+                ));
+            }
+        }
+    }
+
+    fn visit_decoration(&mut self, decoration: &Decoration, _cx: &VisitContext) {
+        for rule in &self.rules {
+            let forbidden = rule
+                .forbidden_decorations
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&decoration.name));
+            if forbidden {
+                self.diagnostics.push(rule.diagnostic(
+                    decoration.range,
+                    format!("decoration '{}' is not allowed", decoration.name),
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::RuleExt;
+    use chamber_parser::parse;
+
+    fn options(json: serde_json::Value) -> CustomRulesOptions {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_no_rules() {
+        let tune = parse("X:1\nK:C\n!crescendo(!C");
+        assert!(CustomRules::check(&tune).is_empty());
+    }
+
+    #[test]
+    fn test_required_fields() {
+        let options = options(serde_json::json!({
+            "rules": [{ "name": "requireOrigin", "requiredFields": ["R", "O:"] }]
+        }));
+
+        let tune = parse("X:1\nT:Tune\nR:reel\nK:C\nCDEF|");
+        let diagnostics = CustomRules::check_with(&tune, &options);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "requireOrigin: missing 'O:' field");
+        assert_eq!(diagnostics[0].code, DiagnosticCode::CustomRule);
+
+        let tune = parse("X:1\nT:Tune\nR:reel\nO:Ireland\nK:C\nCDEF|");
+        assert!(CustomRules::check_with(&tune, &options).is_empty());
+    }
+
+    #[test]
+    fn test_required_field_alternatives() {
+        // Checked like the fields of `requiredFields`
+        let options = options(serde_json::json!({
+            "rules": [{ "name": "requireSource", "requiredFields": ["Z|S"] }]
+        }));
+        let tune = parse("X:1\nT:Tune\nS:Tommy Peoples\nK:C\nCDEF|");
+        assert!(CustomRules::check_with(&tune, &options).is_empty());
+        let tune = parse("X:1\nT:Tune\nZ:\nK:C\nCDEF|");
+        let diagnostics = CustomRules::check_with(&tune, &options);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "requireSource: empty 'Z: or S:' field"
+        );
+    }
+
+    #[test]
+    fn test_forbidden_decorations() {
+        let options = options(serde_json::json!({
+            "rules": [{
+                "name": "noCrescendo",
+                "message": "use !<(! instead",
+                "forbiddenDecorations": ["Crescendo("]
+            }]
+        }));
+
+        let tune = parse("X:1\nK:C\n!crescendo(!C (!crescendo(!D E)");
+        let diagnostics = CustomRules::check_with(&tune, &options);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].message, "noCrescendo: use !<(! instead");
+    }
+
+    #[test]
+    fn test_header_patterns() {
+        let options = options(serde_json::json!({
+            "rules": [{ "name": "titleCase", "headerPatterns": { "T": "^[A-Z]" } }]
+        }));
+
+        let tune = parse("X:1\nT:The Kesh\nT:the other title\nK:G\nGAB|");
+        let diagnostics = CustomRules::check_with(&tune, &options);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("'the other title'"));
+    }
+
+    #[test]
+    fn test_invalid_definitions_rejected() {
+        let invalid = [
+            serde_json::json!({ "rules": [{ "name": "x", "headerPatterns": { "T": "(" } }] }),
+            serde_json::json!({ "rules": [{ "name": "x", "requiredFields": ["RR"] }] }),
+            serde_json::json!({ "rules": [{ "requiredFields": ["R"] }] }),
+            serde_json::json!({ "rules": [{ "name": "x", "requiredField": ["R"] }] }),
+        ];
+        for json in invalid {
+            assert!(
+                serde_json::from_value::<CustomRulesOptions>(json.clone()).is_err(),
+                "{}",
+                json
+            );
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! W007: Tune with the same melody as another tune.
+//!
+//! Finds tunes of a collection with the same notes in the same order, for
+//! example a tune copied in twice under different titles. Bar lines,
+//! decorations, chord symbols and grace notes are ignored.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use chamber_ast::visit::{walk_tune, VisitContext, Visitor};
+use chamber_ast::{Duration, Note, Rest};
+use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use chamber_text_size::TextRange;
+use serde::{Deserialize, Serialize};
+
+use crate::context::RuleContext;
+use crate::rule::{Category, CollectionRule, RuleMeta};
+
+/// Collection rule that finds tunes with the same melody.
+pub struct DuplicateMelody {
+    min_notes: usize,
+    /// The first tune with each melody, by fingerprint.
+    seen: HashMap<u64, TextRange>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Options for [`DuplicateMelody`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct DuplicateMelodyOptions {
+    /// Tunes with fewer notes are not compared (short exercises and
+    /// fragments are often alike).
+    pub min_notes: usize,
+}
+
+impl Default for DuplicateMelodyOptions {
+    fn default() -> Self {
+        Self { min_notes: 8 }
+    }
+}
+
+impl RuleMeta for DuplicateMelody {
+    const NAME: &'static str = "duplicateMelody";
+    const CODE: DiagnosticCode = DiagnosticCode::DuplicateMelody;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Lint;
+    const DOCS: &'static str =
+        "Warns when tunes of a collection have the same notes in the same order.";
+}
+
+impl CollectionRule for DuplicateMelody {
+    type Options = DuplicateMelodyOptions;
+
+    fn new(options: &Self::Options) -> Self {
+        Self {
+            min_notes: options.min_notes,
+            seen: HashMap::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn visit_tune(&mut self, cx: &RuleContext<Self::Options>) {
+        let tune = cx.tune();
+        let mut melody = Melody::default();
+        walk_tune(&mut melody, tune);
+        if melody.notes == 0 || melody.notes < self.min_notes {
+            return;
+        }
+
+        // Point at the first header line (usually `X:`)
+        let range = tune
+            .header
+            .fields
+            .first()
+            .map_or(tune.body.range, |f| f.range)
+            + cx.offset();
+        match self.seen.get(&melody.hasher.finish()) {
+            Some(&first) => self.diagnostics.push(
+                Diagnostic::warning(
+                    DiagnosticCode::DuplicateMelody,
+                    range,
+                    "tune has the same melody as an earlier tune",
+                )
+                .with_label(first, "earlier tune with this melody"),
+            ),
+            None => {
+                self.seen.insert(melody.hasher.finish(), range);
+            }
+        }
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn finish(self, _options: &Self::Options) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+/// Fingerprint of a tune's notes and rests.
+#[derive(Default)]
+struct Melody {
+    hasher: DefaultHasher,
+    notes: usize,
+}
+
+impl Melody {
+    fn hash_duration(&mut self, duration: Option<&Duration>) {
+        duration
+            .map(|d| (d.numerator, d.denominator))
+            .hash(&mut self.hasher);
+    }
+}
+
+impl Visitor for Melody {
+    fn visit_note(&mut self, note: &Note, cx: &VisitContext) {
+        if cx.in_grace_notes {
+            return;
+        }
+        (note.pitch, note.octave, note.accidental, cx.in_chord).hash(&mut self.hasher);
+        self.hash_duration(note.duration.as_ref());
+        self.notes += 1;
+    }
+
+    fn visit_rest(&mut self, rest: &Rest, _cx: &VisitContext) {
+        rest.multi_measure.hash(&mut self.hasher);
+        self.hash_duration(rest.duration.as_ref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::check_collection;
+
+    fn check(source: &str) -> Vec<Diagnostic> {
+        check_collection::<DuplicateMelody>(source, &DuplicateMelodyOptions::default())
+    }
+
+    #[test]
+    fn test_same_melody() {
+        let source = "X:1\nT:Reel\nK:D\n!p!DFAd fdAF|GBdg|]\n\n\
+                      X:2\nT:Other Reel\nK:D\nDF Ad | fdAF |\nGBdg|]\n";
+        let diagnostics = check(source);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].labels.len(), 1);
+    }
+
+    #[test]
+    fn test_different_melodies() {
+        let source = "X:1\nK:D\nDFAd fdAF|GBdg|]\n\nX:2\nK:D\nDFAd fdAF|GBdb|]\n";
+        assert!(check(source).is_empty());
+
+        // Durations count too
+        let source = "X:1\nK:D\nDFAd fdAF|GBdg|]\n\nX:2\nK:D\nD2FAd fdAF|GBdg|]\n";
+        assert!(check(source).is_empty());
+    }
+
+    #[test]
+    fn test_short_tunes_ignored() {
+        let source = "X:1\nK:C\nCDE|]\n\nX:2\nK:C\nCDE|]\n";
+        assert!(check(source).is_empty());
+
+        let options = DuplicateMelodyOptions { min_notes: 3 };
+        assert_eq!(
+            check_collection::<DuplicateMelody>(source, &options).len(),
+            1
+        );
+    }
+}
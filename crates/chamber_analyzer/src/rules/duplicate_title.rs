@@ -0,0 +1,123 @@
+//! W006: Title used by more than one tune.
+//!
+//! Reports tunes of a collection whose titles are the same, ignoring case
+//! and spacing. This is often deliberate (two settings of one tune), so it
+//! is only info.
+
+use std::collections::HashMap;
+
+use chamber_ast::HeaderFieldKind;
+use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use chamber_text_size::TextRange;
+
+use crate::context::RuleContext;
+use crate::rule::{Category, CollectionRule, NoOptions, RuleMeta};
+
+/// Collection rule that reports titles used more than once.
+pub struct DuplicateTitle {
+    /// The first `T:` field with each normalized title.
+    seen: HashMap<String, TextRange>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl RuleMeta for DuplicateTitle {
+    const NAME: &'static str = "duplicateTitle";
+    const CODE: DiagnosticCode = DiagnosticCode::DuplicateTitle;
+    const SEVERITY: Severity = Severity::Info;
+    const CATEGORY: Category = Category::Lint;
+    const DOCS: &'static str =
+        "Reports tunes of a collection with the same title (ignoring case and spacing).";
+}
+
+impl CollectionRule for DuplicateTitle {
+    type Options = NoOptions;
+
+    fn new(_options: &Self::Options) -> Self {
+        Self {
+            seen: HashMap::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn visit_tune(&mut self, cx: &RuleContext<Self::Options>) {
+        // Later `T:` fields are subtitles
+        let Some(field) = cx
+            .tune()
+            .header
+            .fields
+            .iter()
+            .find(|f| f.kind == HeaderFieldKind::Title)
+        else {
+            return;
+        };
+
+        let title = field.value.trim();
+        let key = normalize(title);
+        if key.is_empty() {
+            return;
+        }
+        let range = field.range + cx.offset();
+        match self.seen.get(&key) {
+            Some(&first) => self.diagnostics.push(
+                Diagnostic::info(
+                    DiagnosticCode::DuplicateTitle,
+                    range,
+                    format!("title '{}' is used more than once", title),
+                )
+                .with_label(first, "first used here"),
+            ),
+            None => {
+                self.seen.insert(key, range);
+            }
+        }
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn finish(self, _options: &Self::Options) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+/// Lowercases `title` and collapses its whitespace.
+fn normalize(title: &str) -> String {
+    title
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::check_collection;
+
+    fn check(source: &str) -> Vec<Diagnostic> {
+        check_collection::<DuplicateTitle>(source, &NoOptions {})
+    }
+
+    #[test]
+    fn test_different_titles() {
+        assert!(check("X:1\nT:The Kesh\nK:G\nG|\n\nX:2\nT:Kesh Jig\nK:G\nG|\n").is_empty());
+    }
+
+    #[test]
+    fn test_same_title_ignoring_case_and_spacing() {
+        let diagnostics = check("X:1\nT:The Kesh\nK:G\nG|\n\nX:2\nT:the  kesh\nK:G\nG|\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "title 'the  kesh' is used more than once"
+        );
+        assert_eq!(diagnostics[0].labels.len(), 1);
+    }
+
+    #[test]
+    fn test_subtitles_ignored() {
+        let source = "X:1\nT:Reel\nT:Medley\nK:D\nD|\n\nX:2\nT:Medley\nK:D\nD|\n";
+        assert!(check(source).is_empty());
+    }
+}
@@ -0,0 +1,112 @@
+//! W005: Reference number used by more than one tune.
+//!
+//! `X:` numbers identify the tunes of a collection, so each should be used
+//! once.
+
+use std::collections::HashMap;
+
+use chamber_ast::HeaderFieldKind;
+use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use chamber_text_size::TextRange;
+
+use crate::context::RuleContext;
+use crate::rule::{Category, CollectionRule, NoOptions, RuleMeta};
+
+/// Collection rule that checks that `X:` numbers are unique.
+pub struct DuplicateTuneNumber {
+    /// The first `X:` field with each number.
+    seen: HashMap<String, TextRange>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl RuleMeta for DuplicateTuneNumber {
+    const NAME: &'static str = "duplicateTuneNumber";
+    const CODE: DiagnosticCode = DiagnosticCode::DuplicateTuneNumber;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Lint;
+    const DOCS: &'static str = "Warns when tunes of a collection share a reference number (X:).";
+}
+
+impl CollectionRule for DuplicateTuneNumber {
+    type Options = NoOptions;
+
+    fn new(_options: &Self::Options) -> Self {
+        Self {
+            seen: HashMap::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn visit_tune(&mut self, cx: &RuleContext<Self::Options>) {
+        let field = cx
+            .tune()
+            .header
+            .fields
+            .iter()
+            .find(|f| f.kind == HeaderFieldKind::ReferenceNumber);
+        // A missing or empty `X:` is reported by the parser
+        let Some(field) = field.filter(|f| !f.value.trim().is_empty()) else {
+            return;
+        };
+
+        let number = field.value.trim();
+        let range = field.range + cx.offset();
+        match self.seen.get(number) {
+            Some(&first) => self.diagnostics.push(
+                Diagnostic::warning(
+                    DiagnosticCode::DuplicateTuneNumber,
+                    range,
+                    format!("tune number {} is used more than once", number),
+                )
+                .with_label(first, "first used here"),
+            ),
+            None => {
+                self.seen.insert(number.to_string(), range);
+            }
+        }
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn finish(self, _options: &Self::Options) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::check_collection;
+    use chamber_text_size::TextSize;
+
+    fn check(source: &str) -> Vec<Diagnostic> {
+        check_collection::<DuplicateTuneNumber>(source, &NoOptions {})
+    }
+
+    #[test]
+    fn test_unique_numbers() {
+        assert!(check("X:1\nK:C\nC|\n\nX:2\nK:C\nD|\n").is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_number() {
+        let source = "X:1\nK:C\nC|\n\nX:2\nK:C\nD|\n\nX:1\nK:C\nE|\n";
+        let diagnostics = check(source);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "tune number 1 is used more than once"
+        );
+
+        // Both occurrences, in collection offsets
+        assert_eq!(diagnostics[0].range.start(), TextSize::new(24));
+        assert_eq!(diagnostics[0].labels[0].range.start(), TextSize::new(0));
+    }
+
+    #[test]
+    fn test_single_tune() {
+        assert!(check("X:1\nK:C\nC|\n").is_empty());
+    }
+}
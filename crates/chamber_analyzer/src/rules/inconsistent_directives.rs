@@ -0,0 +1,188 @@
+//! W008: `%%` directive set to different values in a collection.
+//!
+//! Formatting directives such as `%%scale` or `%%staffwidth` usually belong
+//! to the whole collection; setting them differently from tune to tune is
+//! often a leftover from merging files. Directives are compared by their
+//! first word (two for `%%MIDI`), ignoring case.
+
+use std::collections::HashMap;
+
+use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use chamber_text_size::{TextRange, TextSize};
+use serde::{Deserialize, Serialize};
+
+use crate::context::RuleContext;
+use crate::rule::{Category, CollectionRule, RuleMeta};
+
+/// Collection rule that checks that `%%` directives agree between tunes.
+pub struct InconsistentDirectives {
+    ignored: Vec<String>,
+    /// The first value and range of each directive, by name.
+    seen: HashMap<String, (String, TextRange)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Options for [`InconsistentDirectives`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct InconsistentDirectivesOptions {
+    /// Directives that are expected to differ between tunes (e.g., "text",
+    /// "MIDI program").
+    pub ignored: Vec<String>,
+}
+
+impl Default for InconsistentDirectivesOptions {
+    fn default() -> Self {
+        let ignored = [
+            "text",
+            "center",
+            "begintext",
+            "endtext",
+            "newpage",
+            "vskip",
+            "sep",
+        ];
+        Self {
+            ignored: ignored.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+}
+
+impl RuleMeta for InconsistentDirectives {
+    const NAME: &'static str = "inconsistentDirectives";
+    const CODE: DiagnosticCode = DiagnosticCode::InconsistentDirective;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Lint;
+    const DOCS: &'static str =
+        "Warns when tunes of a collection set a %% directive to different values.";
+}
+
+impl CollectionRule for InconsistentDirectives {
+    type Options = InconsistentDirectivesOptions;
+
+    fn new(options: &Self::Options) -> Self {
+        Self {
+            ignored: options
+                .ignored
+                .iter()
+                .map(|name| name.to_lowercase())
+                .collect(),
+            seen: HashMap::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn visit_tune(&mut self, cx: &RuleContext<Self::Options>) {
+        let Some(source) = cx.source() else {
+            return;
+        };
+
+        // Only the first setting in each tune is compared
+        let mut in_tune = Vec::new();
+        let mut offset = 0;
+        for line in source.split_inclusive('\n') {
+            let start = offset;
+            offset += line.len();
+            let Some((name, value)) = parse_directive(line) else {
+                continue;
+            };
+            if in_tune.contains(&name) || self.ignored.contains(&name) {
+                continue;
+            }
+            let text = line.trim_end();
+            let range = TextRange::new(
+                TextSize::new(start as u32),
+                TextSize::new((start + text.len()) as u32),
+            ) + cx.offset();
+
+            match self.seen.get(&name) {
+                Some((first, first_range)) if *first != value => {
+                    self.diagnostics.push(
+                        Diagnostic::warning(
+                            DiagnosticCode::InconsistentDirective,
+                            range,
+                            format!(
+                                "'%%{}' is '{}' here but '{}' in an earlier tune",
+                                name, value, first
+                            ),
+                        )
+                        .with_label(*first_range, format!("set to '{}' here", first)),
+                    );
+                }
+                Some(_) => {}
+                None => {
+                    self.seen.insert(name.clone(), (value, range));
+                }
+            }
+            in_tune.push(name);
+        }
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn finish(self, _options: &Self::Options) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+/// Splits a `%%` line into its lowercase name and its value.
+fn parse_directive(line: &str) -> Option<(String, String)> {
+    let mut words = line.trim().strip_prefix("%%")?.split_whitespace();
+    let mut name = words.next()?.to_lowercase();
+    if name == "midi" {
+        name = format!("midi {}", words.next()?.to_lowercase());
+    }
+    Some((name, words.collect::<Vec<_>>().join(" ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::check_collection;
+
+    fn check(source: &str) -> Vec<Diagnostic> {
+        check_collection::<InconsistentDirectives>(source, &Default::default())
+    }
+
+    #[test]
+    fn test_consistent_directives() {
+        let source = "X:1\n%%scale 0.8\nK:C\nC|\n\nX:2\n%%scale  0.8\nK:C\nD|\n";
+        assert!(check(source).is_empty());
+    }
+
+    #[test]
+    fn test_inconsistent_directive() {
+        let source = "X:1\n%%scale 0.8\nK:C\nC|\n\nX:2\n%%SCALE 0.7\nK:C\nD|\n";
+        let diagnostics = check(source);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "'%%scale' is '0.7' here but '0.8' in an earlier tune"
+        );
+        assert_eq!(diagnostics[0].range.start(), TextSize::new(28));
+        assert_eq!(diagnostics[0].labels[0].range.start(), TextSize::new(4));
+        assert_eq!(diagnostics[0].labels[0].message, "set to '0.8' here");
+    }
+
+    #[test]
+    fn test_midi_subcommands() {
+        let source = "X:1\n%%MIDI program 1\nK:C\nC|\n\nX:2\n%%MIDI channel 2\nK:C\nD|\n";
+        assert!(check(source).is_empty());
+
+        let source = "X:1\n%%MIDI program 1\nK:C\nC|\n\nX:2\n%%MIDI program 40\nK:C\nD|\n";
+        assert_eq!(check(source).len(), 1);
+
+        let options = InconsistentDirectivesOptions {
+            ignored: vec!["MIDI program".to_string()],
+        };
+        assert!(check_collection::<InconsistentDirectives>(source, &options).is_empty());
+    }
+
+    #[test]
+    fn test_ignored_directives() {
+        let source = "X:1\n%%text First\nK:C\nC|\n\nX:2\n%%text Second\nK:C\nD|\n";
+        assert!(check(source).is_empty());
+    }
+}
@@ -0,0 +1,628 @@
+//! W016: Instrument range warning.
+//!
+//! Warns about notes a voice's instrument can't play, or can only play
+//! with difficulty. The instrument is the one configured for the voice, or
+//! the one its `V:` field names in full (`V:1 name="Fiddle"`), or the one
+//! set by a `%%MIDI program` line, or the configured default.
+//!
+//! Pitches are compared as written: with the key signature and the
+//! accidentals carried over in the bar, but not the voice's `transpose=`,
+//! which only changes how it sounds. Ranges are in the octave the
+//! instrument's music is usually written in, so a tin whistle plays from
+//! `D` to `d'`, an octave below how it sounds.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chamber_ast::visit::{VisitContext, Visitor};
+use chamber_ast::{BarLine, HeaderField, InlineField, Note};
+use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use chamber_text_size::{TextRange, TextSize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::context::RuleContext;
+use crate::model::{BarAccidentals, PitchModel, VoiceProperties, Voices};
+use crate::rule::{Category, Rule, RuleMeta};
+
+/// Rule that warns about notes outside the range of a voice's instrument.
+pub struct InstrumentRange {
+    options: InstrumentRangeOptions,
+    key: PitchModel,
+    voices: Voices<VoiceState>,
+    /// Where each `V:` field switches to a voice, in order.
+    switches: Vec<(TextSize, String)>,
+}
+
+#[derive(Debug, Default)]
+struct VoiceState {
+    accidentals: BarAccidentals,
+    /// The instrument the voice's `V:` field names, and where.
+    named: Option<(Instrument, TextRange)>,
+    /// Each note and its written pitch (MIDI note number).
+    notes: Vec<(TextRange, i32)>,
+}
+
+/// Options for [`InstrumentRange`].
+///
+/// ```json
+/// {
+///   "instrument": "fiddle",
+///   "voices": { "2": "cello" },
+///   "ranges": { "bouzouki": { "lowest": "G2", "highest": "E5" } }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct InstrumentRangeOptions {
+    /// Instrument of voices that don't declare one.
+    pub instrument: Option<String>,
+    /// Instrument of each voice, by voice id, over what the tune declares.
+    pub voices: BTreeMap<String, String>,
+    /// Ranges of instruments that aren't built in, or that replace built-in
+    /// ones, by name.
+    pub ranges: BTreeMap<String, InstrumentRangeDefinition>,
+}
+
+/// The range of an instrument declared in the configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct InstrumentRangeDefinition {
+    /// The lowest note the instrument can play.
+    pub lowest: PitchName,
+    /// The highest note the instrument can play.
+    pub highest: PitchName,
+    /// The lowest note it plays comfortably; `lowest` if not set.
+    #[serde(default)]
+    pub comfortable_lowest: Option<PitchName>,
+    /// The highest note it plays comfortably; `highest` if not set.
+    #[serde(default)]
+    pub comfortable_highest: Option<PitchName>,
+}
+
+/// A pitch in scientific notation, written "C4" (middle C), "F#5" or "Bb3"
+/// in the configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PitchName(i32);
+
+impl PitchName {
+    /// Returns the pitch with MIDI note number `number` (60 is middle C).
+    pub fn from_midi(number: i32) -> Self {
+        Self(number)
+    }
+
+    /// Parses a pitch in scientific notation.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut chars = text.chars();
+        let semitone = match chars.next()? {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            'B' => 11,
+            _ => return None,
+        };
+        let rest = chars.as_str();
+        let (alteration, octave) = if let Some(octave) = rest.strip_prefix('#') {
+            (1, octave)
+        } else if let Some(octave) = rest.strip_prefix('b') {
+            (-1, octave)
+        } else {
+            (0, rest)
+        };
+        let octave: i32 = octave.parse().ok()?;
+        Some(Self(12 * (octave + 1) + semitone + alteration))
+    }
+
+    /// Returns the MIDI note number.
+    pub fn midi(self) -> i32 {
+        self.0
+    }
+}
+
+impl fmt::Display for PitchName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const NAMES: [&str; 12] = [
+            "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+        ];
+        let name = NAMES[self.0.rem_euclid(12) as usize];
+        write!(f, "{}{}", name, self.0.div_euclid(12) - 1)
+    }
+}
+
+impl Serialize for PitchName {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PitchName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        PitchName::parse(&text).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "invalid pitch '{}', expected a note and octave like \"G3\"",
+                text
+            ))
+        })
+    }
+}
+
+/// A built-in instrument.
+struct BuiltinInstrument {
+    /// The name used in the configuration.
+    name: &'static str,
+    /// The name used in messages.
+    display: &'static str,
+    /// Names a `V:` field may give the instrument, in lower case. Single
+    /// letters, which are as often voice ids, are not names.
+    aliases: &'static [&'static str],
+    /// General MIDI programs (from 0) that play it.
+    programs: &'static [u32],
+    /// The lowest and highest notes it can play (MIDI note numbers).
+    playable: (i32, i32),
+    /// The lowest and highest notes it plays comfortably.
+    comfortable: (i32, i32),
+}
+
+const BUILTIN_INSTRUMENTS: &[BuiltinInstrument] = &[
+    BuiltinInstrument {
+        name: "tinWhistle",
+        display: "tin whistle",
+        aliases: &["tin whistle", "whistle", "penny whistle"],
+        programs: &[78],
+        playable: (62, 86),
+        comfortable: (62, 83),
+    },
+    BuiltinInstrument {
+        name: "flute",
+        display: "flute",
+        aliases: &["flute", "fl"],
+        programs: &[73],
+        playable: (60, 96),
+        comfortable: (60, 91),
+    },
+    BuiltinInstrument {
+        name: "fiddle",
+        display: "fiddle",
+        aliases: &["fiddle", "violin", "vln", "vl"],
+        programs: &[40, 110],
+        playable: (55, 100),
+        comfortable: (55, 88),
+    },
+    BuiltinInstrument {
+        name: "viola",
+        display: "viola",
+        aliases: &["viola", "vla"],
+        programs: &[41],
+        playable: (48, 88),
+        comfortable: (48, 81),
+    },
+    BuiltinInstrument {
+        name: "cello",
+        display: "cello",
+        aliases: &["cello", "violoncello", "vc"],
+        programs: &[42],
+        playable: (36, 81),
+        comfortable: (36, 69),
+    },
+    BuiltinInstrument {
+        name: "soprano",
+        display: "soprano",
+        aliases: &["soprano"],
+        programs: &[],
+        playable: (60, 81),
+        comfortable: (62, 77),
+    },
+    BuiltinInstrument {
+        name: "alto",
+        display: "alto",
+        aliases: &["alto"],
+        programs: &[],
+        playable: (53, 74),
+        comfortable: (55, 72),
+    },
+    BuiltinInstrument {
+        name: "tenor",
+        display: "tenor",
+        aliases: &["tenor"],
+        programs: &[],
+        playable: (48, 69),
+        comfortable: (50, 67),
+    },
+    BuiltinInstrument {
+        name: "bass",
+        display: "bass",
+        aliases: &["bass"],
+        programs: &[],
+        playable: (40, 64),
+        comfortable: (43, 60),
+    },
+];
+
+/// An instrument and its ranges, built in or configured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Instrument {
+    name: String,
+    playable: (i32, i32),
+    comfortable: (i32, i32),
+}
+
+impl From<&BuiltinInstrument> for Instrument {
+    fn from(builtin: &BuiltinInstrument) -> Self {
+        Self {
+            name: builtin.display.to_string(),
+            playable: builtin.playable,
+            comfortable: builtin.comfortable,
+        }
+    }
+}
+
+impl InstrumentRangeOptions {
+    /// Returns the instrument named `name` in the configuration or built
+    /// in, ignoring case.
+    fn instrument(&self, name: &str) -> Option<Instrument> {
+        if let Some((name, range)) = self
+            .ranges
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        {
+            return Some(Instrument {
+                name: name.clone(),
+                playable: (range.lowest.midi(), range.highest.midi()),
+                comfortable: (
+                    range.comfortable_lowest.unwrap_or(range.lowest).midi(),
+                    range.comfortable_highest.unwrap_or(range.highest).midi(),
+                ),
+            });
+        }
+        BUILTIN_INSTRUMENTS
+            .iter()
+            .find(|builtin| builtin.name.eq_ignore_ascii_case(name))
+            .map(Instrument::from)
+    }
+
+    /// Returns the instrument a voice's `name=` or `subname=` calls for
+    /// ("Violin II", "Tenor 1").
+    fn named(&self, name: &str) -> Option<Instrument> {
+        let name = name.to_lowercase();
+        let mut words: Vec<_> = name.split_whitespace().collect();
+        // A part number
+        if words.len() > 1
+            && words.last().is_some_and(|word| {
+                word.chars()
+                    .all(|c| c.is_ascii_digit() || "ivx".contains(c))
+            })
+        {
+            words.pop();
+        }
+        let name = words.join(" ");
+        let name = name.trim_end_matches(|c: char| c.is_ascii_digit());
+        self.instrument(name).or_else(|| {
+            BUILTIN_INSTRUMENTS
+                .iter()
+                .find(|builtin| builtin.aliases.contains(&name))
+                .map(Instrument::from)
+        })
+    }
+}
+
+/// Returns the General MIDI program of a `%%MIDI program` line, if it is
+/// one.
+fn midi_program(line: &str) -> Option<u32> {
+    let mut words = line.trim().strip_prefix("%%")?.split_whitespace();
+    if !words.next()?.eq_ignore_ascii_case("midi") || !words.next()?.eq_ignore_ascii_case("program")
+    {
+        return None;
+    }
+    // `%%MIDI program [channel] number`
+    words.last()?.parse().ok()
+}
+
+impl RuleMeta for InstrumentRange {
+    const NAME: &'static str = "instrumentRange";
+    const CODE: DiagnosticCode = DiagnosticCode::InstrumentRange;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Lint;
+    const DOCS: &'static str =
+        "Warns about notes outside the playable or comfortable range of a voice's instrument.";
+}
+
+impl Rule for InstrumentRange {
+    type Options = InstrumentRangeOptions;
+
+    fn new(cx: &RuleContext<Self::Options>) -> Self {
+        Self {
+            options: cx.options().clone(),
+            key: cx.pitch().clone(),
+            voices: Voices::new(),
+            switches: Vec::new(),
+        }
+    }
+
+    fn finish(self, cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        // `%%MIDI program` lines set the instrument of the voice they are in
+        let mut programs: BTreeMap<&str, (Instrument, TextRange)> = BTreeMap::new();
+        let mut offset = 0;
+        for line in cx.source().unwrap_or_default().split_inclusive('\n') {
+            let start = offset;
+            offset += line.len();
+            let Some(program) = midi_program(line) else {
+                continue;
+            };
+            let Some(builtin) = BUILTIN_INSTRUMENTS
+                .iter()
+                .find(|builtin| builtin.programs.contains(&program))
+            else {
+                continue;
+            };
+            let at = self
+                .switches
+                .partition_point(|(switch, _)| switch.raw() as usize <= start);
+            let voice = at
+                .checked_sub(1)
+                .map_or("", |i| self.switches[i].1.as_str());
+            let range = TextRange::new(
+                TextSize::new(start as u32),
+                TextSize::new((start + line.trim_end().len()) as u32),
+            );
+            programs
+                .entry(voice)
+                .or_insert((Instrument::from(builtin), range));
+        }
+
+        let mut diagnostics = Vec::new();
+        for (id, voice) in self.voices.iter() {
+            let declared = voice
+                .named
+                .as_ref()
+                .or_else(|| programs.get(id))
+                .or_else(|| programs.get(""))
+                .map(|(instrument, range)| (instrument.clone(), Some(*range)));
+            let instrument = match self.options.voices.get(id) {
+                Some(name) => self.options.instrument(name).map(|i| (i, None)),
+                None => declared.or_else(|| {
+                    let name = self.options.instrument.as_ref()?;
+                    self.options.instrument(name).map(|i| (i, None))
+                }),
+            };
+            let Some((instrument, declared_at)) = instrument else {
+                continue;
+            };
+            for &(range, pitch) in &voice.notes {
+                if let Some(diagnostic) = check_note(&instrument, range, pitch) {
+                    diagnostics.push(match declared_at {
+                        Some(at) => diagnostic.with_label(at, "instrument set here"),
+                        None => diagnostic,
+                    });
+                }
+            }
+        }
+        diagnostics.sort_by_key(|d| d.range.start());
+        diagnostics
+    }
+}
+
+/// Reports a note outside the instrument's playable or comfortable range.
+fn check_note(instrument: &Instrument, range: TextRange, pitch: i32) -> Option<Diagnostic> {
+    let (lowest, highest) = instrument.playable;
+    let (comfortable_lowest, comfortable_highest) = instrument.comfortable;
+    let name = PitchName::from_midi(pitch);
+    let (message, (from, to)) = if pitch < lowest {
+        (
+            format!("{} is below the range of the {}", name, instrument.name),
+            instrument.playable,
+        )
+    } else if pitch > highest {
+        (
+            format!("{} is above the range of the {}", name, instrument.name),
+            instrument.playable,
+        )
+    } else if pitch < comfortable_lowest {
+        (
+            format!(
+                "{} is below the comfortable range of the {}",
+                name, instrument.name
+            ),
+            instrument.comfortable,
+        )
+    } else if pitch > comfortable_highest {
+        (
+            format!(
+                "{} is above the comfortable range of the {}",
+                name, instrument.name
+            ),
+            instrument.comfortable,
+        )
+    } else {
+        return None;
+    };
+    let kind = if (from, to) == instrument.playable {
+        "range"
+    } else {
+        "comfortable range"
+    };
+    Some(
+        Diagnostic::warning(DiagnosticCode::InstrumentRange, range, message).with_note(format!(
+            "the {}'s {} is {} to {}",
+            instrument.name,
+            kind,
+            PitchName::from_midi(from),
+            PitchName::from_midi(to)
+        )),
+    )
+}
+
+impl InstrumentRange {
+    /// Applies the properties of a `V:` field at `range`.
+    fn declare_voice(&mut self, value: &str, range: TextRange) {
+        let properties = VoiceProperties::parse(value);
+        self.switches.push((range.start(), properties.id.clone()));
+        let named = [&properties.name, &properties.subname]
+            .into_iter()
+            .flatten()
+            .find_map(|name| self.options.named(name));
+        if let Some(instrument) = named {
+            self.voices.get_mut(&properties.id).named = Some((instrument, range));
+        }
+    }
+}
+
+impl Visitor for InstrumentRange {
+    fn visit_header_field(&mut self, field: &HeaderField, _cx: &VisitContext) {
+        if field.kind.to_char() == 'V' {
+            self.declare_voice(&field.value, field.range);
+        }
+    }
+
+    fn visit_note(&mut self, note: &Note, cx: &VisitContext) {
+        let voice = self.voices.current(cx);
+        let pitch = voice.accidentals.midi_pitch(&self.key, note);
+        voice.accidentals.add(note);
+        voice.notes.push((note.range, pitch));
+    }
+
+    fn visit_bar_line(&mut self, _bar_line: &BarLine, cx: &VisitContext) {
+        self.voices.current(cx).accidentals.clear();
+    }
+
+    fn visit_inline_field(&mut self, field: &InlineField, _cx: &VisitContext) {
+        self.key.apply_field(field.label, &field.value);
+        if field.label == 'V' {
+            self.declare_voice(&field.value, field.range);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::check_source;
+
+    fn messages(source: &str, options: &InstrumentRangeOptions) -> Vec<String> {
+        check_source::<InstrumentRange>(source, options)
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    }
+
+    fn fiddle() -> InstrumentRangeOptions {
+        InstrumentRangeOptions {
+            instrument: Some("fiddle".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pitch_names() {
+        assert_eq!(PitchName::parse("C4"), Some(PitchName::from_midi(60)));
+        assert_eq!(PitchName::parse("F#5").unwrap().midi(), 78);
+        assert_eq!(PitchName::parse("Bb3").unwrap().midi(), 58);
+        assert_eq!(PitchName::parse("H2"), None);
+        assert_eq!(PitchName::from_midi(61).to_string(), "C#4");
+    }
+
+    #[test]
+    fn test_no_instrument() {
+        let source = "X:1\nK:G\nC,,,,G''''|\n";
+        assert!(messages(source, &InstrumentRangeOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_configured_instrument() {
+        let source = "X:1\nK:G\nG,ABc|d'e'd'b|\n";
+        assert!(messages(source, &fiddle()).is_empty());
+        assert_eq!(
+            messages("X:1\nK:G\nF,G,A,B,|c''4|\n", &fiddle()),
+            [
+                "F#3 is below the range of the fiddle",
+                "C7 is above the comfortable range of the fiddle"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_declared_instrument() {
+        let options = InstrumentRangeOptions::default();
+        let source = "X:1\nV:1 name=\"Tin Whistle\"\nV:2 name=\"Cello\"\nK:D\n\
+                      [V:1]C4 D4|[V:2]C,,4 C4|\n";
+        assert_eq!(
+            messages(source, &options),
+            ["C#4 is below the range of the tin whistle"]
+        );
+
+        let source = "X:1\nV:S name=\"Soprano 1\"\nK:C\na2 g2|\n";
+        let diagnostics = check_source::<InstrumentRange>(source, &options);
+        assert_eq!(
+            diagnostics[0].message,
+            "A5 is above the comfortable range of the soprano"
+        );
+        assert_eq!(diagnostics[0].labels[0].message, "instrument set here");
+    }
+
+    #[test]
+    fn test_midi_program() {
+        let options = InstrumentRangeOptions::default();
+        let source = "X:1\nV:1\n%%MIDI program 73\nV:2\n%%MIDI program 1 42\nK:C\n\
+                      [V:1]B,C|[V:2]B,C|\n";
+        assert_eq!(
+            messages(source, &options),
+            ["B3 is below the range of the flute"]
+        );
+    }
+
+    #[test]
+    fn test_voice_ids_are_not_names() {
+        let options = InstrumentRangeOptions::default();
+        let source = "X:1\nV:T\nV:b\nK:C\n[V:T]C,,c''|[V:b]C,,c''|\n";
+        assert!(messages(source, &options).is_empty());
+        let source = "X:1\nV:1 name=\"T\" subname=\"B\"\nK:C\nC,,c''|\n";
+        assert!(messages(source, &options).is_empty());
+    }
+
+    #[test]
+    fn test_written_pitch_and_accidentals() {
+        // `transpose=` changes how the voice sounds, not how it is written
+        let source = "X:1\nV:1 transpose=-12\nK:C\nG,c'|\n";
+        assert!(messages(source, &fiddle()).is_empty());
+        let source = "X:1\nV:1 transpose=2\nK:C\nF,G,|\n";
+        assert_eq!(
+            messages(source, &fiddle()),
+            ["F3 is below the range of the fiddle"]
+        );
+        let source = "X:1\nK:C\n_A,G,|\n";
+        assert!(messages(source, &fiddle()).is_empty());
+        let source = "X:1\nK:Ab\n_G,G,|\n";
+        assert_eq!(
+            messages(source, &fiddle()),
+            [
+                "F#3 is below the range of the fiddle",
+                "F#3 is below the range of the fiddle"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_ranges() {
+        let options: InstrumentRangeOptions = serde_json::from_value(serde_json::json!({
+            "voices": { "1": "bouzouki", "2": "fiddle" },
+            "ranges": { "bouzouki": { "lowest": "G2", "highest": "E5", "comfortableHighest": "D5" } }
+        }))
+        .unwrap();
+        let source = "X:1\nK:C\n[V:1]F,,G,,e|[V:2]G,|\n";
+        assert_eq!(
+            messages(source, &options),
+            [
+                "F2 is below the range of the bouzouki",
+                "E5 is above the comfortable range of the bouzouki"
+            ]
+        );
+        assert!(
+            serde_json::from_value::<InstrumentRangeOptions>(serde_json::json!({
+                "ranges": { "bouzouki": { "lowest": "G", "highest": "E5" } }
+            }))
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! W013: Invalid chord symbol warning.
+//!
+//! Warns about a quoted annotation that is meant as a chord symbol but
+//! can't be read as one (`"Hm"`, `"Cmajor"`), and about a chord symbol
+//! written before grace notes, which puts it on the grace notes rather
+//! than the note they lead to.
+//!
+//! Annotations starting with `^`, `_`, `<`, `>` or `@` are free text, and
+//! "N.C." means no chord; neither is checked.
+
+use chamber_ast::visit::{VisitContext, Visitor};
+use chamber_ast::{
+    Annotation, BarLine, BrokenRhythm, Chord, GraceNotes, InlineField, Note, Rest, Tie, Tuplet,
+};
+use chamber_diagnostics::{Applicability, Diagnostic, DiagnosticCode, Fix, Severity, TextEdit};
+use chamber_text_size::TextSize;
+
+use crate::context::RuleContext;
+use crate::model::ChordSymbol;
+use crate::rule::{Category, NoOptions, Rule, RuleMeta};
+
+/// Rule that warns about chord symbols that can't be read or are placed on
+/// grace notes.
+pub struct InvalidChordSymbol {
+    diagnostics: Vec<Diagnostic>,
+    /// A chord symbol not yet followed by a note or grace notes.
+    pending: Option<Annotation>,
+}
+
+impl RuleMeta for InvalidChordSymbol {
+    const NAME: &'static str = "invalidChordSymbol";
+    const CODE: DiagnosticCode = DiagnosticCode::InvalidChordSymbol;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Lint;
+    const DOCS: &'static str =
+        "Warns about chord symbols that can't be read, and chord symbols on grace notes.";
+}
+
+impl Rule for InvalidChordSymbol {
+    type Options = NoOptions;
+
+    fn new(_cx: &RuleContext<Self::Options>) -> Self {
+        Self {
+            diagnostics: Vec::new(),
+            pending: None,
+        }
+    }
+
+    fn finish(self, _cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+impl InvalidChordSymbol {
+    fn clear(&mut self, cx: &VisitContext) {
+        // Grace notes are visited one by one after the group
+        if !cx.in_grace_notes {
+            self.pending = None;
+        }
+    }
+}
+
+impl Visitor for InvalidChordSymbol {
+    fn visit_annotation(&mut self, annotation: &Annotation, _cx: &VisitContext) {
+        if !ChordSymbol::is_chord_text(&annotation.text) {
+            return;
+        }
+        if ChordSymbol::parse(annotation.text.trim()).is_some() {
+            self.pending = Some(annotation.clone());
+            return;
+        }
+
+        // Free text goes above the note with `^`
+        let quote = annotation.range.start() + TextSize::new(1);
+        self.diagnostics.push(
+            Diagnostic::warning(
+                DiagnosticCode::InvalidChordSymbol,
+                annotation.range,
+                format!("'{}' is not a chord symbol", annotation.text),
+            )
+            .with_note("text that isn't a chord starts with `^` (above) or `_` (below)")
+            .with_fix(Fix::edit(
+                "place it as text above the staff",
+                TextEdit::insert(quote, "^"),
+                Applicability::MaybeIncorrect,
+            )),
+        );
+    }
+
+    fn visit_grace_notes(&mut self, grace_notes: &GraceNotes, _cx: &VisitContext) {
+        let Some(annotation) = self.pending.take() else {
+            return;
+        };
+        let moved = format!("\"{}\"", annotation.text);
+        self.diagnostics.push(
+            Diagnostic::warning(
+                DiagnosticCode::InvalidChordSymbol,
+                annotation.range,
+                "chord symbol is on grace notes",
+            )
+            .with_label(grace_notes.range, "grace notes")
+            .with_note("a chord symbol belongs to the note it is written before")
+            .with_fix(Fix::new(
+                "move the chord symbol after the grace notes",
+                vec![
+                    TextEdit::delete(annotation.range),
+                    TextEdit::insert(grace_notes.range.end(), moved),
+                ],
+                Applicability::MachineApplicable,
+            )),
+        );
+    }
+
+    fn visit_note(&mut self, _note: &Note, cx: &VisitContext) {
+        self.clear(cx);
+    }
+
+    fn visit_chord(&mut self, _chord: &Chord, cx: &VisitContext) {
+        self.clear(cx);
+    }
+
+    fn visit_rest(&mut self, _rest: &Rest, cx: &VisitContext) {
+        self.clear(cx);
+    }
+
+    fn visit_bar_line(&mut self, _bar_line: &BarLine, cx: &VisitContext) {
+        self.clear(cx);
+    }
+
+    fn visit_tuplet(&mut self, _tuplet: &Tuplet, cx: &VisitContext) {
+        self.clear(cx);
+    }
+
+    fn visit_broken_rhythm(&mut self, _broken_rhythm: &BrokenRhythm, cx: &VisitContext) {
+        self.clear(cx);
+    }
+
+    fn visit_tie(&mut self, _tie: &Tie, cx: &VisitContext) {
+        self.clear(cx);
+    }
+
+    fn visit_inline_field(&mut self, _field: &InlineField, cx: &VisitContext) {
+        self.clear(cx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::{check_source, RuleExt};
+    use chamber_diagnostics::apply_fixes;
+    use chamber_parser::parse;
+
+    fn messages(body: &str) -> Vec<String> {
+        InvalidChordSymbol::check(&parse(&format!("X:1\nK:G\n{}", body)))
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_chord_symbols() {
+        assert!(messages("\"G\"GABc \"Am7\"dcBA|\"D7/F#\"F4 \"Gmaj7\"G4|").is_empty());
+        assert!(messages("\"^Fine\"G4 \"_rit.\"A4 \"N.C.\"B4|").is_empty());
+        // The chord symbol goes before the grace notes' note
+        assert!(messages("{g}\"Am\"A4|").is_empty());
+    }
+
+    #[test]
+    fn test_unreadable_chord_symbol() {
+        assert_eq!(messages("\"Hm\"G4|"), ["'Hm' is not a chord symbol"]);
+
+        let source = "X:1\nK:G\n\"Fine\"G4|";
+        let diagnostics = check_source::<InvalidChordSymbol>(source, &NoOptions::default());
+        assert_eq!(diagnostics.len(), 1);
+        let fixed = apply_fixes(source, &diagnostics, Applicability::MaybeIncorrect).source;
+        assert_eq!(fixed, "X:1\nK:G\n\"^Fine\"G4|");
+    }
+
+    #[test]
+    fn test_chord_symbol_on_grace_notes() {
+        let source = "X:1\nK:G\n\"Am\"{g}A4|";
+        let diagnostics = check_source::<InvalidChordSymbol>(source, &NoOptions::default());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "chord symbol is on grace notes");
+        let fixed = apply_fixes(source, &diagnostics, Applicability::MachineApplicable).source;
+        assert_eq!(fixed, "X:1\nK:G\n{g}\"Am\"A4|");
+    }
+}
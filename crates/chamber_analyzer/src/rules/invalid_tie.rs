@@ -0,0 +1,329 @@
+//! W009: Invalid tie warning.
+//!
+//! Warns when a tie (`-`) joins notes of different pitches, or has no note
+//! to join: at the end of the tune, next to a rest, or with nothing before
+//! it.
+//!
+//! Pitches are compared as they sound, with the key signature and the
+//! accidentals carried over in the bar (`^C-C` is fine, `C-^C` is not). An
+//! accidental also carries through the tie itself, across a bar line
+//! (`^C-|C`). Chords are fine when at least one of their notes is tied.
+
+use chamber_ast::visit::{VisitContext, Visitor};
+use chamber_ast::{BarLine, Chord, InlineField, Note, Pitch, Rest, Tie};
+use chamber_diagnostics::{Applicability, Diagnostic, DiagnosticCode, Fix, Severity, TextEdit};
+use chamber_text_size::TextRange;
+
+use crate::context::RuleContext;
+use crate::model::{BarAccidentals, PitchModel, Voices};
+use crate::rule::{Category, NoOptions, Rule, RuleMeta};
+
+/// Rule that warns about ties between different pitches and ties with no
+/// note to join.
+pub struct InvalidTie {
+    diagnostics: Vec<Diagnostic>,
+    key: PitchModel,
+    voices: Voices<VoiceState>,
+}
+
+#[derive(Debug, Default)]
+struct VoiceState {
+    accidentals: BarAccidentals,
+    /// The last note, chord or rest. A chord's notes are added as they are
+    /// visited.
+    last: Option<Sounding>,
+    /// A tie after `last`, waiting for the next note or chord.
+    tie: Option<(TextRange, Sounding)>,
+    /// A tie to `last`, checked once `last` is complete.
+    tied_to_last: Option<(TextRange, Sounding)>,
+}
+
+/// A note, chord or rest.
+#[derive(Debug, Clone)]
+struct Sounding {
+    range: TextRange,
+    /// The notes; none for a rest.
+    tones: Vec<Tone>,
+    rest: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Tone {
+    pitch: Pitch,
+    octave: i8,
+    midi_pitch: i32,
+    /// Whether the note has its own accidental.
+    has_accidental: bool,
+}
+
+impl Tone {
+    /// Returns true if a tie from `self` to `other` holds one pitch.
+    fn ties_to(self, other: Tone) -> bool {
+        // Without an accidental of its own, the second note keeps the first
+        // one's, even in the next bar
+        let carried =
+            !other.has_accidental && (self.pitch, self.octave) == (other.pitch, other.octave);
+        carried || self.midi_pitch == other.midi_pitch
+    }
+}
+
+impl RuleMeta for InvalidTie {
+    const NAME: &'static str = "invalidTie";
+    const CODE: DiagnosticCode = DiagnosticCode::InvalidTie;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Lint;
+    const DOCS: &'static str = "Warns when a tie joins different pitches, or has no note to join.";
+}
+
+impl Rule for InvalidTie {
+    type Options = NoOptions;
+
+    fn new(cx: &RuleContext<Self::Options>) -> Self {
+        Self {
+            diagnostics: Vec::new(),
+            key: cx.pitch().clone(),
+            voices: Voices::new(),
+        }
+    }
+
+    fn finish(mut self, _cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        for voice in self.voices.values_mut() {
+            if let (Some((tie, from)), Some(to)) = (voice.tied_to_last.take(), &voice.last) {
+                self.diagnostics.extend(check_pitches(tie, &from, to));
+            }
+            if let Some((tie, _)) = voice.tie.take() {
+                self.diagnostics.push(dangling(
+                    tie,
+                    "tie at the end of the tune has no note to join",
+                ));
+            }
+        }
+        self.diagnostics
+    }
+}
+
+impl InvalidTie {
+    /// Starts the next note, chord or rest of the current voice.
+    fn start(&mut self, sounding: Sounding, cx: &VisitContext) {
+        let voice = self.voices.current(cx);
+        if let (Some((tie, from)), Some(to)) = (voice.tied_to_last.take(), &voice.last) {
+            self.diagnostics.extend(check_pitches(tie, &from, to));
+        }
+        if let Some((tie, from)) = voice.tie.take() {
+            if sounding.rest {
+                self.diagnostics.push(
+                    dangling(tie, "tie is followed by a rest")
+                        .with_label(sounding.range, "rest here"),
+                );
+            } else {
+                voice.tied_to_last = Some((tie, from));
+            }
+        }
+        voice.last = Some(sounding);
+    }
+}
+
+/// Reports a tie from `from` to `to` if no note of `to` holds a pitch of
+/// `from`.
+fn check_pitches(tie: TextRange, from: &Sounding, to: &Sounding) -> Option<Diagnostic> {
+    let tied = from
+        .tones
+        .iter()
+        .any(|&a| to.tones.iter().any(|&b| a.ties_to(b)));
+    if tied {
+        return None;
+    }
+    let slur = vec![
+        TextEdit::insert(from.range.start(), "("),
+        TextEdit::delete(tie),
+        TextEdit::insert(to.range.end(), ")"),
+    ];
+    Some(
+        Diagnostic::warning(
+            DiagnosticCode::InvalidTie,
+            tie,
+            "tie joins notes of different pitches",
+        )
+        .with_label(from.range, "tied from here")
+        .with_label(to.range, "to a different pitch here")
+        .with_note("a tie holds one pitch; to join different pitches, use a slur")
+        .with_fix(Fix::new(
+            "replace the tie with a slur",
+            slur,
+            Applicability::MaybeIncorrect,
+        )),
+    )
+}
+
+/// Reports a tie with no note on one side; removing it changes nothing.
+fn dangling(tie: TextRange, message: &str) -> Diagnostic {
+    Diagnostic::warning(DiagnosticCode::InvalidTie, tie, message).with_fix(Fix::edit(
+        "remove the tie",
+        TextEdit::delete(tie),
+        Applicability::MachineApplicable,
+    ))
+}
+
+impl Visitor for InvalidTie {
+    fn visit_note(&mut self, note: &Note, cx: &VisitContext) {
+        let voice = self.voices.current(cx);
+        let tone = Tone {
+            pitch: note.pitch,
+            octave: note.octave,
+            midi_pitch: voice.accidentals.midi_pitch(&self.key, note),
+            has_accidental: note.accidental.is_some(),
+        };
+        voice.accidentals.add(note);
+        if cx.in_grace_notes {
+            return;
+        }
+        if cx.in_chord {
+            if let Some(chord) = &mut voice.last {
+                chord.tones.push(tone);
+            }
+            return;
+        }
+        let sounding = Sounding {
+            range: note.range,
+            tones: vec![tone],
+            rest: false,
+        };
+        self.start(sounding, cx);
+    }
+
+    fn visit_chord(&mut self, chord: &Chord, cx: &VisitContext) {
+        let sounding = Sounding {
+            range: chord.range,
+            tones: Vec::new(),
+            rest: false,
+        };
+        self.start(sounding, cx);
+    }
+
+    fn visit_rest(&mut self, rest: &Rest, cx: &VisitContext) {
+        let sounding = Sounding {
+            range: rest.range,
+            tones: Vec::new(),
+            rest: true,
+        };
+        self.start(sounding, cx);
+    }
+
+    fn visit_tie(&mut self, tie: &Tie, cx: &VisitContext) {
+        let voice = self.voices.current(cx);
+        // The note before this tie may end another one (`C-C-C`)
+        if let (Some((earlier, from)), Some(to)) = (voice.tied_to_last.take(), &voice.last) {
+            self.diagnostics.extend(check_pitches(earlier, &from, to));
+        }
+        match &voice.last {
+            Some(last) if last.rest => self.diagnostics.push(
+                dangling(tie.range, "tie follows a rest").with_label(last.range, "rest here"),
+            ),
+            Some(last) => voice.tie = Some((tie.range, last.clone())),
+            None => self
+                .diagnostics
+                .push(dangling(tie.range, "tie has no note before it")),
+        }
+    }
+
+    fn visit_bar_line(&mut self, _bar_line: &BarLine, cx: &VisitContext) {
+        self.voices.current(cx).accidentals.clear();
+    }
+
+    fn visit_inline_field(&mut self, field: &InlineField, _cx: &VisitContext) {
+        self.key.apply_field(field.label, &field.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::RuleExt;
+    use chamber_diagnostics::apply_fixes;
+    use chamber_parser::parse;
+
+    fn check(body: &str) -> Vec<Diagnostic> {
+        InvalidTie::check(&parse(&format!("X:1\nK:G\n{}", body)))
+    }
+
+    #[test]
+    fn test_same_pitch() {
+        assert!(check("G2-G2 A-|A4|").is_empty());
+        assert!(check("(G2-G2) A4|").is_empty());
+    }
+
+    #[test]
+    fn test_different_pitches() {
+        let source = "X:1\nK:G\nG2-A2 B4|";
+        let diagnostics = InvalidTie::check(&parse(source));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "tie joins notes of different pitches"
+        );
+        assert_eq!(diagnostics[0].labels.len(), 2);
+
+        let fixed = apply_fixes(source, &diagnostics, Applicability::MaybeIncorrect);
+        assert_eq!(fixed.source, "X:1\nK:G\n(G2A2) B4|");
+    }
+
+    #[test]
+    fn test_accidentals() {
+        // F is sharp in G; the key signature and carried accidentals count
+        assert!(check("^F-F =F-F|").is_empty());
+        assert_eq!(check("F-=F|").len(), 1);
+        assert_eq!(check("=F-^F|").len(), 1);
+        // Through the tie into the next bar
+        assert!(check("=F2-|F2").is_empty());
+        // Enharmonic spellings sound the same
+        assert!(check("^C-_D|").is_empty());
+        // Octaves differ
+        assert_eq!(check("F-f|").len(), 1);
+    }
+
+    #[test]
+    fn test_inline_key_change() {
+        // B is flat in F, the same pitch as A sharp
+        assert!(check("[K:F]B2-^A2|").is_empty());
+        assert_eq!(check("[K:C]B2-^A2|").len(), 1);
+    }
+
+    #[test]
+    fn test_chords() {
+        assert!(check("[GB]-[GB] [GB]-G [GB]-[Bd]|").is_empty());
+        assert_eq!(check("[GB]-[Ac]|").len(), 1);
+        assert_eq!(check("G-[Ac]|").len(), 1);
+    }
+
+    #[test]
+    fn test_dangling_ties() {
+        let diagnostics = check("G4-|");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("end of the tune"));
+
+        let diagnostics = check("G2-z2|");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "tie is followed by a rest");
+
+        let diagnostics = check("z2-G2|");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "tie follows a rest");
+
+        let source = "X:1\nK:G\nG4-|]";
+        let diagnostics = InvalidTie::check(&parse(source));
+        let fixed = apply_fixes(source, &diagnostics, Applicability::MachineApplicable);
+        assert_eq!(fixed.source, "X:1\nK:G\nG4|]");
+    }
+
+    #[test]
+    fn test_grace_notes_ignored() {
+        assert!(check("G2-{AG}G2|").is_empty());
+    }
+
+    #[test]
+    fn test_voices() {
+        // Each voice ties to its own next note
+        assert!(check("[V:1]G2-|[V:2]B,4|[V:1]G2|").is_empty());
+        assert_eq!(check("[V:1]G2-|[V:2]B,4|[V:1]A2|").len(), 1);
+    }
+}
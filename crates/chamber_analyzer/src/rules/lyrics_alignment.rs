@@ -0,0 +1,434 @@
+//! W018: Lyrics alignment warning.
+//!
+//! A lyrics line (`w:`) is sung to the notes since the previous lyrics line
+//! of the voice, one syllable (or `_`, `*`, or a lone `-`) per note; rests
+//! and grace notes take no syllable, and `|` moves on to the next bar.
+//! Several `w:` lines in a row are verses of the same notes.
+//!
+//! The rule warns about the first place where a verse and its notes stop
+//! lining up: a bar with more or fewer syllables than notes, a line with
+//! more or fewer syllables than notes, or a syllable written for a rest.
+//! It also warns about a `_` with no syllable before it to hold, and, when
+//! the verse lines up, about syllables on tied notes, which are sung to the
+//! note before them.
+
+use std::ops::Range;
+
+use chamber_ast::visit::{VisitContext, Visitor};
+use chamber_ast::{Chord, Lyrics, Note, Rest, Syllable, SyllableKind, Tie};
+use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use chamber_text_size::TextRange;
+
+use crate::context::RuleContext;
+use crate::model::Voices;
+use crate::rule::{Category, NoOptions, Rule, RuleMeta};
+
+/// Rule that warns about lyrics that don't line up with their notes.
+pub struct LyricsAlignment {
+    diagnostics: Vec<Diagnostic>,
+    voices: Voices<VoiceState>,
+}
+
+#[derive(Debug, Default)]
+struct VoiceState {
+    /// The notes and rests since the last lyrics line.
+    events: Vec<Event>,
+    /// Whether a lyrics line came after the last note.
+    sung: bool,
+    /// Whether the next note is tied to the one before.
+    tied: bool,
+}
+
+/// A note, chord or rest that lyrics are sung to.
+#[derive(Debug, Clone, Copy)]
+struct Event {
+    range: TextRange,
+    bar: u32,
+    rest: bool,
+    /// Whether the note is tied to the one before.
+    tied: bool,
+}
+
+impl RuleMeta for LyricsAlignment {
+    const NAME: &'static str = "lyricsAlignment";
+    const CODE: DiagnosticCode = DiagnosticCode::LyricsAlignment;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Lint;
+    const DOCS: &'static str =
+        "Warns about lyrics with more or fewer syllables than notes, syllables on rests or \
+         tied notes, and `_` with nothing to hold.";
+}
+
+impl Rule for LyricsAlignment {
+    type Options = NoOptions;
+
+    fn new(_cx: &RuleContext<Self::Options>) -> Self {
+        Self {
+            diagnostics: Vec::new(),
+            voices: Voices::new(),
+        }
+    }
+
+    fn finish(self, _cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+impl LyricsAlignment {
+    fn add(&mut self, range: TextRange, rest: bool, cx: &VisitContext) {
+        let voice = self.voices.current(cx);
+        // The notes after a lyrics line are for the next one
+        if std::mem::take(&mut voice.sung) {
+            voice.events.clear();
+        }
+        let tied = std::mem::take(&mut voice.tied) && !rest;
+        voice.events.push(Event {
+            range,
+            bar: cx.bar,
+            rest,
+            tied,
+        });
+    }
+}
+
+/// How an item of a lyrics line is shown in messages.
+fn display(syllable: &Syllable) -> &str {
+    match syllable.kind {
+        SyllableKind::Syllable => &syllable.text,
+        SyllableKind::Hold => "_",
+        SyllableKind::Skip => "*",
+        SyllableKind::Hyphen => "-",
+        SyllableKind::BarLine => "|",
+    }
+}
+
+fn count(n: usize, what: &str) -> String {
+    if n == 1 {
+        format!("1 {}", what)
+    } else {
+        format!("{} {}s", n, what)
+    }
+}
+
+/// A part of a lyrics line between `|`s, and the music it is sung to.
+struct Segment<'a> {
+    /// The items that take a note.
+    items: Vec<&'a Syllable>,
+    /// The `|` that ends the segment, if any.
+    end: Option<&'a Syllable>,
+    /// The notes and rests of its bar, or of all the bars left for the last
+    /// segment.
+    events: &'a [Event],
+}
+
+impl Segment<'_> {
+    fn notes(&self) -> Vec<&Event> {
+        self.events.iter().filter(|event| !event.rest).collect()
+    }
+}
+
+/// Splits `events` into bars.
+fn bars(events: &[Event]) -> Vec<Range<usize>> {
+    let mut bars: Vec<Range<usize>> = Vec::new();
+    for (i, event) in events.iter().enumerate() {
+        match bars.last_mut() {
+            Some(bar) if events[bar.start].bar == event.bar => bar.end = i + 1,
+            _ => bars.push(i..i + 1),
+        }
+    }
+    bars
+}
+
+/// Returns the note each item of the verse is sung to, if any: the next
+/// note that isn't a rest, from the start of the next bar after a `|`.
+fn align<'a>(syllables: &[Syllable], events: &'a [Event]) -> Vec<Option<&'a Event>> {
+    let bars = bars(events);
+    let mut bar = 0;
+    let mut next = 0;
+    syllables
+        .iter()
+        .map(|syllable| {
+            if !syllable.kind.takes_note() {
+                bar += 1;
+                next = bars.get(bar).map_or(events.len(), |bar| bar.start);
+                return None;
+            }
+            while events.get(next).is_some_and(|event| event.rest) {
+                next += 1;
+            }
+            next += 1;
+            events.get(next - 1)
+        })
+        .collect()
+}
+
+/// Checks the first segment of the verse that doesn't line up with its
+/// notes.
+fn check_segments(syllables: &[Syllable], events: &[Event]) -> Option<Diagnostic> {
+    let bars = bars(events);
+    let mut segments = vec![Segment {
+        items: Vec::new(),
+        end: None,
+        events: &[],
+    }];
+    for syllable in syllables {
+        let segment = segments.last_mut()?;
+        if syllable.kind.takes_note() {
+            segment.items.push(syllable);
+        } else {
+            segment.end = Some(syllable);
+            segments.push(Segment {
+                items: Vec::new(),
+                end: None,
+                events: &[],
+            });
+        }
+    }
+    let last = segments.len() - 1;
+    for (i, segment) in segments.iter_mut().enumerate() {
+        segment.events = match bars.get(i) {
+            Some(bar) if i == last => &events[bar.start..],
+            Some(bar) => &events[bar.clone()],
+            None => &[],
+        };
+    }
+
+    let syllable_count: usize = segments.iter().map(|segment| segment.items.len()).sum();
+    let note_count = events.iter().filter(|event| !event.rest).count();
+
+    for (i, segment) in segments.iter().enumerate() {
+        let notes = segment.notes();
+        let items = &segment.items;
+        if items.len() == notes.len() {
+            continue;
+        }
+        let message = match segment.events.first() {
+            Some(first) if i < last => format!(
+                "bar {} has {} for {}",
+                first.bar,
+                count(items.len(), "syllable"),
+                count(notes.len(), "note")
+            ),
+            None if i > 0 && !items.is_empty() => format!(
+                "lyrics have {} for {} of music",
+                count(segments.len(), "bar"),
+                count(bars.len(), "bar")
+            ),
+            _ => format!(
+                "lyrics have {} for {}",
+                count(syllable_count, "syllable"),
+                count(note_count, "note")
+            ),
+        };
+
+        if items.len() < notes.len() {
+            let at = segment
+                .end
+                .or_else(|| syllables.iter().rev().find(|s| s.kind.takes_note()))?;
+            let note = notes[items.len()];
+            return Some(
+                Diagnostic::warning(DiagnosticCode::LyricsAlignment, at.range, message)
+                    .with_label(note.range, "first note without a syllable"),
+            );
+        }
+
+        // Rests take no syllable, though it is easy to write one for them
+        let rests = segment.events.len() - notes.len();
+        if rests > 0 && items.len() <= segment.events.len() {
+            if let Some((item, rest)) = items
+                .iter()
+                .zip(segment.events)
+                .find(|(_, event)| event.rest)
+            {
+                return Some(
+                    Diagnostic::warning(
+                        DiagnosticCode::LyricsAlignment,
+                        item.range,
+                        format!("'{}' is sung on a rest", display(item)),
+                    )
+                    .with_label(rest.range, "rest")
+                    .with_note("rests take no syllable; it goes on the next note"),
+                );
+            }
+        }
+
+        let extra = items[notes.len()];
+        let mut diagnostic =
+            Diagnostic::warning(DiagnosticCode::LyricsAlignment, extra.range, message);
+        if let Some(note) = notes.last() {
+            diagnostic = diagnostic.with_label(note.range, "last note");
+        }
+        return Some(diagnostic);
+    }
+    None
+}
+
+impl Visitor for LyricsAlignment {
+    fn visit_note(&mut self, note: &Note, cx: &VisitContext) {
+        // Chord notes are sung with their chord; grace notes are not sung
+        if cx.in_chord || cx.in_grace_notes {
+            return;
+        }
+        self.add(note.range, false, cx);
+    }
+
+    fn visit_chord(&mut self, chord: &Chord, cx: &VisitContext) {
+        self.add(chord.range, false, cx);
+    }
+
+    fn visit_rest(&mut self, rest: &Rest, cx: &VisitContext) {
+        self.add(rest.range, true, cx);
+    }
+
+    fn visit_tie(&mut self, _tie: &Tie, cx: &VisitContext) {
+        if cx.in_chord {
+            return;
+        }
+        let voice = self.voices.current(cx);
+        voice.tied = true;
+    }
+
+    fn visit_lyrics(&mut self, lyrics: &Lyrics, cx: &VisitContext) {
+        let voice = self.voices.current(cx);
+        voice.sung = true;
+        let (syllables, events) = (&lyrics.syllables, &voice.events);
+        if syllables.is_empty() {
+            return;
+        }
+        let aligned = align(syllables, events);
+
+        // A `_` holds the syllable before it over one more note
+        let mut held = false;
+        for (syllable, note) in syllables.iter().zip(&aligned) {
+            match syllable.kind {
+                SyllableKind::Syllable => held = true,
+                SyllableKind::Hold if !held => {
+                    let mut diagnostic = Diagnostic::warning(
+                        DiagnosticCode::LyricsAlignment,
+                        syllable.range,
+                        "'_' has no syllable before it to hold",
+                    );
+                    if let Some(note) = note {
+                        diagnostic = diagnostic.with_label(note.range, "the note it holds");
+                    }
+                    self.diagnostics.push(diagnostic);
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(diagnostic) = check_segments(syllables, events) {
+            self.diagnostics.push(diagnostic);
+            return;
+        }
+
+        // Only checked when the verse lines up, as it does not otherwise
+        for (syllable, note) in syllables.iter().zip(&aligned) {
+            let Some(note) = note.filter(|note| note.tied) else {
+                continue;
+            };
+            if syllable.kind == SyllableKind::Syllable {
+                self.diagnostics.push(
+                    Diagnostic::warning(
+                        DiagnosticCode::LyricsAlignment,
+                        syllable.range,
+                        format!("'{}' is sung on a tied note", display(syllable)),
+                    )
+                    .with_label(note.range, "tied to the note before")
+                    .with_note("a tied note goes on with the syllable before; write `_` for it"),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::RuleExt;
+    use chamber_parser::parse;
+
+    fn messages(body: &str) -> Vec<String> {
+        let source = format!("X:1\nM:4/4\nL:1/4\nK:G\n{}", body);
+        LyricsAlignment::check(&parse(&source))
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_aligned_lyrics() {
+        assert!(messages("G A B c|d2 e2|\nw: Hap-py birth-day|to you").is_empty());
+        // Holds, skips, hyphens of their own and rests
+        assert!(messages("G A B c|d z e f|\nw: Hap-py * _|ho - ho").is_empty());
+        // Verses, and a second line
+        assert!(
+            messages("G A B c|\nw: one two three four\nw: five six se-ven\nd4|\nw: eight")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_line_count() {
+        let source = "X:1\nM:4/4\nL:1/4\nK:G\nG A B c|\nw: one two three four five";
+        let diagnostics = LyricsAlignment::check(&parse(source));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "lyrics have 5 syllables for 4 notes"
+        );
+        assert_eq!(diagnostics[0].labels[0].message, "last note");
+
+        assert_eq!(
+            messages("G A B c|d4|\nw: one two three four"),
+            ["lyrics have 4 syllables for 5 notes"]
+        );
+    }
+
+    #[test]
+    fn test_bar_count() {
+        assert_eq!(
+            messages("G A B c|d2 e2|\nw: one two three|four five"),
+            ["bar 1 has 3 syllables for 4 notes"]
+        );
+        assert_eq!(
+            messages("G A B c|d2 e2|\nw: one two three four five|six"),
+            ["bar 1 has 5 syllables for 4 notes"]
+        );
+    }
+
+    #[test]
+    fn test_syllable_on_rest() {
+        let source = "X:1\nM:4/4\nL:1/4\nK:G\nG A z c|\nw: one two three four";
+        let diagnostics = LyricsAlignment::check(&parse(source));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "'three' is sung on a rest");
+        assert_eq!(diagnostics[0].labels[0].message, "rest");
+    }
+
+    #[test]
+    fn test_hold_without_syllable() {
+        assert_eq!(
+            messages("G A B c|\nw: _ one two three"),
+            ["'_' has no syllable before it to hold"]
+        );
+        // Held over the bar line
+        assert!(messages("G A B c|d4|\nw: one two three four|_").is_empty());
+    }
+
+    #[test]
+    fn test_syllable_on_tied_note() {
+        assert_eq!(
+            messages("G A B c-|c4|\nw: Hap-py birth-day to"),
+            ["'to' is sung on a tied note"]
+        );
+        assert!(messages("G A B c-|c4|\nw: Hap-py birth-day _").is_empty());
+    }
+
+    #[test]
+    fn test_voices() {
+        assert!(
+            messages("[V:1]G A B c|\n[V:2]G,4|\nw: one\n[V:1]\nw: one two three four").is_empty()
+        );
+    }
+}
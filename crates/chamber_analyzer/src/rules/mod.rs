@@ -1,13 +1,59 @@
 //! Analysis rules for ABC notation.
 //!
-//! Each rule checks for a specific semantic issue.
+//! Each rule checks for a specific semantic issue, in one tune or, for
+//! collection rules, across the tunes of a collection.
 
 pub mod bar_length;
+pub mod beam_grouping;
+pub mod chord_melody_clash;
+pub mod chord_spelling;
+pub mod custom_rules;
+pub mod duplicate_melody;
+pub mod duplicate_title;
+pub mod duplicate_tune_number;
+pub mod inconsistent_directives;
+pub mod instrument_range;
+pub mod invalid_chord_symbol;
+pub mod invalid_tie;
+pub mod lyrics_alignment;
+pub mod redundant_accidental;
+pub mod required_fields;
+pub mod rhythm_meter;
 pub mod suspicious_duration;
+pub mod tune_numbering;
+pub mod unbalanced_repeat;
+pub mod unit_note_length;
 pub mod unknown_decoration;
 pub mod unusual_octave;
+pub mod voice_consistency;
 
-pub use bar_length::BarLength;
-pub use suspicious_duration::SuspiciousDuration;
-pub use unknown_decoration::UnknownDecoration;
-pub use unusual_octave::UnusualOctave;
+pub use bar_length::{BarLength, BarLengthMode, BarLengthOptions};
+pub use beam_grouping::BeamGrouping;
+pub use chord_melody_clash::ChordMelodyClash;
+pub use chord_spelling::{
+    AugmentedSpelling, ChordSpelling, ChordSpellingOptions, DiminishedSpelling, MajorSpelling,
+    MinorSpelling,
+};
+pub use custom_rules::{
+    CustomRuleDefinition, CustomRules, CustomRulesOptions, FieldLabel, HeaderPattern,
+};
+pub use duplicate_melody::{DuplicateMelody, DuplicateMelodyOptions};
+pub use duplicate_title::DuplicateTitle;
+pub use duplicate_tune_number::DuplicateTuneNumber;
+pub use inconsistent_directives::{InconsistentDirectives, InconsistentDirectivesOptions};
+pub use instrument_range::{
+    InstrumentRange, InstrumentRangeDefinition, InstrumentRangeOptions, PitchName,
+};
+pub use invalid_chord_symbol::InvalidChordSymbol;
+pub use invalid_tie::InvalidTie;
+pub use lyrics_alignment::LyricsAlignment;
+pub use redundant_accidental::{AccidentalMode, RedundantAccidental, RedundantAccidentalOptions};
+pub use required_fields::{RequiredField, RequiredFields, RequiredFieldsOptions};
+pub use rhythm_meter::{RhythmMeter, RhythmMeterOptions};
+pub use suspicious_duration::{SuspiciousDuration, SuspiciousDurationOptions};
+pub use tune_numbering::{TuneNumbering, TuneNumberingOptions};
+pub use unbalanced_repeat::UnbalancedRepeat;
+pub use unit_note_length::UnitNoteLength;
+pub use unknown_decoration::{UnknownDecoration, UnknownDecorationOptions};
+pub use unusual_octave::{UnusualOctave, UnusualOctaveOptions};
+pub use voice_consistency::VoiceConsistency;
@@ -0,0 +1,391 @@
+//! W010: Redundant or contradictory accidental warning.
+//!
+//! Warns about accidentals that change nothing: `^f` in `K:G`, where the key
+//! already sharpens F, a natural on a note nothing altered, or an accidental
+//! repeated later in the bar. Also warns about a chord that alters one note
+//! two ways (`[^c_c]`).
+//!
+//! A courtesy accidental restates a note that the previous bar altered
+//! differently (`^F4|=F4`). In `courtesy` mode (the default) these are
+//! accepted, and a missing one is reported; in `strict` mode they are
+//! redundant like any other.
+//!
+//! A note reached through a tie keeps the accidental of the note tied to it,
+//! even across a bar line (`^c4-|c4`), and needs no courtesy accidental.
+
+use std::collections::HashSet;
+
+use chamber_ast::visit::{VisitContext, Visitor};
+use chamber_ast::{Accidental, BarLine, Chord, InlineField, Note, Pitch, Rest, Tie};
+use chamber_diagnostics::{Applicability, Diagnostic, DiagnosticCode, Fix, Severity, TextEdit};
+use chamber_text_size::{TextRange, TextSize};
+use serde::{Deserialize, Serialize};
+
+use crate::context::RuleContext;
+use crate::model::{BarAccidentals, PitchModel, Voices};
+use crate::rule::{Category, Rule, RuleMeta};
+
+/// Rule that warns about redundant and contradictory accidentals.
+pub struct RedundantAccidental {
+    options: RedundantAccidentalOptions,
+    diagnostics: Vec<Diagnostic>,
+    key: PitchModel,
+    voices: Voices<VoiceState>,
+}
+
+#[derive(Debug, Default)]
+struct VoiceState {
+    bar: BarAccidentals,
+    /// The accidentals of the previous bar, for courtesy accidentals.
+    previous_bar: BarAccidentals,
+    /// Notes (pitch and octave) already seen in the bar.
+    seen: HashSet<(Pitch, i8)>,
+    /// The notes of the current chord and the accidentals they play with.
+    chord: Vec<(Pitch, i8, Accidental)>,
+    /// The last note, or the notes of the last chord, and the accidentals
+    /// they play with.
+    last: Vec<(Pitch, i8, Accidental)>,
+    /// Notes held by a tie into the next note or chord.
+    tied: Vec<(Pitch, i8, Accidental)>,
+    /// Notes held by a tie into the current chord.
+    tied_into_chord: Vec<(Pitch, i8, Accidental)>,
+}
+
+/// Options for [`RedundantAccidental`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct RedundantAccidentalOptions {
+    /// How courtesy accidentals are treated.
+    pub mode: AccidentalMode,
+}
+
+/// How [`RedundantAccidental`] treats courtesy accidentals.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AccidentalMode {
+    /// Courtesy accidentals are allowed, and reported where missing.
+    #[default]
+    Courtesy,
+    /// Every accidental that changes nothing is redundant.
+    Strict,
+}
+
+impl RuleMeta for RedundantAccidental {
+    const NAME: &'static str = "redundantAccidental";
+    const CODE: DiagnosticCode = DiagnosticCode::RedundantAccidental;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Style;
+    const DOCS: &'static str =
+        "Warns about accidentals that change nothing, missing courtesy accidentals, and \
+         chords that alter a note two ways.";
+}
+
+impl Rule for RedundantAccidental {
+    type Options = RedundantAccidentalOptions;
+
+    fn new(cx: &RuleContext<Self::Options>) -> Self {
+        Self {
+            options: *cx.options(),
+            diagnostics: Vec::new(),
+            key: cx.pitch().clone(),
+            voices: Voices::new(),
+        }
+    }
+
+    fn finish(self, _cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+/// Returns true if playing a note with `accidental` is the same as with
+/// `implied` (no accidental is natural).
+fn same(accidental: Accidental, implied: Option<Accidental>) -> bool {
+    accidental == implied.unwrap_or(Accidental::Natural)
+}
+
+fn accidental_text(accidental: Accidental) -> &'static str {
+    match accidental {
+        Accidental::Sharp => "^",
+        Accidental::DoubleSharp => "^^",
+        Accidental::Flat => "_",
+        Accidental::DoubleFlat => "__",
+        Accidental::Natural => "=",
+    }
+}
+
+fn accidental_name(accidental: Accidental) -> &'static str {
+    match accidental {
+        Accidental::Sharp => "sharp",
+        Accidental::DoubleSharp => "double sharp",
+        Accidental::Flat => "flat",
+        Accidental::DoubleFlat => "double flat",
+        Accidental::Natural => "natural",
+    }
+}
+
+/// Returns where a note's accidental is, or would be: after its
+/// decorations.
+fn accidental_start(note: &Note) -> TextSize {
+    note.decorations
+        .last()
+        .map_or(note.range.start(), |decoration| decoration.range.end())
+}
+
+impl RedundantAccidental {
+    fn redundant(&mut self, note: &Note, accidental: Accidental, message: &str) {
+        let start = accidental_start(note);
+        let len = TextSize::new(accidental_text(accidental).len() as u32);
+        let range = TextRange::new(start, start + len);
+        self.diagnostics.push(
+            Diagnostic::warning(DiagnosticCode::RedundantAccidental, range, message).with_fix(
+                Fix::edit(
+                    "remove the accidental",
+                    TextEdit::delete(range),
+                    Applicability::MachineApplicable,
+                ),
+            ),
+        );
+    }
+}
+
+impl Visitor for RedundantAccidental {
+    fn visit_note(&mut self, note: &Note, cx: &VisitContext) {
+        let voice = self.voices.current(cx);
+        // The accidental a tie into this note holds
+        let tied = if cx.in_grace_notes {
+            Vec::new()
+        } else if cx.in_chord {
+            voice.tied_into_chord.clone()
+        } else {
+            std::mem::take(&mut voice.tied)
+        };
+        let tied_from = tied
+            .iter()
+            .find(|&&(pitch, octave, _)| (pitch, octave) == (note.pitch, note.octave))
+            .map(|&(_, _, accidental)| accidental);
+        let carried = voice.bar.carried(note);
+        let implied = carried.or_else(|| self.key.signature(note.pitch));
+        let plays = note
+            .accidental
+            .or(tied_from)
+            .or(implied)
+            .unwrap_or(Accidental::Natural);
+        if !cx.in_grace_notes {
+            if !cx.in_chord {
+                voice.last.clear();
+            }
+            voice.last.push((note.pitch, note.octave, plays));
+        }
+
+        // The previous bar left this note altered differently
+        let first_in_bar = voice.seen.insert((note.pitch, note.octave));
+        let courtesy = first_in_bar
+            && carried.is_none()
+            && voice
+                .previous_bar
+                .carried(note)
+                .is_some_and(|previous| !same(previous, implied));
+
+        if cx.in_chord {
+            let contradiction = voice
+                .chord
+                .iter()
+                .find(|&&(pitch, octave, _)| (pitch, octave) == (note.pitch, note.octave))
+                .filter(|&&(_, _, other)| other != plays)
+                .map(|&(_, _, other)| other);
+            voice.chord.push((note.pitch, note.octave, plays));
+            if let Some(other) = contradiction {
+                self.diagnostics.push(Diagnostic::warning(
+                    DiagnosticCode::RedundantAccidental,
+                    note.range,
+                    format!(
+                        "chord has {} both {} and {}",
+                        note.pitch.to_char(),
+                        accidental_name(other),
+                        accidental_name(plays)
+                    ),
+                ));
+            }
+        }
+        voice.bar.add(note);
+
+        let strict = self.options.mode == AccidentalMode::Strict;
+        match note.accidental {
+            Some(accidental) if same(accidental, implied) => {
+                let message = if carried.is_some() {
+                    "accidental repeats one earlier in the bar"
+                } else if courtesy && !strict {
+                    return;
+                } else if implied.is_some() {
+                    "accidental repeats the key signature"
+                } else {
+                    "natural has no effect, the note is not altered"
+                };
+                self.redundant(note, accidental, message);
+            }
+            None if courtesy && !strict && tied_from.is_none() => {
+                let start = accidental_start(note);
+                self.diagnostics.push(
+                    Diagnostic::warning(
+                        DiagnosticCode::RedundantAccidental,
+                        note.range,
+                        format!(
+                            "missing courtesy accidental, {} was altered differently in the \
+                             previous bar",
+                            note.pitch.to_char()
+                        ),
+                    )
+                    .with_fix(Fix::edit(
+                        format!("add a courtesy {}", accidental_name(plays)),
+                        TextEdit::insert(start, accidental_text(plays)),
+                        Applicability::MaybeIncorrect,
+                    )),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_chord(&mut self, _chord: &Chord, cx: &VisitContext) {
+        let voice = self.voices.current(cx);
+        voice.chord.clear();
+        voice.last.clear();
+        voice.tied_into_chord = std::mem::take(&mut voice.tied);
+    }
+
+    fn visit_rest(&mut self, _rest: &Rest, cx: &VisitContext) {
+        let voice = self.voices.current(cx);
+        voice.last.clear();
+        voice.tied.clear();
+    }
+
+    fn visit_tie(&mut self, _tie: &Tie, cx: &VisitContext) {
+        let voice = self.voices.current(cx);
+        voice.tied = voice.last.clone();
+    }
+
+    fn visit_bar_line(&mut self, _bar_line: &BarLine, cx: &VisitContext) {
+        let voice = self.voices.current(cx);
+        voice.previous_bar = std::mem::take(&mut voice.bar);
+        voice.seen.clear();
+    }
+
+    fn visit_inline_field(&mut self, field: &InlineField, _cx: &VisitContext) {
+        self.key.apply_field(field.label, &field.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::RuleExt;
+    use chamber_diagnostics::apply_fixes;
+    use chamber_parser::parse;
+
+    fn check(body: &str, mode: AccidentalMode) -> Vec<Diagnostic> {
+        let tune = parse(&format!("X:1\nK:G\n{}", body));
+        RedundantAccidental::check_with(&tune, &RedundantAccidentalOptions { mode })
+    }
+
+    fn fix(body: &str) -> String {
+        fix_with(body, Applicability::MachineApplicable)
+    }
+
+    fn fix_with(body: &str, applicability: Applicability) -> String {
+        let source = format!("X:1\nK:G\n{}", body);
+        let diagnostics = RedundantAccidental::check(&parse(&source));
+        let fixed = apply_fixes(&source, &diagnostics, applicability);
+        fixed.source["X:1\nK:G\n".len()..].to_string()
+    }
+
+    #[test]
+    fn test_needed_accidentals() {
+        assert!(RedundantAccidental::check(&parse("X:1\nK:G\n=F ^c _B ^^G|")).is_empty());
+        // A natural after a sharp in the bar
+        assert!(RedundantAccidental::check(&parse("X:1\nK:G\n=F ^F =F|")).is_empty());
+    }
+
+    #[test]
+    fn test_redundant_accidentals() {
+        let diagnostics = check("^f2 ^c ^c =C|", AccidentalMode::Courtesy);
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "accidental repeats the key signature",
+                "accidental repeats one earlier in the bar",
+                "natural has no effect, the note is not altered"
+            ]
+        );
+
+        assert_eq!(fix("^f2 ^c ^c =C|"), "f2 ^c c C|");
+        assert_eq!(fix("!trill!^f2|"), "!trill!f2|");
+        assert_eq!(fix("^G ^^G ^^G|"), "^G ^^G G|");
+    }
+
+    #[test]
+    fn test_courtesy_accidentals() {
+        // The natural is a courtesy after the previous bar's sharp
+        assert!(check("^C4|=C4|", AccidentalMode::Courtesy).is_empty());
+        assert_eq!(check("^C4|=C4|", AccidentalMode::Strict).len(), 1);
+
+        // A second natural in the bar is not
+        assert_eq!(check("^C4|=C2 =C2|", AccidentalMode::Courtesy).len(), 1);
+    }
+
+    #[test]
+    fn test_missing_courtesy_accidental() {
+        let diagnostics = check("=F4|F4|", AccidentalMode::Courtesy);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0]
+            .message
+            .starts_with("missing courtesy accidental"));
+        // Adding one may not be what was meant
+        assert_eq!(fix("=F4|F4|"), "=F4|F4|");
+        let maybe_incorrect = Applicability::MaybeIncorrect;
+        assert_eq!(fix_with("=F4|F4|", maybe_incorrect), "=F4|^F4|");
+        assert_eq!(fix_with("^c4|c4|", maybe_incorrect), "^c4|=c4|");
+
+        // Other octaves and later bars need none
+        assert!(check("=F4|f4|F4|", AccidentalMode::Courtesy).is_empty());
+        assert!(check("=F4|F4|", AccidentalMode::Strict).is_empty());
+    }
+
+    #[test]
+    fn test_tied_notes() {
+        // The tie holds the sharp into the next bar; a natural would break it
+        assert!(check("^c4-|c4|", AccidentalMode::Courtesy).is_empty());
+        assert_eq!(
+            fix_with("^c4-|c4|", Applicability::MaybeIncorrect),
+            "^c4-|c4|"
+        );
+        assert!(check("[^cE]-|[cE]|", AccidentalMode::Courtesy).is_empty());
+        assert!(check("=F2-|F2 z2|", AccidentalMode::Courtesy).is_empty());
+
+        // Only a note reached through the tie keeps it
+        assert!(check("^c4-|e4|c4|", AccidentalMode::Courtesy).is_empty());
+        assert_eq!(check("^c2-c2|c4|", AccidentalMode::Courtesy).len(), 1);
+        assert_eq!(check("^c2-z2|c4|", AccidentalMode::Courtesy).len(), 1);
+    }
+
+    #[test]
+    fn test_contradictory_chord() {
+        let diagnostics = check("[^c_c]|[c^c]|[^cc]|", AccidentalMode::Strict);
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "chord has C both sharp and flat",
+                "chord has C both natural and sharp"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_voices_and_key_changes() {
+        // Each voice has its own bar
+        assert!(check("[V:1]^c2 [V:2]^c2|", AccidentalMode::Courtesy).is_empty());
+        // The key signature in effect counts
+        assert_eq!(check("[K:D]^c2|", AccidentalMode::Courtesy).len(), 1);
+    }
+}
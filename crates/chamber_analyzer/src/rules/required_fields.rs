@@ -0,0 +1,285 @@
+//! W019: Header field the collection requires is missing.
+//!
+//! Collections often ask more of a tune than `X:`, `T:` and `K:`: a rhythm,
+//! an origin, or where the transcription comes from. The fields are set in
+//! the options; an entry like `"Z|S"` is satisfied by either field. A field
+//! with an empty value counts as missing.
+//!
+//! The `requiredFields` of a house rule (see
+//! [`CustomRules`](crate::rules::CustomRules)) are checked the same way.
+
+use std::fmt;
+
+use chamber_ast::visit::Visitor;
+use chamber_ast::{Header, HeaderField, HeaderFieldKind};
+use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::context::RuleContext;
+use crate::rule::{Category, Rule, RuleMeta};
+use crate::rules::custom_rules::FieldLabel;
+
+/// Rule that checks that every tune has the header fields the collection
+/// requires.
+pub struct RequiredFields;
+
+/// Options for [`RequiredFields`].
+///
+/// ```json
+/// { "fields": ["T", "R", "M", "L", "K", "O", "Z|S"] }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct RequiredFieldsOptions {
+    /// The fields every tune must have.
+    pub fields: Vec<RequiredField>,
+}
+
+/// A required header field, or fields of which one is required, written
+/// "O" or "Z|S" in the configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequiredField(Vec<FieldLabel>);
+
+impl RequiredField {
+    /// Returns the field labels, any of which satisfies the requirement.
+    pub fn labels(&self) -> &[FieldLabel] {
+        &self.0
+    }
+}
+
+impl fmt::Display for RequiredField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, label) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " or ")?;
+            }
+            write!(f, "{}", label)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for RequiredField {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let labels: Vec<_> = self.0.iter().map(|l| l.as_char().to_string()).collect();
+        serializer.serialize_str(&labels.join("|"))
+    }
+}
+
+impl<'de> Deserialize<'de> for RequiredField {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        let labels = text
+            .split('|')
+            .map(|label| {
+                let mut chars = label
+                    .trim()
+                    .strip_suffix(':')
+                    .unwrap_or(label.trim())
+                    .chars();
+                match (chars.next().and_then(FieldLabel::new), chars.next()) {
+                    (Some(label), None) => Ok(label),
+                    _ => Err(serde::de::Error::custom(format!(
+                        "invalid field '{}', expected a letter like \"O\" or letters like \"Z|S\"",
+                        text
+                    ))),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self(labels))
+    }
+}
+
+/// Returns what the field with `label` holds, for messages.
+fn field_name(label: char) -> Option<&'static str> {
+    let name = match label {
+        'A' => "area",
+        'B' => "book",
+        'C' => "composer",
+        'D' => "discography",
+        'F' => "file URL",
+        'G' => "group",
+        'H' => "history",
+        'I' => "instruction",
+        'K' => "key",
+        'L' => "unit note length",
+        'M' => "meter",
+        'N' => "notes",
+        'O' => "origin",
+        'P' => "parts",
+        'Q' => "tempo",
+        'R' => "rhythm",
+        'S' => "source",
+        'T' => "title",
+        'W' => "words",
+        'X' => "reference number",
+        'Z' => "transcription",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Returns the fields of `required` that `header` lacks, with the first of
+/// the fields it has with an empty value, if any. A missing `X:`, `T:` or
+/// `K:` is left to the parser.
+pub(crate) fn missing_fields<'a>(
+    header: &'a Header,
+    required: &'a [RequiredField],
+) -> impl Iterator<Item = (&'a RequiredField, Option<&'a HeaderField>)> {
+    required.iter().filter_map(|required| {
+        let fields: Vec<_> = header
+            .fields
+            .iter()
+            .filter(|field| {
+                let label = field.kind.to_char();
+                required.labels().iter().any(|l| l.as_char() == label)
+            })
+            .collect();
+        if fields.iter().any(|field| !field.value.trim().is_empty()) {
+            return None;
+        }
+        let reported = fields.is_empty()
+            && required.labels().iter().any(|l| {
+                matches!(
+                    HeaderFieldKind::from_char(l.as_char()),
+                    HeaderFieldKind::ReferenceNumber
+                        | HeaderFieldKind::Title
+                        | HeaderFieldKind::Key
+                )
+            });
+        (!reported).then(|| (required, fields.first().copied()))
+    })
+}
+
+impl RuleMeta for RequiredFields {
+    const NAME: &'static str = "requiredFields";
+    const CODE: DiagnosticCode = DiagnosticCode::MissingHeaderField;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Style;
+    const DOCS: &'static str =
+        "Warns when a tune lacks a header field the collection requires (configured).";
+}
+
+impl Rule for RequiredFields {
+    type Options = RequiredFieldsOptions;
+
+    fn new(_cx: &RuleContext<Self::Options>) -> Self {
+        Self
+    }
+
+    fn finish(self, cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        let header = &cx.tune().header;
+        // Point at the first header line rather than the whole header
+        let range = header.fields.first().map_or(header.range, |f| f.range);
+        let mut diagnostics = Vec::new();
+        for (required, empty) in missing_fields(header, &cx.options().fields) {
+            let names: Vec<_> = required
+                .labels()
+                .iter()
+                .filter_map(|l| field_name(l.as_char()))
+                .collect();
+            let mut diagnostic = match empty {
+                Some(field) => Diagnostic::warning(
+                    DiagnosticCode::MissingHeaderField,
+                    field.range,
+                    format!("empty {} field", required),
+                ),
+                None => Diagnostic::warning(
+                    DiagnosticCode::MissingHeaderField,
+                    range,
+                    format!("missing {} field", required),
+                ),
+            };
+            if names.len() == required.labels().len() {
+                diagnostic = diagnostic.with_note(format!(
+                    "the collection requires the {} of every tune",
+                    names.join(" or ")
+                ));
+            }
+            diagnostics.push(diagnostic);
+        }
+        diagnostics
+    }
+}
+
+// Everything is checked in the header, in `finish`
+impl Visitor for RequiredFields {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::check_source;
+
+    fn messages(source: &str, fields: serde_json::Value) -> Vec<String> {
+        let options: RequiredFieldsOptions =
+            serde_json::from_value(serde_json::json!({ "fields": fields })).unwrap();
+        check_source::<RequiredFields>(source, &options)
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_no_fields_required() {
+        assert!(messages("X:1\nT:Reel\nK:D\nDFAd|\n", serde_json::json!([])).is_empty());
+    }
+
+    #[test]
+    fn test_missing_fields() {
+        let source = "X:1\nT:The Kesh\nR:jig\nM:6/8\nK:G\nGAG GAB|\n";
+        assert_eq!(
+            messages(source, serde_json::json!(["R", "M", "L", "O:"])),
+            ["missing L: field", "missing O: field"]
+        );
+    }
+
+    #[test]
+    fn test_alternatives() {
+        let fields = serde_json::json!(["Z|S"]);
+        assert!(messages("X:1\nT:Reel\nS:Tommy Peoples\nK:D\nDFAd|\n", fields.clone()).is_empty());
+        assert!(messages(
+            "X:1\nT:Reel\nZ:abc-transcription\nK:D\nDFAd|\n",
+            fields.clone()
+        )
+        .is_empty());
+        assert_eq!(
+            messages("X:1\nT:Reel\nK:D\nDFAd|\n", fields),
+            ["missing Z: or S: field"]
+        );
+    }
+
+    #[test]
+    fn test_empty_field() {
+        let diagnostics = check_source::<RequiredFields>(
+            "X:1\nT:Reel\nO:\nK:D\nDFAd|\n",
+            &serde_json::from_value(serde_json::json!({ "fields": ["O"] })).unwrap(),
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "empty O: field");
+        assert_eq!(
+            diagnostics[0].notes,
+            ["the collection requires the origin of every tune"]
+        );
+    }
+
+    #[test]
+    fn test_parser_fields_not_repeated() {
+        // The parser reports the missing `T:`
+        assert!(messages("X:1\nK:D\nDFAd|\n", serde_json::json!(["T", "K"])).is_empty());
+    }
+
+    #[test]
+    fn test_options() {
+        let options: RequiredFieldsOptions =
+            serde_json::from_value(serde_json::json!({ "fields": ["R", "Z|S:"] })).unwrap();
+        assert_eq!(options.fields[1].labels().len(), 2);
+        assert_eq!(
+            serde_json::to_value(&options).unwrap(),
+            serde_json::json!({ "fields": ["R", "Z|S"] })
+        );
+        assert!(serde_json::from_value::<RequiredFieldsOptions>(
+            serde_json::json!({ "fields": ["Rhythm"] })
+        )
+        .is_err());
+    }
+}
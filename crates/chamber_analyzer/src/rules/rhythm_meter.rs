@@ -0,0 +1,308 @@
+//! W021: Rhythm that is unknown or doesn't match the meter.
+//!
+//! `R:` names the dance or tune type, and each type has its meters: a jig is
+//! in 6/8, a slip jig in 9/8, a reel in 4/4 or 2/2. A rhythm whose meter
+//! doesn't match the `M:` field is a wrong rhythm or meter. Names are
+//! matched without case, spaces or hyphens (`Slip-Jig` is `slip jig`).
+//!
+//! The options add rhythms, or change the meters of built-in ones; an empty
+//! list of meters allows any. Collections use many more rhythms than the
+//! built-in ones, so rhythms outside the vocabulary are only reported, as a
+//! typo (`R:jigg`) or a house spelling, with `allowUnknown` off. Only the
+//! header meter is checked.
+
+use std::collections::BTreeMap;
+
+use chamber_ast::visit::Visitor;
+use chamber_ast::{HeaderField, HeaderFieldKind};
+use chamber_diagnostics::{Applicability, Diagnostic, DiagnosticCode, Fix, Severity, TextEdit};
+use chamber_text_size::{TextRange, TextSize};
+use serde::{Deserialize, Serialize};
+
+use crate::context::RuleContext;
+use crate::model::normalize_meter;
+use crate::rule::{Category, Rule, RuleMeta};
+use crate::rules::unknown_decoration::levenshtein_distance;
+
+/// Rule that checks `R:` rhythms against a vocabulary and the meter.
+pub struct RhythmMeter;
+
+/// Options for [`RhythmMeter`].
+///
+/// ```json
+/// { "rhythms": { "barn dance": ["4/4", "2/2"], "bourrée": ["2/2"] }, "allowUnknown": false }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct RhythmMeterOptions {
+    /// Rhythms besides the built-in ones, or built-in ones with other
+    /// meters, with the meters they are written in (any when empty).
+    pub rhythms: BTreeMap<String, Vec<String>>,
+    /// Don't report rhythms outside the vocabulary (the default).
+    pub allow_unknown: bool,
+}
+
+impl Default for RhythmMeterOptions {
+    fn default() -> Self {
+        Self {
+            rhythms: BTreeMap::new(),
+            allow_unknown: true,
+        }
+    }
+}
+
+/// Built-in rhythms and their meters; no meters means any.
+const RHYTHMS: &[(&str, &[&str])] = &[
+    ("jig", &["6/8"]),
+    ("single jig", &["6/8", "12/8"]),
+    ("double jig", &["6/8"]),
+    ("slip jig", &["9/8"]),
+    ("hop jig", &["9/8"]),
+    ("slide", &["12/8"]),
+    ("reel", &["4/4", "2/2"]),
+    ("hornpipe", &["4/4", "2/2"]),
+    ("barndance", &["4/4"]),
+    ("fling", &["4/4"]),
+    ("highland", &["4/4"]),
+    ("strathspey", &["4/4"]),
+    ("schottische", &["4/4", "2/4"]),
+    ("polka", &["2/4"]),
+    ("march", &["2/4", "4/4", "2/2", "6/8"]),
+    ("waltz", &["3/4"]),
+    ("mazurka", &["3/4"]),
+    ("polska", &["3/4"]),
+    ("three-two", &["3/2"]),
+    ("set dance", &[]),
+    ("air", &[]),
+    ("slow air", &[]),
+    ("song", &[]),
+];
+
+/// Returns the name of a rhythm as it is matched: lowercase, without spaces,
+/// hyphens or underscores.
+fn normalize_rhythm(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '_'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl RhythmMeterOptions {
+    /// Returns the rhythms, by normalized name, with their names as written
+    /// and their meters.
+    fn vocabulary(&self) -> BTreeMap<String, (String, Vec<String>)> {
+        let builtin = RHYTHMS.iter().map(|&(name, meters)| {
+            let meters = meters.iter().map(|m| m.to_string()).collect();
+            (name.to_string(), meters)
+        });
+        let configured = self
+            .rhythms
+            .iter()
+            .map(|(name, meters)| (name.clone(), meters.clone()));
+        builtin
+            .chain(configured)
+            .map(|(name, meters)| (normalize_rhythm(&name), (name, meters)))
+            .collect()
+    }
+}
+
+/// Finds the closest rhythm name for a typo.
+fn suggest_rhythm<'a>(
+    name: &str,
+    vocabulary: &'a BTreeMap<String, (String, Vec<String>)>,
+) -> Option<&'a str> {
+    let max_distance = if name.len() <= 4 { 1 } else { 2 };
+    vocabulary
+        .iter()
+        .map(|(key, (written, _))| (levenshtein_distance(name, key), written))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, written)| written.as_str())
+}
+
+/// Returns where the value of `field` is in `source`, if it is written as
+/// it was read.
+fn value_range(source: &str, field: &HeaderField) -> Option<TextRange> {
+    let text = source.get(field.range.start().raw() as usize..field.range.end().raw() as usize)?;
+    let value = field.value.trim();
+    let start = field.range.start() + TextSize::new(text.find(value)? as u32);
+    Some(TextRange::new(
+        start,
+        start + TextSize::new(value.len() as u32),
+    ))
+}
+
+impl RuleMeta for RhythmMeter {
+    const NAME: &'static str = "rhythmMeter";
+    const CODE: DiagnosticCode = DiagnosticCode::InvalidRhythm;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Lint;
+    const DOCS: &'static str =
+        "Warns when the rhythm (R:) is not a known one or doesn't match the meter (M:).";
+}
+
+impl Rule for RhythmMeter {
+    type Options = RhythmMeterOptions;
+
+    fn new(_cx: &RuleContext<Self::Options>) -> Self {
+        Self
+    }
+
+    fn finish(self, cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        let fields = &cx.tune().header.fields;
+        let Some(rhythm) = fields
+            .iter()
+            .find(|f| f.kind == HeaderFieldKind::Other('R') && !f.value.trim().is_empty())
+        else {
+            return Vec::new();
+        };
+        let name = rhythm.value.trim();
+        let vocabulary = cx.options().vocabulary();
+
+        let Some((written, meters)) = vocabulary.get(&normalize_rhythm(name)) else {
+            if cx.options().allow_unknown {
+                return Vec::new();
+            }
+            let suggestion = suggest_rhythm(&normalize_rhythm(name), &vocabulary);
+            let diagnostic = match suggestion {
+                Some(suggestion) => {
+                    let mut diagnostic = Diagnostic::warning(
+                        DiagnosticCode::InvalidRhythm,
+                        rhythm.range,
+                        format!("unknown rhythm '{}', did you mean '{}'?", name, suggestion),
+                    );
+                    if let Some(range) = cx.source().and_then(|s| value_range(s, rhythm)) {
+                        diagnostic = diagnostic.with_fix(Fix::edit(
+                            format!("replace with '{}'", suggestion),
+                            TextEdit::replace(range, suggestion),
+                            Applicability::MaybeIncorrect,
+                        ));
+                    }
+                    diagnostic
+                }
+                None => Diagnostic::warning(
+                    DiagnosticCode::InvalidRhythm,
+                    rhythm.range,
+                    format!("unknown rhythm '{}'", name),
+                )
+                .with_note("add it to the rule's `rhythms` option if the collection uses it"),
+            };
+            return vec![diagnostic];
+        };
+
+        // Without a meter there is nothing to compare
+        let Some(meter) = fields
+            .iter()
+            .find(|f| f.kind == HeaderFieldKind::Meter && !f.value.trim().is_empty())
+        else {
+            return Vec::new();
+        };
+        let value = normalize_meter(&meter.value);
+        if meters.is_empty() || meters.iter().any(|m| normalize_meter(m) == value) {
+            return Vec::new();
+        }
+        vec![Diagnostic::warning(
+            DiagnosticCode::InvalidRhythm,
+            rhythm.range,
+            format!("R:{} doesn't match M:{}", name, meter.value.trim()),
+        )
+        .with_label(meter.range, "meter set here")
+        .with_note(format!("a {} is in {}", written, meters.join(" or ")))]
+    }
+}
+
+// Everything is checked in the header, in `finish`
+impl Visitor for RhythmMeter {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::check_source;
+    use chamber_diagnostics::apply_fixes;
+
+    fn check(rhythm: &str, meter: &str, options: &RhythmMeterOptions) -> Vec<Diagnostic> {
+        let source = format!(
+            "X:1\nT:Tune\nR:{}\nM:{}\nL:1/8\nK:D\nDFAd|\n",
+            rhythm, meter
+        );
+        check_source::<RhythmMeter>(&source, options)
+    }
+
+    fn messages(rhythm: &str, meter: &str) -> Vec<String> {
+        check(rhythm, meter, &Default::default())
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_matching_meter() {
+        assert!(messages("jig", "6/8").is_empty());
+        assert!(messages("Slip Jig", "9/8").is_empty());
+        assert!(messages("slip-jig", "9/8").is_empty());
+        assert!(messages("reel", "C|").is_empty());
+        assert!(messages("Hornpipe", "C").is_empty());
+        assert!(messages("air", "5/4").is_empty());
+    }
+
+    #[test]
+    fn test_meter_mismatch() {
+        let diagnostics = check("reel", "6/8", &Default::default());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "R:reel doesn't match M:6/8");
+        assert_eq!(diagnostics[0].labels[0].message, "meter set here");
+        assert_eq!(diagnostics[0].notes, ["a reel is in 4/4 or 2/2"]);
+
+        assert_eq!(messages("Polka", "4/4"), ["R:Polka doesn't match M:4/4"]);
+    }
+
+    #[test]
+    fn test_unknown_rhythm() {
+        // Allowed by default
+        assert!(messages("bourrée", "2/2").is_empty());
+
+        let options = RhythmMeterOptions {
+            allow_unknown: false,
+            ..Default::default()
+        };
+        let messages = |rhythm: &str, meter: &str| -> Vec<String> {
+            check(rhythm, meter, &options)
+                .into_iter()
+                .map(|d| d.message)
+                .collect()
+        };
+        assert_eq!(
+            messages("jigg", "6/8"),
+            ["unknown rhythm 'jigg', did you mean 'jig'?"]
+        );
+        assert_eq!(messages("bourrée", "2/2"), ["unknown rhythm 'bourrée'"]);
+
+        let source = "X:1\nT:Tune\nR:Hornpip\nM:4/4\nK:D\nDFAd|\n";
+        let diagnostics = check_source::<RhythmMeter>(source, &options);
+        let fixed = apply_fixes(source, &diagnostics, Applicability::MaybeIncorrect).source;
+        assert_eq!(fixed, "X:1\nT:Tune\nR:hornpipe\nM:4/4\nK:D\nDFAd|\n");
+    }
+
+    #[test]
+    fn test_no_meter() {
+        let source = "X:1\nT:Tune\nR:jig\nK:D\nDFAd|\n";
+        assert!(check_source::<RhythmMeter>(source, &Default::default()).is_empty());
+    }
+
+    #[test]
+    fn test_options() {
+        let options: RhythmMeterOptions = serde_json::from_value(serde_json::json!({
+            "rhythms": { "bourrée": ["2/2"], "Polka": ["2/4", "4/4"] }
+        }))
+        .unwrap();
+        assert!(check("bourrée", "C|", &options).is_empty());
+        assert!(check("polka", "4/4", &options).is_empty());
+        assert_eq!(check("bourrée", "3/4", &options).len(), 1);
+
+        let options: RhythmMeterOptions =
+            serde_json::from_value(serde_json::json!({ "allowUnknown": false })).unwrap();
+        assert_eq!(check("bourrée", "2/2", &options).len(), 1);
+        assert_eq!(check("reel", "6/8", &options).len(), 1);
+        assert!(RhythmMeterOptions::default().allow_unknown);
+    }
+}
@@ -2,76 +2,87 @@
 //!
 //! Warns when notes have unusually long durations.
 
-use chamber_ast::{Duration, MusicElement, Tune};
+use chamber_ast::visit::{VisitContext, Visitor};
+use chamber_ast::{Chord, Duration, Note, Rest};
 use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use chamber_text_size::TextRange;
+use serde::{Deserialize, Serialize};
 
+use crate::context::RuleContext;
 use crate::rule::{Category, Rule, RuleMeta};
 
 /// Rule that warns about suspicious (very long) durations.
-pub struct SuspiciousDuration;
+pub struct SuspiciousDuration {
+    threshold: f64,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Options for [`SuspiciousDuration`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct SuspiciousDurationOptions {
+    /// Durations of at least this many unit note lengths are reported.
+    pub threshold: f64,
+}
+
+impl Default for SuspiciousDurationOptions {
+    fn default() -> Self {
+        Self { threshold: 16.0 }
+    }
+}
 
 impl RuleMeta for SuspiciousDuration {
     const NAME: &'static str = "suspiciousDuration";
     const CODE: DiagnosticCode = DiagnosticCode::SuspiciousDuration;
     const SEVERITY: Severity = Severity::Warning;
     const CATEGORY: Category = Category::Lint;
-    const DOCS: &'static str = "Warns when notes have unusually long durations (>= 16 beats).";
+    const DOCS: &'static str =
+        "Warns when notes have unusually long durations (>= 16 beats by default).";
 }
 
 impl Rule for SuspiciousDuration {
-    fn run(tune: &Tune, diagnostics: &mut Vec<Diagnostic>) {
-        for element in &tune.body.elements {
-            check_element(element, diagnostics);
+    type Options = SuspiciousDurationOptions;
+
+    fn new(cx: &RuleContext<Self::Options>) -> Self {
+        Self {
+            threshold: cx.options().threshold,
+            diagnostics: Vec::new(),
         }
     }
+
+    fn finish(self, _cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
 }
 
-fn check_element(element: &MusicElement, diagnostics: &mut Vec<Diagnostic>) {
-    match element {
-        MusicElement::Note(note) => {
-            check_duration(note.duration.as_ref(), note.range, diagnostics);
-        }
-        MusicElement::Rest(rest) => {
-            check_duration(rest.duration.as_ref(), rest.range, diagnostics);
-        }
-        MusicElement::Chord(chord) => {
-            check_duration(chord.duration.as_ref(), chord.range, diagnostics);
-        }
-        MusicElement::Slur(slur) => {
-            for elem in &slur.elements {
-                check_element(elem, diagnostics);
-            }
-        }
-        MusicElement::Tuplet(tuplet) => {
-            for note in &tuplet.notes {
-                check_duration(note.duration.as_ref(), note.range, diagnostics);
-            }
-        }
-        MusicElement::GraceNotes(grace) => {
-            for note in &grace.notes {
-                check_duration(note.duration.as_ref(), note.range, diagnostics);
-            }
-        }
-        _ => {}
+impl Visitor for SuspiciousDuration {
+    fn visit_note(&mut self, note: &Note, _cx: &VisitContext) {
+        self.check_duration(note.duration.as_ref(), note.range);
+    }
+
+    fn visit_rest(&mut self, rest: &Rest, _cx: &VisitContext) {
+        self.check_duration(rest.duration.as_ref(), rest.range);
+    }
+
+    fn visit_chord(&mut self, chord: &Chord, _cx: &VisitContext) {
+        self.check_duration(chord.duration.as_ref(), chord.range);
     }
 }
 
-fn check_duration(
-    duration: Option<&Duration>,
-    range: chamber_text_size::TextRange,
-    diagnostics: &mut Vec<Diagnostic>,
-) {
-    if let Some(dur) = duration {
-        let effective = dur.numerator as f64 / dur.denominator as f64;
-        if effective >= 16.0 {
-            diagnostics.push(Diagnostic::warning(
-                DiagnosticCode::SuspiciousDuration,
-                range,
-                format!(
-                    "suspicious duration {}/{} (very long note)",
-                    dur.numerator, dur.denominator
-                ),
-            ));
+impl SuspiciousDuration {
+    fn check_duration(&mut self, duration: Option<&Duration>, range: TextRange) {
+        if let Some(dur) = duration {
+            let effective = dur.numerator as f64 / dur.denominator as f64;
+            if effective >= self.threshold {
+                self.diagnostics.push(Diagnostic::warning(
+                    DiagnosticCode::SuspiciousDuration,
+                    range,
+                    format!(
+                        "suspicious duration {}/{} (very long note)",
+                        dur.numerator, dur.denominator
+                    ),
+                ));
+            }
         }
     }
 }
@@ -103,4 +114,14 @@ mod tests {
         let diagnostics = SuspiciousDuration::check(&tune);
         assert_eq!(diagnostics.len(), 1);
     }
+
+    #[test]
+    fn test_custom_threshold() {
+        let tune = parse("X:1\nK:C\nC16 D8");
+        let options = SuspiciousDurationOptions { threshold: 32.0 };
+        assert!(SuspiciousDuration::check_with(&tune, &options).is_empty());
+
+        let options = SuspiciousDurationOptions { threshold: 8.0 };
+        assert_eq!(SuspiciousDuration::check_with(&tune, &options).len(), 2);
+    }
 }
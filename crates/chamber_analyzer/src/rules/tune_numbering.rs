@@ -0,0 +1,192 @@
+//! W020: Tune numbers out of sequence.
+//!
+//! The `X:` numbers of a collection usually count up by one from the first
+//! tune, so a gap or a step back is a tune lost or moved. Each number is
+//! checked against the one before it, so a single gap is reported once.
+//! Numbers used twice are left to W005, and ones that aren't numbers to the
+//! parser.
+
+use std::collections::HashSet;
+
+use chamber_ast::HeaderFieldKind;
+use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use chamber_text_size::TextRange;
+use serde::{Deserialize, Serialize};
+
+use crate::context::RuleContext;
+use crate::rule::{Category, CollectionRule, RuleMeta};
+
+/// Collection rule that checks that `X:` numbers count up by one.
+pub struct TuneNumbering {
+    options: TuneNumberingOptions,
+    /// The last `X:` number and where it is.
+    previous: Option<(u32, TextRange)>,
+    /// Every number seen, to leave duplicates to W005.
+    seen: HashSet<u32>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Options for [`TuneNumbering`].
+///
+/// ```json
+/// { "start": 1 }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct TuneNumberingOptions {
+    /// The number of the first tune; any number when unset.
+    pub start: Option<u32>,
+}
+
+impl RuleMeta for TuneNumbering {
+    const NAME: &'static str = "tuneNumbering";
+    const CODE: DiagnosticCode = DiagnosticCode::TuneNumberSequence;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Style;
+    const DOCS: &'static str =
+        "Warns when the reference numbers (X:) of a collection don't count up by one.";
+}
+
+impl CollectionRule for TuneNumbering {
+    type Options = TuneNumberingOptions;
+
+    fn new(options: &Self::Options) -> Self {
+        Self {
+            options: *options,
+            previous: None,
+            seen: HashSet::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn visit_tune(&mut self, cx: &RuleContext<Self::Options>) {
+        let field = cx
+            .tune()
+            .header
+            .fields
+            .iter()
+            .find(|f| f.kind == HeaderFieldKind::ReferenceNumber);
+        // A missing or invalid `X:` is reported by the parser
+        let Some((field, number)) =
+            field.and_then(|f| Some((f, f.value.trim().parse::<u32>().ok()?)))
+        else {
+            return;
+        };
+        if !self.seen.insert(number) {
+            return;
+        }
+
+        let range = field.range + cx.offset();
+        match self.previous {
+            Some((previous, previous_range)) if previous.checked_add(1) != Some(number) => {
+                let message = match previous.checked_add(1) {
+                    Some(expected) => format!(
+                        "tune number {} follows {}, expected {}",
+                        number, previous, expected
+                    ),
+                    // Nothing can follow the largest number
+                    None => format!("tune number {} follows {}", number, previous),
+                };
+                self.diagnostics.push(
+                    Diagnostic::warning(DiagnosticCode::TuneNumberSequence, range, message)
+                        .with_label(previous_range, "previous tune"),
+                );
+            }
+            None => match self.options.start {
+                Some(start) if number != start => {
+                    self.diagnostics.push(Diagnostic::warning(
+                        DiagnosticCode::TuneNumberSequence,
+                        range,
+                        format!("first tune is number {}, expected {}", number, start),
+                    ));
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        self.previous = Some((number, range));
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn finish(self, _options: &Self::Options) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::check_collection;
+    use chamber_text_size::TextSize;
+
+    fn messages(numbers: &[&str], options: TuneNumberingOptions) -> Vec<String> {
+        let source: String = numbers
+            .iter()
+            .map(|n| format!("X:{}\nK:C\nC|\n\n", n))
+            .collect();
+        check_collection::<TuneNumbering>(&source, &options)
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_sequential_numbers() {
+        assert!(messages(&["1", "2", "3"], Default::default()).is_empty());
+        assert!(messages(&["12", "13"], Default::default()).is_empty());
+    }
+
+    #[test]
+    fn test_gap() {
+        let source = "X:1\nK:C\nC|\n\nX:3\nK:C\nD|\n\nX:4\nK:C\nE|\n";
+        let diagnostics = check_collection::<TuneNumbering>(source, &Default::default());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "tune number 3 follows 1, expected 2"
+        );
+        assert_eq!(diagnostics[0].range.start(), TextSize::new(12));
+        assert_eq!(diagnostics[0].labels[0].range.start(), TextSize::new(0));
+    }
+
+    #[test]
+    fn test_step_back() {
+        assert_eq!(
+            messages(&["1", "2", "10", "3"], Default::default()),
+            [
+                "tune number 10 follows 2, expected 3",
+                "tune number 3 follows 10, expected 11"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicates_and_invalid_numbers_skipped() {
+        assert!(messages(&["1", "2", "2", "3"], Default::default()).is_empty());
+        assert!(messages(&["1", "A", "2"], Default::default()).is_empty());
+    }
+
+    #[test]
+    fn test_largest_number() {
+        let max = u32::MAX.to_string();
+        let before = (u32::MAX - 1).to_string();
+        assert!(messages(&[&before, &max], Default::default()).is_empty());
+        assert_eq!(
+            messages(&[&max, "1"], Default::default()),
+            [format!("tune number 1 follows {}", max)]
+        );
+    }
+
+    #[test]
+    fn test_start() {
+        let options = TuneNumberingOptions { start: Some(1) };
+        assert!(messages(&["1", "2"], options).is_empty());
+        assert_eq!(
+            messages(&["0", "1"], options),
+            ["first tune is number 0, expected 1"]
+        );
+    }
+}
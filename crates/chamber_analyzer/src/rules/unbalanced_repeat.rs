@@ -0,0 +1,265 @@
+//! W011: Unbalanced repeat warning.
+//!
+//! Warns about repeats and endings that renderers and players read
+//! differently from what was meant: a `|:` that is never closed or opens a
+//! repeat inside another, a `:|` with no `|:` after an earlier repeat, a
+//! repeat with no music, and endings that are out of order, don't go back
+//! with `:|`, or are outside any repeat.
+//!
+//! A `:|` with no `|:` at the start of the tune, or after a double bar line,
+//! repeats from there and is fine.
+
+use chamber_ast::visit::{VisitContext, Visitor};
+use chamber_ast::{BarLine, BarLineKind, Chord, Ending, Note, Rest};
+use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use chamber_text_size::TextRange;
+
+use crate::context::RuleContext;
+use crate::model::{Repeat, RepeatEnding, RepeatStart, RepeatStructure, Voices};
+use crate::rule::{Category, NoOptions, Rule, RuleMeta};
+
+/// Rule that warns about mismatched repeats and endings.
+pub struct UnbalancedRepeat {
+    voices: Voices<RepeatStructure>,
+}
+
+impl RuleMeta for UnbalancedRepeat {
+    const NAME: &'static str = "unbalancedRepeat";
+    const CODE: DiagnosticCode = DiagnosticCode::UnbalancedRepeat;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Lint;
+    const DOCS: &'static str =
+        "Warns about repeats that are never closed or nested, and endings out of order.";
+}
+
+impl Rule for UnbalancedRepeat {
+    type Options = NoOptions;
+
+    fn new(_cx: &RuleContext<Self::Options>) -> Self {
+        Self {
+            voices: Voices::new(),
+        }
+    }
+
+    fn finish(self, _cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for structure in self.voices.into_values() {
+            let repeats = structure.finish();
+            for (i, repeat) in repeats.iter().enumerate() {
+                check_repeat(repeat, repeats.get(i + 1), &mut diagnostics);
+                check_endings(repeat, &mut diagnostics);
+            }
+        }
+        diagnostics.sort_by_key(|d| d.range.start());
+        diagnostics
+    }
+}
+
+fn warning(range: TextRange, message: impl Into<String>) -> Diagnostic {
+    Diagnostic::warning(DiagnosticCode::UnbalancedRepeat, range, message)
+}
+
+/// Reports a repeat that is never closed, has no start, or has no music.
+fn check_repeat(repeat: &Repeat, next: Option<&Repeat>, diagnostics: &mut Vec<Diagnostic>) {
+    match (&repeat.start, &repeat.end) {
+        (RepeatStart::Written(start), None) => match next.map(|next| &next.start) {
+            Some(RepeatStart::Written(inner)) if inner.kind == BarLineKind::RepeatStart => {
+                diagnostics.push(
+                    warning(inner.range, "repeat starts inside another repeat")
+                        .with_label(start.range, "outer repeat starts here")
+                        .with_note("end the first repeat with `:|` before the next `|:`"),
+                );
+            }
+            _ => {
+                let mut diagnostic = warning(start.range, "repeat is never closed");
+                if start.kind == BarLineKind::RepeatBoth {
+                    diagnostic = diagnostic.with_note(
+                        "`::` ends one repeat and starts another; end the last repeat with `:|`",
+                    );
+                }
+                diagnostics.push(diagnostic);
+            }
+        },
+        // Endings with no repeat, reported by `check_endings`
+        (_, None) => {}
+        (RepeatStart::AfterRepeat(previous), Some(end)) => diagnostics.push(
+            warning(end.range, "repeat end has no matching start")
+                .with_label(*previous, "previous repeat ends here")
+                .with_note(
+                    "without `|:`, a repeat goes back to the start of the tune or of the \
+                     section; add `|:` where this one starts",
+                ),
+        ),
+        (start, Some(end)) if !repeat.has_music => {
+            let mut diagnostic = warning(end.range, "repeat has no music");
+            if let RepeatStart::Written(start) = start {
+                diagnostic = diagnostic.with_label(start.range, "repeat starts here");
+            }
+            diagnostics.push(diagnostic);
+        }
+        _ => {}
+    }
+}
+
+/// Reports endings out of order, not going back to the repeat, or outside
+/// any repeat.
+fn check_endings(repeat: &Repeat, diagnostics: &mut Vec<Diagnostic>) {
+    if repeat.end.is_none() && !repeat.is_written() {
+        for ending in &repeat.endings {
+            diagnostics.push(
+                warning(ending.range, "ending is outside a repeat")
+                    .with_note("an ending is taken on one time through a repeat; end it with `:|`"),
+            );
+        }
+        return;
+    }
+
+    let mut expected = 1;
+    let mut previous: Option<&RepeatEnding> = None;
+    for (i, ending) in repeat.endings.iter().enumerate() {
+        let first = ending.numbers.first().copied().unwrap_or(expected);
+        if first != expected {
+            let diagnostic = match previous {
+                Some(previous) if expected > 1 => warning(
+                    ending.range,
+                    format!("ending {} follows ending {}", first, expected - 1),
+                )
+                .with_label(previous.range, "previous ending")
+                .with_note(format!("expected ending {}", expected)),
+                _ => warning(
+                    ending.range,
+                    format!("ending {} has no ending 1 before it", first),
+                ),
+            };
+            diagnostics.push(diagnostic);
+        }
+        expected = ending
+            .numbers
+            .iter()
+            .max()
+            .map_or(expected, |&last| last + 1);
+
+        match (repeat.endings.get(i + 1), ending.repeat_end) {
+            (Some(next), None) => diagnostics.push(
+                warning(
+                    ending.range,
+                    format!("ending {} does not end with a repeat", first),
+                )
+                .with_label(next.range, "next ending starts here")
+                .with_note("every ending but the last goes back with `:|`"),
+            ),
+            (None, Some(end)) => diagnostics.push(
+                warning(ending.range, format!("no ending follows ending {}", first))
+                    .with_label(end, "goes back here for another time through"),
+            ),
+            _ => {}
+        }
+        previous = Some(ending);
+    }
+}
+
+impl Visitor for UnbalancedRepeat {
+    fn visit_note(&mut self, _note: &Note, cx: &VisitContext) {
+        self.voices.current(cx).add_music();
+    }
+
+    fn visit_chord(&mut self, _chord: &Chord, cx: &VisitContext) {
+        self.voices.current(cx).add_music();
+    }
+
+    fn visit_rest(&mut self, _rest: &Rest, cx: &VisitContext) {
+        self.voices.current(cx).add_music();
+    }
+
+    fn visit_bar_line(&mut self, bar_line: &BarLine, cx: &VisitContext) {
+        self.voices.current(cx).add_bar_line(bar_line);
+    }
+
+    fn visit_ending(&mut self, ending: &Ending, cx: &VisitContext) {
+        self.voices.current(cx).add_ending(ending);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::RuleExt;
+    use chamber_parser::parse;
+
+    fn messages(body: &str) -> Vec<String> {
+        UnbalancedRepeat::check(&parse(&format!("X:1\nK:G\n{}", body)))
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_balanced_repeats() {
+        assert!(messages("|:GABc:|dcBA|").is_empty());
+        // From the start of the tune, or of a section
+        assert!(messages("GABc:|dcBA|:GABc:|").is_empty());
+        assert!(messages("GABc:|dcBA||cBAG:|").is_empty());
+        assert!(messages("|:GABc::dcBA:|").is_empty());
+        assert!(messages("|:GABc|1dcBA:|2dcBG|]").is_empty());
+        assert!(messages("|:GABc|[1-3dcBA:|[4dcBG|]").is_empty());
+    }
+
+    #[test]
+    fn test_unclosed_and_nested_repeats() {
+        assert_eq!(messages("|:GABc|dcBA|"), ["repeat is never closed"]);
+        assert_eq!(messages("|:GABc::dcBA|"), ["repeat is never closed"]);
+
+        let diagnostics = UnbalancedRepeat::check(&parse("X:1\nK:G\n|:GABc|:dcBA:|"));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "repeat starts inside another repeat"
+        );
+        assert_eq!(diagnostics[0].labels.len(), 1);
+    }
+
+    #[test]
+    fn test_unmatched_repeat_end() {
+        let diagnostics = UnbalancedRepeat::check(&parse("X:1\nK:G\nGABc:|dcBA:|"));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "repeat end has no matching start");
+        assert_eq!(
+            diagnostics[0].labels[0].message,
+            "previous repeat ends here"
+        );
+    }
+
+    #[test]
+    fn test_empty_repeat() {
+        assert_eq!(messages("::GABc:|"), ["repeat has no music"]);
+    }
+
+    #[test]
+    fn test_endings() {
+        assert_eq!(
+            messages("|:GABc:|2dcBA|]"),
+            ["ending 2 has no ending 1 before it"]
+        );
+        assert_eq!(
+            messages("|:GABc|1dcBA:|3dcBG|]"),
+            ["ending 3 follows ending 1"]
+        );
+        assert_eq!(
+            messages("|:GABc|1dcBA|2dcBG:|"),
+            [
+                "ending 1 does not end with a repeat",
+                "no ending follows ending 2"
+            ]
+        );
+        assert_eq!(messages("GABc|[2dcBA|]"), ["ending is outside a repeat"]);
+    }
+
+    #[test]
+    fn test_voices() {
+        assert!(messages("[V:1]|:GABc|[V:2]|:B,4|[V:1]dcBA:|[V:2]G,4:|").is_empty());
+        assert_eq!(
+            messages("[V:1]|:GABc:|[V:2]B,4:|[V:1]dcBA:|"),
+            ["repeat end has no matching start"]
+        );
+    }
+}
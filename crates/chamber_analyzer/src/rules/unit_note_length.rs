@@ -0,0 +1,252 @@
+//! W022: Unit note length left to a default that doesn't fit.
+//!
+//! Without an `L:` field the unit note length comes from the meter: 1/16
+//! below 3/4, 1/8 otherwise. A tune in 2/4 written in eighth notes, or one
+//! in 4/4 written in sixteenths, then has every bar twice or half as long as
+//! it should be. When no bar adds up with the default but most do with the
+//! other length, the `L:` field was left out; the fix adds it.
+//!
+//! The first and last bars of a voice are not counted, as they may be a
+//! pickup and its other half. Tunes that set `L:` in the body are not
+//! checked.
+
+use std::collections::BTreeMap;
+
+use chamber_ast::visit::{VisitContext, Visitor};
+use chamber_ast::{BarLine, Chord, Duration, HeaderFieldKind, InlineField, Note, Rest};
+use chamber_diagnostics::{Applicability, Diagnostic, DiagnosticCode, Fix, Severity, TextEdit};
+
+use crate::context::RuleContext;
+use crate::model::{Fraction, TimingModel};
+use crate::rule::{Category, NoOptions, Rule, RuleMeta};
+use crate::rules::bar_length::tuplet_time;
+
+/// Rule that warns when a tune without `L:` only adds up with the other
+/// unit note length.
+pub struct UnitNoteLength {
+    /// The meter in effect.
+    timing: TimingModel,
+    /// Whether `L:` is set in the body, which stops the check.
+    inline_length: bool,
+    /// The bars of each voice, by voice id ("" before any `V:` field).
+    voices: BTreeMap<String, VoiceBars>,
+}
+
+#[derive(Debug, Default)]
+struct VoiceBars {
+    /// Length of each bar in unit note lengths, and its meter.
+    bars: Vec<(Fraction, Option<Fraction>)>,
+    /// Length of the current bar in unit note lengths, if it has music.
+    current: Option<Fraction>,
+    /// Whether the current bar has a multi-measure rest.
+    multi_measure: bool,
+}
+
+impl RuleMeta for UnitNoteLength {
+    const NAME: &'static str = "unitNoteLength";
+    const CODE: DiagnosticCode = DiagnosticCode::MissingUnitNoteLength;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Lint;
+    const DOCS: &'static str =
+        "Warns when a tune has no L: field and its bars only add up with the other unit note length.";
+}
+
+impl Rule for UnitNoteLength {
+    type Options = NoOptions;
+
+    fn new(cx: &RuleContext<Self::Options>) -> Self {
+        Self {
+            timing: *cx.timing(),
+            inline_length: false,
+            voices: BTreeMap::new(),
+        }
+    }
+
+    fn finish(mut self, cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        if cx.header().unit_note_length.is_some() || self.inline_length {
+            return Vec::new();
+        }
+        let ids: Vec<_> = self.voices.keys().cloned().collect();
+        for id in ids {
+            self.end_bar(&id);
+        }
+
+        let default = cx.timing().unit_note_length();
+        let other = if default == Fraction::new(1, 16) {
+            Fraction::new(1, 8)
+        } else {
+            Fraction::new(1, 16)
+        };
+        let mut bars = Vec::new();
+        for voice in self.voices.values() {
+            let voice_bars = voice.bars.as_slice();
+            let inner = match voice_bars {
+                [_, inner @ .., _] if !inner.is_empty() => inner,
+                _ => voice_bars,
+            };
+            bars.extend(
+                inner
+                    .iter()
+                    .filter_map(|&(units, meter)| Some((units, meter?))),
+            );
+        }
+        let fits = |length: Fraction| {
+            bars.iter()
+                .filter(|&&(units, meter)| units * length == meter)
+                .count()
+        };
+        if bars.is_empty() || fits(default) > 0 || fits(other) * 2 < bars.len() {
+            return Vec::new();
+        }
+
+        let fields = &cx.tune().header.fields;
+        let range = fields
+            .iter()
+            .find(|f| f.kind == HeaderFieldKind::Meter)
+            .or(fields.first())
+            .map_or(cx.tune().header.range, |f| f.range);
+        let mut diagnostic = Diagnostic::warning(
+            DiagnosticCode::MissingUnitNoteLength,
+            range,
+            format!(
+                "unit note length defaults to {}, but the bars add up with {}",
+                default, other
+            ),
+        )
+        .with_note(
+            "without an L: field, the unit note length is 1/16 in meters below 3/4, and 1/8 otherwise",
+        );
+        // `L:` goes before `K:`, which ends the header
+        if let Some(key) = fields.iter().find(|f| f.kind == HeaderFieldKind::Key) {
+            diagnostic = diagnostic.with_fix(Fix::edit(
+                format!("add L:{}", other),
+                TextEdit::insert(key.range.start(), format!("L:{}\n", other)),
+                Applicability::MaybeIncorrect,
+            ));
+        }
+        vec![diagnostic]
+    }
+}
+
+impl UnitNoteLength {
+    fn voice(&mut self, cx: &VisitContext) -> &mut VoiceBars {
+        self.voices
+            .entry(cx.voice.unwrap_or_default().to_string())
+            .or_default()
+    }
+
+    fn end_bar(&mut self, id: &str) {
+        let meter = self.timing.meter();
+        let voice = self.voices.entry(id.to_string()).or_default();
+        let multi_measure = std::mem::take(&mut voice.multi_measure);
+        if let Some(units) = voice.current.take() {
+            // A multi-measure rest stands for bars of any length
+            if !multi_measure {
+                voice.bars.push((units, meter));
+            }
+        }
+    }
+
+    fn add_units(&mut self, duration: Option<&Duration>, cx: &VisitContext) {
+        let mut units = duration.map_or(Fraction::new(1, 1), |d| {
+            Fraction::new(d.numerator, d.denominator)
+        });
+        if let Some(ratio) = cx.tuplet {
+            units = units * Fraction::new(tuplet_time(ratio), ratio);
+        }
+        let voice = self.voice(cx);
+        voice.current = Some(voice.current.unwrap_or(Fraction::zero()) + units);
+    }
+}
+
+impl Visitor for UnitNoteLength {
+    fn visit_note(&mut self, note: &Note, cx: &VisitContext) {
+        // Chord notes count with their chord, and grace notes take no time
+        if cx.in_chord || cx.in_grace_notes {
+            return;
+        }
+        self.add_units(note.duration.as_ref(), cx);
+    }
+
+    fn visit_chord(&mut self, chord: &Chord, cx: &VisitContext) {
+        self.add_units(chord.duration.as_ref(), cx);
+    }
+
+    fn visit_rest(&mut self, rest: &Rest, cx: &VisitContext) {
+        if rest.multi_measure {
+            let voice = self.voice(cx);
+            voice.multi_measure = true;
+            voice.current.get_or_insert(Fraction::zero());
+            return;
+        }
+        self.add_units(rest.duration.as_ref(), cx);
+    }
+
+    fn visit_bar_line(&mut self, _bar_line: &BarLine, cx: &VisitContext) {
+        self.end_bar(cx.voice.unwrap_or_default());
+    }
+
+    fn visit_inline_field(&mut self, field: &InlineField, _cx: &VisitContext) {
+        match field.label {
+            'L' => self.inline_length = true,
+            label => self.timing.apply_field(label, &field.value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::check_source;
+    use chamber_diagnostics::apply_fixes;
+
+    fn check(source: &str) -> Vec<Diagnostic> {
+        check_source::<UnitNoteLength>(source, &NoOptions::default())
+    }
+
+    #[test]
+    fn test_default_fits() {
+        assert!(check("X:1\nT:Reel\nM:4/4\nK:D\nDFAd fdAF|GBdg bgdB|\n").is_empty());
+        assert!(check("X:1\nT:Polka\nM:2/4\nK:D\nD2F2 A2d2|f2d2 A4|\n").is_empty());
+        // With `L:` there is no default
+        assert!(check("X:1\nT:Polka\nM:2/4\nL:1/8\nK:D\nDF Ad|fd A2|\n").is_empty());
+    }
+
+    #[test]
+    fn test_other_length_fits() {
+        let source = "X:1\nT:Polka\nM:2/4\nK:D\nDF Ad|fd A2|FA dA|d2 z2|\n";
+        let diagnostics = check(source);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "unit note length defaults to 1/16, but the bars add up with 1/8"
+        );
+        let fixed = apply_fixes(source, &diagnostics, Applicability::MaybeIncorrect).source;
+        assert_eq!(
+            fixed,
+            "X:1\nT:Polka\nM:2/4\nL:1/8\nK:D\nDF Ad|fd A2|FA dA|d2 z2|\n"
+        );
+
+        assert_eq!(
+            check("X:1\nT:Reel\nM:4/4\nK:D\nD2F2A2d2 f2d2A2F2|G2B2d2g2 b2g2d2B2|\n")[0].message,
+            "unit note length defaults to 1/8, but the bars add up with 1/16"
+        );
+    }
+
+    #[test]
+    fn test_pickup_and_tuplets() {
+        // The pickup and the last bar are not counted
+        let source = "X:1\nT:Polka\nM:2/4\nK:D\nA|(3DFA d2|fd A2|FA dA|d3|\n";
+        assert_eq!(check(source).len(), 1);
+    }
+
+    #[test]
+    fn test_neither_fits() {
+        assert!(check("X:1\nT:Tune\nM:2/4\nK:D\nDFA|dfa|FAd|\n").is_empty());
+    }
+
+    #[test]
+    fn test_inline_length() {
+        assert!(check("X:1\nT:Polka\nM:2/4\nK:D\n[L:1/8]DF Ad|fd A2|FA dA|\n").is_empty());
+    }
+}
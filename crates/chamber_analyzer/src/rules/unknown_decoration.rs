@@ -2,9 +2,13 @@
 //!
 //! Checks that decoration names are valid ABC 2.1 standard decorations.
 
-use chamber_ast::{Decoration, MusicElement, Tune};
-use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use chamber_ast::visit::{VisitContext, Visitor};
+use chamber_ast::Decoration;
+use chamber_diagnostics::{Applicability, Diagnostic, DiagnosticCode, Fix, Severity, TextEdit};
+use chamber_text_size::{TextRange, TextSize};
+use serde::{Deserialize, Serialize};
 
+use crate::context::RuleContext;
 use crate::rule::{Category, Rule, RuleMeta};
 
 /// Standard ABC 2.1 decoration names.
@@ -49,7 +53,25 @@ const STANDARD_DECORATIONS: &[&str] = &[
 ];
 
 /// Rule that checks for unknown decoration names.
-pub struct UnknownDecoration;
+pub struct UnknownDecoration {
+    options: UnknownDecorationOptions,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Options for [`UnknownDecoration`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct UnknownDecorationOptions {
+    /// Custom decoration names to accept, in addition to the standard ones
+    /// (e.g., names defined with `U:` or understood by a particular program).
+    pub allowed: Vec<String>,
+}
+
+impl UnknownDecorationOptions {
+    fn allows(&self, name: &str) -> bool {
+        is_valid_decoration(name) || self.allowed.iter().any(|d| d.eq_ignore_ascii_case(name))
+    }
+}
 
 impl RuleMeta for UnknownDecoration {
     const NAME: &'static str = "unknownDecoration";
@@ -60,64 +82,51 @@ impl RuleMeta for UnknownDecoration {
 }
 
 impl Rule for UnknownDecoration {
-    fn run(tune: &Tune, diagnostics: &mut Vec<Diagnostic>) {
-        for element in &tune.body.elements {
-            check_element(element, diagnostics);
+    type Options = UnknownDecorationOptions;
+
+    fn new(cx: &RuleContext<Self::Options>) -> Self {
+        Self {
+            options: cx.options().clone(),
+            diagnostics: Vec::new(),
         }
     }
-}
 
-fn check_element(element: &MusicElement, diagnostics: &mut Vec<Diagnostic>) {
-    match element {
-        MusicElement::Note(note) => {
-            check_decorations(&note.decorations, diagnostics);
-        }
-        MusicElement::Rest(rest) => {
-            check_decorations(&rest.decorations, diagnostics);
-        }
-        MusicElement::Chord(chord) => {
-            check_decorations(&chord.decorations, diagnostics);
-            for note in &chord.notes {
-                check_decorations(&note.decorations, diagnostics);
-            }
-        }
-        MusicElement::Slur(slur) => {
-            for elem in &slur.elements {
-                check_element(elem, diagnostics);
-            }
-        }
-        MusicElement::Tuplet(tuplet) => {
-            for note in &tuplet.notes {
-                check_decorations(&note.decorations, diagnostics);
-            }
-        }
-        MusicElement::GraceNotes(grace) => {
-            for note in &grace.notes {
-                check_decorations(&note.decorations, diagnostics);
-            }
-        }
-        _ => {}
+    fn finish(self, _cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        self.diagnostics
     }
 }
 
-fn check_decorations(decorations: &[Decoration], diagnostics: &mut Vec<Diagnostic>) {
-    for decoration in decorations {
-        if !decoration.name.is_empty() && !is_valid_decoration(&decoration.name) {
-            let message = if let Some(suggestion) = suggest_decoration(&decoration.name) {
-                format!(
-                    "unknown decoration '{}', did you mean '{}'?",
-                    decoration.name, suggestion
-                )
-            } else {
-                format!("unknown decoration '{}'", decoration.name)
-            };
-
-            diagnostics.push(Diagnostic::error(
-                DiagnosticCode::UnknownDecoration,
-                decoration.range,
-                message,
+impl Visitor for UnknownDecoration {
+    fn visit_decoration(&mut self, decoration: &Decoration, _cx: &VisitContext) {
+        if decoration.name.is_empty() || self.options.allows(&decoration.name) {
+            return;
+        }
+
+        let suggestion = suggest_decoration(&decoration.name);
+        let message = if let Some(suggestion) = suggestion {
+            format!(
+                "unknown decoration '{}', did you mean '{}'?",
+                decoration.name, suggestion
+            )
+        } else {
+            format!("unknown decoration '{}'", decoration.name)
+        };
+
+        let mut diagnostic =
+            Diagnostic::error(DiagnosticCode::UnknownDecoration, decoration.range, message);
+        if let Some(suggestion) = suggestion {
+            // Replace the name between the delimiters (`!trillx!`)
+            let name = TextRange::new(
+                decoration.range.start() + TextSize::new(1),
+                decoration.range.end() - TextSize::new(1),
+            );
+            diagnostic = diagnostic.with_fix(Fix::edit(
+                format!("replace with '{}'", suggestion),
+                TextEdit::replace(name, suggestion),
+                Applicability::MaybeIncorrect,
             ));
         }
+        self.diagnostics.push(diagnostic);
     }
 }
 
@@ -148,7 +157,7 @@ fn suggest_decoration(name: &str) -> Option<&'static str> {
 }
 
 /// Calculates the Levenshtein distance between two strings.
-fn levenshtein_distance(a: &str, b: &str) -> usize {
+pub(crate) fn levenshtein_distance(a: &str, b: &str) -> usize {
     let a_chars: Vec<char> = a.chars().collect();
     let b_chars: Vec<char> = b.chars().collect();
     let m = a_chars.len();
@@ -211,4 +220,18 @@ mod tests {
         assert_eq!(levenshtein_distance("abc", "abd"), 1);
         assert_eq!(levenshtein_distance("trill", "trillx"), 1);
     }
+
+    #[test]
+    fn test_allowed_decorations() {
+        use crate::rule::RuleExt;
+        use chamber_parser::parse;
+
+        let tune = parse("X:1\nK:C\n!bend!C !Vibrato!D !trillx!E");
+        let options = UnknownDecorationOptions {
+            allowed: vec!["bend".to_string(), "vibrato".to_string()],
+        };
+        let diagnostics = UnknownDecoration::check_with(&tune, &options);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("trillx"));
+    }
 }
@@ -2,70 +2,79 @@
 //!
 //! Warns when notes are in extremely high or low octaves.
 
-use chamber_ast::{MusicElement, Note, Tune};
+use chamber_ast::visit::{VisitContext, Visitor};
+use chamber_ast::Note;
 use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use serde::{Deserialize, Serialize};
 
+use crate::context::RuleContext;
 use crate::rule::{Category, Rule, RuleMeta};
 
 /// Rule that warns about unusual octaves.
-pub struct UnusualOctave;
+pub struct UnusualOctave {
+    options: UnusualOctaveOptions,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Options for [`UnusualOctave`].
+///
+/// Octaves count from 0 for `C`-`B`; `c` is 1, `c'` is 2 and `C,` is -1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct UnusualOctaveOptions {
+    /// The lowest octave that is not reported.
+    pub lowest: i8,
+    /// The highest octave that is not reported.
+    pub highest: i8,
+}
+
+impl Default for UnusualOctaveOptions {
+    fn default() -> Self {
+        Self {
+            lowest: -2,
+            highest: 3,
+        }
+    }
+}
 
 impl RuleMeta for UnusualOctave {
     const NAME: &'static str = "unusualOctave";
     const CODE: DiagnosticCode = DiagnosticCode::UnusualOctave;
     const SEVERITY: Severity = Severity::Warning;
     const CATEGORY: Category = Category::Lint;
-    const DOCS: &'static str = "Warns when notes are in extremely high or low octaves (> 3 or < -2).";
+    const DOCS: &'static str =
+        "Warns when notes are in extremely high or low octaves (> 3 or < -2 by default).";
 }
 
 impl Rule for UnusualOctave {
-    fn run(tune: &Tune, diagnostics: &mut Vec<Diagnostic>) {
-        for element in &tune.body.elements {
-            check_element(element, diagnostics);
+    type Options = UnusualOctaveOptions;
+
+    fn new(cx: &RuleContext<Self::Options>) -> Self {
+        Self {
+            options: *cx.options(),
+            diagnostics: Vec::new(),
         }
     }
-}
 
-fn check_element(element: &MusicElement, diagnostics: &mut Vec<Diagnostic>) {
-    match element {
-        MusicElement::Note(note) => {
-            check_note(note, diagnostics);
-        }
-        MusicElement::Chord(chord) => {
-            for note in &chord.notes {
-                check_note(note, diagnostics);
-            }
-        }
-        MusicElement::Slur(slur) => {
-            for elem in &slur.elements {
-                check_element(elem, diagnostics);
-            }
-        }
-        MusicElement::Tuplet(tuplet) => {
-            for note in &tuplet.notes {
-                check_note(note, diagnostics);
-            }
-        }
-        MusicElement::GraceNotes(grace) => {
-            for note in &grace.notes {
-                check_note(note, diagnostics);
-            }
-        }
-        _ => {}
+    fn finish(self, _cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        self.diagnostics
     }
 }
 
-fn check_note(note: &Note, diagnostics: &mut Vec<Diagnostic>) {
-    if note.octave > 3 || note.octave < -2 {
-        diagnostics.push(Diagnostic::warning(
-            DiagnosticCode::UnusualOctave,
-            note.range,
-            format!(
-                "unusual octave {} (notes this {} are rare)",
-                note.octave,
-                if note.octave > 3 { "high" } else { "low" }
-            ),
-        ));
+impl Visitor for UnusualOctave {
+    fn visit_note(&mut self, note: &Note, _cx: &VisitContext) {
+        let options = &self.options;
+        if note.octave > options.highest || note.octave < options.lowest {
+            self.diagnostics.push(Diagnostic::warning(
+                DiagnosticCode::UnusualOctave,
+                note.range,
+                format!(
+                    "unusual octave {} (notes this {} are rare)",
+                    note.octave,
+                    if note.octave > options.highest { "high" } else { "low" }
+                ),
+            ));
+        }
     }
 }
 
@@ -99,4 +108,22 @@ mod tests {
         assert_eq!(diagnostics.len(), 1);
         assert!(diagnostics[0].message.contains("low"));
     }
+
+    #[test]
+    fn test_custom_range() {
+        // A bass line down to C,,, and a piccolo part up to c''''
+        let tune = parse("X:1\nK:C\nC,,, c''''");
+        let options = UnusualOctaveOptions {
+            lowest: -3,
+            highest: 5,
+        };
+        assert!(UnusualOctave::check_with(&tune, &options).is_empty());
+
+        let options = UnusualOctaveOptions {
+            lowest: 0,
+            highest: 3,
+        };
+        let diagnostics = UnusualOctave::check_with(&parse("X:1\nK:C\nC,"), &options);
+        assert_eq!(diagnostics.len(), 1);
+    }
 }
@@ -0,0 +1,519 @@
+//! W017: Voice consistency warning.
+//!
+//! In a tune with several voices, the voices play together bar by bar:
+//! each must have the same number of bars, in the same meters, with the
+//! same repeats and endings, and each bar must last as long in every voice.
+//! When one voice drifts (a bar missing, a `:|` left out), everything after
+//! it is misaligned, so the rule points at the first bar where a voice
+//! diverges from the first voice, and only that bar.
+//!
+//! A multi-measure rest (`Z4`) counts as that many full bars.
+
+use chamber_ast::visit::{VisitContext, Visitor};
+use chamber_ast::{BarLine, BarLineKind, Chord, Ending, HeaderField, InlineField, Note, Rest};
+use chamber_diagnostics::{Diagnostic, DiagnosticCode, Severity};
+use chamber_text_size::TextRange;
+
+use crate::context::RuleContext;
+use crate::model::{normalize_meter, Fraction, TimingModel, Voices};
+use crate::rule::{Category, NoOptions, Rule, RuleMeta};
+use crate::rules::bar_length::tuplet_time;
+
+/// Rule that warns about voices that don't line up bar by bar.
+pub struct VoiceConsistency {
+    /// The meter and unit note length each voice starts with.
+    timing: TimingModel,
+    meter: Option<String>,
+    /// State of each voice, in the order their music starts.
+    voices: Voices<VoiceState>,
+}
+
+#[derive(Debug)]
+struct VoiceState {
+    timing: TimingModel,
+    /// The `M:` field in effect, as written.
+    meter: Option<String>,
+    bars: Vec<Bar>,
+    current: Bar,
+}
+
+/// A bar of a voice, or the bars of a multi-measure rest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Bar {
+    range: Option<TextRange>,
+    length: Fraction,
+    /// The `M:` field in effect where the music of the bar starts.
+    meter: Option<String>,
+    /// Whether a repeat starts with the bar (`|:` or `::` before it).
+    repeat_start: bool,
+    /// Whether a repeat ends with the bar (`:|` or `::` after it).
+    repeat_end: bool,
+    /// The numbers of an ending that starts in the bar.
+    ending: Option<Vec<u32>>,
+    /// The bars of a multi-measure rest (`Z4`) in the bar, which it stands
+    /// for when it has no other music.
+    rest_bars: u32,
+    has_music: bool,
+}
+
+impl Default for Bar {
+    fn default() -> Self {
+        Self {
+            range: None,
+            length: Fraction::zero(),
+            meter: None,
+            repeat_start: false,
+            repeat_end: false,
+            ending: None,
+            rest_bars: 0,
+            has_music: false,
+        }
+    }
+}
+
+impl Bar {
+    /// Returns the number of bars it stands for.
+    fn count(&self) -> u32 {
+        self.rest_bars.max(1)
+    }
+}
+
+/// One bar of a [`Bar`], which is one of several for a multi-measure rest:
+/// the repeat signs go with its first and last bars.
+#[derive(Debug, Clone, Copy)]
+struct BarView<'a> {
+    bar: &'a Bar,
+    first: bool,
+    last: bool,
+}
+
+impl BarView<'_> {
+    fn repeat_signs(&self) -> (bool, bool, Option<&Vec<u32>>) {
+        let bar = self.bar;
+        (
+            bar.repeat_start && self.first,
+            bar.repeat_end && self.last,
+            bar.ending.as_ref().filter(|_| self.first),
+        )
+    }
+
+    /// Describes the repeat signs of the bar ("`|:` and ending 1").
+    fn repeats(&self) -> String {
+        let (repeat_start, repeat_end, ending) = self.repeat_signs();
+        let mut signs = Vec::new();
+        if repeat_start {
+            signs.push("`|:`".to_string());
+        }
+        if let Some(numbers) = ending {
+            let numbers: Vec<_> = numbers.iter().map(u32::to_string).collect();
+            signs.push(format!("ending {}", numbers.join(",")));
+        }
+        if repeat_end {
+            signs.push("`:|`".to_string());
+        }
+        if signs.is_empty() {
+            "no repeat signs".to_string()
+        } else {
+            signs.join(" and ")
+        }
+    }
+}
+
+/// Walks the bars of a voice one at a time, without expanding
+/// multi-measure rests.
+struct BarCursor<'a> {
+    bars: &'a [Bar],
+    index: usize,
+    /// How many bars of `bars[index]` are behind.
+    offset: u32,
+}
+
+impl<'a> BarCursor<'a> {
+    fn new(bars: &'a [Bar]) -> Self {
+        Self {
+            bars,
+            index: 0,
+            offset: 0,
+        }
+    }
+
+    fn current(&self) -> Option<BarView<'a>> {
+        let bar = self.bars.get(self.index)?;
+        Some(BarView {
+            bar,
+            first: self.offset == 0,
+            last: self.offset + 1 == bar.count(),
+        })
+    }
+
+    /// Returns the bars of a multi-measure rest after the current one.
+    fn remaining(&self) -> u32 {
+        self.bars
+            .get(self.index)
+            .map_or(0, |bar| bar.count() - self.offset - 1)
+    }
+
+    /// Moves `n` bars on, at most to the first bar after the current
+    /// [`Bar`].
+    fn advance(&mut self, n: u32) {
+        self.offset += n;
+        if self
+            .bars
+            .get(self.index)
+            .is_some_and(|bar| self.offset >= bar.count())
+        {
+            self.index += 1;
+            self.offset = 0;
+        }
+    }
+}
+
+/// Returns the number of bars in `bars`.
+fn bar_count(bars: &[Bar]) -> u64 {
+    bars.iter().map(|bar| u64::from(bar.count())).sum()
+}
+
+/// Returns the [`Bar`] bar `n` (from 0) is in.
+fn bar_at(bars: &[Bar], n: u64) -> Option<&Bar> {
+    let mut start = 0;
+    bars.iter().find(|bar| {
+        start += u64::from(bar.count());
+        n < start
+    })
+}
+
+impl RuleMeta for VoiceConsistency {
+    const NAME: &'static str = "voiceConsistency";
+    const CODE: DiagnosticCode = DiagnosticCode::VoiceConsistency;
+    const SEVERITY: Severity = Severity::Warning;
+    const CATEGORY: Category = Category::Lint;
+    const DOCS: &'static str =
+        "Warns where voices stop lining up: bar counts, meters, repeats or bar lengths.";
+}
+
+impl Rule for VoiceConsistency {
+    type Options = NoOptions;
+
+    fn new(cx: &RuleContext<Self::Options>) -> Self {
+        Self {
+            timing: *cx.timing(),
+            meter: cx.header().meter.map(normalize_meter),
+            voices: Voices::default(),
+        }
+    }
+
+    fn finish(mut self, _cx: &RuleContext<Self::Options>) -> Vec<Diagnostic> {
+        for voice in self.voices.values_mut() {
+            voice.end_bar(None);
+        }
+        let mut voices = self
+            .voices
+            .iter()
+            .filter(|(_, voice)| !voice.bars.is_empty());
+        let Some(first) = voices.next() else {
+            return Vec::new();
+        };
+        let mut diagnostics: Vec<_> = voices
+            .filter_map(|voice| compare_voices(first, voice))
+            .collect();
+        diagnostics.sort_by_key(|d| d.range.start());
+        diagnostics
+    }
+}
+
+fn voice_name(id: &str) -> String {
+    if id.is_empty() {
+        "the voice before any V: field".to_string()
+    } else {
+        format!("voice {}", id)
+    }
+}
+
+impl VoiceState {
+    fn add(&mut self, range: TextRange, duration: Fraction) {
+        let bar = &mut self.current;
+        if !bar.has_music {
+            bar.has_music = true;
+            bar.meter = self.meter.clone();
+        }
+        bar.range = Some(bar.range.map_or(range, |bar_range| bar_range.cover(range)));
+        bar.length = bar.length + duration;
+    }
+
+    /// Ends the current bar at `bar_line`, or at the end of the tune.
+    fn end_bar(&mut self, bar_line: Option<&BarLine>) {
+        let kind = bar_line.map(|bar_line| bar_line.kind);
+        let starts_repeat = matches!(
+            kind,
+            Some(BarLineKind::RepeatStart | BarLineKind::RepeatBoth)
+        );
+        // Bar lines before any music (e.g. a leading `|:`) do not end a bar
+        if !self.current.has_music {
+            self.current.repeat_start |= starts_repeat;
+            return;
+        }
+
+        let mut bar = std::mem::take(&mut self.current);
+        bar.repeat_end = matches!(kind, Some(BarLineKind::RepeatEnd | BarLineKind::RepeatBoth));
+        if let (Some(range), Some(bar_line)) = (bar.range, bar_line) {
+            bar.range = Some(range.cover(bar_line.range));
+        }
+        self.current.repeat_start = starts_repeat;
+
+        // Whole bars of rest, each as long as the meter
+        if bar.rest_bars > 0 && bar.length.is_zero() {
+            bar.length = self.timing.meter().unwrap_or(Fraction::zero());
+        } else {
+            bar.rest_bars = 0;
+        }
+        self.bars.push(bar);
+    }
+}
+
+/// Reports the first bar where `voice` diverges from `first`.
+fn compare_voices(first: (&str, &VoiceState), voice: (&str, &VoiceState)) -> Option<Diagnostic> {
+    let (first_id, first) = first;
+    let (id, voice) = voice;
+    let (first_name, name) = (voice_name(first_id), voice_name(id));
+
+    let (mut expected_bars, mut bars) = (BarCursor::new(&first.bars), BarCursor::new(&voice.bars));
+    let mut number: u64 = 0;
+    while let (Some(expected_view), Some(view)) = (expected_bars.current(), bars.current()) {
+        number += 1;
+        let (expected, bar) = (expected_view.bar, view.bar);
+        let message = if expected.meter != bar.meter {
+            let meter = |bar: &Bar| bar.meter.clone().unwrap_or_else(|| "4/4".to_string());
+            format!(
+                "{} is in {} at bar {}, {} is in {}",
+                name,
+                meter(bar),
+                number,
+                first_name,
+                meter(expected)
+            )
+        } else if expected_view.repeat_signs() != view.repeat_signs() {
+            format!(
+                "{} has {} at bar {}, {} has {}",
+                name,
+                view.repeats(),
+                number,
+                first_name,
+                expected_view.repeats()
+            )
+        } else if expected.length != bar.length {
+            format!(
+                "bar {} lasts {} in {}, but {} in {}",
+                number, bar.length, name, expected.length, first_name
+            )
+        } else {
+            // The bars in the middle of two multi-measure rests are alike
+            let inside = |view: BarView, cursor: &BarCursor| !view.first && cursor.remaining() > 0;
+            let step = if inside(expected_view, &expected_bars) && inside(view, &bars) {
+                expected_bars.remaining().min(bars.remaining())
+            } else {
+                1
+            };
+            number += u64::from(step - 1);
+            expected_bars.advance(step);
+            bars.advance(step);
+            continue;
+        };
+        return Some(
+            diagnostic(bar, message)
+                .with_label(expected.range?, format!("bar {} of {}", number, first_name)),
+        );
+    }
+
+    let (count, first_count) = (bar_count(&voice.bars), bar_count(&first.bars));
+    let message = format!(
+        "{} has {} bars, {} has {}",
+        name, count, first_name, first_count
+    );
+    if count < first_count {
+        let bar = voice.bars.last()?;
+        let next = bar_at(&first.bars, count)?.range?;
+        Some(diagnostic(bar, message).with_label(next, format!("{} goes on here", first_name)))
+    } else if count > first_count {
+        let last = first.bars.last()?.range?;
+        let bar = bar_at(&voice.bars, first_count)?;
+        Some(diagnostic(bar, message).with_label(last, format!("{} ends here", first_name)))
+    } else {
+        None
+    }
+}
+
+fn diagnostic(bar: &Bar, message: String) -> Diagnostic {
+    let range = bar.range.unwrap_or_default();
+    Diagnostic::warning(DiagnosticCode::VoiceConsistency, range, message)
+}
+
+impl VoiceConsistency {
+    fn voice(&mut self, cx: &VisitContext) -> &mut VoiceState {
+        let (timing, meter) = (self.timing, &self.meter);
+        self.voices
+            .get_or_insert_with(cx.voice.unwrap_or_default(), || VoiceState {
+                timing,
+                meter: meter.clone(),
+                bars: Vec::new(),
+                current: Bar::default(),
+            })
+    }
+
+    fn add(
+        &mut self,
+        range: TextRange,
+        duration: Option<&chamber_ast::Duration>,
+        cx: &VisitContext,
+    ) {
+        let voice = self.voice(cx);
+        let mut duration = voice.timing.duration(duration);
+        if let Some(ratio) = cx.tuplet {
+            duration = duration * Fraction::new(tuplet_time(ratio), ratio);
+        }
+        voice.add(range, duration);
+    }
+}
+
+impl Visitor for VoiceConsistency {
+    fn visit_header_field(&mut self, field: &HeaderField, _cx: &VisitContext) {
+        // Meter and unit note length set after a header `V:` field still
+        // apply to every voice
+        match field.kind.to_char() {
+            'M' => self.meter = Some(normalize_meter(&field.value)),
+            'L' | 'K' => {}
+            _ => return,
+        }
+        self.timing.apply_field(field.kind.to_char(), &field.value);
+    }
+
+    fn visit_note(&mut self, note: &Note, cx: &VisitContext) {
+        // Chord notes count with their chord; grace notes take no time
+        if cx.in_chord || cx.in_grace_notes {
+            return;
+        }
+        self.add(note.range, note.duration.as_ref(), cx);
+    }
+
+    fn visit_chord(&mut self, chord: &Chord, cx: &VisitContext) {
+        self.add(chord.range, chord.duration.as_ref(), cx);
+    }
+
+    fn visit_rest(&mut self, rest: &Rest, cx: &VisitContext) {
+        if !rest.multi_measure {
+            self.add(rest.range, rest.duration.as_ref(), cx);
+            return;
+        }
+        let bars = rest.duration.as_ref().map_or(1, |duration| {
+            duration.numerator / duration.denominator.max(1)
+        });
+        let voice = self.voice(cx);
+        voice.add(rest.range, Fraction::zero());
+        voice.current.rest_bars = voice.current.rest_bars.saturating_add(bars.max(1));
+    }
+
+    fn visit_bar_line(&mut self, bar_line: &BarLine, cx: &VisitContext) {
+        self.voice(cx).end_bar(Some(bar_line));
+    }
+
+    fn visit_ending(&mut self, ending: &Ending, cx: &VisitContext) {
+        self.voice(cx).current.ending = Some(ending.numbers.clone());
+    }
+
+    fn visit_inline_field(&mut self, field: &InlineField, cx: &VisitContext) {
+        if !matches!(field.label, 'M' | 'L') {
+            return;
+        }
+        let voice = self.voice(cx);
+        voice.timing.apply_field(field.label, &field.value);
+        if field.label == 'M' {
+            voice.meter = Some(normalize_meter(&field.value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::RuleExt;
+    use chamber_parser::parse;
+
+    fn messages(body: &str) -> Vec<String> {
+        let source = format!("X:1\nM:4/4\nL:1/8\nK:G\n{}", body);
+        VoiceConsistency::check(&parse(&source))
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_consistent_voices() {
+        assert!(messages("GABc dcBA|G8|").is_empty());
+        assert!(messages("[V:1]|:GABc dcBA:|G8|]\n[V:2]|:G,8:|G,8|]").is_empty());
+        // Pickups and multi-measure rests
+        assert!(messages("[V:1]GA|B8|c6|\n[V:2]z2|Z|G,6|").is_empty());
+        assert!(messages("[V:1]G8|A8|B8|\n[V:2]Z3|").is_empty());
+    }
+
+    #[test]
+    fn test_bar_count() {
+        let source = "X:1\nM:4/4\nL:1/8\nK:G\n[V:1]G8|A8|B8|\n[V:2]G,8|A,8|";
+        let diagnostics = VoiceConsistency::check(&parse(source));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "voice 2 has 2 bars, voice 1 has 3");
+        assert_eq!(diagnostics[0].labels[0].message, "voice 1 goes on here");
+
+        assert_eq!(
+            messages("[V:1]G8|A8|\n[V:2]G,8|A,8|B,8|"),
+            ["voice 2 has 3 bars, voice 1 has 2"]
+        );
+    }
+
+    #[test]
+    fn test_first_divergence() {
+        // A short bar throws off the rest; only the first is reported
+        assert_eq!(
+            messages("[V:1]G8|A8|B8|c8|\n[V:2]G,8|A,6|B,8|C8|D8|"),
+            ["bar 2 lasts 3/4 in voice 2, but 1/1 in voice 1"]
+        );
+        assert_eq!(
+            messages("[V:1]G8|[M:3/4]A6|\n[V:2]G,8|A,6|"),
+            ["voice 2 is in 4/4 at bar 2, voice 1 is in 3/4"]
+        );
+    }
+
+    #[test]
+    fn test_repeats() {
+        assert_eq!(
+            messages("[V:1]|:G8:|A8|\n[V:2]G,8|A,8|"),
+            ["voice 2 has no repeat signs at bar 1, voice 1 has `|:` and `:|`"]
+        );
+        assert_eq!(
+            messages("[V:1]|:G8|1A8:|2B8|]\n[V:2]|:G,8|1A,8:|B,8|]"),
+            ["voice 2 has no repeat signs at bar 3, voice 1 has ending 2"]
+        );
+        // The repeat signs of a multi-measure rest go with its first and
+        // last bars
+        assert!(messages("[V:1]|:Z3:|G8|\n[V:2]|:Z2|Z:|G,8|").is_empty());
+        assert_eq!(
+            messages("[V:1]|:Z3:|G8|\n[V:2]|:Z3|G,8:|"),
+            ["voice 2 has no repeat signs at bar 3, voice 1 has `:|`"]
+        );
+    }
+
+    #[test]
+    fn test_long_multi_measure_rests() {
+        // Counted, not expanded bar by bar
+        assert!(messages("[V:1]Z999999999|G8|\n[V:2]Z999999999|G,8|").is_empty());
+        assert!(
+            messages("[V:1]Z4000000000 Z4000000000|\n[V:2]Z4000000000 Z4000000000|").is_empty()
+        );
+        assert_eq!(
+            messages("[V:1]Z999999999|G8|\n[V:2]Z999999998|G,8|"),
+            ["voice 2 has 999999999 bars, voice 1 has 1000000000"]
+        );
+        assert_eq!(
+            messages("[V:1]Z999999999|G8|\n[V:2]Z999999998|G,8|A,6|"),
+            ["bar 1000000000 lasts 3/4 in voice 2, but 1/1 in voice 1"]
+        );
+    }
+}
@@ -0,0 +1,416 @@
+//! Suppression comments (`% chamber-ignore W003`).
+//!
+//! Comments can silence diagnostics by code (`W003`) or rule name
+//! (`barLength`); with no targets they silence everything in scope:
+//!
+//! - `% chamber-ignore ...` — the line it trails, or the next line with
+//!   content if it is on its own line
+//! - `% chamber-disable ...` / `% chamber-enable ...` — the lines between
+//! - `% chamber-ignore-tune ...` — the whole tune
+//!
+//! Text after `--` is a reason and is ignored. Suppressions that match no
+//! diagnostic are reported as W004 so that stale comments get cleaned up.
+
+use chamber_diagnostics::{Diagnostic, DiagnosticCode, LineIndex};
+use chamber_lexer::{Lexer, TokenKind};
+use chamber_text_size::{TextRange, TextSize};
+
+use crate::registry::RuleRegistry;
+
+/// Where a suppression applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    /// Diagnostics on one line (0-based); `None` if no line follows the
+    /// comment. A diagnostic spanning several lines is on each of them.
+    Line(Option<u32>),
+    /// Offsets in the range.
+    Range(TextRange),
+    /// The whole tune.
+    Tune,
+}
+
+/// One suppressed target from a comment.
+#[derive(Debug, Clone)]
+struct Suppression {
+    /// The comment.
+    range: TextRange,
+    scope: Scope,
+    /// The target as written, or `None` for all diagnostics.
+    target: Option<String>,
+    /// The code the target resolves to, or `None` for all diagnostics.
+    code: Option<DiagnosticCode>,
+}
+
+impl Suppression {
+    fn matches(&self, diagnostic: &Diagnostic, line_index: &LineIndex) -> bool {
+        if self.code.is_some_and(|code| code != diagnostic.code) {
+            return false;
+        }
+        let offset = diagnostic.range.start();
+        match self.scope {
+            Scope::Line(line) => line.is_some_and(|line| {
+                // A bar's range starts at the bar line before it, which
+                // may end the previous line
+                let first = line_index.line_col(offset).line;
+                let last = line_index.line_col(diagnostic.range.end()).line;
+                (first..=last).contains(&line)
+            }),
+            Scope::Range(range) => range.contains(offset),
+            Scope::Tune => true,
+        }
+    }
+}
+
+/// The suppression comments of one tune.
+#[derive(Debug, Clone)]
+pub(crate) struct Suppressions {
+    suppressions: Vec<Suppression>,
+    /// Malformed comments, reported as W004.
+    errors: Vec<Diagnostic>,
+    line_index: LineIndex,
+}
+
+impl Suppressions {
+    /// Collects the suppression comments in `source`, resolving rule names
+    /// through `registry`.
+    pub(crate) fn parse(source: &str, registry: &RuleRegistry) -> Self {
+        let line_index = LineIndex::new(source);
+        let mut parser = SuppressionParser {
+            source,
+            registry,
+            line_index: &line_index,
+            suppressions: Vec::new(),
+            errors: Vec::new(),
+            open: Vec::new(),
+        };
+
+        for token in Lexer::new(source).tokenize() {
+            if token.kind == TokenKind::Comment {
+                parser.comment(token.range);
+            }
+        }
+
+        let SuppressionParser {
+            suppressions,
+            errors,
+            ..
+        } = parser;
+        Self {
+            suppressions,
+            errors,
+            line_index,
+        }
+    }
+
+    /// Removes suppressed diagnostics and reports unused suppressions.
+    ///
+    /// Suppressions for codes in `inactive` (rules that are turned off) are
+    /// not reported as unused.
+    pub(crate) fn apply(
+        &self,
+        diagnostics: Vec<Diagnostic>,
+        inactive: &[DiagnosticCode],
+    ) -> Vec<Diagnostic> {
+        let mut used = vec![false; self.suppressions.len()];
+        let mut kept: Vec<Diagnostic> = diagnostics
+            .into_iter()
+            .filter(|diagnostic| {
+                let mut suppressed = false;
+                for (suppression, used) in self.suppressions.iter().zip(&mut used) {
+                    if suppression.matches(diagnostic, &self.line_index) {
+                        *used = true;
+                        suppressed = true;
+                    }
+                }
+                !suppressed
+            })
+            .collect();
+
+        for (suppression, used) in self.suppressions.iter().zip(used) {
+            if used
+                || suppression
+                    .code
+                    .is_some_and(|code| inactive.contains(&code))
+            {
+                continue;
+            }
+            let message = match &suppression.target {
+                Some(target) => format!("unused suppression for '{}'", target),
+                None => "unused suppression comment".to_string(),
+            };
+            kept.push(Diagnostic::warning(
+                DiagnosticCode::UnusedSuppression,
+                suppression.range,
+                message,
+            ));
+        }
+        kept.extend(self.errors.iter().cloned());
+
+        kept.sort_by_key(|d| d.range.start());
+        kept
+    }
+}
+
+struct SuppressionParser<'a> {
+    source: &'a str,
+    registry: &'a RuleRegistry,
+    line_index: &'a LineIndex,
+    suppressions: Vec<Suppression>,
+    errors: Vec<Diagnostic>,
+    /// Indices of `chamber-disable` suppressions not yet enabled again.
+    open: Vec<usize>,
+}
+
+impl SuppressionParser<'_> {
+    fn comment(&mut self, range: TextRange) {
+        let text = slice(self.source, range).trim_start_matches('%');
+        // Anything after "--" is a reason
+        let text = text.split("--").next().unwrap_or_default();
+        let mut words = text
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| !word.is_empty());
+
+        let Some(directive) = words.next().filter(|w| w.starts_with("chamber-")) else {
+            return;
+        };
+        let targets: Vec<&str> = words.collect();
+
+        let scope = match directive {
+            "chamber-ignore" => Scope::Line(self.target_line(range)),
+            "chamber-ignore-tune" => Scope::Tune,
+            "chamber-disable" => Scope::Range(TextRange::new(
+                range.end(),
+                TextSize::new(self.source.len() as u32),
+            )),
+            "chamber-enable" => {
+                self.enable(range, &targets);
+                return;
+            }
+            _ => {
+                self.errors.push(Diagnostic::warning(
+                    DiagnosticCode::UnusedSuppression,
+                    range,
+                    format!("unknown suppression directive '{}'", directive),
+                ));
+                return;
+            }
+        };
+
+        if targets.is_empty() {
+            self.push(range, scope, None, None);
+        }
+        for target in targets {
+            match self.resolve(target) {
+                Some(code) => self.push(range, scope, Some(target), Some(code)),
+                None => self.errors.push(Diagnostic::warning(
+                    DiagnosticCode::UnusedSuppression,
+                    range,
+                    format!("unknown rule or code '{}' in suppression", target),
+                )),
+            }
+        }
+    }
+
+    fn push(
+        &mut self,
+        range: TextRange,
+        scope: Scope,
+        target: Option<&str>,
+        code: Option<DiagnosticCode>,
+    ) {
+        if matches!(scope, Scope::Range(_)) {
+            self.open.push(self.suppressions.len());
+        }
+        self.suppressions.push(Suppression {
+            range,
+            scope,
+            target: target.map(str::to_string),
+            code,
+        });
+    }
+
+    /// Ends the open `chamber-disable` ranges for `targets` (all if empty).
+    fn enable(&mut self, range: TextRange, targets: &[&str]) {
+        let codes: Vec<_> = targets.iter().filter_map(|t| self.resolve(t)).collect();
+        let before = self.open.len();
+        let suppressions = &mut self.suppressions;
+        self.open.retain(|&index| {
+            let suppression = &mut suppressions[index];
+            let closes = targets.is_empty() || suppression.code.is_some_and(|c| codes.contains(&c));
+            if closes {
+                if let Scope::Range(ref mut disabled) = suppression.scope {
+                    *disabled = TextRange::new(disabled.start(), range.start());
+                }
+            }
+            !closes
+        });
+
+        if self.open.len() == before {
+            self.errors.push(Diagnostic::warning(
+                DiagnosticCode::UnusedSuppression,
+                range,
+                "'chamber-enable' without a matching 'chamber-disable'",
+            ));
+        }
+    }
+
+    /// Resolves a code ("W003") or rule name ("barLength").
+    fn resolve(&self, target: &str) -> Option<DiagnosticCode> {
+        self.registry.resolve_code(target)
+    }
+
+    /// The line a `chamber-ignore` comment applies to.
+    fn target_line(&self, range: TextRange) -> Option<u32> {
+        let line = self.line_index.line_col(range.start()).line;
+        let line_start = self.line_index.line_start(line)?;
+        let before = slice(self.source, TextRange::new(line_start, range.start()));
+        if !before.trim().is_empty() {
+            // Trailing comment: the line it is on
+            return Some(line);
+        }
+
+        // Own-line comment: the next line with content
+        (line + 1..self.line_index.line_count()).find(|&next| {
+            self.line_index
+                .line_text(next, self.source)
+                .map(str::trim)
+                .is_some_and(|text| !text.is_empty() && !text.starts_with('%'))
+        })
+    }
+}
+
+fn slice(source: &str, range: TextRange) -> &str {
+    &source[range.start().raw() as usize..range.end().raw() as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Analyzer, AnalyzerConfig, RuleLevel};
+    use chamber_diagnostics::{Diagnostic, DiagnosticCode};
+    use chamber_parser::parse;
+
+    fn check(source: &str) -> Vec<Diagnostic> {
+        check_with(&Analyzer::new(), source)
+    }
+
+    fn check_with(analyzer: &Analyzer, source: &str) -> Vec<Diagnostic> {
+        let diagnostics = analyzer.analyze(&parse(source)).diagnostics;
+        analyzer.apply_suppressions(source, diagnostics)
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<DiagnosticCode> {
+        diagnostics.iter().map(|d| d.code).collect()
+    }
+
+    #[test]
+    fn test_trailing_ignore() {
+        let diagnostics = check("X:1\nK:C\n!trillx!C % chamber-ignore M014\n!trillx!D");
+        assert_eq!(codes(&diagnostics), [DiagnosticCode::UnknownDecoration]);
+        assert_eq!(u32::from(diagnostics[0].range.start()), 40);
+    }
+
+    #[test]
+    fn test_ignore_next_line() {
+        let source =
+            "X:1\nK:C\n% chamber-ignore unknownDecoration\n\n%%comment\n!trillx!C\n!trillx!D";
+        let diagnostics = check(source);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].range.start() > 60.into());
+    }
+
+    #[test]
+    fn test_ignore_bar_at_line_start() {
+        let source = "X:1\nM:3/8\nL:1/8\nK:C\nGAB|cde|\n% chamber-ignore W003\nGA|cde|\n";
+        assert!(check(source).is_empty());
+
+        // The bar before is not covered
+        let source = "X:1\nM:3/8\nL:1/8\nK:C\nGAB|cd|\n% chamber-ignore W003\nGAB|cde|\n";
+        assert_eq!(
+            codes(&check(source)),
+            [
+                DiagnosticCode::BarLengthMismatch,
+                DiagnosticCode::UnusedSuppression
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disable_enable() {
+        let source = "X:1\nK:C\n% chamber-disable M014\n!a!C !b!D\n% chamber-enable M014\n!c!E";
+        let diagnostics = check(source);
+        assert_eq!(codes(&diagnostics), [DiagnosticCode::UnknownDecoration]);
+        assert!(diagnostics[0].message.contains("'c'"));
+    }
+
+    #[test]
+    fn test_disable_until_end() {
+        let diagnostics = check("X:1\nK:C\n% chamber-disable\n!a!C\n!b!D");
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_ignore_tune() {
+        let source =
+            "X:1\n% chamber-ignore-tune W001, unknownDecoration -- old notation\nK:C\n!a!C c''''";
+        assert!(check(source).is_empty());
+    }
+
+    #[test]
+    fn test_ignore_without_targets() {
+        let diagnostics = check("X:1\nK:C\n!a!C c'''' % chamber-ignore");
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_other_codes_not_suppressed() {
+        let diagnostics = check("X:1\nK:C\n!a!C c'''' % chamber-ignore W001");
+        assert_eq!(codes(&diagnostics), [DiagnosticCode::UnknownDecoration]);
+    }
+
+    #[test]
+    fn test_reason_is_ignored() {
+        let diagnostics = check("X:1\nK:C\n!a!C % chamber-ignore M014 -- W001 is fine here");
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_unused_suppression() {
+        let source = "X:1\nK:C\nC % chamber-ignore W001";
+        let diagnostics = check(source);
+        assert_eq!(codes(&diagnostics), [DiagnosticCode::UnusedSuppression]);
+        assert_eq!(diagnostics[0].message, "unused suppression for 'W001'");
+        assert_eq!(u32::from(diagnostics[0].range.start()), 10);
+    }
+
+    #[test]
+    fn test_unknown_target() {
+        let diagnostics = check("X:1\nK:C\n!a!C % chamber-ignore M014 noSuchRule");
+        assert_eq!(codes(&diagnostics), [DiagnosticCode::UnusedSuppression]);
+        assert!(diagnostics[0].message.contains("noSuchRule"));
+    }
+
+    #[test]
+    fn test_unknown_directive() {
+        let diagnostics = check("X:1\nK:C\nC % chamber-ignroe W001");
+        assert_eq!(codes(&diagnostics), [DiagnosticCode::UnusedSuppression]);
+        assert!(diagnostics[0].message.contains("chamber-ignroe"));
+    }
+
+    #[test]
+    fn test_enable_without_disable() {
+        let diagnostics = check("X:1\nK:C\n% chamber-enable\nC");
+        assert_eq!(codes(&diagnostics), [DiagnosticCode::UnusedSuppression]);
+    }
+
+    #[test]
+    fn test_ordinary_comments_ignored() {
+        assert!(check("X:1\n% just a comment\nK:C\nC % chamber music").is_empty());
+    }
+
+    #[test]
+    fn test_suppression_for_rule_turned_off_not_reported() {
+        let config = AnalyzerConfig::new().with_rule("unusualOctave", RuleLevel::Off);
+        let analyzer = Analyzer::new().with_config(config).unwrap();
+        assert!(check_with(&analyzer, "X:1\nK:C\nc'''' % chamber-ignore W001").is_empty());
+    }
+}
@@ -0,0 +1,112 @@
+//! Runs the examples of every diagnostic code's explanation.
+
+use chamber_analyzer::{Analyzer, AnalyzerConfig, RuleLevel};
+use chamber_diagnostics::{Diagnostic, DiagnosticCode};
+use chamber_parser::TuneStream;
+use chamber_text_size::TextSize;
+
+/// Checks `source` like `chamber check`: parser and per-tune diagnostics
+/// minus suppressions, then the collection rules.
+fn check(analyzer: &Analyzer, source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut collection = analyzer.collection();
+    for tune in TuneStream::new(source.as_bytes()) {
+        let mut tune = tune.unwrap();
+        let mut tune_diagnostics = std::mem::take(&mut tune.diagnostics);
+        tune_diagnostics.extend(
+            analyzer
+                .analyze_source(&tune.source, &tune.tune)
+                .diagnostics,
+        );
+        diagnostics.extend(analyzer.apply_suppressions(&tune.source, tune_diagnostics));
+        collection.add_tune(&tune.source, &tune.tune, TextSize::new(tune.offset as u32));
+    }
+    diagnostics.extend(collection.finish().diagnostics);
+    diagnostics
+}
+
+fn describe(diagnostics: &[Diagnostic]) -> String {
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|d| format!("{} {}", d.code, d.message))
+        .collect();
+    format!("[{}]", messages.join(", "))
+}
+
+/// The analyzer an example of `code` runs with.
+fn analyzer_for(code: DiagnosticCode) -> Analyzer {
+    let config = match code {
+        // The house rule the example breaks
+        DiagnosticCode::CustomRule => AnalyzerConfig::new().with_options(
+            "customRules",
+            serde_json::json!({ "rules": [{ "name": "requireOrigin", "requiredFields": ["O"] }] }),
+        ),
+        // Off by default
+        DiagnosticCode::BeamGrouping => {
+            AnalyzerConfig::new().with_rule("beamGrouping", RuleLevel::Warn)
+        }
+        // No fields are required by default
+        DiagnosticCode::MissingHeaderField => AnalyzerConfig::new().with_options(
+            "requiredFields",
+            serde_json::json!({ "fields": ["O", "Z|S"] }),
+        ),
+        _ => return Analyzer::new(),
+    };
+    Analyzer::new().with_config(config).unwrap()
+}
+
+#[test]
+fn test_bad_examples_are_reported() {
+    let mut failures = Vec::new();
+    for &code in DiagnosticCode::ALL {
+        // The lexer only produces note tokens for A-G, so M007 is a safeguard
+        // no source can reach
+        if code == DiagnosticCode::InvalidNoteName {
+            continue;
+        }
+        let diagnostics = check(&analyzer_for(code), code.explanation().bad);
+        if !diagnostics.iter().any(|d| d.code == code) {
+            failures.push(format!("{}: got {}", code, describe(&diagnostics)));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "bad examples not reported:\n{}",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn test_good_examples_are_clean() {
+    let mut failures = Vec::new();
+    for &code in DiagnosticCode::ALL {
+        let diagnostics = check(&analyzer_for(code), code.explanation().good);
+        if !diagnostics.is_empty() {
+            failures.push(format!("{}: got {}", code, describe(&diagnostics)));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "good examples with diagnostics:\n{}",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn test_explanations_are_complete() {
+    for &code in DiagnosticCode::ALL {
+        let explanation = code.explanation();
+        assert!(!explanation.rationale.is_empty(), "{}", code);
+        assert!(!explanation.fix.is_empty(), "{}", code);
+        assert!(!explanation.related.contains(&code), "{}", code);
+    }
+}
+
+#[test]
+fn test_every_code_can_be_explained() {
+    let analyzer = Analyzer::new();
+    for &code in DiagnosticCode::ALL {
+        let text = analyzer.explain(code);
+        assert!(text.starts_with(&format!("# {}: ", code)), "{}", text);
+    }
+}
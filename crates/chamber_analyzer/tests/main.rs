@@ -1,5 +1,5 @@
 use chamber_analyzer::analyze;
-use chamber_diagnostics::DiagnosticCode;
+use chamber_diagnostics::{apply_fixes, Applicability, DiagnosticCode};
 use chamber_parser::parse;
 
 #[test]
@@ -113,3 +113,24 @@ fn test_multiple_unknown_decorations() {
 
     assert_eq!(errors.len(), 3);
 }
+
+#[test]
+fn test_unknown_decoration_fix() {
+    let source = "X:1\nK:C\n!trillx!C +fermatta+D";
+    let diagnostics = analyze(&parse(source));
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].fixes[0].title, "replace with 'trill'");
+    assert_eq!(
+        diagnostics[0].fixes[0].applicability,
+        Applicability::MaybeIncorrect
+    );
+
+    let fixed = apply_fixes(source, &diagnostics, Applicability::MaybeIncorrect);
+    assert_eq!(fixed.source, "X:1\nK:C\n!trill!C +fermata+D");
+}
+
+#[test]
+fn test_unknown_decoration_without_suggestion_has_no_fix() {
+    let diagnostics = analyze(&parse("X:1\nK:C\n!xyzabc!C"));
+    assert!(diagnostics[0].fixes.is_empty());
+}
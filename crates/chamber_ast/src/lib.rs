@@ -1,9 +1,12 @@
 //! AST types for ABC notation.
 //!
 //! This crate defines the abstract syntax tree for ABC music notation.
-//! It contains only data types with no parsing or analysis logic.
+//! It contains only data types with no parsing or analysis logic, plus a
+//! [`visit::Visitor`] for walking a tune.
 
-use chamber_text_size::TextRange;
+pub mod visit;
+
+use chamber_text_size::{TextRange, TextSize};
 use serde::{Deserialize, Serialize};
 
 /// A complete ABC tune.
@@ -68,6 +71,20 @@ impl HeaderFieldKind {
             _ => Self::Other(c),
         }
     }
+
+    /// Returns the field label for this kind.
+    pub fn to_char(self) -> char {
+        match self {
+            Self::ReferenceNumber => 'X',
+            Self::Title => 'T',
+            Self::Composer => 'C',
+            Self::Meter => 'M',
+            Self::UnitNoteLength => 'L',
+            Self::Tempo => 'Q',
+            Self::Key => 'K',
+            Self::Other(c) => c,
+        }
+    }
 }
 
 /// The music body of a tune.
@@ -84,6 +101,7 @@ pub enum MusicElement {
     Rest(Rest),
     Chord(Chord),
     BarLine(BarLine),
+    Ending(Ending),
     Tuplet(Tuplet),
     Slur(Slur),
     GraceNotes(GraceNotes),
@@ -91,6 +109,7 @@ pub enum MusicElement {
     Tie(Tie),
     InlineField(InlineField),
     Annotation(Annotation),
+    Lyrics(Lyrics),
 }
 
 /// A single note.
@@ -141,6 +160,19 @@ impl Pitch {
             _ => None,
         }
     }
+
+    /// Returns the uppercase note letter for this pitch.
+    pub fn to_char(self) -> char {
+        match self {
+            Self::C => 'C',
+            Self::D => 'D',
+            Self::E => 'E',
+            Self::F => 'F',
+            Self::G => 'G',
+            Self::A => 'A',
+            Self::B => 'B',
+        }
+    }
 }
 
 /// Accidental.
@@ -214,6 +246,8 @@ pub enum BarLineKind {
     Double,
     RepeatStart,
     RepeatEnd,
+    /// End of one repeat and start of the next (`::` or `:|:`)
+    RepeatBoth,
     ThinThick,
     ThickThin,
 }
@@ -225,6 +259,30 @@ pub struct BarLine {
     pub range: TextRange,
 }
 
+/// The start of an ending, or volta (`[1`, `:|2`, `[1,3`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ending {
+    /// The times through the repeat that take this ending (`[1-3` is 1, 2, 3)
+    pub numbers: Vec<u32>,
+    pub range: TextRange,
+}
+
+impl Ending {
+    /// Parses the numbers of an ending as written, with or without the `[`
+    /// (`[1`, `2`, `1,3`, `1-3`).
+    pub fn parse_numbers(text: &str) -> Vec<u32> {
+        let mut numbers = Vec::new();
+        for part in text.trim_start_matches('[').split(',') {
+            let (first, last) = part.split_once('-').unwrap_or((part, part));
+            if let (Ok(first), Ok(last)) = (first.parse::<u32>(), last.parse::<u32>()) {
+                // A typo like [1-9999 should not make a huge list
+                numbers.extend((first..=last).take(64));
+            }
+        }
+        numbers
+    }
+}
+
 /// A tuplet (e.g., triplet).
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Tuplet {
@@ -282,3 +340,109 @@ pub struct Annotation {
     pub text: String,
     pub range: TextRange,
 }
+
+/// A line of lyrics (`w:`), sung to the notes before it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lyrics {
+    /// The text after `w:`, as written
+    pub text: String,
+    pub syllables: Vec<Syllable>,
+    pub range: TextRange,
+}
+
+/// An item of a lyrics line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Syllable {
+    pub kind: SyllableKind,
+    /// The syllable as sung, with `~` as a space and `\-` as a hyphen; empty
+    /// for the other kinds
+    pub text: String,
+    pub range: TextRange,
+}
+
+/// Kind of an item of a lyrics line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SyllableKind {
+    /// A syllable, sung on the next note
+    Syllable,
+    /// `_`: the previous syllable is held over the next note
+    Hold,
+    /// `*`: the next note has no syllable
+    Skip,
+    /// A `-` after a space or another `-`: the word goes on over the next
+    /// note
+    Hyphen,
+    /// `|`: the next syllable goes on the first note of the next bar
+    BarLine,
+}
+
+impl SyllableKind {
+    /// Whether an item of this kind is aligned with a note.
+    pub fn takes_note(self) -> bool {
+        self != SyllableKind::BarLine
+    }
+}
+
+impl Syllable {
+    /// Splits the text of a lyrics line into syllables, with ranges relative
+    /// to the start of `text` (`Hap-py birth-day|to~you`).
+    pub fn parse_line(text: &str) -> Vec<Syllable> {
+        let mut syllables = Vec::new();
+        let mut current: Option<(usize, String)> = None;
+        // A `-` right after a syllable ends it; any other is a hyphen of its
+        // own
+        let mut hyphen_stands_alone = true;
+        let mut chars = text.char_indices().peekable();
+
+        while let Some((i, c)) = chars.next() {
+            let kind = match c {
+                '_' => Some(SyllableKind::Hold),
+                '*' => Some(SyllableKind::Skip),
+                '|' => Some(SyllableKind::BarLine),
+                '-' if hyphen_stands_alone => Some(SyllableKind::Hyphen),
+                '-' | ' ' | '\t' => None,
+                _ => {
+                    hyphen_stands_alone = false;
+                    let (_, word) = current.get_or_insert_with(|| (i, String::new()));
+                    match c {
+                        '~' => word.push(' '),
+                        '\\' => match chars.next() {
+                            Some((_, escaped)) => word.push(escaped),
+                            None => word.push('\\'),
+                        },
+                        _ => word.push(c),
+                    }
+                    continue;
+                }
+            };
+
+            if let Some((start, word)) = current.take() {
+                syllables.push(Syllable {
+                    kind: SyllableKind::Syllable,
+                    text: word,
+                    range: range(start, i),
+                });
+            }
+            hyphen_stands_alone = true;
+            if let Some(kind) = kind {
+                syllables.push(Syllable {
+                    kind,
+                    text: String::new(),
+                    range: range(i, i + c.len_utf8()),
+                });
+            }
+        }
+        if let Some((start, word)) = current {
+            syllables.push(Syllable {
+                kind: SyllableKind::Syllable,
+                text: word,
+                range: range(start, text.len()),
+            });
+        }
+        syllables
+    }
+}
+
+fn range(start: usize, end: usize) -> TextRange {
+    TextRange::new(TextSize::new(start as u32), TextSize::new(end as u32))
+}
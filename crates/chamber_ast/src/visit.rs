@@ -0,0 +1,370 @@
+//! Traversal of a tune with a [`Visitor`].
+//!
+//! [`walk_tune`] visits every element of a tune in source order, including
+//! the notes inside chords, tuplets, slurs and grace notes, and keeps a
+//! [`VisitContext`] with the key, meter and bar number in effect.
+//!
+//! A visitor implements only the methods it needs. Several visitors can share
+//! one traversal: slices and boxes of visitors are visitors themselves.
+//!
+//! ```
+//! use chamber_ast::visit::{walk_tune, VisitContext, Visitor};
+//! use chamber_ast::{Note, Tune};
+//!
+//! #[derive(Default)]
+//! struct NoteCounter {
+//!     notes: usize,
+//! }
+//!
+//! impl Visitor for NoteCounter {
+//!     fn visit_note(&mut self, _note: &Note, _cx: &VisitContext) {
+//!         self.notes += 1;
+//!     }
+//! }
+//!
+//! fn count_notes(tune: &Tune) -> usize {
+//!     let mut counter = NoteCounter::default();
+//!     walk_tune(&mut counter, tune);
+//!     counter.notes
+//! }
+//! ```
+
+use crate::{
+    Annotation, BarLine, BrokenRhythm, Chord, Decoration, Ending, GraceNotes, HeaderField,
+    InlineField, Lyrics, MusicElement, Note, Rest, Slur, Tie, Tune, Tuplet,
+};
+
+/// Where a visited element is in the tune.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VisitContext<'a> {
+    /// Value of the `K:` field in effect, if any.
+    pub key: Option<&'a str>,
+    /// Value of the `M:` field in effect, if any.
+    pub meter: Option<&'a str>,
+    /// Value of the `L:` field in effect, if any.
+    pub unit_note_length: Option<&'a str>,
+    /// Id of the current voice (`V:`), if any.
+    pub voice: Option<&'a str>,
+    /// Number of the current bar, from 1.
+    pub bar: u32,
+    /// Whether the element is a note of a chord.
+    pub in_chord: bool,
+    /// Whether the element is a grace note.
+    pub in_grace_notes: bool,
+    /// Ratio of the enclosing tuplet (e.g. 3 for a triplet), if any.
+    pub tuplet: Option<u32>,
+    /// Whether the current bar has any notes, rests or chords yet.
+    bar_has_music: bool,
+}
+
+impl<'a> VisitContext<'a> {
+    fn new() -> Self {
+        Self {
+            key: None,
+            meter: None,
+            unit_note_length: None,
+            voice: None,
+            bar: 1,
+            in_chord: false,
+            in_grace_notes: false,
+            tuplet: None,
+            bar_has_music: false,
+        }
+    }
+
+    /// Applies a header or inline field.
+    fn apply_field(&mut self, label: char, value: &'a str) {
+        let value = value.trim();
+        match label {
+            'K' => self.key = Some(value),
+            'M' => self.meter = Some(value),
+            'L' => self.unit_note_length = Some(value),
+            'V' => self.voice = value.split_whitespace().next(),
+            _ => {}
+        }
+    }
+}
+
+/// Callbacks for the elements of a tune, called by [`walk_tune`].
+///
+/// Containers are visited before their contents: `visit_chord` is called
+/// before `visit_note` for each of its notes, and decorations are visited
+/// after the element they belong to.
+#[allow(unused_variables)]
+pub trait Visitor {
+    /// Called for each header field, after it was applied to `cx`.
+    fn visit_header_field(&mut self, field: &HeaderField, cx: &VisitContext) {}
+
+    /// Called for every note, including those in chords, tuplets, slurs and
+    /// grace notes.
+    fn visit_note(&mut self, note: &Note, cx: &VisitContext) {}
+
+    /// Called for each rest.
+    fn visit_rest(&mut self, rest: &Rest, cx: &VisitContext) {}
+
+    /// Called for each chord, before its notes.
+    fn visit_chord(&mut self, chord: &Chord, cx: &VisitContext) {}
+
+    /// Called for each bar line; `cx.bar` is the bar it ends.
+    fn visit_bar_line(&mut self, bar_line: &BarLine, cx: &VisitContext) {}
+
+    /// Called for each tuplet, before its notes.
+    fn visit_tuplet(&mut self, tuplet: &Tuplet, cx: &VisitContext) {}
+
+    /// Called for each slur, before its elements.
+    fn visit_slur(&mut self, slur: &Slur, cx: &VisitContext) {}
+
+    /// Called for each group of grace notes, before its notes.
+    fn visit_grace_notes(&mut self, grace_notes: &GraceNotes, cx: &VisitContext) {}
+
+    /// Called for each broken rhythm marker (`>` or `<`).
+    fn visit_broken_rhythm(&mut self, broken_rhythm: &BrokenRhythm, cx: &VisitContext) {}
+
+    /// Called for each tie.
+    fn visit_tie(&mut self, tie: &Tie, cx: &VisitContext) {}
+
+    /// Called for the start of each ending (`[1`).
+    fn visit_ending(&mut self, ending: &Ending, cx: &VisitContext) {}
+
+    /// Called for each inline field, after it was applied to `cx`.
+    fn visit_inline_field(&mut self, field: &InlineField, cx: &VisitContext) {}
+
+    /// Called for each annotation.
+    fn visit_annotation(&mut self, annotation: &Annotation, cx: &VisitContext) {}
+
+    /// Called for each lyrics line (`w:`), after the notes it is sung to.
+    fn visit_lyrics(&mut self, lyrics: &Lyrics, cx: &VisitContext) {}
+
+    /// Called for each decoration, after the element it belongs to.
+    fn visit_decoration(&mut self, decoration: &Decoration, cx: &VisitContext) {}
+}
+
+/// Visits every element of `tune` in source order.
+pub fn walk_tune<V: Visitor + ?Sized>(visitor: &mut V, tune: &Tune) {
+    let mut cx = VisitContext::new();
+    for field in &tune.header.fields {
+        cx.apply_field(field.kind.to_char(), &field.value);
+        visitor.visit_header_field(field, &cx);
+    }
+    for element in &tune.body.elements {
+        walk_element(visitor, element, &mut cx);
+    }
+}
+
+fn walk_element<'a, V: Visitor + ?Sized>(
+    visitor: &mut V,
+    element: &'a MusicElement,
+    cx: &mut VisitContext<'a>,
+) {
+    match element {
+        MusicElement::Note(note) => {
+            cx.bar_has_music = true;
+            walk_note(visitor, note, cx);
+        }
+        MusicElement::Rest(rest) => {
+            cx.bar_has_music = true;
+            visitor.visit_rest(rest, cx);
+            walk_decorations(visitor, &rest.decorations, cx);
+        }
+        MusicElement::Chord(chord) => {
+            cx.bar_has_music = true;
+            visitor.visit_chord(chord, cx);
+            walk_decorations(visitor, &chord.decorations, cx);
+            let outer = std::mem::replace(&mut cx.in_chord, true);
+            for note in &chord.notes {
+                walk_note(visitor, note, cx);
+            }
+            cx.in_chord = outer;
+        }
+        MusicElement::BarLine(bar_line) => {
+            visitor.visit_bar_line(bar_line, cx);
+            // Bar lines before any music (e.g. a leading `|:`) do not end a bar
+            if cx.bar_has_music {
+                cx.bar += 1;
+                cx.bar_has_music = false;
+            }
+        }
+        MusicElement::Tuplet(tuplet) => {
+            cx.bar_has_music = true;
+            visitor.visit_tuplet(tuplet, cx);
+            let outer = cx.tuplet.replace(tuplet.ratio);
+            for note in &tuplet.notes {
+                walk_note(visitor, note, cx);
+            }
+            cx.tuplet = outer;
+        }
+        MusicElement::Slur(slur) => {
+            visitor.visit_slur(slur, cx);
+            for element in &slur.elements {
+                walk_element(visitor, element, cx);
+            }
+        }
+        MusicElement::GraceNotes(grace_notes) => {
+            visitor.visit_grace_notes(grace_notes, cx);
+            let outer = std::mem::replace(&mut cx.in_grace_notes, true);
+            for note in &grace_notes.notes {
+                walk_note(visitor, note, cx);
+            }
+            cx.in_grace_notes = outer;
+        }
+        MusicElement::BrokenRhythm(broken_rhythm) => {
+            visitor.visit_broken_rhythm(broken_rhythm, cx);
+        }
+        MusicElement::Tie(tie) => visitor.visit_tie(tie, cx),
+        MusicElement::Ending(ending) => visitor.visit_ending(ending, cx),
+        MusicElement::InlineField(field) => {
+            cx.apply_field(field.label, &field.value);
+            visitor.visit_inline_field(field, cx);
+        }
+        MusicElement::Annotation(annotation) => visitor.visit_annotation(annotation, cx),
+        MusicElement::Lyrics(lyrics) => visitor.visit_lyrics(lyrics, cx),
+    }
+}
+
+fn walk_note<V: Visitor + ?Sized>(visitor: &mut V, note: &Note, cx: &VisitContext) {
+    visitor.visit_note(note, cx);
+    walk_decorations(visitor, &note.decorations, cx);
+}
+
+fn walk_decorations<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    decorations: &[Decoration],
+    cx: &VisitContext,
+) {
+    for decoration in decorations {
+        visitor.visit_decoration(decoration, cx);
+    }
+}
+
+/// Forwards each callback to the boxed visitor.
+impl<V: Visitor + ?Sized> Visitor for Box<V> {
+    fn visit_header_field(&mut self, field: &HeaderField, cx: &VisitContext) {
+        (**self).visit_header_field(field, cx)
+    }
+
+    fn visit_note(&mut self, note: &Note, cx: &VisitContext) {
+        (**self).visit_note(note, cx)
+    }
+
+    fn visit_rest(&mut self, rest: &Rest, cx: &VisitContext) {
+        (**self).visit_rest(rest, cx)
+    }
+
+    fn visit_chord(&mut self, chord: &Chord, cx: &VisitContext) {
+        (**self).visit_chord(chord, cx)
+    }
+
+    fn visit_bar_line(&mut self, bar_line: &BarLine, cx: &VisitContext) {
+        (**self).visit_bar_line(bar_line, cx)
+    }
+
+    fn visit_tuplet(&mut self, tuplet: &Tuplet, cx: &VisitContext) {
+        (**self).visit_tuplet(tuplet, cx)
+    }
+
+    fn visit_slur(&mut self, slur: &Slur, cx: &VisitContext) {
+        (**self).visit_slur(slur, cx)
+    }
+
+    fn visit_grace_notes(&mut self, grace_notes: &GraceNotes, cx: &VisitContext) {
+        (**self).visit_grace_notes(grace_notes, cx)
+    }
+
+    fn visit_broken_rhythm(&mut self, broken_rhythm: &BrokenRhythm, cx: &VisitContext) {
+        (**self).visit_broken_rhythm(broken_rhythm, cx)
+    }
+
+    fn visit_tie(&mut self, tie: &Tie, cx: &VisitContext) {
+        (**self).visit_tie(tie, cx)
+    }
+
+    fn visit_ending(&mut self, ending: &Ending, cx: &VisitContext) {
+        (**self).visit_ending(ending, cx)
+    }
+
+    fn visit_inline_field(&mut self, field: &InlineField, cx: &VisitContext) {
+        (**self).visit_inline_field(field, cx)
+    }
+
+    fn visit_annotation(&mut self, annotation: &Annotation, cx: &VisitContext) {
+        (**self).visit_annotation(annotation, cx)
+    }
+
+    fn visit_lyrics(&mut self, lyrics: &Lyrics, cx: &VisitContext) {
+        (**self).visit_lyrics(lyrics, cx)
+    }
+
+    fn visit_decoration(&mut self, decoration: &Decoration, cx: &VisitContext) {
+        (**self).visit_decoration(decoration, cx)
+    }
+}
+
+/// Forwards each callback to every visitor, in order, so that several
+/// visitors share one traversal.
+impl<V: Visitor> Visitor for [V] {
+    fn visit_header_field(&mut self, field: &HeaderField, cx: &VisitContext) {
+        self.iter_mut()
+            .for_each(|v| v.visit_header_field(field, cx))
+    }
+
+    fn visit_note(&mut self, note: &Note, cx: &VisitContext) {
+        self.iter_mut().for_each(|v| v.visit_note(note, cx))
+    }
+
+    fn visit_rest(&mut self, rest: &Rest, cx: &VisitContext) {
+        self.iter_mut().for_each(|v| v.visit_rest(rest, cx))
+    }
+
+    fn visit_chord(&mut self, chord: &Chord, cx: &VisitContext) {
+        self.iter_mut().for_each(|v| v.visit_chord(chord, cx))
+    }
+
+    fn visit_bar_line(&mut self, bar_line: &BarLine, cx: &VisitContext) {
+        self.iter_mut().for_each(|v| v.visit_bar_line(bar_line, cx))
+    }
+
+    fn visit_tuplet(&mut self, tuplet: &Tuplet, cx: &VisitContext) {
+        self.iter_mut().for_each(|v| v.visit_tuplet(tuplet, cx))
+    }
+
+    fn visit_slur(&mut self, slur: &Slur, cx: &VisitContext) {
+        self.iter_mut().for_each(|v| v.visit_slur(slur, cx))
+    }
+
+    fn visit_grace_notes(&mut self, grace_notes: &GraceNotes, cx: &VisitContext) {
+        self.iter_mut()
+            .for_each(|v| v.visit_grace_notes(grace_notes, cx))
+    }
+
+    fn visit_broken_rhythm(&mut self, broken_rhythm: &BrokenRhythm, cx: &VisitContext) {
+        self.iter_mut()
+            .for_each(|v| v.visit_broken_rhythm(broken_rhythm, cx))
+    }
+
+    fn visit_tie(&mut self, tie: &Tie, cx: &VisitContext) {
+        self.iter_mut().for_each(|v| v.visit_tie(tie, cx))
+    }
+
+    fn visit_ending(&mut self, ending: &Ending, cx: &VisitContext) {
+        self.iter_mut().for_each(|v| v.visit_ending(ending, cx))
+    }
+
+    fn visit_inline_field(&mut self, field: &InlineField, cx: &VisitContext) {
+        self.iter_mut()
+            .for_each(|v| v.visit_inline_field(field, cx))
+    }
+
+    fn visit_annotation(&mut self, annotation: &Annotation, cx: &VisitContext) {
+        self.iter_mut()
+            .for_each(|v| v.visit_annotation(annotation, cx))
+    }
+
+    fn visit_lyrics(&mut self, lyrics: &Lyrics, cx: &VisitContext) {
+        self.iter_mut().for_each(|v| v.visit_lyrics(lyrics, cx))
+    }
+
+    fn visit_decoration(&mut self, decoration: &Decoration, cx: &VisitContext) {
+        self.iter_mut()
+            .for_each(|v| v.visit_decoration(decoration, cx))
+    }
+}
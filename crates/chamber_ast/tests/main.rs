@@ -0,0 +1,182 @@
+use chamber_ast::visit::{walk_tune, VisitContext, Visitor};
+use chamber_ast::*;
+use chamber_parser::parse;
+
+// ============================================
+// Helpers
+// ============================================
+
+/// Records each visit as a line of text.
+#[derive(Default)]
+struct Recorder {
+    events: Vec<String>,
+}
+
+impl Visitor for Recorder {
+    fn visit_note(&mut self, note: &Note, cx: &VisitContext) {
+        let mut event = format!("note {:?} bar {}", note.pitch, cx.bar);
+        if cx.in_chord {
+            event.push_str(" chord");
+        }
+        if cx.in_grace_notes {
+            event.push_str(" grace");
+        }
+        if let Some(ratio) = cx.tuplet {
+            event.push_str(&format!(" tuplet {}", ratio));
+        }
+        self.events.push(event);
+    }
+
+    fn visit_chord(&mut self, _chord: &Chord, cx: &VisitContext) {
+        self.events.push(format!("chord bar {}", cx.bar));
+    }
+
+    fn visit_bar_line(&mut self, _bar_line: &BarLine, cx: &VisitContext) {
+        self.events.push(format!("bar line {}", cx.bar));
+    }
+
+    fn visit_decoration(&mut self, decoration: &Decoration, _cx: &VisitContext) {
+        self.events.push(format!("decoration {}", decoration.name));
+    }
+}
+
+fn record(source: &str) -> Vec<String> {
+    let mut recorder = Recorder::default();
+    walk_tune(&mut recorder, &parse(source));
+    recorder.events
+}
+
+/// Records the context values seen at each note.
+#[derive(Default)]
+struct ContextRecorder {
+    contexts: Vec<(Option<String>, Option<String>, Option<String>)>,
+}
+
+impl Visitor for ContextRecorder {
+    fn visit_note(&mut self, _note: &Note, cx: &VisitContext) {
+        self.contexts.push((
+            cx.key.map(String::from),
+            cx.meter.map(String::from),
+            cx.voice.map(String::from),
+        ));
+    }
+}
+
+// ============================================
+// Traversal
+// ============================================
+
+#[test]
+fn test_visits_nested_notes() {
+    let events = record("X:1\nK:C\n[CE] (3DEF {G}A (Bc)");
+    assert_eq!(
+        events,
+        [
+            "chord bar 1",
+            "note C bar 1 chord",
+            "note E bar 1 chord",
+            "note D bar 1 tuplet 3",
+            "note E bar 1 tuplet 3",
+            "note F bar 1 tuplet 3",
+            "note G bar 1 grace",
+            "note A bar 1",
+            "note B bar 1",
+            "note C bar 1",
+        ]
+    );
+}
+
+#[test]
+fn test_decorations_follow_their_element() {
+    let events = record("X:1\nK:C\n!trill!C !f![CE]");
+    assert_eq!(
+        events,
+        [
+            "note C bar 1",
+            "decoration trill",
+            "chord bar 1",
+            "decoration f",
+            "note C bar 1 chord",
+            "note E bar 1 chord",
+        ]
+    );
+}
+
+#[test]
+fn test_bar_numbers() {
+    // A leading repeat sign does not end a bar
+    let events = record("X:1\nK:C\n|:C|D:|");
+    assert_eq!(
+        events,
+        [
+            "bar line 1",
+            "note C bar 1",
+            "bar line 1",
+            "note D bar 2",
+            "bar line 2",
+        ]
+    );
+}
+
+// ============================================
+// Context
+// ============================================
+
+#[test]
+fn test_context_from_header_and_inline_fields() {
+    let tune = parse("X:1\nM:4/4\nV:1 clef=treble\nK:G\nC|[M:3/4][K:D]D|[V:2]E");
+    let mut recorder = ContextRecorder::default();
+    walk_tune(&mut recorder, &tune);
+
+    let context = |key: &str, meter: &str, voice: &str| {
+        (
+            Some(key.to_string()),
+            Some(meter.to_string()),
+            Some(voice.to_string()),
+        )
+    };
+    assert_eq!(
+        recorder.contexts,
+        [
+            context("G", "4/4", "1"),
+            context("D", "3/4", "1"),
+            context("D", "3/4", "2"),
+        ]
+    );
+}
+
+#[test]
+fn test_slice_of_visitors_shares_one_walk() {
+    let tune = parse("X:1\nK:C\nCD|E");
+    let mut recorders = [Recorder::default(), Recorder::default()];
+    walk_tune(&mut recorders[..], &tune);
+
+    assert_eq!(recorders[0].events.len(), 4);
+    assert_eq!(recorders[0].events, recorders[1].events);
+}
+
+#[test]
+fn test_lyrics_follow_their_notes() {
+    #[derive(Default)]
+    struct LyricsRecorder {
+        events: Vec<String>,
+    }
+
+    impl Visitor for LyricsRecorder {
+        fn visit_note(&mut self, note: &Note, _cx: &VisitContext) {
+            self.events.push(format!("note {:?}", note.pitch));
+        }
+
+        fn visit_lyrics(&mut self, lyrics: &Lyrics, cx: &VisitContext) {
+            self.events
+                .push(format!("lyrics {} bar {}", lyrics.syllables.len(), cx.bar));
+        }
+    }
+
+    let mut recorder = LyricsRecorder::default();
+    walk_tune(&mut recorder, &parse("X:1\nK:C\nCD|\nw:la-la\nE"));
+    assert_eq!(
+        recorder.events,
+        ["note C", "note D", "lyrics 2 bar 2", "note E"]
+    );
+}
@@ -1,10 +1,19 @@
+use std::collections::BTreeMap;
 use std::env;
+use std::fmt::{self, Write};
 use std::fs;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
+use std::sync::Mutex;
 
-use chamber_analyzer::Analyzer;
-use chamber_diagnostics::{Diagnostic, LineIndex, Severity};
-use chamber_parser::parse_with_diagnostics;
+use chamber_analyzer::{AnalysisCache, Analyzer, AnalyzerConfig, RuleLevel};
+use chamber_diagnostics::{
+    fix_until_stable, Applicability, Diagnostic, LineCol, LineIndex, Severity,
+};
+use chamber_parser::{parse_with_diagnostics, MusicElement, Tune, TuneSources};
+use chamber_text_size::TextSize;
+use rayon::prelude::*;
 
 fn main() -> ExitCode {
     let args: Vec<String> = env::args().collect();
@@ -16,13 +25,53 @@ fn main() -> ExitCode {
 
     match args[1].as_str() {
         "check" => {
-            if args.len() < 3 {
+            let (config_path, mut paths) = match split_config_arg(&args[2..]) {
+                Ok(split) => split,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return ExitCode::from(1);
+                }
+            };
+            let cache = take_flag(&mut paths, "--cache");
+            let fix = take_flag(&mut paths, "--fix");
+            let fix = if take_flag(&mut paths, "--unsafe-fixes") {
+                Some(Applicability::MaybeIncorrect)
+            } else if fix {
+                Some(Applicability::MachineApplicable)
+            } else {
+                None
+            };
+            if paths.is_empty() {
                 eprintln!("Error: missing file path");
-                eprintln!("Usage: {} check <file.abc>", args[0]);
+                eprintln!(
+                    "Usage: {} check [--config <file>] [--cache] [--fix] [--unsafe-fixes] <path>...",
+                    args[0]
+                );
                 return ExitCode::from(1);
             }
-            cmd_check(&args[2])
+            cmd_check(config_path.as_deref(), &paths, fix, cache)
         }
+        "rules" => match split_config_arg(&args[2..]) {
+            Ok((config_path, _)) => cmd_rules(config_path.as_deref()),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                ExitCode::from(1)
+            }
+        },
+        "explain" => match split_config_arg(&args[2..]) {
+            Ok((config_path, targets)) => match targets.as_slice() {
+                [target] => cmd_explain(config_path.as_deref(), target),
+                _ => {
+                    eprintln!("Error: expected one diagnostic code or rule name");
+                    eprintln!("Usage: {} explain [--config <file>] <code|rule>", args[0]);
+                    ExitCode::from(1)
+                }
+            },
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                ExitCode::from(1)
+            }
+        },
         "help" | "--help" | "-h" => {
             print_usage(&args[0]);
             ExitCode::SUCCESS
@@ -46,188 +95,753 @@ fn print_usage(program: &str) {
 Usage: {} <command> [options]
 
 Commands:
-  check <file>    Check an ABC file for errors
+  check <path>... Check ABC files (or directories of them) for errors
+  rules           List lint rules and their levels
+  explain <code>  Explain a diagnostic code (or the code of a rule)
   help            Show this help message
   version         Show version information
 
+Options:
+  --config <file> Rule configuration (default: ./{} if present)
+  --cache         Skip tunes that are unchanged since the last check
+                  (results are kept in ./{})
+  --fix           Apply safe fixes to the files before checking them
+  --unsafe-fixes  Also apply fixes that may be wrong (e.g., "did you mean")
+
+Tunes are checked in parallel; set RAYON_NUM_THREADS to limit the threads.
+
 Examples:
   {} check tune.abc
+  {} check songbook/
+  {} check --config strict.json tune.abc
+  {} check --fix songbook/
+  {} explain W003
 "#,
-        program, program
+        program, CONFIG_FILE, CACHE_FILE, program, program, program, program, program
     );
 }
 
-fn cmd_check(path: &str) -> ExitCode {
-    // Read file
-    let source = match fs::read_to_string(path) {
-        Ok(content) => content,
+/// Configuration file looked up in the current directory.
+///
+/// ```json
+/// { "rules": { "unusualOctave": "off", "barLength": "error" } }
+/// ```
+const CONFIG_FILE: &str = "chamber.json";
+
+/// Results of `check --cache`, in the current directory.
+///
+/// Holds the tunes of the last check; results of other configurations or
+/// Chamber versions are ignored.
+const CACHE_FILE: &str = ".chamber-cache.json";
+
+/// Splits `--config <file>` out of the arguments.
+fn split_config_arg(args: &[String]) -> Result<(Option<String>, Vec<String>), String> {
+    let mut config = None;
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            match args.next() {
+                Some(path) => config = Some(path.clone()),
+                None => return Err("--config requires a file path".to_string()),
+            }
+        } else {
+            rest.push(arg.clone());
+        }
+    }
+    Ok((config, rest))
+}
+
+/// Removes every `flag` from the arguments, returning whether there was one.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let before = args.len();
+    args.retain(|arg| arg != flag);
+    args.len() != before
+}
+
+/// Builds the analyzer from the given configuration file, or from
+/// `chamber.json` in the current directory if it exists.
+fn load_analyzer(config_path: Option<&str>) -> Result<Analyzer, String> {
+    let path = match config_path {
+        Some(path) => path,
+        None if Path::new(CONFIG_FILE).is_file() => CONFIG_FILE,
+        None => return Ok(Analyzer::new()),
+    };
+
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("reading config file '{}': {}", path, e))?;
+    let config: AnalyzerConfig = serde_json::from_str(&text)
+        .map_err(|e| format!("invalid config file '{}': {}", path, e))?;
+    Analyzer::new()
+        .with_config(config)
+        .map_err(|e| format!("invalid config file '{}': {}", path, e))
+}
+
+fn cmd_rules(config_path: Option<&str>) -> ExitCode {
+    let analyzer = match load_analyzer(config_path) {
+        Ok(analyzer) => analyzer,
         Err(e) => {
-            eprintln!("Error reading file '{}': {}", path, e);
+            eprintln!("Error: {}", e);
             return ExitCode::from(1);
         }
     };
 
-    // Parse
-    let parse_result = parse_with_diagnostics(&source);
-    let line_index = LineIndex::new(&source);
+    let registry = analyzer.registry();
+    let width = registry
+        .iter()
+        .map(|rule| rule.info.name.len())
+        .max()
+        .unwrap_or(0);
+    for rule in registry.iter() {
+        let level = analyzer
+            .rule_level(rule.info.name)
+            .unwrap_or(RuleLevel::Error);
+        println!(
+            "{:<width$} {}  {:<5}  {}",
+            rule.info.name,
+            rule.info.code,
+            level.as_str(),
+            rule.info.docs
+        );
+        if let Some(options) = analyzer
+            .rule_options(rule.info.name)
+            .filter(|options| options.as_object().is_some_and(|o| !o.is_empty()))
+        {
+            println!("{:<width$} options: {}", "", options);
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn cmd_explain(config_path: Option<&str>, target: &str) -> ExitCode {
+    let analyzer = match load_analyzer(config_path) {
+        Ok(analyzer) => analyzer,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(1);
+        }
+    };
 
-    // Analyze
-    let analysis_result = Analyzer::new().analyze(&parse_result.tune);
+    match analyzer.registry().resolve_code(target) {
+        Some(code) => {
+            print!("{}", analyzer.explain(code));
+            ExitCode::SUCCESS
+        }
+        None => {
+            eprintln!("Error: unknown diagnostic code or rule '{}'", target);
+            ExitCode::from(1)
+        }
+    }
+}
 
-    // Combine diagnostics: parser errors + analyzer diagnostics
-    let mut all_diagnostics: Vec<&Diagnostic> = parse_result.diagnostics.iter().collect();
-    all_diagnostics.extend(analysis_result.diagnostics.iter());
+/// Number of tunes parsed and analyzed together on the thread pool.
+///
+/// Bounds memory use: only this many tunes of a file are held at once.
+const BATCH_SIZE: usize = 256;
+
+fn cmd_check(
+    config_path: Option<&str>,
+    paths: &[String],
+    fix: Option<Applicability>,
+    use_cache: bool,
+) -> ExitCode {
+    let analyzer = match load_analyzer(config_path) {
+        Ok(analyzer) => analyzer,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(1);
+        }
+    };
+    let cache = use_cache.then(|| load_cache(&analyzer));
 
-    // Sort by position
-    all_diagnostics.sort_by_key(|d| d.range.start());
+    // Expand directories into the .abc files they contain
+    let mut files = Vec::new();
+    for path in paths {
+        if let Err(e) = collect_abc_files(Path::new(path), &mut files) {
+            eprintln!("Error reading '{}': {}", path, e);
+            return ExitCode::from(1);
+        }
+    }
+
+    // Files are checked in parallel; their output is printed in order
+    let output = OrderedOutput::new();
+    let reports: Vec<FileReport> = files
+        .par_iter()
+        .enumerate()
+        .map(|(index, path)| {
+            let out = FileOutput {
+                output: &output,
+                index,
+            };
+            let report = check_file(&analyzer, cache.as_ref(), path, fix, &out);
+            output.finish(index);
+            report
+        })
+        .collect();
+
+    if let Some(cache) = &cache {
+        save_cache(cache);
+    }
 
-    // Print diagnostics
     let mut error_count = 0;
     let mut warning_count = 0;
+    let mut unreadable_count = 0;
+    for report in &reports {
+        error_count += report.error_count;
+        warning_count += report.warning_count;
+        if report.failed {
+            unreadable_count += 1;
+        }
+    }
+
+    if reports.len() > 1 {
+        let mut total = summary(error_count, warning_count).unwrap_or_else(|| "OK".to_string());
+        if unreadable_count > 0 {
+            total.push_str(&format!(" ({} could not be read)", unreadable_count));
+        }
+        eprintln!();
+        eprintln!("Checked {} files: {}", reports.len(), total);
+    }
+
+    if unreadable_count > 0 || error_count > 0 {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Reads the cache of the last `check --cache`, or starts an empty one.
+fn load_cache(analyzer: &Analyzer) -> AnalysisCache {
+    let file = match fs::File::open(CACHE_FILE) {
+        Ok(file) => file,
+        Err(_) => return AnalysisCache::new(analyzer),
+    };
+    AnalysisCache::load(analyzer, BufReader::new(file)).unwrap_or_else(|e| {
+        eprintln!("Warning: ignoring '{}': {}", CACHE_FILE, e);
+        AnalysisCache::new(analyzer)
+    })
+}
+
+/// Writes the results of the tunes just checked to the cache file. Failing
+/// to do so only costs time on the next check.
+fn save_cache(cache: &AnalysisCache) {
+    cache.prune();
+    let result = match fs::File::create(CACHE_FILE) {
+        Ok(file) => cache
+            .save(io::BufWriter::new(file))
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = result {
+        eprintln!("Warning: could not write '{}': {}", CACHE_FILE, e);
+    }
+}
+
+/// Adds `path` to `files`, or the `.abc` files under it if it is a directory.
+fn collect_abc_files(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<_>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() || entry.extension().is_some_and(|ext| ext == "abc") {
+            collect_abc_files(&entry, files)?;
+        }
+    }
+    Ok(())
+}
+
+/// Output of the files being checked, printed in file order as it is
+/// written: the first unfinished file writes through to stderr, and later
+/// files hold their output until their turn.
+struct OrderedOutput {
+    state: Mutex<OrderedState>,
+}
+
+#[derive(Default)]
+struct OrderedState {
+    /// The file writing through.
+    current: usize,
+    /// Output held for later files, and whether they are finished.
+    held: BTreeMap<usize, (String, bool)>,
+}
+
+impl OrderedOutput {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(OrderedState::default()),
+        }
+    }
+
+    fn write(&self, index: usize, text: &str) {
+        let mut state = self.state.lock().unwrap();
+        if index == state.current {
+            eprint!("{}", text);
+        } else {
+            state.held.entry(index).or_default().0.push_str(text);
+        }
+    }
+
+    /// Marks file `index` finished, and prints what the files after it held
+    /// once it is their turn.
+    fn finish(&self, index: usize) {
+        let mut state = self.state.lock().unwrap();
+        if index != state.current {
+            state.held.entry(index).or_default().1 = true;
+            return;
+        }
+        state.current += 1;
+        loop {
+            let current = state.current;
+            let Some((text, finished)) = state.held.remove(&current) else {
+                break;
+            };
+            eprint!("{}", text);
+            if !finished {
+                break;
+            }
+            state.current += 1;
+        }
+    }
+}
+
+/// Where one file writes its output.
+struct FileOutput<'a> {
+    output: &'a OrderedOutput,
+    index: usize,
+}
 
-    for diag in &all_diagnostics {
-        print_diagnostic(path, &source, &line_index, diag);
+impl FileOutput<'_> {
+    fn write(&self, text: &str) {
+        self.output.write(self.index, text);
+    }
+}
+
+/// Counts for one checked file.
+struct FileReport {
+    error_count: usize,
+    warning_count: usize,
+    /// Whether the file could not be read.
+    failed: bool,
+}
 
+impl FileReport {
+    fn count(&mut self, diag: &Diagnostic) {
         match diag.severity {
-            Severity::Error => error_count += 1,
-            Severity::Warning => warning_count += 1,
+            Severity::Error => self.error_count += 1,
+            Severity::Warning => self.warning_count += 1,
             Severity::Info => {}
         }
     }
+}
+
+fn check_file(
+    analyzer: &Analyzer,
+    cache: Option<&AnalysisCache>,
+    path: &Path,
+    fix: Option<Applicability>,
+    out: &FileOutput,
+) -> FileReport {
+    let mut report = FileReport {
+        error_count: 0,
+        warning_count: 0,
+        failed: false,
+    };
+    if let Some(applicability) = fix {
+        match fix_file(analyzer, path, applicability) {
+            Ok(0) => {}
+            Ok(count) => out.write(&format!(
+                "Fixed {} issue{} in '{}'\n\n",
+                count,
+                if count == 1 { "" } else { "s" },
+                path.display()
+            )),
+            Err(e) => {
+                report.failed = true;
+                out.write(&format!("Error fixing file '{}': {}\n", path.display(), e));
+                return report;
+            }
+        }
+    }
+    if let Err(e) = write_file_report(analyzer, cache, path, &mut report, out) {
+        report.failed = true;
+        out.write(&format!("Error reading file '{}': {}\n", path.display(), e));
+    }
+    report
+}
+
+/// Combines the parser and analyzer diagnostics of a tune, minus those
+/// silenced by suppression comments.
+fn tune_diagnostics(
+    analyzer: &Analyzer,
+    cache: Option<&AnalysisCache>,
+    source: &str,
+    tune: &Tune,
+    mut diagnostics: Vec<Diagnostic>,
+) -> Vec<Diagnostic> {
+    if let Some(cache) = cache {
+        return analyzer.analyze_cached(cache, source, tune, diagnostics);
+    }
+    diagnostics.extend(analyzer.analyze_source(source, tune).diagnostics);
+    analyzer.apply_suppressions(source, diagnostics)
+}
+
+/// Applies fixes to each tune of a file and writes it back if anything
+/// changed. Returns the number of fixes applied.
+fn fix_file(analyzer: &Analyzer, path: &Path, applicability: Applicability) -> io::Result<usize> {
+    let source = fs::read_to_string(path)?;
+    let tunes = TuneSources::new(source.as_bytes()).collect::<io::Result<Vec<_>>>()?;
+
+    let mut fixed = source.clone();
+    let mut applied = 0;
+    // From the last tune, so the offsets of earlier tunes stay valid
+    for tune in tunes.iter().rev() {
+        let has_content = tune.source.lines().any(|line| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('%')
+        });
+        if !has_content {
+            continue;
+        }
+
+        let result = fix_until_stable(&tune.source, applicability, |source| {
+            let mut parsed = parse_with_diagnostics(source);
+            tune.inherit(&mut parsed.tune);
+            tune_diagnostics(analyzer, None, source, &parsed.tune, parsed.diagnostics)
+        });
+        if result.applied > 0 {
+            fixed.replace_range(tune.offset..tune.offset + tune.source.len(), &result.source);
+            applied += result.applied;
+        }
+    }
+
+    if applied > 0 {
+        fs::write(path, fixed)?;
+    }
+    Ok(applied)
+}
+
+fn write_file_report(
+    analyzer: &Analyzer,
+    cache: Option<&AnalysisCache>,
+    path: &Path,
+    report: &mut FileReport,
+    file_output: &FileOutput,
+) -> io::Result<()> {
+    let display = path.display().to_string();
+    let mut out = String::new();
+
+    // Tunes are read in batches; each batch is parsed and analyzed in parallel
+    let mut sources = TuneSources::new(BufReader::new(fs::File::open(path)?));
+    let mut collection = analyzer.collection();
+    let mut tune_count = 0;
+    let mut first_tune = None;
+    // Where the lines of earlier tunes are, to locate collection diagnostics
+    let mut earlier = Vec::new();
+
+    loop {
+        let batch = sources
+            .by_ref()
+            .take(BATCH_SIZE)
+            .collect::<io::Result<Vec<_>>>()?;
+        if batch.is_empty() {
+            break;
+        }
+
+        let checked: Vec<_> = batch
+            .into_par_iter()
+            .map(|source| {
+                // The cache is keyed on the tune's text alone, which doesn't
+                // cover the fields it inherits
+                let cache = cache.filter(|_| source.file_header.is_none());
+                let mut streamed = source.parse();
+                let parsed = std::mem::take(&mut streamed.diagnostics);
+                let diagnostics =
+                    tune_diagnostics(analyzer, cache, &streamed.source, &streamed.tune, parsed);
+                (streamed, diagnostics)
+            })
+            .collect();
+
+        for (streamed, all_diagnostics) in checked {
+            let lines = TuneLines {
+                offset: streamed.offset as u32,
+                first_line: streamed.line,
+                line_index: LineIndex::new(&streamed.source),
+            };
+            let view = SourceView {
+                source: &streamed.source,
+                lines: &lines,
+                earlier: &[],
+            };
+            for diag in &all_diagnostics {
+                write_diagnostic(&mut out, &display, &view, diag, lines.offset)
+                    .map_err(io::Error::other)?;
+                report.count(diag);
+            }
+
+            // Collection rules report with offsets in the file rather than
+            // in the tune
+            let offset = TextSize::new(streamed.offset as u32);
+            collection.add_tune(&streamed.source, &streamed.tune, offset);
+            let view = SourceView {
+                earlier: &earlier,
+                ..view
+            };
+            for diag in &collection.take_diagnostics() {
+                write_diagnostic(&mut out, &display, &view, diag, 0).map_err(io::Error::other)?;
+                report.count(diag);
+            }
+
+            earlier.push(lines);
+            tune_count += 1;
+            if first_tune.is_none() {
+                first_tune = Some(streamed.tune);
+            }
+        }
+        file_output.write(&std::mem::take(&mut out));
+    }
 
+    // The rest of the collection diagnostics come once the whole file was
+    // read, when the tunes' text is gone: they are located without a snippet
+    let end = TuneLines {
+        offset: u32::MAX,
+        first_line: 0,
+        line_index: LineIndex::new(""),
+    };
+    let view = SourceView {
+        source: "",
+        lines: &end,
+        earlier: &earlier,
+    };
+    for diag in &collection.finish().diagnostics {
+        write_diagnostic(&mut out, &display, &view, diag, 0).map_err(io::Error::other)?;
+        report.count(diag);
+    }
+
+    write_file_summary(
+        &mut out,
+        &display,
+        report.error_count,
+        report.warning_count,
+        tune_count,
+        first_tune,
+    )
+    .map_err(io::Error::other)?;
+    file_output.write(&out);
+    Ok(())
+}
+
+/// Where the lines of a tune are in its file.
+struct TuneLines {
+    /// Byte offset of the tune in the file.
+    offset: u32,
+    /// Line of the tune in the file (0-indexed).
+    first_line: u32,
+    line_index: LineIndex,
+}
+
+/// The tune a diagnostic is rendered from, and the tunes before it.
+#[derive(Clone, Copy)]
+struct SourceView<'a> {
+    source: &'a str,
+    lines: &'a TuneLines,
+    earlier: &'a [TuneLines],
+}
+
+impl SourceView<'_> {
+    /// Returns the position of `offset` in the file, and in the tune if it
+    /// is in this tune. `base` is where the diagnostic's offsets start.
+    fn locate(&self, offset: TextSize, base: u32) -> (LineCol, Option<LineCol>) {
+        let offset = offset.raw() + base;
+        let index = self.earlier.partition_point(|t| t.offset <= offset);
+        let lines = match index.checked_sub(1) {
+            Some(index) if offset < self.lines.offset => &self.earlier[index],
+            _ => self.lines,
+        };
+        let pos = lines
+            .line_index
+            .line_col(TextSize::new(offset.saturating_sub(lines.offset)));
+        let in_file = LineCol::new(lines.first_line + pos.line, pos.col);
+        (in_file, std::ptr::eq(lines, self.lines).then_some(pos))
+    }
+}
+
+/// Returns e.g. "2 errors, 1 warning", or `None` if there are neither.
+fn summary(error_count: usize, warning_count: usize) -> Option<String> {
+    let mut parts = Vec::new();
+    if error_count > 0 {
+        parts.push(format!(
+            "{} error{}",
+            error_count,
+            if error_count == 1 { "" } else { "s" }
+        ));
+    }
+    if warning_count > 0 {
+        parts.push(format!(
+            "{} warning{}",
+            warning_count,
+            if warning_count == 1 { "" } else { "s" }
+        ));
+    }
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+fn write_file_summary(
+    out: &mut String,
+    path: &str,
+    error_count: usize,
+    warning_count: usize,
+    tune_count: usize,
+    first_tune: Option<Tune>,
+) -> fmt::Result {
     // Print summary
-    if error_count > 0 || warning_count > 0 {
-        eprintln!();
-        eprint!("{}: ", path);
-
-        let mut parts = Vec::new();
-        if error_count > 0 {
-            parts.push(format!(
-                "{} error{}",
-                error_count,
-                if error_count == 1 { "" } else { "s" }
-            ));
-        }
-        if warning_count > 0 {
-            parts.push(format!(
-                "{} warning{}",
-                warning_count,
-                if warning_count == 1 { "" } else { "s" }
-            ));
-        }
-        eprintln!("{}", parts.join(", "));
-    } else {
-        eprintln!("{}: OK", path);
+    match summary(error_count, warning_count) {
+        Some(summary) => {
+            writeln!(out)?;
+            writeln!(out, "{}: {}", path, summary)?;
+        }
+        None => writeln!(out, "{}: OK", path)?,
     }
 
-    // Print tune info
-    let tune = &parse_result.tune;
-    if !tune.header.fields.is_empty() {
-        eprintln!();
-        eprintln!("Tune info:");
+    // Print tune info for single-tune files, a count for collections
+    if tune_count > 1 {
+        writeln!(out)?;
+        writeln!(out, "Collection: {} tunes", tune_count)?;
+    } else if let Some(tune) = first_tune.filter(|t| !t.header.fields.is_empty()) {
+        writeln!(out)?;
+        writeln!(out, "Tune info:")?;
         for field in &tune.header.fields {
-            eprintln!("  {:?}: {}", field.kind, field.value);
+            writeln!(out, "  {:?}: {}", field.kind, field.value)?;
         }
 
         let note_count = tune
             .body
             .elements
             .iter()
-            .filter(|e| matches!(e, chamber_parser::MusicElement::Note(_)))
+            .filter(|e| matches!(e, MusicElement::Note(_)))
             .count();
         let bar_count = tune
             .body
             .elements
             .iter()
-            .filter(|e| matches!(e, chamber_parser::MusicElement::BarLine(_)))
+            .filter(|e| matches!(e, MusicElement::BarLine(_)))
             .count();
 
-        eprintln!();
-        eprintln!("Body: {} notes, {} bar lines", note_count, bar_count);
+        writeln!(out)?;
+        writeln!(out, "Body: {} notes, {} bar lines", note_count, bar_count)?;
     }
 
-    if error_count > 0 {
-        ExitCode::from(1)
-    } else {
-        ExitCode::SUCCESS
-    }
+    Ok(())
 }
 
-fn print_diagnostic(path: &str, source: &str, line_index: &LineIndex, diag: &Diagnostic) {
-    let start_pos = line_index.line_col(diag.range.start());
-    let end_pos = line_index.line_col(diag.range.end());
+/// Writes a diagnostic whose offsets start at `base` in the file. The
+/// source snippet is only shown when the diagnostic is in the view's tune.
+fn write_diagnostic(
+    out: &mut String,
+    path: &str,
+    view: &SourceView,
+    diag: &Diagnostic,
+    base: u32,
+) -> fmt::Result {
+    let (file_pos, start_pos) = view.locate(diag.range.start(), base);
+    let (_, end_pos) = view.locate(diag.range.end(), base);
 
     // Severity color/prefix
     let (severity_str, color_code) = match diag.severity {
-        Severity::Error => ("error", "\x1b[31m"),    // Red
+        Severity::Error => ("error", "\x1b[31m"),     // Red
         Severity::Warning => ("warning", "\x1b[33m"), // Yellow
-        Severity::Info => ("info", "\x1b[34m"),      // Blue
+        Severity::Info => ("info", "\x1b[34m"),       // Blue
     };
     let reset = "\x1b[0m";
     let bold = "\x1b[1m";
+    let cyan = "\x1b[36m";
 
     // Print main diagnostic line
-    eprintln!(
+    writeln!(
+        out,
         "{}{}{}[{}]{}: {}",
         bold, color_code, severity_str, diag.code, reset, diag.message
-    );
+    )?;
 
     // Print location
-    eprintln!(
+    writeln!(
+        out,
         "  {}-->{} {}:{}:{}",
-        "\x1b[36m", // Cyan
+        cyan,
         reset,
         path,
-        start_pos.line_display(),
-        start_pos.col_display()
-    );
+        file_pos.line_display(),
+        file_pos.col_display()
+    )?;
 
     // Print source snippet
-    if let Some(line_text) = line_index.line_text(start_pos.line, source) {
-        let line_num = format!("{}", start_pos.line_display());
+    let snippet = start_pos.and_then(|start_pos| {
+        let line_text = view
+            .lines
+            .line_index
+            .line_text(start_pos.line, view.source)?;
+        Some((start_pos, line_text))
+    });
+    if let Some((start_pos, line_text)) = snippet {
+        let line_num = format!("{}", file_pos.line_display());
         let padding = " ".repeat(line_num.len());
 
-        eprintln!("  {} {}|{}", padding, "\x1b[36m", reset);
-        eprintln!(
-            "  {}{} |{} {}",
-            "\x1b[36m", line_num, reset, line_text
-        );
+        writeln!(out, "  {} {}|{}", padding, cyan, reset)?;
+        writeln!(out, "  {}{} |{} {}", cyan, line_num, reset, line_text)?;
 
         // Print underline
         let underline_start = start_pos.col as usize;
-        let underline_len = if start_pos.line == end_pos.line {
-            (end_pos.col as usize).saturating_sub(underline_start).max(1)
-        } else {
-            line_text.len().saturating_sub(underline_start).max(1)
+        let underline_len = match end_pos.filter(|end_pos| end_pos.line == start_pos.line) {
+            Some(end_pos) => (end_pos.col as usize)
+                .saturating_sub(underline_start)
+                .max(1),
+            None => line_text.len().saturating_sub(underline_start).max(1),
         };
 
         let spaces = " ".repeat(underline_start);
         let carets = "^".repeat(underline_len);
 
-        eprintln!(
+        writeln!(
+            out,
             "  {} {}|{} {}{}{}{}",
-            padding, "\x1b[36m", reset, spaces, color_code, carets, reset
-        );
+            padding, cyan, reset, spaces, color_code, carets, reset
+        )?;
     }
 
     // Print labels
     for label in &diag.labels {
-        let label_pos = line_index.line_col(label.range.start());
-        eprintln!(
+        let (label_pos, _) = view.locate(label.range.start(), base);
+        writeln!(
+            out,
             "  {} = note: {} (at {}:{})",
             padding_for(3),
             label.message,
             label_pos.line_display(),
             label_pos.col_display()
-        );
+        )?;
     }
 
     // Print notes
     for note in &diag.notes {
-        eprintln!("  {} = note: {}", padding_for(3), note);
+        writeln!(out, "  {} = note: {}", padding_for(3), note)?;
+    }
+
+    // Print fixes
+    for fix in &diag.fixes {
+        let hint = match fix.applicability {
+            Applicability::MachineApplicable => "--fix",
+            Applicability::MaybeIncorrect => "--unsafe-fixes",
+        };
+        writeln!(out, "  {} = help: {} ({})", padding_for(3), fix.title, hint)?;
     }
 
-    eprintln!();
+    writeln!(out)
 }
 
 fn padding_for(n: usize) -> String {
@@ -0,0 +1,727 @@
+//! Fluent builder for constructing tunes in code.
+
+use chamber_ast::*;
+use chamber_diagnostics::Diagnostic;
+use chamber_text_size::TextRange;
+
+use crate::write_tune;
+
+/// Field labels the lexer recognises as fields.
+const FIELD_LABELS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// Error produced while building a tune.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// A note could not be parsed from its text (e.g. `"H"` or `"c#"`).
+    InvalidNote(String),
+    /// A duration with a zero denominator.
+    InvalidDuration { numerator: u32, denominator: u32 },
+    /// A field label that is not recognised as a field.
+    InvalidFieldLabel(char),
+    /// Text that cannot be written in its position (e.g. a newline in a title).
+    InvalidText { context: &'static str, text: String },
+    /// A modifier such as `dur` was used with nothing to apply it to.
+    NothingToModify(&'static str),
+    /// A decoration was added but no note, rest or chord followed it.
+    DanglingDecoration(String),
+    /// The finished tune failed validation.
+    Invalid(Vec<Diagnostic>),
+}
+
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::InvalidNote(text) => write!(f, "invalid note '{}'", text),
+            BuildError::InvalidDuration {
+                numerator,
+                denominator,
+            } => write!(f, "invalid duration {}/{}", numerator, denominator),
+            BuildError::InvalidFieldLabel(label) => {
+                write!(f, "'{}' is not a valid field label", label)
+            }
+            BuildError::InvalidText { context, text } => {
+                write!(f, "invalid {} text '{}'", context, text.escape_debug())
+            }
+            BuildError::NothingToModify(modifier) => {
+                write!(f, "'{}' needs a preceding note, rest or chord", modifier)
+            }
+            BuildError::DanglingDecoration(name) => {
+                write!(
+                    f,
+                    "decoration '{}' is not followed by a note, rest or chord",
+                    name
+                )
+            }
+            BuildError::Invalid(diagnostics) => {
+                write!(f, "tune failed validation")?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n  {}: {}", diagnostic.code, diagnostic.message)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Builder for a [`Tune`].
+///
+/// Header fields are written in the order they are set, except that `X:`
+/// always comes first (defaulting to `X:1`) and `K:` always comes last.
+/// The body is built bar by bar.
+///
+/// Errors are recorded as soon as they happen and returned by [`build`],
+/// which also runs the parser's validation over the finished tune. The
+/// built tune carries ranges into its canonical text (see [`write_tune`]).
+///
+/// # Example
+///
+/// ```
+/// use chamber_codegen::{write_tune, TuneBuilder};
+///
+/// let tune = TuneBuilder::new()
+///     .title("Example")
+///     .meter(6, 8)
+///     .key("G")
+///     .bar(|b| b.note("G").note("A").dur(2).note("B").note("c").note("d"))
+///     .final_bar(|b| b.chord(&["G", "B", "d"]).dur(6))
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(
+///     write_tune(&tune),
+///     "X:1\nT:Example\nM:6/8\nK:G\nGA2Bcd | [GBd]6 |]\n"
+/// );
+/// ```
+///
+/// [`build`]: TuneBuilder::build
+#[derive(Debug, Clone, Default)]
+pub struct TuneBuilder {
+    reference: Option<String>,
+    fields: Vec<HeaderField>,
+    key: Option<String>,
+    elements: Vec<MusicElement>,
+    error: Option<BuildError>,
+}
+
+impl TuneBuilder {
+    /// Creates an empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the reference number (`X:`).
+    pub fn reference(mut self, number: u32) -> Self {
+        self.reference = Some(number.to_string());
+        self
+    }
+
+    /// Adds a title (`T:`).
+    pub fn title(self, title: &str) -> Self {
+        self.field('T', title)
+    }
+
+    /// Adds a composer (`C:`).
+    pub fn composer(self, composer: &str) -> Self {
+        self.field('C', composer)
+    }
+
+    /// Sets the meter (`M:`), e.g. `meter(6, 8)`.
+    pub fn meter(self, numerator: u32, denominator: u32) -> Self {
+        self.field('M', &format!("{}/{}", numerator, denominator))
+    }
+
+    /// Sets the unit note length (`L:`), e.g. `unit_length(1, 8)`.
+    pub fn unit_length(self, numerator: u32, denominator: u32) -> Self {
+        self.field('L', &format!("{}/{}", numerator, denominator))
+    }
+
+    /// Sets the tempo (`Q:`), e.g. `tempo("1/4=120")`.
+    pub fn tempo(self, tempo: &str) -> Self {
+        self.field('Q', tempo)
+    }
+
+    /// Sets the key (`K:`), e.g. `key("G")` or `key("Ador")`.
+    pub fn key(mut self, key: &str) -> Self {
+        if let Some(key) = self.header_text(key) {
+            self.key = Some(key);
+        }
+        self
+    }
+
+    /// Adds an arbitrary header field.
+    ///
+    /// `X` and `K` set the reference number and key respectively.
+    pub fn field(mut self, label: char, value: &str) -> Self {
+        if !FIELD_LABELS.contains(label) {
+            self.fail(BuildError::InvalidFieldLabel(label));
+            return self;
+        }
+        let Some(value) = self.header_text(value) else {
+            return self;
+        };
+        match label {
+            'X' => self.reference = Some(value),
+            'K' => self.key = Some(value),
+            _ => self.fields.push(HeaderField {
+                kind: HeaderFieldKind::from_char(label),
+                value,
+                range: TextRange::default(),
+            }),
+        }
+        self
+    }
+
+    /// Adds a bar ending in a single bar line.
+    pub fn bar<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&mut BarBuilder) -> &mut BarBuilder,
+    {
+        self.bar_with(BarLineKind::Single, f)
+    }
+
+    /// Adds a bar ending in a thin-thick bar line (`|]`).
+    pub fn final_bar<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&mut BarBuilder) -> &mut BarBuilder,
+    {
+        self.bar_with(BarLineKind::ThinThick, f)
+    }
+
+    /// Adds a bar ending in the given kind of bar line.
+    pub fn bar_with<F>(mut self, kind: BarLineKind, f: F) -> Self
+    where
+        F: FnOnce(&mut BarBuilder) -> &mut BarBuilder,
+    {
+        let mut bar = BarBuilder::default();
+        f(&mut bar);
+        let elements = bar.finish();
+        match elements {
+            Ok(elements) => {
+                self.elements.extend(elements);
+                self.elements.push(bar_line(kind));
+            }
+            Err(err) => self.fail(err),
+        }
+        self
+    }
+
+    /// Adds a repeat start (`|:`) before the next bar.
+    pub fn start_repeat(mut self) -> Self {
+        self.elements.push(bar_line(BarLineKind::RepeatStart));
+        self
+    }
+
+    /// Finishes the tune.
+    ///
+    /// Returns the first error recorded while building, or the parser's
+    /// errors if the canonical text of the tune does not validate.
+    pub fn build(self) -> Result<Tune, BuildError> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+
+        let mut fields = Vec::with_capacity(self.fields.len() + 2);
+        fields.push(HeaderField {
+            kind: HeaderFieldKind::ReferenceNumber,
+            value: self.reference.unwrap_or_else(|| "1".to_string()),
+            range: TextRange::default(),
+        });
+        fields.extend(self.fields);
+        if let Some(key) = self.key {
+            fields.push(HeaderField {
+                kind: HeaderFieldKind::Key,
+                value: key,
+                range: TextRange::default(),
+            });
+        }
+
+        let tune = Tune {
+            header: Header {
+                fields,
+                range: TextRange::default(),
+            },
+            body: Body {
+                elements: self.elements,
+                range: TextRange::default(),
+            },
+            range: TextRange::default(),
+        };
+
+        // Re-parse the canonical text: this both validates the tune and
+        // gives every node a range into that text.
+        let result = chamber_parser::parse_with_diagnostics(&write_tune(&tune));
+        let errors: Vec<_> = result
+            .diagnostics
+            .into_iter()
+            .filter(|d| d.is_error())
+            .collect();
+        if !errors.is_empty() {
+            return Err(BuildError::Invalid(errors));
+        }
+        Ok(result.tune)
+    }
+
+    /// Validates and trims a header field value.
+    fn header_text(&mut self, value: &str) -> Option<String> {
+        if value.contains(['\n', '\r']) {
+            self.fail(BuildError::InvalidText {
+                context: "header field",
+                text: value.to_string(),
+            });
+            return None;
+        }
+        Some(value.trim().to_string())
+    }
+
+    fn fail(&mut self, err: BuildError) {
+        self.error.get_or_insert(err);
+    }
+}
+
+/// Builder for the contents of a bar (or a slur inside one).
+///
+/// Methods that take note text accept the ABC spelling of a single note:
+/// an optional accidental, the letter, octave marks and an optional
+/// duration, e.g. `"^f"`, `"B,"`, `"c'2"` or `"G3/2"`.
+#[derive(Debug, Clone, Default)]
+pub struct BarBuilder {
+    elements: Vec<MusicElement>,
+    decorations: Vec<Decoration>,
+    error: Option<BuildError>,
+}
+
+impl BarBuilder {
+    /// Adds a note, e.g. `note("^c'")`.
+    pub fn note(&mut self, text: &str) -> &mut Self {
+        if let Some(note) = self.parse_note(text) {
+            self.elements.push(MusicElement::Note(note));
+        }
+        self
+    }
+
+    /// Adds a rest (`z`).
+    pub fn rest(&mut self) -> &mut Self {
+        let decorations = std::mem::take(&mut self.decorations);
+        self.elements.push(MusicElement::Rest(Rest {
+            multi_measure: false,
+            duration: None,
+            decorations,
+            range: TextRange::default(),
+        }));
+        self
+    }
+
+    /// Adds a chord, e.g. `chord(&["C", "E", "G"])`.
+    pub fn chord(&mut self, notes: &[&str]) -> &mut Self {
+        let decorations = std::mem::take(&mut self.decorations);
+        let notes = self.parse_notes(notes);
+        self.elements.push(MusicElement::Chord(Chord {
+            notes,
+            duration: None,
+            decorations,
+            range: TextRange::default(),
+        }));
+        self
+    }
+
+    /// Adds a tuplet of the given notes, e.g. `tuplet(&["G", "A", "B"])` for `(3GAB`.
+    pub fn tuplet(&mut self, notes: &[&str]) -> &mut Self {
+        let notes = self.parse_notes(notes);
+        self.elements.push(MusicElement::Tuplet(Tuplet {
+            ratio: notes.len() as u32,
+            notes,
+            range: TextRange::default(),
+        }));
+        self
+    }
+
+    /// Adds grace notes, e.g. `grace(&["g"])` for `{g}`.
+    pub fn grace(&mut self, notes: &[&str]) -> &mut Self {
+        let notes = self.parse_notes(notes);
+        self.elements.push(MusicElement::GraceNotes(GraceNotes {
+            notes,
+            range: TextRange::default(),
+        }));
+        self
+    }
+
+    /// Adds a slur around the elements added by `f`.
+    pub fn slur<F>(&mut self, f: F) -> &mut Self
+    where
+        F: FnOnce(&mut BarBuilder) -> &mut BarBuilder,
+    {
+        let mut inner = BarBuilder::default();
+        f(&mut inner);
+        match inner.finish() {
+            Ok(elements) => self.elements.push(MusicElement::Slur(Slur {
+                elements,
+                range: TextRange::default(),
+            })),
+            Err(err) => self.fail(err),
+        }
+        self
+    }
+
+    /// Adds a decoration to the next note, rest or chord, e.g. `decoration("trill")`.
+    pub fn decoration(&mut self, name: &str) -> &mut Self {
+        if name.contains(['\n', '\r']) || (name.contains('!') && name.contains('+')) {
+            self.fail(BuildError::InvalidText {
+                context: "decoration",
+                text: name.to_string(),
+            });
+        } else {
+            self.decorations
+                .push(Decoration::new(name.to_string(), TextRange::default()));
+        }
+        self
+    }
+
+    /// Adds an annotation or chord symbol, e.g. `annotation("Am")`.
+    pub fn annotation(&mut self, text: &str) -> &mut Self {
+        if text.contains(['\n', '\r']) {
+            self.fail(BuildError::InvalidText {
+                context: "annotation",
+                text: text.to_string(),
+            });
+        } else {
+            self.elements.push(MusicElement::Annotation(Annotation {
+                text: text.to_string(),
+                range: TextRange::default(),
+            }));
+        }
+        self
+    }
+
+    /// Adds an inline field, e.g. `inline_field('K', "D")` for `[K:D]`.
+    pub fn inline_field(&mut self, label: char, value: &str) -> &mut Self {
+        let value = value.trim();
+        // The value must start with a character the lexer reads as field text
+        let starts_ok = value.starts_with(|c: char| c.is_ascii_alphanumeric());
+        if !FIELD_LABELS.contains(label) {
+            self.fail(BuildError::InvalidFieldLabel(label));
+        } else if !starts_ok || value.contains([']', '%', '\n', '\r']) {
+            self.fail(BuildError::InvalidText {
+                context: "inline field",
+                text: value.to_string(),
+            });
+        } else {
+            self.elements.push(MusicElement::InlineField(InlineField {
+                label,
+                value: value.to_string(),
+                range: TextRange::default(),
+            }));
+        }
+        self
+    }
+
+    /// Adds a tie (`-`) to the next note.
+    pub fn tie(&mut self) -> &mut Self {
+        self.elements.push(MusicElement::Tie(Tie {
+            range: TextRange::default(),
+        }));
+        self
+    }
+
+    /// Adds a broken rhythm (`>` when `dotted_first`, `<` otherwise).
+    pub fn broken_rhythm(&mut self, dotted_first: bool) -> &mut Self {
+        self.elements.push(MusicElement::BrokenRhythm(BrokenRhythm {
+            dotted_first,
+            count: 1,
+            range: TextRange::default(),
+        }));
+        self
+    }
+
+    /// Sets the duration of the preceding note, rest or chord to `length` units.
+    pub fn dur(&mut self, length: u32) -> &mut Self {
+        self.dur_frac(length, 1)
+    }
+
+    /// Sets the duration of the preceding note, rest or chord to `numerator/denominator` units.
+    pub fn dur_frac(&mut self, numerator: u32, denominator: u32) -> &mut Self {
+        if denominator == 0 {
+            self.fail(BuildError::InvalidDuration {
+                numerator,
+                denominator,
+            });
+            return self;
+        }
+        let duration = Some(Duration::new(numerator, denominator));
+        match self.elements.last_mut() {
+            Some(MusicElement::Note(note)) => note.duration = duration,
+            Some(MusicElement::Rest(rest)) => rest.duration = duration,
+            Some(MusicElement::Chord(chord)) => chord.duration = duration,
+            _ => self.fail(BuildError::NothingToModify("dur")),
+        }
+        self
+    }
+
+    /// Returns the built elements, or the first error.
+    fn finish(mut self) -> Result<Vec<MusicElement>, BuildError> {
+        if let Some(decoration) = self.decorations.pop() {
+            self.fail(BuildError::DanglingDecoration(decoration.name));
+        }
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.elements),
+        }
+    }
+
+    fn parse_notes(&mut self, notes: &[&str]) -> Vec<Note> {
+        notes
+            .iter()
+            .filter_map(|text| {
+                let note = parse_note_text(text);
+                if note.is_none() {
+                    self.fail(BuildError::InvalidNote(text.to_string()));
+                }
+                note
+            })
+            .collect()
+    }
+
+    fn parse_note(&mut self, text: &str) -> Option<Note> {
+        let Some(mut note) = parse_note_text(text) else {
+            self.fail(BuildError::InvalidNote(text.to_string()));
+            return None;
+        };
+        note.decorations = std::mem::take(&mut self.decorations);
+        Some(note)
+    }
+
+    fn fail(&mut self, err: BuildError) {
+        self.error.get_or_insert(err);
+    }
+}
+
+fn bar_line(kind: BarLineKind) -> MusicElement {
+    MusicElement::BarLine(BarLine {
+        kind,
+        range: TextRange::default(),
+    })
+}
+
+/// Parses the ABC spelling of a single note: `[accidental]letter[octaves][duration]`.
+fn parse_note_text(text: &str) -> Option<Note> {
+    let mut rest = text;
+
+    let accidental = [
+        ("^^", Accidental::DoubleSharp),
+        ("__", Accidental::DoubleFlat),
+        ("^", Accidental::Sharp),
+        ("_", Accidental::Flat),
+        ("=", Accidental::Natural),
+    ]
+    .into_iter()
+    .find_map(|(prefix, accidental)| {
+        rest.strip_prefix(prefix).map(|stripped| {
+            rest = stripped;
+            accidental
+        })
+    });
+
+    let mut chars = rest.chars();
+    let (pitch, mut octave) = Pitch::from_char(chars.next()?)?;
+    rest = chars.as_str();
+
+    while let Some(stripped) = rest.strip_prefix(['\'', ',']) {
+        octave += if rest.starts_with('\'') { 1 } else { -1 };
+        rest = stripped;
+    }
+
+    let duration = if rest.is_empty() {
+        None
+    } else {
+        let (numerator, denominator) = match rest.split_once('/') {
+            Some((n, "")) => (n, "2"),
+            Some((n, d)) => (n, d),
+            None => (rest, "1"),
+        };
+        let numerator = if numerator.is_empty() {
+            1
+        } else {
+            numerator.parse().ok()?
+        };
+        let denominator: u32 = denominator.parse().ok()?;
+        if denominator == 0 {
+            return None;
+        }
+        Some(Duration::new(numerator, denominator))
+    };
+
+    Some(Note {
+        pitch,
+        octave,
+        accidental,
+        duration,
+        decorations: Vec::new(),
+        range: TextRange::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_note_text() {
+        let note = parse_note_text("^^c''3/2").unwrap();
+        assert_eq!(note.pitch, Pitch::C);
+        assert_eq!(note.octave, 3);
+        assert_eq!(note.accidental, Some(Accidental::DoubleSharp));
+        assert_eq!(note.duration, Some(Duration::new(3, 2)));
+
+        let note = parse_note_text("B,,/").unwrap();
+        assert_eq!(note.octave, -2);
+        assert_eq!(note.duration, Some(Duration::new(1, 2)));
+
+        assert!(parse_note_text("H").is_none());
+        assert!(parse_note_text("c#").is_none());
+        assert!(parse_note_text("c/0").is_none());
+        assert!(parse_note_text("").is_none());
+    }
+
+    #[test]
+    fn test_header_order() {
+        let tune = TuneBuilder::new()
+            .key("D")
+            .title("Tune")
+            .reference(7)
+            .composer("Trad")
+            .build()
+            .unwrap();
+        let labels: Vec<char> = tune
+            .header
+            .fields
+            .iter()
+            .map(|f| f.kind.to_char())
+            .collect();
+        assert_eq!(labels, vec!['X', 'T', 'C', 'K']);
+        assert_eq!(tune.header.fields[0].value, "7");
+    }
+
+    #[test]
+    fn test_ranges_point_into_canonical_text() {
+        let tune = TuneBuilder::new()
+            .key("C")
+            .bar(|b| b.note("C").note("D"))
+            .build()
+            .unwrap();
+        let text = write_tune(&tune);
+        let MusicElement::Note(note) = &tune.body.elements[1] else {
+            panic!("expected a note");
+        };
+        let range = note.range;
+        assert_eq!(
+            &text[range.start().raw() as usize..range.end().raw() as usize],
+            "D"
+        );
+    }
+
+    #[test]
+    fn test_decorations_and_slurs() {
+        let tune = TuneBuilder::new()
+            .key("G")
+            .bar(|b| {
+                b.annotation("G")
+                    .decoration("trill")
+                    .note("G")
+                    .dur(2)
+                    .slur(|s| s.note("A").note("B"))
+                    .tie()
+                    .note("B")
+            })
+            .build()
+            .unwrap();
+        assert_eq!(write_tune(&tune), "X:1\nK:G\n\"G\"!trill!G2(AB)-B |\n");
+    }
+
+    #[test]
+    fn test_invalid_note() {
+        let err = TuneBuilder::new()
+            .key("C")
+            .bar(|b| b.note("C").note("H"))
+            .build()
+            .unwrap_err();
+        assert_eq!(err, BuildError::InvalidNote("H".to_string()));
+    }
+
+    #[test]
+    fn test_first_error_wins() {
+        let err = TuneBuilder::new()
+            .title("bad\ntitle")
+            .field('1', "x")
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            BuildError::InvalidText {
+                context: "header field",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_dur_without_note() {
+        let err = TuneBuilder::new()
+            .key("C")
+            .bar(|b| b.tie().dur(2))
+            .build()
+            .unwrap_err();
+        assert_eq!(err, BuildError::NothingToModify("dur"));
+    }
+
+    #[test]
+    fn test_zero_duration() {
+        let err = TuneBuilder::new()
+            .key("C")
+            .bar(|b| b.note("C").dur_frac(1, 0))
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, BuildError::InvalidDuration { .. }));
+    }
+
+    #[test]
+    fn test_dangling_decoration() {
+        let err = TuneBuilder::new()
+            .key("C")
+            .bar(|b| b.note("C").decoration("fermata"))
+            .build()
+            .unwrap_err();
+        assert_eq!(err, BuildError::DanglingDecoration("fermata".to_string()));
+    }
+
+    #[test]
+    fn test_invalid_inline_field() {
+        let err = TuneBuilder::new()
+            .key("C")
+            .bar(|b| b.inline_field('K', "]"))
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            BuildError::InvalidText {
+                context: "inline field",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_validation_errors_from_parser() {
+        let err = TuneBuilder::new().key("H#").build().unwrap_err();
+        let BuildError::Invalid(diagnostics) = err else {
+            panic!("expected validation errors");
+        };
+        assert_eq!(diagnostics[0].code.code(), "H008");
+    }
+
+    #[test]
+    fn test_missing_key_is_invalid() {
+        let err = TuneBuilder::new().title("No key").build().unwrap_err();
+        assert!(matches!(err, BuildError::Invalid(_)));
+    }
+}
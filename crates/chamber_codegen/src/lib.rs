@@ -0,0 +1,56 @@
+//! ABC code generation from the AST.
+//!
+//! This crate turns `chamber_ast` values back into ABC notation text,
+//! and provides a builder for constructing those values in code.
+//! Where the formatter rewrites existing source through the CST, the
+//! writer here starts from an AST (for example one built in code) and
+//! produces canonical ABC for it.
+//!
+//! The output is guaranteed to parse back to the same AST, ignoring
+//! ranges: `parse(&write_tune(&tune)) == tune`.
+//!
+//! # Example
+//!
+//! ```
+//! use chamber_codegen::write_tune;
+//! use chamber_parser::parse;
+//!
+//! let tune = parse("X:1\nT:Example\nK:G\n!trill!G2 A/B | [CEG]4 |]");
+//! assert_eq!(
+//!     write_tune(&tune),
+//!     "X:1\nT:Example\nK:G\n!trill!G2A/2B | [CEG]4 |]\n"
+//! );
+//! ```
+//!
+//! # Canonical form
+//!
+//! - One header field per line, in the order they appear in the AST
+//! - The whole body on a single line
+//! - Bar lines surrounded by single spaces, everything else written
+//!   without separators (notes are beamed together)
+//! - Octaves as lowercase letters plus `'`, or uppercase letters plus `,`
+//! - Durations as `n`, `/d` or `n/d`; a `1/1` duration is written as `1`
+//!
+//! # Building tunes
+//!
+//! [`TuneBuilder`] constructs tunes without filling in ranges by hand:
+//!
+//! ```
+//! use chamber_codegen::{write_tune, TuneBuilder};
+//!
+//! let tune = TuneBuilder::new()
+//!     .title("Scale")
+//!     .key("D")
+//!     .bar(|b| b.note("D").note("E").note("^F").note("G"))
+//!     .final_bar(|b| b.note("A").note("B").note("^c").note("d"))
+//!     .build()
+//!     .unwrap();
+//!
+//! assert_eq!(write_tune(&tune), "X:1\nT:Scale\nK:D\nDE^FG | AB^cd |]\n");
+//! ```
+
+mod builder;
+mod writer;
+
+pub use builder::{BarBuilder, BuildError, TuneBuilder};
+pub use writer::{write_tune, ToAbc};
@@ -0,0 +1,393 @@
+//! Canonical ABC writer.
+
+use chamber_ast::*;
+use chamber_lexer::escape;
+
+/// Types that can be written as ABC notation.
+pub trait ToAbc {
+    /// Appends the ABC representation of `self` to `out`.
+    fn write_abc(&self, out: &mut String);
+
+    /// Returns the ABC representation of `self`.
+    fn to_abc(&self) -> String {
+        let mut out = String::new();
+        self.write_abc(&mut out);
+        out
+    }
+}
+
+/// Writes a tune as canonical ABC text.
+pub fn write_tune(tune: &Tune) -> String {
+    tune.to_abc()
+}
+
+impl ToAbc for Tune {
+    fn write_abc(&self, out: &mut String) {
+        self.header.write_abc(out);
+        if !self.body.elements.is_empty() {
+            self.body.write_abc(out);
+            out.push('\n');
+        }
+    }
+}
+
+impl ToAbc for Header {
+    fn write_abc(&self, out: &mut String) {
+        for field in &self.fields {
+            field.write_abc(out);
+            out.push('\n');
+        }
+    }
+}
+
+impl ToAbc for HeaderField {
+    fn write_abc(&self, out: &mut String) {
+        out.push(self.kind.to_char());
+        out.push(':');
+        out.push_str(&escape(&self.value));
+    }
+}
+
+impl ToAbc for Body {
+    fn write_abc(&self, out: &mut String) {
+        write_elements(&self.elements, out);
+    }
+}
+
+impl ToAbc for MusicElement {
+    fn write_abc(&self, out: &mut String) {
+        match self {
+            MusicElement::Note(note) => note.write_abc(out),
+            MusicElement::Rest(rest) => rest.write_abc(out),
+            MusicElement::Chord(chord) => chord.write_abc(out),
+            MusicElement::BarLine(bar) => bar.write_abc(out),
+            MusicElement::Ending(ending) => ending.write_abc(out),
+            MusicElement::Tuplet(tuplet) => tuplet.write_abc(out),
+            MusicElement::Slur(slur) => slur.write_abc(out),
+            MusicElement::GraceNotes(grace) => grace.write_abc(out),
+            MusicElement::BrokenRhythm(broken) => broken.write_abc(out),
+            MusicElement::Tie(tie) => tie.write_abc(out),
+            MusicElement::InlineField(field) => field.write_abc(out),
+            MusicElement::Annotation(annotation) => annotation.write_abc(out),
+            MusicElement::Lyrics(lyrics) => lyrics.write_abc(out),
+        }
+    }
+}
+
+impl ToAbc for Note {
+    fn write_abc(&self, out: &mut String) {
+        write_decorations(&self.decorations, out);
+        if let Some(accidental) = self.accidental {
+            accidental.write_abc(out);
+        }
+
+        // Octave 1 and above use lowercase letters, the rest uppercase
+        let letter = self.pitch.to_char();
+        if self.octave >= 1 {
+            out.push(letter.to_ascii_lowercase());
+            for _ in 1..self.octave {
+                out.push('\'');
+            }
+        } else {
+            out.push(letter);
+            for _ in self.octave..0 {
+                out.push(',');
+            }
+        }
+
+        write_duration(self.duration.as_ref(), out);
+    }
+}
+
+impl ToAbc for Accidental {
+    fn write_abc(&self, out: &mut String) {
+        out.push_str(match self {
+            Accidental::Sharp => "^",
+            Accidental::DoubleSharp => "^^",
+            Accidental::Flat => "_",
+            Accidental::DoubleFlat => "__",
+            Accidental::Natural => "=",
+        });
+    }
+}
+
+impl ToAbc for Decoration {
+    fn write_abc(&self, out: &mut String) {
+        // Prefer !name!, falling back to +name+ when the name contains '!'
+        let delimiter = if self.name.contains('!') { '+' } else { '!' };
+        out.push(delimiter);
+        out.push_str(&self.name);
+        out.push(delimiter);
+    }
+}
+
+impl ToAbc for Duration {
+    fn write_abc(&self, out: &mut String) {
+        match (self.numerator, self.denominator) {
+            (n, 1) => out.push_str(&n.to_string()),
+            (1, d) => {
+                out.push('/');
+                out.push_str(&d.to_string());
+            }
+            (n, d) => {
+                out.push_str(&n.to_string());
+                out.push('/');
+                out.push_str(&d.to_string());
+            }
+        }
+    }
+}
+
+impl ToAbc for Rest {
+    fn write_abc(&self, out: &mut String) {
+        write_decorations(&self.decorations, out);
+        out.push(if self.multi_measure { 'Z' } else { 'z' });
+        write_duration(self.duration.as_ref(), out);
+    }
+}
+
+impl ToAbc for Chord {
+    fn write_abc(&self, out: &mut String) {
+        write_decorations(&self.decorations, out);
+        out.push('[');
+        for note in &self.notes {
+            note.write_abc(out);
+        }
+        out.push(']');
+        write_duration(self.duration.as_ref(), out);
+    }
+}
+
+impl ToAbc for BarLine {
+    fn write_abc(&self, out: &mut String) {
+        out.push_str(match self.kind {
+            BarLineKind::Single => "|",
+            BarLineKind::Double => "||",
+            BarLineKind::RepeatStart => "|:",
+            BarLineKind::RepeatEnd => ":|",
+            BarLineKind::RepeatBoth => "::",
+            BarLineKind::ThinThick => "|]",
+            BarLineKind::ThickThin => "[|",
+        });
+    }
+}
+
+impl ToAbc for Ending {
+    fn write_abc(&self, out: &mut String) {
+        out.push('[');
+        let numbers: Vec<_> = self.numbers.iter().map(u32::to_string).collect();
+        out.push_str(&numbers.join(","));
+    }
+}
+
+impl ToAbc for Tuplet {
+    fn write_abc(&self, out: &mut String) {
+        out.push('(');
+        out.push_str(&self.ratio.to_string());
+        for note in &self.notes {
+            note.write_abc(out);
+        }
+    }
+}
+
+impl ToAbc for Slur {
+    fn write_abc(&self, out: &mut String) {
+        out.push('(');
+        write_elements(&self.elements, out);
+        out.push(')');
+    }
+}
+
+impl ToAbc for GraceNotes {
+    fn write_abc(&self, out: &mut String) {
+        out.push('{');
+        for note in &self.notes {
+            note.write_abc(out);
+        }
+        out.push('}');
+    }
+}
+
+impl ToAbc for BrokenRhythm {
+    fn write_abc(&self, out: &mut String) {
+        let c = if self.dotted_first { '>' } else { '<' };
+        for _ in 0..self.count {
+            out.push(c);
+        }
+    }
+}
+
+impl ToAbc for Tie {
+    fn write_abc(&self, out: &mut String) {
+        out.push('-');
+    }
+}
+
+impl ToAbc for InlineField {
+    fn write_abc(&self, out: &mut String) {
+        out.push('[');
+        out.push(self.label);
+        out.push(':');
+        // `]` would close the field
+        out.push_str(&escape(&self.value).replace(']', "\\]"));
+        out.push(']');
+    }
+}
+
+impl ToAbc for Annotation {
+    fn write_abc(&self, out: &mut String) {
+        out.push('"');
+        out.push_str(&escape(&self.text));
+        out.push('"');
+    }
+}
+
+impl ToAbc for Lyrics {
+    fn write_abc(&self, out: &mut String) {
+        out.push_str("w:");
+        out.push_str(&self.text);
+    }
+}
+
+/// Writes a sequence of elements, inserting separators where needed.
+fn write_elements(elements: &[MusicElement], out: &mut String) {
+    let mut prev: Option<&MusicElement> = None;
+    for element in elements {
+        if let Some(prev) = prev {
+            out.push_str(separator(prev, element));
+        }
+        element.write_abc(out);
+        prev = Some(element);
+    }
+}
+
+/// Returns the text to put between two adjacent elements.
+fn separator(prev: &MusicElement, next: &MusicElement) -> &'static str {
+    match (prev, next) {
+        // Lyrics are a line of their own
+        (MusicElement::Lyrics(_), _) | (_, MusicElement::Lyrics(_)) => "\n",
+        // "| :|" would lex as "| :" (repeat start) followed by "|", and
+        // "| ::" likewise
+        (
+            MusicElement::BarLine(BarLine {
+                kind: BarLineKind::Single,
+                ..
+            }),
+            MusicElement::BarLine(BarLine {
+                kind: BarLineKind::RepeatEnd | BarLineKind::RepeatBoth,
+                ..
+            }),
+        ) => "\n",
+        (MusicElement::BarLine(_), _) | (_, MusicElement::BarLine(_)) => " ",
+        // ">" followed by ">" would merge into ">>"
+        (MusicElement::BrokenRhythm(_), MusicElement::BrokenRhythm(_)) => " ",
+        _ => "",
+    }
+}
+
+fn write_decorations(decorations: &[Decoration], out: &mut String) {
+    for decoration in decorations {
+        decoration.write_abc(out);
+    }
+}
+
+fn write_duration(duration: Option<&Duration>, out: &mut String) {
+    if let Some(duration) = duration {
+        duration.write_abc(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chamber_text_size::TextRange;
+
+    fn note(pitch: Pitch, octave: i8) -> Note {
+        Note {
+            pitch,
+            octave,
+            accidental: None,
+            duration: None,
+            decorations: Vec::new(),
+            range: TextRange::default(),
+        }
+    }
+
+    fn bar(kind: BarLineKind) -> MusicElement {
+        MusicElement::BarLine(BarLine {
+            kind,
+            range: TextRange::default(),
+        })
+    }
+
+    #[test]
+    fn test_octaves() {
+        assert_eq!(note(Pitch::C, 0).to_abc(), "C");
+        assert_eq!(note(Pitch::C, 1).to_abc(), "c");
+        assert_eq!(note(Pitch::E, 3).to_abc(), "e''");
+        assert_eq!(note(Pitch::G, -2).to_abc(), "G,,");
+    }
+
+    #[test]
+    fn test_durations() {
+        assert_eq!(Duration::new(1, 1).to_abc(), "1");
+        assert_eq!(Duration::new(3, 1).to_abc(), "3");
+        assert_eq!(Duration::new(1, 4).to_abc(), "/4");
+        assert_eq!(Duration::new(3, 2).to_abc(), "3/2");
+    }
+
+    #[test]
+    fn test_note_with_everything() {
+        let n = Note {
+            accidental: Some(Accidental::DoubleFlat),
+            duration: Some(Duration::new(3, 2)),
+            decorations: vec![
+                Decoration::new("trill".to_string(), TextRange::default()),
+                Decoration::new("f!".to_string(), TextRange::default()),
+            ],
+            ..note(Pitch::B, -1)
+        };
+        assert_eq!(n.to_abc(), "!trill!+f!+__B,3/2");
+    }
+
+    #[test]
+    fn test_header_field() {
+        let field = HeaderField {
+            kind: HeaderFieldKind::Other('R'),
+            value: "reel".to_string(),
+            range: TextRange::default(),
+        };
+        assert_eq!(field.to_abc(), "R:reel");
+    }
+
+    #[test]
+    fn test_bar_separators() {
+        let body = Body {
+            elements: vec![
+                MusicElement::Note(note(Pitch::C, 0)),
+                bar(BarLineKind::Single),
+                bar(BarLineKind::RepeatEnd),
+                bar(BarLineKind::Single),
+                bar(BarLineKind::Single),
+            ],
+            range: TextRange::default(),
+        };
+        assert_eq!(body.to_abc(), "C |\n:| | |");
+    }
+
+    #[test]
+    fn test_adjacent_broken_rhythms() {
+        let broken = |dotted_first| {
+            MusicElement::BrokenRhythm(BrokenRhythm {
+                dotted_first,
+                count: 2,
+                range: TextRange::default(),
+            })
+        };
+        let body = Body {
+            elements: vec![broken(true), broken(false)],
+            range: TextRange::default(),
+        };
+        assert_eq!(body.to_abc(), ">> <<");
+    }
+}
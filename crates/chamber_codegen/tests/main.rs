@@ -0,0 +1,424 @@
+use chamber_ast::*;
+use chamber_codegen::write_tune;
+use chamber_parser::parse;
+use chamber_text_size::TextRange;
+use proptest::prelude::*;
+
+// ============================================
+// Helpers
+// ============================================
+
+/// Resets every range in the tune so that trees can be compared structurally.
+fn clear_ranges(tune: &mut Tune) {
+    tune.range = TextRange::default();
+    tune.header.range = TextRange::default();
+    for field in &mut tune.header.fields {
+        field.range = TextRange::default();
+    }
+    tune.body.range = TextRange::default();
+    clear_elements(&mut tune.body.elements);
+}
+
+fn clear_elements(elements: &mut [MusicElement]) {
+    for element in elements {
+        match element {
+            MusicElement::Note(note) => clear_note(note),
+            MusicElement::Rest(rest) => {
+                rest.range = TextRange::default();
+                clear_decorations(&mut rest.decorations);
+            }
+            MusicElement::Chord(chord) => {
+                chord.range = TextRange::default();
+                clear_decorations(&mut chord.decorations);
+                chord.notes.iter_mut().for_each(clear_note);
+            }
+            MusicElement::BarLine(bar) => bar.range = TextRange::default(),
+            MusicElement::Ending(ending) => ending.range = TextRange::default(),
+            MusicElement::Tuplet(tuplet) => {
+                tuplet.range = TextRange::default();
+                tuplet.notes.iter_mut().for_each(clear_note);
+            }
+            MusicElement::Slur(slur) => {
+                slur.range = TextRange::default();
+                clear_elements(&mut slur.elements);
+            }
+            MusicElement::GraceNotes(grace) => {
+                grace.range = TextRange::default();
+                grace.notes.iter_mut().for_each(clear_note);
+            }
+            MusicElement::BrokenRhythm(broken) => broken.range = TextRange::default(),
+            MusicElement::Tie(tie) => tie.range = TextRange::default(),
+            MusicElement::InlineField(field) => field.range = TextRange::default(),
+            MusicElement::Annotation(annotation) => annotation.range = TextRange::default(),
+            MusicElement::Lyrics(lyrics) => {
+                lyrics.range = TextRange::default();
+                for syllable in &mut lyrics.syllables {
+                    syllable.range = TextRange::default();
+                }
+            }
+        }
+    }
+}
+
+fn clear_note(note: &mut Note) {
+    note.range = TextRange::default();
+    clear_decorations(&mut note.decorations);
+}
+
+fn clear_decorations(decorations: &mut [Decoration]) {
+    for decoration in decorations {
+        decoration.range = TextRange::default();
+    }
+}
+
+fn assert_round_trip(source: &str) {
+    let mut original = parse(source);
+    let written = write_tune(&original);
+    let mut reparsed = parse(&written);
+    clear_ranges(&mut original);
+    clear_ranges(&mut reparsed);
+    assert_eq!(original, reparsed, "written as:\n{}", written);
+}
+
+// ============================================
+// Round trips from source
+// ============================================
+
+#[test]
+fn test_round_trip_simple() {
+    assert_round_trip("X:1\nT:Simple\nM:4/4\nL:1/8\nK:G\nGABc dedB | A4 G4 |]\n");
+}
+
+#[test]
+fn test_round_trip_all_elements() {
+    assert_round_trip("X:1\nK:D\n|: \"D\"!trill!^f2>e (3def {g}a- a[K:A] | [A,CE]2 z/ Z4 :|\n");
+}
+
+#[test]
+fn test_round_trip_endings() {
+    assert_round_trip("X:1\nK:G\n|: GA | B4 :: c4 |[1 d4 :|[2,3 e4 |[1-3 f4 |]\n");
+}
+
+#[test]
+fn test_round_trip_short_tuplets() {
+    assert_round_trip("X:1\nK:C\n(3CD | (3 :: (5C[1,3 D (3z :|\n");
+}
+
+#[test]
+fn test_round_trip_j_and_z_fields() {
+    assert_round_trip("X:1\nJ:jig\nZ:transcribed\nK:C\nC[J:x][Z:y]D |]\n");
+}
+
+#[test]
+fn test_round_trip_nested_slurs() {
+    assert_round_trip("X:1\nK:C\n(C (DE) F) ((3CDE G)\n");
+}
+
+#[test]
+fn test_round_trip_octaves() {
+    assert_round_trip("X:1\nK:C\nC,,, C, C c c' c''' B,,\n");
+}
+
+#[test]
+fn test_round_trip_lyrics() {
+    assert_round_trip("X:1\nK:G\nG2 A2-A2 B2|c4 z2 d2|\nw: Hap-py _ birth|day~to * you\nw:\ngab\n");
+}
+
+#[test]
+fn test_round_trip_inline_field_escapes() {
+    assert_round_trip("X:1\nK:C\n[P:A\\] 100\\%] CDEF | [I:a\\\\b] G4 |]\n");
+}
+
+#[test]
+fn test_round_trip_examples() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../../examples/valid");
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "abc") {
+            assert_round_trip(&std::fs::read_to_string(&path).unwrap());
+        }
+    }
+}
+
+// ============================================
+// Canonical output
+// ============================================
+
+#[test]
+fn test_header_one_field_per_line() {
+    let tune = parse("X:1\nT:Title\nM:6/8\nK:Em\n");
+    assert_eq!(write_tune(&tune), "X:1\nT:Title\nM:6/8\nK:Em\n");
+}
+
+#[test]
+fn test_body_canonical_spacing() {
+    let tune = parse("X:1\nK:C\nC  D E|F G\nA B|]");
+    assert_eq!(write_tune(&tune), "X:1\nK:C\nCDE | FGAB |]\n");
+}
+
+#[test]
+fn test_canonical_durations() {
+    let tune = parse("X:1\nK:C\nC1 D/ E1/2 F3/2 G//");
+    // "G//" is not understood by the parser, which reads it as "G/" + "/"
+    assert_eq!(write_tune(&tune), "X:1\nK:C\nC1D/2E/2F3/2G/2\n");
+}
+
+#[test]
+fn test_single_bar_before_repeat_end() {
+    let tune = parse("X:1\nK:C\nC |\n:|");
+    let written = write_tune(&tune);
+    assert_eq!(written, "X:1\nK:C\nC |\n:|\n");
+}
+
+// ============================================
+// Property test over generated ASTs
+// ============================================
+
+/// Field labels that the lexer recognises.
+const LABELS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+fn label() -> impl Strategy<Value = char> {
+    proptest::sample::select(LABELS.chars().collect::<Vec<_>>())
+}
+
+fn pitch() -> impl Strategy<Value = Pitch> {
+    prop_oneof![
+        Just(Pitch::C),
+        Just(Pitch::D),
+        Just(Pitch::E),
+        Just(Pitch::F),
+        Just(Pitch::G),
+        Just(Pitch::A),
+        Just(Pitch::B),
+    ]
+}
+
+fn accidental() -> impl Strategy<Value = Accidental> {
+    prop_oneof![
+        Just(Accidental::Sharp),
+        Just(Accidental::DoubleSharp),
+        Just(Accidental::Flat),
+        Just(Accidental::DoubleFlat),
+        Just(Accidental::Natural),
+    ]
+}
+
+fn duration() -> impl Strategy<Value = Duration> {
+    (0u32..=16, 1u32..=16).prop_map(|(n, d)| Duration::new(n, d))
+}
+
+fn decoration() -> impl Strategy<Value = Decoration> {
+    prop_oneof!["[a-zA-Z0-9.+]{0,8}", "[a-z]{0,3}![a-z]{0,3}",]
+        .prop_map(|name| Decoration::new(name, TextRange::default()))
+}
+
+fn decorations() -> impl Strategy<Value = Vec<Decoration>> {
+    proptest::collection::vec(decoration(), 0..3)
+}
+
+fn note() -> impl Strategy<Value = Note> {
+    (
+        pitch(),
+        -4i8..=5,
+        proptest::option::of(accidental()),
+        proptest::option::of(duration()),
+        decorations(),
+    )
+        .prop_map(|(pitch, octave, accidental, duration, decorations)| Note {
+            pitch,
+            octave,
+            accidental,
+            duration,
+            decorations,
+            range: TextRange::default(),
+        })
+}
+
+fn bar_line() -> impl Strategy<Value = MusicElement> {
+    prop_oneof![
+        Just(BarLineKind::Single),
+        Just(BarLineKind::Double),
+        Just(BarLineKind::RepeatStart),
+        Just(BarLineKind::RepeatEnd),
+        Just(BarLineKind::RepeatBoth),
+        Just(BarLineKind::ThinThick),
+        Just(BarLineKind::ThickThin),
+    ]
+    .prop_map(|kind| {
+        MusicElement::BarLine(BarLine {
+            kind,
+            range: TextRange::default(),
+        })
+    })
+}
+
+/// Elements that may appear anywhere, including inside slurs.
+fn leaf_element() -> impl Strategy<Value = MusicElement> {
+    prop_oneof![
+        4 => note().prop_map(MusicElement::Note),
+        1 => (any::<bool>(), proptest::option::of(duration()), decorations()).prop_map(
+            |(multi_measure, duration, decorations)| MusicElement::Rest(Rest {
+                multi_measure,
+                duration,
+                decorations,
+                range: TextRange::default(),
+            })
+        ),
+        1 => (
+            proptest::collection::vec(note(), 0..4),
+            proptest::option::of(duration()),
+            decorations(),
+        )
+            .prop_map(|(notes, duration, decorations)| MusicElement::Chord(Chord {
+                notes,
+                duration,
+                decorations,
+                range: TextRange::default(),
+            })),
+        // Tuplets may have fewer notes than their ratio
+        1 => (2u32..=9)
+            .prop_flat_map(|ratio| (Just(ratio), proptest::collection::vec(note(), 0..=ratio as usize)))
+            .prop_map(|(ratio, notes)| MusicElement::Tuplet(Tuplet {
+                ratio,
+                notes,
+                range: TextRange::default(),
+            })),
+        1 => proptest::collection::vec(note(), 0..4)
+            .prop_map(|notes| MusicElement::GraceNotes(GraceNotes { notes, range: TextRange::default() })),
+        1 => (any::<bool>(), 1u32..=3).prop_map(|(dotted_first, count)| {
+            MusicElement::BrokenRhythm(BrokenRhythm {
+                dotted_first,
+                count,
+                range: TextRange::default(),
+            })
+        }),
+        1 => Just(MusicElement::Tie(Tie { range: TextRange::default() })),
+        // The value must start with a character the lexer reads as field text
+        1 => (label(), "[A-Za-z0-9][A-Za-z0-9 /=\"\\\\%&;\\[\\]]{0,6}").prop_map(|(label, value)| {
+            MusicElement::InlineField(InlineField {
+                label,
+                value: value.trim().to_string(),
+                range: TextRange::default(),
+            })
+        }),
+        1 => "[A-Za-z0-9 #\"\\\\%&;é]{0,8}".prop_map(|text| MusicElement::Annotation(Annotation {
+            text,
+            range: TextRange::default(),
+        })),
+    ]
+}
+
+/// Slur contents: anything except bar lines, which end a slur.
+fn slur_element() -> impl Strategy<Value = MusicElement> {
+    leaf_element().prop_recursive(3, 16, 4, |inner| {
+        proptest::collection::vec(inner, 0..4).prop_map(|elements| {
+            MusicElement::Slur(Slur {
+                elements,
+                range: TextRange::default(),
+            })
+        })
+    })
+}
+
+/// A lyrics line, which can't be inside a slur.
+fn lyrics() -> impl Strategy<Value = MusicElement> {
+    "[A-Za-z~*_|-]([A-Za-z ~*_|-]{0,10}[A-Za-z~*_|-])?".prop_map(|text| {
+        let syllables = Syllable::parse_line(&text)
+            .into_iter()
+            .map(|syllable| Syllable {
+                range: TextRange::default(),
+                ..syllable
+            })
+            .collect();
+        MusicElement::Lyrics(Lyrics {
+            syllables,
+            text,
+            range: TextRange::default(),
+        })
+    })
+}
+
+fn ending() -> impl Strategy<Value = MusicElement> {
+    proptest::collection::vec(1u32..=12, 1..4).prop_map(|numbers| {
+        MusicElement::Ending(Ending {
+            numbers,
+            range: TextRange::default(),
+        })
+    })
+}
+
+fn element() -> impl Strategy<Value = MusicElement> {
+    prop_oneof![6 => slur_element(), 2 => bar_line(), 1 => ending(), 1 => lyrics()]
+}
+
+/// Drops the notes right after a tuplet with fewer notes than its ratio,
+/// which the tuplet would take when parsed.
+fn separate_short_tuplets(elements: &mut Vec<MusicElement>) {
+    let mut short_tuplet = false;
+    elements.retain_mut(|element| {
+        if short_tuplet && matches!(element, MusicElement::Note(_)) {
+            return false;
+        }
+        short_tuplet =
+            matches!(element, MusicElement::Tuplet(t) if (t.notes.len() as u32) < t.ratio);
+        if let MusicElement::Slur(slur) = element {
+            separate_short_tuplets(&mut slur.elements);
+        }
+        true
+    });
+}
+
+fn header_field() -> impl Strategy<Value = HeaderField> {
+    (
+        label().prop_filter("K ends the header", |c| *c != 'K'),
+        "[A-Za-z0-9 /=.,'()\"\\\\%&;é\\[\\]-]{0,12}",
+    )
+        .prop_map(|(label, value)| HeaderField {
+            kind: HeaderFieldKind::from_char(label),
+            value: value.trim().to_string(),
+            range: TextRange::default(),
+        })
+}
+
+fn tune() -> impl Strategy<Value = Tune> {
+    (
+        proptest::collection::vec(header_field(), 0..5),
+        "[A-G][#b]?(m|mix|dor)?",
+        proptest::collection::vec(element(), 0..24),
+    )
+        .prop_map(|(mut fields, key, mut elements)| {
+            separate_short_tuplets(&mut elements);
+            fields.push(HeaderField {
+                kind: HeaderFieldKind::Key,
+                value: key,
+                range: TextRange::default(),
+            });
+            Tune {
+                header: Header {
+                    fields,
+                    range: TextRange::default(),
+                },
+                body: Body {
+                    elements,
+                    range: TextRange::default(),
+                },
+                range: TextRange::default(),
+            }
+        })
+}
+
+proptest! {
+    #[test]
+    fn prop_parse_write_round_trip(tune in tune()) {
+        let written = write_tune(&tune);
+        let mut reparsed = parse(&written);
+        clear_ranges(&mut reparsed);
+        prop_assert_eq!(&reparsed, &tune, "written as:\n{}", written);
+    }
+
+    #[test]
+    fn prop_write_is_idempotent(tune in tune()) {
+        let written = write_tune(&tune);
+        prop_assert_eq!(write_tune(&parse(&written)), written);
+    }
+}
@@ -9,6 +9,7 @@ use crate::Severity;
 /// - M: Music body errors (M001-M099)
 /// - S: Structural errors (S001-S099)
 /// - W: Warnings (W001-W099)
+/// - C: Custom rules (C001-C099)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum DiagnosticCode {
     // =========================================
@@ -16,6 +17,8 @@ pub enum DiagnosticCode {
     // =========================================
     /// L001: Unexpected character in source.
     UnexpectedCharacter,
+    /// L002: Malformed text escape (e.g. `\q` or `&bogus;`).
+    InvalidEscape,
 
     // =========================================
     // Header errors (H001-H099)
@@ -92,14 +95,120 @@ pub enum DiagnosticCode {
     SuspiciousDuration,
     /// W003: Bar length mismatch.
     BarLengthMismatch,
+    /// W004: Suppression comment that suppresses nothing.
+    UnusedSuppression,
+    /// W005: Reference number used by more than one tune of a collection.
+    DuplicateTuneNumber,
+    /// W006: Title used by more than one tune of a collection.
+    DuplicateTitle,
+    /// W007: Tune with the same melody as another tune of a collection.
+    DuplicateMelody,
+    /// W008: `%%` directive set to different values in a collection.
+    InconsistentDirective,
+    /// W009: Tie between different pitches, or with no note to tie to.
+    InvalidTie,
+    /// W010: Accidental that changes nothing, or contradicts another in a chord.
+    RedundantAccidental,
+    /// W011: Repeat never closed or nested, or endings out of order.
+    UnbalancedRepeat,
+    /// W012: Beam that crosses a beat, or notes of one beat not beamed.
+    BeamGrouping,
+    /// W013: Chord symbol that can't be read, or is placed on grace notes.
+    InvalidChordSymbol,
+    /// W014: Chord symbol that clashes with the melody note on a strong beat.
+    ChordMelodyClash,
+    /// W015: Chord symbol spelled differently from the house style.
+    ChordSpelling,
+    /// W016: Note outside the range of the voice's instrument.
+    InstrumentRange,
+    /// W017: Voices that don't line up bar by bar.
+    VoiceConsistency,
+    /// W018: Lyrics that don't line up with their notes.
+    LyricsAlignment,
+    /// W019: Header field the collection requires is missing or empty.
+    MissingHeaderField,
+    /// W020: Tune number that doesn't follow the one before it.
+    TuneNumberSequence,
+    /// W021: Rhythm that is unknown or doesn't match the meter.
+    InvalidRhythm,
+    /// W022: No `L:` field, and the default unit note length doesn't fit.
+    MissingUnitNoteLength,
+
+    // =========================================
+    // Custom rules (C001-C099)
+    // =========================================
+    /// C001: Reported by a rule defined outside Chamber (a house rule).
+    CustomRule,
 }
 
 impl DiagnosticCode {
+    /// All diagnostic codes, in code order.
+    pub const ALL: &'static [DiagnosticCode] = &[
+        DiagnosticCode::UnexpectedCharacter,
+        DiagnosticCode::InvalidEscape,
+        DiagnosticCode::MissingReferenceNumber,
+        DiagnosticCode::MissingKeyField,
+        DiagnosticCode::DuplicateReferenceNumber,
+        DiagnosticCode::InvalidFieldOrder,
+        DiagnosticCode::InvalidMeterValue,
+        DiagnosticCode::InvalidTempo,
+        DiagnosticCode::InvalidUnitNoteLength,
+        DiagnosticCode::InvalidKeySignature,
+        DiagnosticCode::MissingTitle,
+        DiagnosticCode::EmptyTitle,
+        DiagnosticCode::EmptyReferenceNumber,
+        DiagnosticCode::InvalidReferenceNumber,
+        DiagnosticCode::UnclosedChord,
+        DiagnosticCode::UnclosedSlur,
+        DiagnosticCode::UnclosedGraceNotes,
+        DiagnosticCode::UnexpectedClosingBracket,
+        DiagnosticCode::UnexpectedClosingParen,
+        DiagnosticCode::UnexpectedClosingBrace,
+        DiagnosticCode::InvalidNoteName,
+        DiagnosticCode::InvalidDuration,
+        DiagnosticCode::EmptyChord,
+        DiagnosticCode::EmptyTuplet,
+        DiagnosticCode::TupletNoteMismatch,
+        DiagnosticCode::UnclosedInlineField,
+        DiagnosticCode::UnknownDecoration,
+        DiagnosticCode::EmptyTune,
+        DiagnosticCode::UnexpectedToken,
+        DiagnosticCode::UnusualOctave,
+        DiagnosticCode::SuspiciousDuration,
+        DiagnosticCode::BarLengthMismatch,
+        DiagnosticCode::UnusedSuppression,
+        DiagnosticCode::DuplicateTuneNumber,
+        DiagnosticCode::DuplicateTitle,
+        DiagnosticCode::DuplicateMelody,
+        DiagnosticCode::InconsistentDirective,
+        DiagnosticCode::InvalidTie,
+        DiagnosticCode::RedundantAccidental,
+        DiagnosticCode::UnbalancedRepeat,
+        DiagnosticCode::BeamGrouping,
+        DiagnosticCode::InvalidChordSymbol,
+        DiagnosticCode::ChordMelodyClash,
+        DiagnosticCode::ChordSpelling,
+        DiagnosticCode::InstrumentRange,
+        DiagnosticCode::VoiceConsistency,
+        DiagnosticCode::LyricsAlignment,
+        DiagnosticCode::MissingHeaderField,
+        DiagnosticCode::TuneNumberSequence,
+        DiagnosticCode::InvalidRhythm,
+        DiagnosticCode::MissingUnitNoteLength,
+        DiagnosticCode::CustomRule,
+    ];
+
+    /// Looks up a diagnostic by its string code (e.g., "W003").
+    pub fn from_code(code: &str) -> Option<DiagnosticCode> {
+        Self::ALL.iter().copied().find(|c| c.code() == code)
+    }
+
     /// Returns the string code (e.g., "L001", "H002").
     pub fn code(&self) -> &'static str {
         match self {
             // Lexer
             DiagnosticCode::UnexpectedCharacter => "L001",
+            DiagnosticCode::InvalidEscape => "L002",
 
             // Header
             DiagnosticCode::MissingReferenceNumber => "H001",
@@ -138,6 +247,28 @@ impl DiagnosticCode {
             DiagnosticCode::UnusualOctave => "W001",
             DiagnosticCode::SuspiciousDuration => "W002",
             DiagnosticCode::BarLengthMismatch => "W003",
+            DiagnosticCode::UnusedSuppression => "W004",
+            DiagnosticCode::DuplicateTuneNumber => "W005",
+            DiagnosticCode::DuplicateTitle => "W006",
+            DiagnosticCode::DuplicateMelody => "W007",
+            DiagnosticCode::InconsistentDirective => "W008",
+            DiagnosticCode::InvalidTie => "W009",
+            DiagnosticCode::RedundantAccidental => "W010",
+            DiagnosticCode::UnbalancedRepeat => "W011",
+            DiagnosticCode::BeamGrouping => "W012",
+            DiagnosticCode::InvalidChordSymbol => "W013",
+            DiagnosticCode::ChordMelodyClash => "W014",
+            DiagnosticCode::ChordSpelling => "W015",
+            DiagnosticCode::InstrumentRange => "W016",
+            DiagnosticCode::VoiceConsistency => "W017",
+            DiagnosticCode::LyricsAlignment => "W018",
+            DiagnosticCode::MissingHeaderField => "W019",
+            DiagnosticCode::TuneNumberSequence => "W020",
+            DiagnosticCode::InvalidRhythm => "W021",
+            DiagnosticCode::MissingUnitNoteLength => "W022",
+
+            // Custom
+            DiagnosticCode::CustomRule => "C001",
         }
     }
 
@@ -147,6 +278,26 @@ impl DiagnosticCode {
             DiagnosticCode::UnusualOctave
             | DiagnosticCode::SuspiciousDuration
             | DiagnosticCode::BarLengthMismatch
+            | DiagnosticCode::UnusedSuppression
+            | DiagnosticCode::DuplicateTuneNumber
+            | DiagnosticCode::DuplicateMelody
+            | DiagnosticCode::InconsistentDirective
+            | DiagnosticCode::InvalidTie
+            | DiagnosticCode::RedundantAccidental
+            | DiagnosticCode::UnbalancedRepeat
+            | DiagnosticCode::BeamGrouping
+            | DiagnosticCode::InvalidChordSymbol
+            | DiagnosticCode::ChordMelodyClash
+            | DiagnosticCode::ChordSpelling
+            | DiagnosticCode::InstrumentRange
+            | DiagnosticCode::VoiceConsistency
+            | DiagnosticCode::LyricsAlignment
+            | DiagnosticCode::MissingHeaderField
+            | DiagnosticCode::TuneNumberSequence
+            | DiagnosticCode::InvalidRhythm
+            | DiagnosticCode::MissingUnitNoteLength
+            | DiagnosticCode::CustomRule
+            | DiagnosticCode::InvalidEscape
             | DiagnosticCode::MissingTitle
             | DiagnosticCode::EmptyTitle
             | DiagnosticCode::EmptyChord
@@ -155,6 +306,7 @@ impl DiagnosticCode {
             | DiagnosticCode::InvalidFieldOrder
             | DiagnosticCode::EmptyTune
             | DiagnosticCode::UnexpectedToken => Severity::Warning,
+            DiagnosticCode::DuplicateTitle => Severity::Info,
             _ => Severity::Error,
         }
     }
@@ -164,6 +316,7 @@ impl DiagnosticCode {
         match self {
             // Lexer
             DiagnosticCode::UnexpectedCharacter => "unexpected character",
+            DiagnosticCode::InvalidEscape => "invalid text escape",
 
             // Header
             DiagnosticCode::MissingReferenceNumber => "missing reference number field (X:)",
@@ -202,6 +355,28 @@ impl DiagnosticCode {
             DiagnosticCode::UnusualOctave => "unusual octave (very high or very low)",
             DiagnosticCode::SuspiciousDuration => "suspicious duration (very large)",
             DiagnosticCode::BarLengthMismatch => "bar length mismatch",
+            DiagnosticCode::UnusedSuppression => "unused suppression comment",
+            DiagnosticCode::DuplicateTuneNumber => "reference number used by another tune",
+            DiagnosticCode::DuplicateTitle => "title used by another tune",
+            DiagnosticCode::DuplicateMelody => "same melody as another tune",
+            DiagnosticCode::InconsistentDirective => "directive set differently in another tune",
+            DiagnosticCode::InvalidTie => "tie does not join two notes of the same pitch",
+            DiagnosticCode::RedundantAccidental => "redundant or contradictory accidental",
+            DiagnosticCode::UnbalancedRepeat => "unbalanced repeat or ending",
+            DiagnosticCode::BeamGrouping => "notes not beamed by beat",
+            DiagnosticCode::InvalidChordSymbol => "invalid or misplaced chord symbol",
+            DiagnosticCode::ChordMelodyClash => "chord symbol clashes with the melody",
+            DiagnosticCode::ChordSpelling => "chord symbol not in the house spelling",
+            DiagnosticCode::InstrumentRange => "note outside the instrument's range",
+            DiagnosticCode::VoiceConsistency => "voices don't line up bar by bar",
+            DiagnosticCode::LyricsAlignment => "lyrics don't line up with the notes",
+            DiagnosticCode::MissingHeaderField => "required header field missing",
+            DiagnosticCode::TuneNumberSequence => "tune number out of sequence",
+            DiagnosticCode::InvalidRhythm => "rhythm unknown or doesn't match the meter",
+            DiagnosticCode::MissingUnitNoteLength => "default unit note length doesn't fit",
+
+            // Custom
+            DiagnosticCode::CustomRule => "custom rule violation",
         }
     }
 }
@@ -1,7 +1,7 @@
 use chamber_text_size::TextRange;
 use serde::{Deserialize, Serialize};
 
-use crate::{DiagnosticCode, Severity};
+use crate::{DiagnosticCode, Fix, Severity};
 
 /// A diagnostic message with location and details.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -18,6 +18,9 @@ pub struct Diagnostic {
     pub labels: Vec<Label>,
     /// Optional notes providing more context.
     pub notes: Vec<String>,
+    /// Fixes that resolve this diagnostic, most likely first.
+    #[serde(default)]
+    pub fixes: Vec<Fix>,
 }
 
 /// A secondary label pointing to a related source location.
@@ -54,6 +57,7 @@ impl Diagnostic {
             message: message.into(),
             labels: Vec::new(),
             notes: Vec::new(),
+            fixes: Vec::new(),
         }
     }
 
@@ -94,6 +98,12 @@ impl Diagnostic {
         self
     }
 
+    /// Adds a fix to this diagnostic.
+    pub fn with_fix(mut self, fix: Fix) -> Self {
+        self.fixes.push(fix);
+        self
+    }
+
     /// Returns true if this diagnostic is an error.
     pub fn is_error(&self) -> bool {
         self.severity.is_error()
@@ -0,0 +1,555 @@
+//! Long-form explanations of diagnostic codes (`chamber explain`).
+
+use serde::Serialize;
+
+use crate::DiagnosticCode;
+
+/// A long-form explanation of a diagnostic code.
+///
+/// The examples are complete ABC sources: `bad` is reported with the code
+/// and `good` is the same music with nothing to report. The analyzer's
+/// tests check both, so they stay correct.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Explanation {
+    /// Why the diagnostic is reported.
+    pub rationale: &'static str,
+    /// ABC that is reported with the code.
+    pub bad: &'static str,
+    /// The same ABC, corrected.
+    pub good: &'static str,
+    /// How to fix it.
+    pub fix: &'static str,
+    /// Codes that are often reported together or easily confused with this
+    /// one.
+    pub related: &'static [DiagnosticCode],
+}
+
+impl DiagnosticCode {
+    /// Returns the long-form explanation of this code.
+    pub fn explanation(&self) -> Explanation {
+        use DiagnosticCode::*;
+
+        match self {
+            // Lexer
+            UnexpectedCharacter => Explanation {
+                rationale: "The character has no meaning in ABC music. It is often a typo, or a \
+                            symbol from another notation (`#` for sharps instead of `^`).",
+                bad: "X:1\nT:Scale\nK:C\nC@DEF|\n",
+                good: "X:1\nT:Scale\nK:C\nCDEF GABc|\n",
+                fix: "Delete the character, or write what it meant in ABC: `^C` for C sharp, \
+                      `_B` for B flat, and text in quotes.",
+                related: &[InvalidNoteName],
+            },
+            InvalidEscape => Explanation {
+                rationale: "Text in fields and annotations may use escapes such as \
+                            `\\'e` (é) or `&eacute;`. An escape that is not recognized is left \
+                            as written, which is rarely what was meant.",
+                bad: "X:1\nT:Caf\\'q\nK:C\nCDEF GABc|\n",
+                good: "X:1\nT:Caf\\'e\nK:C\nCDEF GABc|\n",
+                fix: "Use a known escape (`\\'e`, `\\u00e9`, `&eacute;`) or type the character \
+                      itself.",
+                related: &[],
+            },
+
+            // Header
+            MissingReferenceNumber => Explanation {
+                rationale: "Every tune starts with an `X:` reference number, which separates \
+                            it from the other tunes of a file and lets programs select it.",
+                bad: "T:Scale\nK:C\nCDEF GABc|\n",
+                good: "X:1\nT:Scale\nK:C\nCDEF GABc|\n",
+                fix: "Add `X:1` (or the next free number) as the first line of the tune. \
+                      `chamber check --fix` does this.",
+                related: &[EmptyReferenceNumber, InvalidFieldOrder],
+            },
+            MissingKeyField => Explanation {
+                rationale: "The `K:` field ends the header and sets the key. Without it the \
+                            music cannot be read: programs don't know where the body starts.",
+                bad: "X:1\nT:Scale\n",
+                good: "X:1\nT:Scale\nK:C\nCDEF GABc|\n",
+                fix: "Add a `K:` line after the other header fields, with the tune's key. \
+                      `chamber check --unsafe-fixes` adds `K:C`, which may be the wrong key.",
+                related: &[InvalidKeySignature],
+            },
+            DuplicateReferenceNumber => Explanation {
+                rationale: "A tune has one reference number. A second `X:` in the header is \
+                            usually the start of another tune that lost its body, or a \
+                            copy-paste leftover.",
+                bad: "X:1\nX:2\nT:Scale\nK:C\nCDEF GABc|\n",
+                good: "X:1\nT:Scale\nK:C\nCDEF GABc|\n",
+                fix: "Remove one of the `X:` lines, or separate two tunes with a blank line.",
+                related: &[DuplicateTuneNumber],
+            },
+            InvalidFieldOrder => Explanation {
+                rationale: "`X:` must be the first field of a tune; some programs only \
+                            recognize a tune that starts with it.",
+                bad: "T:Scale\nX:1\nK:C\nCDEF GABc|\n",
+                good: "X:1\nT:Scale\nK:C\nCDEF GABc|\n",
+                fix: "Move the `X:` line to the start of the header. `chamber check --fix` \
+                      does this.",
+                related: &[MissingReferenceNumber],
+            },
+            InvalidMeterValue => Explanation {
+                rationale: "`M:` takes a fraction (`6/8`), `C`, `C|`, an additive meter \
+                            (`2+3/8`) or `none`. Anything else cannot be used to count bars.",
+                bad: "X:1\nT:Scale\nM:allegro\nK:C\nCDEF GABc|\n",
+                good: "X:1\nT:Scale\nM:4/4\nL:1/4\nK:C\nCDEF|GABc|\n",
+                fix: "Write the meter as a fraction; put tempo words in `Q:` instead \
+                      (`Q:\"Allegro\"`).",
+                related: &[InvalidTempo, BarLengthMismatch],
+            },
+            InvalidTempo => Explanation {
+                rationale: "`Q:` takes beats per minute, optionally with the beat length \
+                            (`1/4=120`) and a quoted text.",
+                bad: "X:1\nT:Scale\nQ:fast\nK:C\nCDEF GABc|\n",
+                good: "X:1\nT:Scale\nQ:1/4=120\nK:C\nCDEF GABc|\n",
+                fix: "Write the tempo as `1/4=120`, or quote the words: `Q:\"Fast\" 1/4=160`.",
+                related: &[InvalidMeterValue],
+            },
+            InvalidUnitNoteLength => Explanation {
+                rationale: "`L:` sets the length of a note written without a duration, as a \
+                            fraction of a whole note.",
+                bad: "X:1\nT:Scale\nL:quarter\nK:C\nCDEF GABc|\n",
+                good: "X:1\nT:Scale\nL:1/8\nK:C\nCDEF GABc|\n",
+                fix: "Write the length as a fraction: `L:1/4` for quarter notes, `L:1/8` for \
+                      eighth notes.",
+                related: &[SuspiciousDuration, BarLengthMismatch],
+            },
+            InvalidKeySignature => Explanation {
+                rationale: "`K:` starts with a tonic from A to G (optionally `#` or `b`) and a \
+                            mode, or is `none`, `HP` or `Hp` for bagpipe music.",
+                bad: "X:1\nT:Scale\nK:Xmaj\nCDEF GABc|\n",
+                good: "X:1\nT:Scale\nK:Cmaj\nCDEF GABc|\n",
+                fix: "Write the key as a note name and mode, such as `K:G`, `K:Em` or \
+                      `K:ADor`.",
+                related: &[MissingKeyField],
+            },
+            MissingTitle => Explanation {
+                rationale: "A tune without a `T:` title is hard to find in a collection, and \
+                            many programs show it as untitled.",
+                bad: "X:1\nK:C\nCDEF GABc|\n",
+                good: "X:1\nT:Scale\nK:C\nCDEF GABc|\n",
+                fix: "Add a `T:` line after `X:`.",
+                related: &[EmptyTitle],
+            },
+            EmptyTitle => Explanation {
+                rationale: "A `T:` field with nothing after it gives the tune no title.",
+                bad: "X:1\nT:\nK:C\nCDEF GABc|\n",
+                good: "X:1\nT:Scale\nK:C\nCDEF GABc|\n",
+                fix: "Write the title after `T:`, or remove the line.",
+                related: &[MissingTitle],
+            },
+            EmptyReferenceNumber => Explanation {
+                rationale: "`X:` needs a number to identify the tune.",
+                bad: "X:\nT:Scale\nK:C\nCDEF GABc|\n",
+                good: "X:1\nT:Scale\nK:C\nCDEF GABc|\n",
+                fix: "Write the tune's number after `X:`.",
+                related: &[InvalidReferenceNumber, MissingReferenceNumber],
+            },
+            InvalidReferenceNumber => Explanation {
+                rationale: "Reference numbers are positive integers; programs select tunes \
+                            by them.",
+                bad: "X:abc\nT:Scale\nK:C\nCDEF GABc|\n",
+                good: "X:1\nT:Scale\nK:C\nCDEF GABc|\n",
+                fix: "Replace the value with a number. Put names in `T:` instead.",
+                related: &[EmptyReferenceNumber],
+            },
+
+            // Music
+            UnclosedChord => Explanation {
+                rationale: "A chord starts with `[` and ends with `]`. Without the `]`, the \
+                            notes after it are read as part of the chord.",
+                bad: "X:1\nT:Chords\nK:C\n[CEG\n",
+                good: "X:1\nT:Chords\nK:C\n[CEG]\n",
+                fix: "Add `]` after the last note of the chord. `chamber check --fix` does \
+                      this.",
+                related: &[UnexpectedClosingBracket, EmptyChord],
+            },
+            UnclosedSlur => Explanation {
+                rationale: "A slur starts with `(` and ends with `)`. A slur that is never \
+                            closed cannot be drawn.",
+                bad: "X:1\nT:Slur\nK:C\n(CDE\n",
+                good: "X:1\nT:Slur\nK:C\n(CDE)\n",
+                fix: "Add `)` after the last slurred note, or remove the `(`.",
+                related: &[UnexpectedClosingParen],
+            },
+            UnclosedGraceNotes => Explanation {
+                rationale: "Grace notes are written in `{...}`. Without the `}`, the notes \
+                            after them are read as grace notes too.",
+                bad: "X:1\nT:Grace\nK:C\n{gab\n",
+                good: "X:1\nT:Grace\nK:C\n{gab}c\n",
+                fix: "Add `}` after the last grace note.",
+                related: &[UnexpectedClosingBrace],
+            },
+            UnexpectedClosingBracket => Explanation {
+                rationale: "A `]` closes a chord or an inline field; one without a matching \
+                            `[` is a typo or a leftover from an edit.",
+                bad: "X:1\nT:Chords\nK:C\nCDE]F\n",
+                good: "X:1\nT:Chords\nK:C\nCDEF\n",
+                fix: "Remove the `]`, or add the `[` that starts the chord.",
+                related: &[UnclosedChord],
+            },
+            UnexpectedClosingParen => Explanation {
+                rationale: "A `)` ends a slur; one without a matching `(` is a typo or a \
+                            leftover from an edit.",
+                bad: "X:1\nT:Slur\nK:C\nCD)E\n",
+                good: "X:1\nT:Slur\nK:C\n(CD)E\n",
+                fix: "Remove the `)`, or add the `(` that starts the slur.",
+                related: &[UnclosedSlur],
+            },
+            UnexpectedClosingBrace => Explanation {
+                rationale: "A `}` ends grace notes; one without a matching `{` is a typo or a \
+                            leftover from an edit.",
+                bad: "X:1\nT:Grace\nK:C\nga}c\n",
+                good: "X:1\nT:Grace\nK:C\n{ga}c\n",
+                fix: "Remove the `}`, or add the `{` that starts the grace notes.",
+                related: &[UnclosedGraceNotes],
+            },
+            InvalidNoteName => Explanation {
+                rationale: "Notes are the letters A to G (a to g an octave higher). Other \
+                            letters in the body have no meaning as notes. The lexer never \
+                            passes them to the note parser (lowercase ones are reported as \
+                            L001), so M007 only guards against a lexer bug.",
+                bad: "X:1\nT:Scale\nK:C\nCDEh\n",
+                good: "X:1\nT:Scale\nK:C\nCDEF\n",
+                fix: "Replace the letter with a note name, or put text in quotes \
+                      (`\"^Coda\"`).",
+                related: &[UnexpectedCharacter],
+            },
+            InvalidDuration => Explanation {
+                rationale: "A duration is a multiplier and/or a divisor of the unit note \
+                            length. Dividing by zero gives no length at all.",
+                bad: "X:1\nT:Scale\nK:C\nC/0\n",
+                good: "X:1\nT:Scale\nK:C\nC/2\n",
+                fix: "Use a positive divisor, such as `C/2` or `C/4`.",
+                related: &[SuspiciousDuration],
+            },
+            EmptyChord => Explanation {
+                rationale: "A chord with no notes plays nothing and is usually a leftover \
+                            from an edit.",
+                bad: "X:1\nT:Chords\nK:C\n[] CEG\n",
+                good: "X:1\nT:Chords\nK:C\n[CEG]\n",
+                fix: "Remove the `[]`, or put the chord's notes inside it.",
+                related: &[UnclosedChord],
+            },
+            EmptyTuplet => Explanation {
+                rationale: "A tuplet such as `(3` applies to the notes after it. With no \
+                            notes after it, it has nothing to apply to.",
+                bad: "X:1\nT:Triplet\nK:C\nCD (3\n",
+                good: "X:1\nT:Triplet\nK:C\nCD (3EFG\n",
+                fix: "Write the notes of the tuplet after it, or remove it.",
+                related: &[TupletNoteMismatch],
+            },
+            TupletNoteMismatch => Explanation {
+                rationale: "`(3` means three notes in the time of two. With fewer notes \
+                            before the end of the bar, the rhythm is not what the tuplet \
+                            says.",
+                bad: "X:1\nT:Triplet\nK:C\n(3CD|\n",
+                good: "X:1\nT:Triplet\nK:C\n(3CDE F2G2A2|\n",
+                fix: "Write as many notes as the tuplet's count, or use `(p:q:r` to say how \
+                      many notes it covers.",
+                related: &[EmptyTuplet, BarLengthMismatch],
+            },
+            UnclosedInlineField => Explanation {
+                rationale: "An inline field such as `[M:3/4]` ends with `]`. Without it, the \
+                            notes after the field are read as its value.",
+                bad: "X:1\nT:Change\nK:C\n[M:3/4 CDEF\n",
+                good: "X:1\nT:Change\nK:C\n[M:3/4] C2D2E2\n",
+                fix: "Add `]` right after the field's value.",
+                related: &[UnclosedChord],
+            },
+            UnknownDecoration => Explanation {
+                rationale: "Decorations are named in `!...!` from a fixed list. An unknown \
+                            name is ignored by most programs, so the mark is lost.",
+                bad: "X:1\nT:Ornament\nK:C\n!trillx!C\n",
+                good: "X:1\nT:Ornament\nK:C\n!trill!C\n",
+                fix: "Use the suggested name (`chamber check --unsafe-fixes` applies it), or \
+                      add the name to the rule's `allowed` option if your software knows it.",
+                related: &[CustomRule],
+            },
+
+            // Structural
+            EmptyTune => Explanation {
+                rationale: "The file or tune has no header or music, so there is nothing to \
+                            check.",
+                bad: "",
+                good: "X:1\nT:Scale\nK:C\nCDEF GABc|\n",
+                fix: "Write the tune, or remove the empty file.",
+                related: &[MissingReferenceNumber, MissingKeyField],
+            },
+            UnexpectedToken => Explanation {
+                rationale: "Header fields such as `M:` belong before `K:`. In the body, \
+                            fields are written inline (`[M:3/4]`) or on their own line only \
+                            where the standard allows it.",
+                bad: "X:1\nT:Change\nK:C\nCDEF\nM:4/4\n",
+                good: "X:1\nT:Change\nM:4/4\nK:C\nCDEF\n",
+                fix: "Move the field into the header, or write it inline: `[M:4/4]`.",
+                related: &[InvalidFieldOrder],
+            },
+
+            // Warnings
+            UnusualOctave => Explanation {
+                rationale: "Notes several octaves above or below the staff are rarely \
+                            intended; an extra `'` or `,` is an easy typo.",
+                bad: "X:1\nT:Scale\nK:C\nCDEc''''\n",
+                good: "X:1\nT:Scale\nK:C\nCDEc'\n",
+                fix: "Remove the extra octave marks. For instruments with a wide range, \
+                      widen the rule's `lowest` and `highest` options.",
+                related: &[SuspiciousDuration],
+            },
+            SuspiciousDuration => Explanation {
+                rationale: "A note of 16 or more unit lengths is usually a typo (`C16` for \
+                            `C1` and `C6`) or a wrong `L:` field.",
+                bad: "X:1\nT:Scale\nK:C\nC16\n",
+                good: "X:1\nT:Scale\nK:C\nC2\n",
+                fix: "Check the duration and the `L:` field. Raise the rule's `threshold` \
+                      option for slow airs with long notes.",
+                related: &[InvalidDuration, InvalidUnitNoteLength],
+            },
+            BarLengthMismatch => Explanation {
+                rationale: "The notes of each bar should add up to the meter. A bar that is \
+                            too short or too long usually has a missing or extra note, or a \
+                            wrong duration.",
+                bad: "X:1\nT:Scale\nM:4/4\nL:1/4\nK:C\nCDEF|GAB|cBAG|\n",
+                good: "X:1\nT:Scale\nM:4/4\nL:1/4\nK:C\nCDEF|GABc|cBAG|\n",
+                fix: "Check the notes and durations of the bar. A short bar opening a \
+                      section (a pickup) is allowed if the bar closing the section makes \
+                      up the rest, unless the rule's `mode` option is `strict`.",
+                related: &[TupletNoteMismatch, InvalidMeterValue],
+            },
+            UnusedSuppression => Explanation {
+                rationale: "A `% chamber-ignore` or `% chamber-disable` comment that matches \
+                            no diagnostic hides nothing; the problem it silenced was fixed, \
+                            or the code in it is wrong.",
+                bad: "X:1\nT:Scale\nK:C\n% chamber-ignore W001\nCDEF GABc|\n",
+                good: "X:1\nT:Scale\nK:C\nCDEF GABc|\n",
+                fix: "Remove the comment, or correct the code or rule name in it.",
+                related: &[],
+            },
+            DuplicateTuneNumber => Explanation {
+                rationale: "`X:` numbers identify the tunes of a file; programs that select \
+                            a tune by number only find the first one.",
+                bad: "X:1\nT:First\nK:C\nCDEF GABc|\n\nX:1\nT:Second\nK:G\nGABc dedB|\n",
+                good: "X:1\nT:First\nK:C\nCDEF GABc|\n\nX:2\nT:Second\nK:G\nGABc dedB|\n",
+                fix: "Renumber one of the tunes.",
+                related: &[DuplicateReferenceNumber, DuplicateTitle],
+            },
+            DuplicateTitle => Explanation {
+                rationale: "Two tunes with the same title are hard to tell apart in an \
+                            index. This is often deliberate (two settings of one tune), so \
+                            it is only reported as info.",
+                bad: "X:1\nT:The Kesh\nK:G\nGABc dedB|\n\nX:2\nT:The Kesh\nK:G\nGBdg bgdB|\n",
+                good: "X:1\nT:The Kesh\nK:G\nGABc dedB|\n\nX:2\nT:The Kesh (second setting)\nK:G\nGBdg bgdB|\n",
+                fix: "Make the titles different, for example by naming the setting: \
+                      `T:The Kesh (second setting)`.",
+                related: &[DuplicateMelody, DuplicateTuneNumber],
+            },
+            DuplicateMelody => Explanation {
+                rationale: "Two tunes with the same notes in the same order are usually one \
+                            tune copied twice, perhaps under different titles.",
+                bad: "X:1\nT:Reel\nK:D\nDFAd fdAF|\n\nX:2\nT:Other Reel\nK:D\nDFAd fdAF|\n",
+                good: "X:1\nT:Reel\nK:D\nDFAd fdAF|\n\nX:2\nT:Other Reel\nK:D\nDFAd fdAd|\n",
+                fix: "Remove the copy, or check that the second tune has its own music.",
+                related: &[DuplicateTitle],
+            },
+            InconsistentDirective => Explanation {
+                rationale: "Formatting directives such as `%%scale` usually apply to a whole \
+                            collection. Setting them differently from tune to tune is often \
+                            a leftover from merging files.",
+                bad: "X:1\nT:First\n%%scale 0.8\nK:C\nCDEF GABc|\n\n\
+                      X:2\nT:Second\n%%scale 0.7\nK:G\nGABc dedB|\n",
+                good: "X:1\nT:First\n%%scale 0.8\nK:C\nCDEF GABc|\n\n\
+                       X:2\nT:Second\n%%scale 0.8\nK:G\nGABc dedB|\n",
+                fix: "Use the same value in every tune, or set it once in the file header. \
+                      Directives meant to differ can be listed in the rule's `ignored` \
+                      option.",
+                related: &[],
+            },
+            InvalidTie => Explanation {
+                rationale: "A tie (`-`) holds one pitch over two notes, so both must sound \
+                            the same, counting the key signature and accidentals earlier in \
+                            the bar. A tie to a different pitch is usually meant as a slur; \
+                            a tie before or after a rest, or at the end of the tune, joins \
+                            nothing.",
+                bad: "X:1\nT:Air\nK:G\nG4-A4|B8-|\n",
+                good: "X:1\nT:Air\nK:G\n(G4A4)|B8|\n",
+                fix: "Replace a tie between different pitches with a slur, and remove ties \
+                      that have no note on one side.",
+                related: &[BarLengthMismatch],
+            },
+            RedundantAccidental => Explanation {
+                rationale: "An accidental lasts until the end of the bar, and the key \
+                            signature applies to every bar. Restating either clutters the \
+                            music and suggests a change that isn't there. A chord that \
+                            sharpens and flattens the same note is a typo. In the default \
+                            `courtesy` mode, an accidental restating a note that the \
+                            previous bar altered is a welcome reminder, and one is asked \
+                            for where it is missing.",
+                bad: "X:1\nT:Air\nK:G\n^f2 g2 ^f2 =C2|\n",
+                good: "X:1\nT:Air\nK:G\nf2 g2 f2 C2|\n",
+                fix: "Remove accidentals that change nothing, and add courtesy accidentals \
+                      where asked. Set the rule's `mode` option to `strict` to treat \
+                      courtesy accidentals as redundant too.",
+                related: &[InvalidTie],
+            },
+            UnbalancedRepeat => Explanation {
+                rationale: "Renderers and players follow repeat signs literally. A `|:` that \
+                            is never closed, a second `|:` inside an open repeat, or a `:|` \
+                            with no `|:` after an earlier repeat leaves it unclear where to \
+                            go back to. Endings must be numbered in order, and every ending \
+                            but the last ends with `:|`. A `:|` with no `|:` at the start of \
+                            the tune, or after a double bar line, repeats from there.",
+                bad: "X:1\nT:Reel\nK:D\n|:DFAd fdAF|DFAd fdAF|\n",
+                good: "X:1\nT:Reel\nK:D\n|:DFAd fdAF|1DFAd fdAF:|2DFAd fedB|]\n",
+                fix: "Close every repeat with `:|` (or `::` to start the next one right \
+                      away), and number the endings from 1.",
+                related: &[BarLengthMismatch],
+            },
+            BeamGrouping => Explanation {
+                rationale: "Notes written without spaces between them are beamed together. \
+                            Beams show the beats of the bar: two groups of three eighths in \
+                            6/8, one group per quarter in 3/4. A beam across a beat, or a \
+                            beat split into single notes, hides the pulse and is harder to \
+                            read.",
+                bad: "X:1\nT:Jig\nM:6/8\nK:D\nDFAdAF|\n",
+                good: "X:1\nT:Jig\nM:6/8\nK:D\nDFA dAF|\n",
+                fix: "Put a space between beats and none within a beat. The rule is off \
+                      by default; the fix only changes whitespace, and is unsafe as \
+                      beaming is partly a matter of taste.",
+                related: &[BarLengthMismatch],
+            },
+            InvalidChordSymbol => Explanation {
+                rationale: "A quoted annotation with no placement prefix is a chord \
+                            symbol, which players and accompaniment generators read as \
+                            harmony. Text that isn't a chord (`\"Fine\"`, `\"Gmajor\"`) is \
+                            shown in the wrong place and breaks playback. A chord symbol \
+                            belongs before the note it goes with; written before grace \
+                            notes, it lands on the grace notes instead.",
+                bad: "X:1\nT:Reel\nK:D\n\"D\"DFAd \"Gmajor\"fdAF|\n",
+                good: "X:1\nT:Reel\nK:D\n\"D\"DFAd \"Bm\"fdAF|\n",
+                fix: "Correct the chord name, or start free text with `^` (above the \
+                      staff) or `_` (below). Move chord symbols after grace notes.",
+                related: &[ChordSpelling],
+            },
+            ChordMelodyClash => Explanation {
+                rationale: "A melody note a semitone from the root of the chord under it, \
+                            and not part of the chord, rubs hard against the harmony. Off \
+                            the beat it passes quickly; on a strong beat it is usually a \
+                            wrong chord or a wrong note.",
+                bad: "X:1\nT:Reel\nK:D\n\"D\"DFAd \"G\"fdAF|\n",
+                good: "X:1\nT:Reel\nK:D\n\"D\"DFAd \"Bm\"fdAF|\n",
+                fix: "Change the chord symbol, move it to the beat where the harmony \
+                      changes, or correct the note.",
+                related: &[InvalidChordSymbol],
+            },
+            ChordSpelling => Explanation {
+                rationale: "Chord qualities can be written many ways: `m`, `min` or `-` for \
+                            minor, `maj7`, `M7` or `Δ` for a major seventh. Mixing them in \
+                            one tune or collection makes the chords harder to read at a \
+                            glance. Without a configured style, the first spelling in the \
+                            tune sets it.",
+                bad: "X:1\nT:Reel\nK:D\n\"Bm\"DFAd \"Bmin\"fdAF|\n",
+                good: "X:1\nT:Reel\nK:D\n\"Bm\"DFAd \"Bm\"fdAF|\n",
+                fix: "Spell each quality one way. Set the rule's `minor`, `major`, \
+                      `diminished` and `augmented` options to choose the spellings.",
+                related: &[InvalidChordSymbol],
+            },
+            InstrumentRange => Explanation {
+                rationale: "A note below an instrument's lowest note can't be played at all, \
+                            and one above its comfortable range is strained or out of tune \
+                            on most instruments and for most singers. The instrument of a \
+                            voice comes from the configuration, the voice's `name=`, or a \
+                            `%%MIDI program` line. Pitches are compared as written, \
+                            without the voice's `transpose=`.",
+                bad: "X:1\nT:Reel\nV:1 name=\"Tin Whistle\"\nK:D\nCDFA dAFA|\n",
+                good: "X:1\nT:Reel\nV:1 name=\"Tin Whistle\"\nK:D\nDEFA dAFA|\n",
+                fix: "Move the passage into the instrument's range, usually by an octave, \
+                      or give the voice the instrument it is written for. Set the rule's \
+                      `instrument`, `voices` and `ranges` options to configure \
+                      instruments.",
+                related: &[UnusualOctave],
+            },
+            VoiceConsistency => Explanation {
+                rationale: "The voices of a tune are played together bar by bar. When one \
+                            has a bar more or less, a different meter, or a repeat the \
+                            others don't, everything after it is out of step, and \
+                            renderers and players misalign the parts. The first bar where \
+                            a voice differs from the first voice is reported.",
+                bad: "X:1\nT:Duet\nM:4/4\nL:1/8\nK:G\n[V:1]GABc dcBA|G8|]\n[V:2]G,8|]\n",
+                good: "X:1\nT:Duet\nM:4/4\nL:1/8\nK:G\n[V:1]GABc dcBA|G8|]\n[V:2]G,8|G,8|]\n",
+                fix: "Add or remove the missing bars, repeat signs or meter changes so \
+                      every voice has the same bars as the first.",
+                related: &[BarLengthMismatch, UnbalancedRepeat],
+            },
+            LyricsAlignment => Explanation {
+                rationale: "Each syllable of a `w:` line is sung to the next note: a \
+                            syllable too many or too few shifts every word after it, \
+                            and renderers print the lyrics under the wrong notes. Rests \
+                            take no syllable, a tied note carries on the syllable before \
+                            it, and `_` holds a syllable over one more note.",
+                bad: "X:1\nT:Song\nM:3/4\nL:1/4\nK:G\nG A B|c2 z|\nw: Hap-py birth|day to\n",
+                good: "X:1\nT:Song\nM:3/4\nL:1/4\nK:G\nG A B|c2 z|\nw: Hap-py birth|day\n",
+                fix: "Add or remove syllables so there is one for each note, with `_` for \
+                      held notes and `*` for notes with no syllable, and `|` where the \
+                      bars are.",
+                related: &[VoiceConsistency],
+            },
+            MissingHeaderField => Explanation {
+                rationale: "Collections often require more of a tune than `X:`, `T:` and \
+                            `K:`: its rhythm, where it comes from, who transcribed it. The \
+                            fields are listed in the rule's `fields` option, which is empty \
+                            by default; `\"Z|S\"` takes either field. The example requires \
+                            `[\"O\", \"Z|S\"]`.",
+                bad: "X:1\nT:Reel\nO:Ireland\nK:D\nDFAd fdAF|\n",
+                good: "X:1\nT:Reel\nO:Ireland\nS:Tommy Peoples\nK:D\nDFAd fdAF|\n",
+                fix: "Add the field with its value, or change the `fields` option.",
+                related: &[CustomRule],
+            },
+            TuneNumberSequence => Explanation {
+                rationale: "The `X:` numbers of a collection usually count up by one, so \
+                            readers can find a tune by its number. A gap or a step back \
+                            is usually a tune deleted or moved without renumbering the \
+                            rest. Set the rule's `start` option to fix the first number.",
+                bad: "X:1\nT:Reel\nK:D\nDFAd fdAF|\n\nX:3\nT:Hornpipe\nK:G\nGABc dBAG|\n",
+                good: "X:1\nT:Reel\nK:D\nDFAd fdAF|\n\nX:2\nT:Hornpipe\nK:G\nGABc dBAG|\n",
+                fix: "Renumber the tunes so each is one more than the tune before it.",
+                related: &[DuplicateTuneNumber],
+            },
+            InvalidRhythm => Explanation {
+                rationale: "`R:` tells players and tune finders what kind of tune it is, \
+                            and each kind has its meter: a jig is in 6/8, a slip jig in \
+                            9/8, a reel or hornpipe in 4/4 or 2/2, a polka in 2/4. A rhythm \
+                            in the wrong meter is a mistake in either field. Rhythms outside \
+                            the vocabulary, often typos, are reported when the rule's \
+                            `allowUnknown` option is off.",
+                bad: "X:1\nT:The Kesh\nR:reel\nM:6/8\nL:1/8\nK:G\nGAG GAB|\n",
+                good: "X:1\nT:The Kesh\nR:jig\nM:6/8\nL:1/8\nK:G\nGAG GAB|\n",
+                fix: "Correct the rhythm or the meter. Add house rhythms, or other meters \
+                      for built-in ones, to the rule's `rhythms` option.",
+                related: &[BarLengthMismatch],
+            },
+            MissingUnitNoteLength => Explanation {
+                rationale: "Without an `L:` field the unit note length comes from the \
+                            meter: 1/16 in meters below 3/4, and 1/8 otherwise. A tune in \
+                            2/4 written in eighth notes without `L:1/8` plays every note \
+                            at half its length. When no bar adds up with the default but \
+                            most do with the other length, `L:` was left out.",
+                bad: "X:1\nT:Polka\nM:2/4\nK:D\nDF Ad|fd A2|\n",
+                good: "X:1\nT:Polka\nM:2/4\nL:1/8\nK:D\nDF Ad|fd A2|\n",
+                fix: "Add the `L:` field the notes are written in, before `K:`.",
+                related: &[BarLengthMismatch],
+            },
+
+            // Custom
+            CustomRule => Explanation {
+                rationale: "House rules are declared in the configuration under \
+                            `customRules`. The example breaks \
+                            `{ \"name\": \"requireOrigin\", \"requiredFields\": [\"O\"] }`.",
+                bad: "X:1\nT:Reel\nK:D\nDFAd fdAF|\n",
+                good: "X:1\nT:Reel\nO:Ireland\nK:D\nDFAd fdAF|\n",
+                fix: "Follow the house rule named in the message, or change the rule in the \
+                      configuration.",
+                related: &[],
+            },
+        }
+    }
+}
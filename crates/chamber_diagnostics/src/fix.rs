@@ -0,0 +1,226 @@
+//! Machine-applicable fixes attached to diagnostics.
+//!
+//! A [`Fix`] is a set of [`TextEdit`]s that resolves a diagnostic. Fixes are
+//! applied with [`apply_fixes`] (one pass) or [`fix_until_stable`] (re-check
+//! and fix again until nothing changes).
+
+use chamber_text_size::{TextRange, TextSize};
+use serde::{Deserialize, Serialize};
+
+use crate::Diagnostic;
+
+/// Maximum number of passes made by [`fix_until_stable`].
+pub const MAX_FIX_PASSES: usize = 10;
+
+/// A replacement of a range of source text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextEdit {
+    /// The range to replace (empty for an insertion).
+    pub range: TextRange,
+    /// The new text (empty for a deletion).
+    pub replacement: String,
+}
+
+impl TextEdit {
+    /// Replaces `range` with `replacement`.
+    pub fn replace(range: TextRange, replacement: impl Into<String>) -> Self {
+        Self {
+            range,
+            replacement: replacement.into(),
+        }
+    }
+
+    /// Inserts `text` at `offset`.
+    pub fn insert(offset: TextSize, text: impl Into<String>) -> Self {
+        Self::replace(TextRange::new(offset, offset), text)
+    }
+
+    /// Deletes `range`.
+    pub fn delete(range: TextRange) -> Self {
+        Self::replace(range, String::new())
+    }
+
+    /// Returns true if this edit and `other` cannot both be applied.
+    ///
+    /// Edits conflict if their ranges overlap, or if they start at the same
+    /// offset (the order of the two would be ambiguous).
+    pub fn conflicts_with(&self, other: &TextEdit) -> bool {
+        let (a, b) = (self.range, other.range);
+        a.start() == b.start() || (a.start() < b.end() && b.start() < a.end())
+    }
+}
+
+/// How confident a fix is.
+///
+/// Ordered from least to most confident, so `fix.applicability >= wanted`
+/// selects fixes that are at least as safe as `wanted`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Applicability {
+    /// The fix is a guess (e.g., a "did you mean" suggestion) and should be
+    /// reviewed.
+    MaybeIncorrect,
+    /// The fix is certainly what was meant and can be applied automatically.
+    MachineApplicable,
+}
+
+/// A set of edits that resolves a diagnostic.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fix {
+    /// A short description shown to the user (e.g., "insert ']'").
+    pub title: String,
+    /// The edits, applied together or not at all.
+    pub edits: Vec<TextEdit>,
+    /// How confident the fix is.
+    pub applicability: Applicability,
+}
+
+impl Fix {
+    /// Creates a fix from several edits.
+    pub fn new(
+        title: impl Into<String>,
+        edits: Vec<TextEdit>,
+        applicability: Applicability,
+    ) -> Self {
+        Self {
+            title: title.into(),
+            edits,
+            applicability,
+        }
+    }
+
+    /// Creates a fix from a single edit.
+    pub fn edit(title: impl Into<String>, edit: TextEdit, applicability: Applicability) -> Self {
+        Self::new(title, vec![edit], applicability)
+    }
+
+    fn conflicts_with(&self, edits: &[&TextEdit]) -> bool {
+        self.edits
+            .iter()
+            .any(|edit| edits.iter().any(|other| edit.conflicts_with(other)))
+    }
+}
+
+/// The result of [`apply_fixes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedFixes {
+    /// The fixed source text.
+    pub source: String,
+    /// Number of fixes applied.
+    pub applied: usize,
+    /// Number of fixes skipped because they conflicted with an earlier fix.
+    pub conflicts: usize,
+}
+
+/// Applies the fixes of `diagnostics` to `source` in one pass.
+///
+/// For each diagnostic, the first fix that is at least as confident as
+/// `applicability` is used. Fixes are taken in diagnostic order, and a fix
+/// whose edits conflict with an already accepted fix is skipped; running
+/// the check again on the result will usually report it again, which is
+/// what [`fix_until_stable`] does.
+///
+/// # Example
+///
+/// ```
+/// use chamber_diagnostics::{apply_fixes, Applicability, Diagnostic, DiagnosticCode, Fix, TextEdit};
+/// use chamber_text_size::{TextRange, TextSize};
+///
+/// let source = "[CEG";
+/// let range = TextRange::new(TextSize::new(0), TextSize::new(4));
+/// let diagnostic = Diagnostic::error(DiagnosticCode::UnclosedChord, range, "unclosed chord")
+///     .with_fix(Fix::edit(
+///         "insert ']'",
+///         TextEdit::insert(TextSize::new(4), "]"),
+///         Applicability::MachineApplicable,
+///     ));
+///
+/// let fixed = apply_fixes(source, &[diagnostic], Applicability::MachineApplicable);
+/// assert_eq!(fixed.source, "[CEG]");
+/// assert_eq!(fixed.applied, 1);
+/// ```
+pub fn apply_fixes(
+    source: &str,
+    diagnostics: &[Diagnostic],
+    applicability: Applicability,
+) -> AppliedFixes {
+    let mut accepted: Vec<&TextEdit> = Vec::new();
+    let mut applied = 0;
+    let mut conflicts = 0;
+
+    for diagnostic in diagnostics {
+        let Some(fix) = diagnostic
+            .fixes
+            .iter()
+            .find(|fix| fix.applicability >= applicability)
+        else {
+            continue;
+        };
+        if fix.conflicts_with(&accepted) {
+            conflicts += 1;
+            continue;
+        }
+        accepted.extend(&fix.edits);
+        applied += 1;
+    }
+
+    // Apply from the end so earlier offsets stay valid
+    accepted.sort_by_key(|edit| edit.range.start());
+    let mut output = source.to_string();
+    for edit in accepted.iter().rev() {
+        let start = edit.range.start().raw() as usize;
+        let end = edit.range.end().raw() as usize;
+        output.replace_range(start..end, &edit.replacement);
+    }
+
+    AppliedFixes {
+        source: output,
+        applied,
+        conflicts,
+    }
+}
+
+/// The result of [`fix_until_stable`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixedSource {
+    /// The fixed source text.
+    pub source: String,
+    /// Total number of fixes applied over all passes.
+    pub applied: usize,
+    /// The diagnostics of the fixed source.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Checks `source` with `check` and applies fixes, repeating until no more
+/// fixes apply (or [`MAX_FIX_PASSES`] is reached).
+///
+/// `check` returns the diagnostics of a source text, typically by parsing
+/// and analyzing it.
+pub fn fix_until_stable(
+    source: &str,
+    applicability: Applicability,
+    mut check: impl FnMut(&str) -> Vec<Diagnostic>,
+) -> FixedSource {
+    let mut source = source.to_string();
+    let mut applied = 0;
+
+    for _ in 0..MAX_FIX_PASSES {
+        let diagnostics = check(&source);
+        let pass = apply_fixes(&source, &diagnostics, applicability);
+        if pass.applied == 0 {
+            return FixedSource {
+                source,
+                applied,
+                diagnostics,
+            };
+        }
+        source = pass.source;
+        applied += pass.applied;
+    }
+
+    let diagnostics = check(&source);
+    FixedSource {
+        source,
+        applied,
+        diagnostics,
+    }
+}
@@ -10,7 +10,10 @@
 //! - `M`: Music body errors
 //! - `S`: Structural errors
 //! - `W`: Warnings
+//! - `C`: Custom rules
 //!
+//! [`DiagnosticCode::explanation`] explains each code at length, with
+//! examples.
 //! See [DIAGNOSTICS.md](https://github.com/user/chamber/blob/main/crates/chamber_diagnostics/DIAGNOSTICS.md)
 //! for a complete list of diagnostic codes.
 //!
@@ -33,12 +36,19 @@
 
 mod code;
 mod diagnostic;
+mod explain;
+mod fix;
 mod line_index;
 mod severity;
 mod sink;
 
 pub use code::DiagnosticCode;
 pub use diagnostic::{Diagnostic, Label};
+pub use explain::Explanation;
+pub use fix::{
+    apply_fixes, fix_until_stable, AppliedFixes, Applicability, Fix, FixedSource, TextEdit,
+    MAX_FIX_PASSES,
+};
 pub use line_index::{LineCol, LineIndex};
 pub use severity::Severity;
 pub use sink::{DiagnosticBag, DiagnosticSink};
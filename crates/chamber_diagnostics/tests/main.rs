@@ -17,10 +17,34 @@ fn test_severity_ordering() {
 #[test]
 fn test_diagnostic_code_display() {
     assert_eq!(DiagnosticCode::UnexpectedCharacter.code(), "L001");
+    assert_eq!(DiagnosticCode::InvalidEscape.code(), "L002");
     assert_eq!(DiagnosticCode::MissingReferenceNumber.code(), "H001");
     assert_eq!(DiagnosticCode::UnclosedChord.code(), "M001");
 }
 
+#[test]
+fn test_diagnostic_code_from_code() {
+    assert_eq!(
+        DiagnosticCode::from_code("W003"),
+        Some(DiagnosticCode::BarLengthMismatch)
+    );
+    assert_eq!(
+        DiagnosticCode::from_code("W004"),
+        Some(DiagnosticCode::UnusedSuppression)
+    );
+    assert_eq!(
+        DiagnosticCode::from_code("C001"),
+        Some(DiagnosticCode::CustomRule)
+    );
+    assert_eq!(DiagnosticCode::from_code("w003"), None);
+    assert_eq!(DiagnosticCode::from_code("X999"), None);
+
+    // Every code round-trips, and codes are unique
+    for code in DiagnosticCode::ALL {
+        assert_eq!(DiagnosticCode::from_code(code.code()), Some(*code));
+    }
+}
+
 #[test]
 fn test_diagnostic_code_severity() {
     assert_eq!(
@@ -31,6 +55,10 @@ fn test_diagnostic_code_severity() {
         DiagnosticCode::UnusualOctave.default_severity(),
         Severity::Warning
     );
+    assert_eq!(
+        DiagnosticCode::InvalidEscape.default_severity(),
+        Severity::Warning
+    );
 }
 
 #[test]
@@ -186,3 +214,106 @@ fn test_line_col_display() {
     let lc2 = LineCol { line: 2, col: 10 };
     assert_eq!(format!("{}", lc2), "3:11");
 }
+
+fn range(start: u32, end: u32) -> TextRange {
+    TextRange::new(TextSize::new(start), TextSize::new(end))
+}
+
+fn with_fix(edits: Vec<TextEdit>, applicability: Applicability) -> Diagnostic {
+    Diagnostic::error(DiagnosticCode::UnclosedChord, range(0, 1), "test")
+        .with_fix(Fix::new("fix", edits, applicability))
+}
+
+#[test]
+fn test_apply_fixes() {
+    let diagnostics = [
+        with_fix(
+            vec![TextEdit::replace(range(0, 1), "D")],
+            Applicability::MachineApplicable,
+        ),
+        with_fix(
+            vec![TextEdit::insert(TextSize::new(4), "]"), TextEdit::delete(range(1, 2))],
+            Applicability::MachineApplicable,
+        ),
+    ];
+
+    let fixed = apply_fixes("CCEG", &diagnostics, Applicability::MachineApplicable);
+    assert_eq!(fixed.source, "DEG]");
+    assert_eq!(fixed.applied, 2);
+    assert_eq!(fixed.conflicts, 0);
+}
+
+#[test]
+fn test_apply_fixes_skips_conflicts() {
+    let diagnostics = [
+        with_fix(
+            vec![TextEdit::replace(range(0, 2), "ab")],
+            Applicability::MachineApplicable,
+        ),
+        with_fix(
+            vec![TextEdit::replace(range(1, 3), "xy")],
+            Applicability::MachineApplicable,
+        ),
+        with_fix(
+            vec![TextEdit::insert(TextSize::new(0), "!")],
+            Applicability::MachineApplicable,
+        ),
+    ];
+
+    let fixed = apply_fixes("CDE", &diagnostics, Applicability::MachineApplicable);
+    assert_eq!(fixed.source, "abE");
+    assert_eq!(fixed.applied, 1);
+    assert_eq!(fixed.conflicts, 2);
+}
+
+#[test]
+fn test_apply_fixes_respects_applicability() {
+    let diagnostics = [with_fix(
+        vec![TextEdit::replace(range(0, 1), "D")],
+        Applicability::MaybeIncorrect,
+    )];
+
+    let fixed = apply_fixes("C", &diagnostics, Applicability::MachineApplicable);
+    assert_eq!(fixed.source, "C");
+    assert_eq!(fixed.applied, 0);
+
+    let fixed = apply_fixes("C", &diagnostics, Applicability::MaybeIncorrect);
+    assert_eq!(fixed.source, "D");
+}
+
+#[test]
+fn test_fix_until_stable() {
+    // Each pass fixes the first "x" only, so three passes are needed
+    let check = |source: &str| -> Vec<Diagnostic> {
+        source
+            .find('x')
+            .map(|i| {
+                with_fix(
+                    vec![TextEdit::replace(range(i as u32, i as u32 + 1), "y")],
+                    Applicability::MachineApplicable,
+                )
+            })
+            .into_iter()
+            .collect()
+    };
+
+    let fixed = fix_until_stable("axbxcx", Applicability::MachineApplicable, check);
+    assert_eq!(fixed.source, "aybycy");
+    assert_eq!(fixed.applied, 3);
+    assert!(fixed.diagnostics.is_empty());
+}
+
+#[test]
+fn test_fix_until_stable_stops() {
+    // A fix that never resolves its diagnostic
+    let check = |_: &str| {
+        vec![with_fix(
+            vec![TextEdit::insert(TextSize::new(0), "x")],
+            Applicability::MachineApplicable,
+        )]
+    };
+
+    let fixed = fix_until_stable("", Applicability::MachineApplicable, check);
+    assert_eq!(fixed.applied, MAX_FIX_PASSES);
+    assert_eq!(fixed.diagnostics.len(), 1);
+}
@@ -259,7 +259,7 @@ impl<'a> Formatter<'a> {
     /// Remove trailing blank lines from output (keep one newline).
     fn trim_trailing_blank_lines(&mut self) {
         // Find the last non-newline character
-        let trimmed = self.output.trim_end_matches(|c| c == '\n' || c == '\r');
+        let trimmed = self.output.trim_end_matches(['\n', '\r']);
         let len = trimmed.len();
         self.output.truncate(len);
         // Add back exactly one newline
@@ -531,10 +531,12 @@ impl<'a> Formatter<'a> {
 
         if prev.is_some() {
             // If previous was a bar and we want space around bars
-            if self.after_bar && self.config.space_around_bars {
-                if !self.output.ends_with(' ') && !self.output.ends_with('\n') {
-                    self.emit(" ");
-                }
+            if self.after_bar
+                && self.config.space_around_bars
+                && !self.output.ends_with(' ')
+                && !self.output.ends_with('\n')
+            {
+                self.emit(" ");
             }
         }
     }
@@ -588,10 +590,7 @@ impl<'a> Formatter<'a> {
             }
             SyntaxKind::WHITESPACE => {
                 // Skip whitespace at start of body (part of blank line removal)
-                if self.at_body_start {
-                    return;
-                }
-                // Skip whitespace in bar lines (we control spacing via space_around_bars)
+                // and in bar lines (we control spacing via space_around_bars)
             }
             _ => {
                 self.at_body_start = false;
@@ -616,6 +615,10 @@ impl<'a> Formatter<'a> {
                 // Normalize "| :" or "|  :" to "|:"
                 self.emit("|:");
             }
+            SyntaxKind::REPEAT_BOTH => {
+                // Normalize ": |:" to ":|:", keeping "::" as written
+                self.emit(&text.replace([' ', '\t'], ""));
+            }
             _ => {
                 self.emit(text);
             }
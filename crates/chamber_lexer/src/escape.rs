@@ -0,0 +1,362 @@
+//! ABC text escapes.
+//!
+//! Free text in ABC (header and inline field values, and annotations) may
+//! contain backslash escapes and HTML-style entities:
+//!
+//! - `\\`, `\%`, `\&` and `\"` for literal characters, and `\]` for a
+//!   bracket that doesn't close an inline field
+//! - Accent mnemonics such as `\'e` (é), `` \`a `` (à), `\^o` (ô), `\"u` (ü),
+//!   `\~n` (ñ), `\cc` (ç), `\oa` (å), `\/o` (ø), `\vs` (š)
+//! - Ligatures `\ss` (ß), `\AE` (Æ), `\ae` (æ), `\OE` (Œ), `\oe` (œ)
+//! - Unicode escapes `\u00e9` and `\U0001F3B5`
+//! - Entities such as `&amp;`, `&eacute;`, `&#233;` and `&#xE9;`
+//!
+//! The lexer keeps escapes in the raw token text; [`unescape`] decodes them.
+
+use chamber_text_size::{TextRange, TextSize};
+
+/// A malformed escape sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EscapeError {
+    /// Location of the sequence, relative to the start of the decoded text.
+    pub range: TextRange,
+    /// Description of the problem.
+    pub message: String,
+}
+
+/// Accents: mnemonic character, entity suffix, base letters, accented letters.
+const ACCENTS: &[(char, &str, &str, &str)] = &[
+    ('`', "grave", "AEIOUaeiou", "ÀÈÌÒÙàèìòù"),
+    (
+        '\'',
+        "acute",
+        "AEIOUYaeiouyCcNnSsZz",
+        "ÁÉÍÓÚÝáéíóúýĆćŃńŚśŹź",
+    ),
+    ('^', "circ", "AEIOUaeiou", "ÂÊÎÔÛâêîôû"),
+    ('~', "tilde", "ANOano", "ÃÑÕãñõ"),
+    ('"', "uml", "AEIOUYaeiouy", "ÄËÏÖÜŸäëïöüÿ"),
+    ('c', "cedil", "CcSs", "ÇçŞş"),
+    ('o', "ring", "AaUu", "ÅåŮů"),
+    ('/', "slash", "Oo", "Øø"),
+    ('v', "caron", "CcSsZzEeRrNn", "ČčŠšŽžĚěŘřŇň"),
+    ('u', "breve", "AaGg", "ĂăĞğ"),
+    ('H', "dblac", "OoUu", "ŐőŰű"),
+    (';', "ogon", "AaEe", "ĄąĘę"),
+    ('=', "macr", "AaEeIiOoUu", "ĀāĒēĪīŌōŪū"),
+    ('.', "dot", "Zz", "Żż"),
+];
+
+/// Two-letter ligature mnemonics (`\ss`, `\AE`, ...).
+const LIGATURES: &[(&str, char)] = &[
+    ("ss", 'ß'),
+    ("AE", 'Æ'),
+    ("ae", 'æ'),
+    ("OE", 'Œ'),
+    ("oe", 'œ'),
+];
+
+/// Named entities other than accented letters.
+const ENTITIES: &[(&str, char)] = &[
+    ("amp", '&'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("apos", '\''),
+    ("nbsp", '\u{a0}'),
+    ("copy", '©'),
+    ("szlig", 'ß'),
+    ("AElig", 'Æ'),
+    ("aelig", 'æ'),
+    ("OElig", 'Œ'),
+    ("oelig", 'œ'),
+];
+
+/// Decodes the escapes in `text`.
+///
+/// Malformed escapes are kept verbatim in the output and reported as errors.
+pub fn unescape(text: &str) -> (String, Vec<EscapeError>) {
+    let mut out = String::with_capacity(text.len());
+    let mut errors = Vec::new();
+    let mut pos = 0;
+
+    while let Some(c) = text[pos..].chars().next() {
+        let rest = &text[pos..];
+        let decoded = match c {
+            '\\' => decode_backslash(rest),
+            '&' => decode_entity(rest),
+            _ => None,
+        };
+
+        match decoded {
+            Some(Ok((ch, len))) => {
+                out.push(ch);
+                pos += len;
+            }
+            Some(Err((len, message))) => {
+                errors.push(EscapeError {
+                    range: TextRange::new(
+                        TextSize::new(pos as u32),
+                        TextSize::new((pos + len) as u32),
+                    ),
+                    message,
+                });
+                out.push_str(&rest[..len]);
+                pos += len;
+            }
+            None => {
+                out.push(c);
+                pos += c.len_utf8();
+            }
+        }
+    }
+
+    (out, errors)
+}
+
+/// Escapes `text` so that [`unescape`] returns it unchanged and it can be
+/// written in a header field or an annotation.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '%' => out.push_str("\\%"),
+            '"' => {
+                // \" followed by a vowel would read as an umlaut
+                let next = chars.peek().map(|&(_, next)| next);
+                if next.is_some_and(|next| accented('"', next).is_some()) {
+                    out.push_str("&quot;");
+                } else {
+                    out.push_str("\\\"");
+                }
+            }
+            '&' if decode_entity(&text[i..]).is_some() => out.push_str("\\&"),
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+type Decoded = Result<(char, usize), (usize, String)>;
+
+/// Decodes a backslash escape at the start of `text`.
+fn decode_backslash(text: &str) -> Option<Decoded> {
+    let mut chars = text[1..].chars();
+    let Some(first) = chars.next() else {
+        return Some(Err((1, "incomplete escape at end of text".to_string())));
+    };
+    let second = chars.next();
+
+    match first {
+        '\\' | '%' | '&' | ']' => return Some(Ok((first, 2))),
+        'U' => return Some(decode_unicode(text, 8)),
+        // \u is also the breve mnemonic (\ua), so only four hex digits make it Unicode
+        'u' if text[2..]
+            .chars()
+            .take(4)
+            .filter(char::is_ascii_hexdigit)
+            .count()
+            == 4 =>
+        {
+            return Some(decode_unicode(text, 4));
+        }
+        _ => {}
+    }
+
+    if let Some(second) = second {
+        let pair: String = [first, second].iter().collect();
+        if let Some(&(_, ch)) = LIGATURES.iter().find(|(name, _)| *name == pair) {
+            return Some(Ok((ch, 3)));
+        }
+        if let Some(ch) = accented(first, second) {
+            return Some(Ok((ch, 2 + second.len_utf8())));
+        }
+    }
+
+    // A quote that does not start an umlaut is a literal quote
+    if first == '"' {
+        return Some(Ok(('"', 2)));
+    }
+
+    let len = 1 + first.len_utf8() + second.map_or(0, char::len_utf8);
+    let len = if ACCENTS.iter().any(|&(mark, ..)| mark == first) {
+        len
+    } else {
+        1 + first.len_utf8()
+    };
+    Some(Err((
+        len,
+        format!("unknown escape sequence '{}'", &text[..len]),
+    )))
+}
+
+/// Decodes `\uXXXX` (`digits` = 4) or `\UXXXXXXXX` (`digits` = 8).
+fn decode_unicode(text: &str, digits: usize) -> Decoded {
+    let hex: String = text[2..]
+        .chars()
+        .take_while(|c| c.is_ascii_hexdigit())
+        .take(digits)
+        .collect();
+    let len = 2 + hex.len();
+    if hex.len() != digits {
+        return Err((
+            len,
+            format!("expected {} hex digits in '{}'", digits, &text[..len]),
+        ));
+    }
+    match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+        Some(ch) => Ok((ch, len)),
+        None => Err((len, format!("invalid code point in '{}'", &text[..len]))),
+    }
+}
+
+/// Decodes an entity (`&name;`, `&#NNN;` or `&#xHH;`) at the start of `text`.
+///
+/// Returns `None` when `text` does not look like an entity, so that a plain
+/// `&` (as in "Tom & Jerry") is kept as is.
+fn decode_entity(text: &str) -> Option<Decoded> {
+    let body_len = text[1..]
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '#'))
+        .unwrap_or(text.len() - 1);
+    if body_len == 0 || !text[1 + body_len..].starts_with(';') {
+        return None;
+    }
+    let body = &text[1..1 + body_len];
+    let len = body_len + 2;
+
+    let ch = if let Some(number) = body.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => number.parse().ok(),
+        };
+        code.and_then(char::from_u32)
+    } else {
+        named_entity(body)
+    };
+
+    Some(match ch {
+        Some(ch) => Ok((ch, len)),
+        None => Err((len, format!("unknown entity '{}'", &text[..len]))),
+    })
+}
+
+fn named_entity(name: &str) -> Option<char> {
+    if let Some(&(_, ch)) = ENTITIES.iter().find(|(entity, _)| *entity == name) {
+        return Some(ch);
+    }
+    // Accented letters: the base letter followed by the accent name
+    let mut chars = name.chars();
+    let base = chars.next()?;
+    let suffix = chars.as_str();
+    let &(mark, ..) = ACCENTS.iter().find(|(_, entity, ..)| *entity == suffix)?;
+    accented(mark, base)
+}
+
+/// Returns `base` with the accent for mnemonic `mark`, if there is one.
+fn accented(mark: char, base: char) -> Option<char> {
+    let &(_, _, bases, composed) = ACCENTS.iter().find(|(m, ..)| *m == mark)?;
+    let index = bases.chars().position(|c| c == base)?;
+    composed.chars().nth(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(text: &str) -> String {
+        let (decoded, errors) = unescape(text);
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        decoded
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(decode("Hello, world"), "Hello, world");
+        assert_eq!(decode("Tom & Jerry"), "Tom & Jerry");
+        assert_eq!(decode("Café"), "Café");
+    }
+
+    #[test]
+    fn test_mnemonics() {
+        assert_eq!(decode("Caf\\'e"), "Café");
+        assert_eq!(
+            decode("\\`a \\^o \\\"u \\~n \\cc \\oa \\/o \\vs"),
+            "à ô ü ñ ç å ø š"
+        );
+        assert_eq!(decode("Stra\\sse \\AEsir"), "Straße Æsir");
+    }
+
+    #[test]
+    fn test_literal_escapes() {
+        assert_eq!(decode("100\\%"), "100%");
+        assert_eq!(decode("a\\\\b"), "a\\b");
+        assert_eq!(decode("say \\\"hi\\\""), "say \"hi\"");
+        assert_eq!(decode("\\&amp;"), "&amp;");
+        assert_eq!(decode("[a\\]b]"), "[a]b]");
+    }
+
+    #[test]
+    fn test_unicode() {
+        assert_eq!(decode("\\u00e9"), "é");
+        assert_eq!(decode("\\U0001F3B5"), "🎵");
+    }
+
+    #[test]
+    fn test_entities() {
+        assert_eq!(decode("R&amp;B"), "R&B");
+        assert_eq!(decode("&eacute;t&eacute;"), "été");
+        assert_eq!(decode("&#233;&#xE9;"), "éé");
+        assert_eq!(decode("&AElig;&szlig;"), "Æß");
+    }
+
+    #[test]
+    fn test_malformed() {
+        let (decoded, errors) = unescape("a\\qb");
+        assert_eq!(decoded, "a\\qb");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].range,
+            TextRange::new(TextSize::new(1), TextSize::new(3))
+        );
+
+        let (decoded, errors) = unescape("\\'x");
+        assert_eq!(decoded, "\\'x");
+        assert_eq!(
+            errors[0].range,
+            TextRange::new(TextSize::new(0), TextSize::new(3))
+        );
+
+        let (_, errors) = unescape("\\u00g9");
+        assert_eq!(errors.len(), 1);
+
+        let (_, errors) = unescape("&bogus;");
+        assert_eq!(errors[0].message, "unknown entity '&bogus;'");
+
+        let (_, errors) = unescape("end\\");
+        assert_eq!(
+            errors[0].range,
+            TextRange::new(TextSize::new(3), TextSize::new(4))
+        );
+    }
+
+    #[test]
+    fn test_escape_round_trip() {
+        for text in [
+            "plain",
+            "100%",
+            "a\\b",
+            "\"quoted\"",
+            "\"a",
+            "R&amp;B",
+            "Tom & Jerry",
+            "Café",
+            "&",
+        ] {
+            assert_eq!(decode(&escape(text)), text, "escaped as {}", escape(text));
+        }
+    }
+}
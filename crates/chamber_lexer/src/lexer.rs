@@ -8,6 +8,14 @@ pub struct Lexer<'a> {
     position: usize,
     /// Whether we're currently in a header context (field value parsing)
     in_header: bool,
+    /// Whether the current field is an inline field (`[K:G]`), ended by `]`
+    in_inline_field: bool,
+    /// Whether we're in the value of a lyrics line (`w:`), which is text to
+    /// the end of the line
+    in_lyrics: bool,
+    /// Whether the previous token was a bar line, after which digits start
+    /// an ending (`|1`, `:|2`)
+    after_bar: bool,
 }
 
 impl<'a> Lexer<'a> {
@@ -17,6 +25,9 @@ impl<'a> Lexer<'a> {
             source,
             position: 0,
             in_header: false,
+            in_inline_field: false,
+            in_lyrics: false,
+            after_bar: false,
         }
     }
 
@@ -50,6 +61,8 @@ impl<'a> Lexer<'a> {
             // Newline
             '\n' => {
                 self.in_header = false;
+                self.in_inline_field = false;
+                self.in_lyrics = false;
                 TokenKind::Newline
             }
             '\r' => {
@@ -57,28 +70,56 @@ impl<'a> Lexer<'a> {
                     self.advance();
                 }
                 self.in_header = false;
+                self.in_inline_field = false;
+                self.in_lyrics = false;
                 TokenKind::Newline
             }
 
             // Comment
             '%' => self.comment(),
 
+            // Lyrics (w: Hap-py birth-day|to you), where `|`, `-` and `_`
+            // are not music
+            _ if self.in_lyrics => self.text(),
+
+            // Escaped character in header text (e.g. T:\'Ecole)
+            '\\' if self.in_header => {
+                self.escaped_char();
+                self.text()
+            }
+
             // Line continuation
             '\\' => TokenKind::LineContinuation,
 
             // Colon - context switch to header
             ':' => {
+                // Lyrics may start with a bar (w:|), which is not a repeat
+                if !self.in_header && self.follows_lyrics_label(start) {
+                    self.in_header = true;
+                    self.in_lyrics = true;
+                    TokenKind::Colon
+                }
                 // Check for repeat markers like :| or : |
-                if self.has_bar_ahead() {
+                else if self.has_bar_ahead() {
                     // Skip whitespace before bar
                     while self.peek().map(|c| c == ' ' || c == '\t').unwrap_or(false) {
                         self.advance();
                     }
                     // Consume the |
                     self.advance();
-                    TokenKind::RepeatEnd
+                    // :|: ends one repeat and starts the next
+                    if self.peek() == Some(':') {
+                        self.advance();
+                        TokenKind::RepeatBoth
+                    } else {
+                        TokenKind::RepeatEnd
+                    }
+                } else if self.peek() == Some(':') {
+                    self.advance();
+                    TokenKind::RepeatBoth
                 } else {
                     self.in_header = true;
+                    self.in_inline_field = self.follows_inline_label(start);
                     TokenKind::Colon
                 }
             }
@@ -92,11 +133,20 @@ impl<'a> Lexer<'a> {
                 if self.peek() == Some('|') {
                     self.advance();
                     TokenKind::ThickThinBar
+                } else if self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    self.ending()
                 } else {
                     TokenKind::LeftBracket
                 }
             }
-            ']' => TokenKind::RightBracket,
+            ']' => {
+                // An empty inline field like [M:] ends here
+                if self.in_inline_field {
+                    self.in_header = false;
+                    self.in_inline_field = false;
+                }
+                TokenKind::RightBracket
+            }
 
             // Parentheses
             '(' => {
@@ -149,6 +199,8 @@ impl<'a> Lexer<'a> {
             '0'..='9' => {
                 if self.in_header {
                     self.text()
+                } else if self.after_bar {
+                    self.ending()
                 } else {
                     while self.peek().map(|c| c.is_ascii_digit()).unwrap_or(false) {
                         self.advance();
@@ -177,18 +229,30 @@ impl<'a> Lexer<'a> {
                 }
             }
 
-            // Rest
+            // Lyrics field label, only at the start of a line
+            'w' if !self.in_header
+                && self.starts_line(start)
+                && self.source[self.position..]
+                    .trim_start_matches([' ', '\t'])
+                    .starts_with(':') =>
+            {
+                TokenKind::FieldLabel
+            }
+
+            // Rest (uppercase can also be the Z: transcription field label)
             'z' | 'Z' => {
                 if self.in_header {
                     self.text()
+                } else if c == 'Z' && self.has_colon_ahead() {
+                    TokenKind::FieldLabel
                 } else {
                     TokenKind::Rest
                 }
             }
 
-            // Field labels (uppercase letters that are not notes: H, I, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y)
-            'H' | 'I' | 'K' | 'L' | 'M' | 'N' | 'O' | 'P' | 'Q' | 'R' | 'S' | 'T' | 'U' | 'V'
-            | 'W' | 'X' | 'Y' => {
+            // Field labels (uppercase letters that are not notes: H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y)
+            'H' | 'I' | 'J' | 'K' | 'L' | 'M' | 'N' | 'O' | 'P' | 'Q' | 'R' | 'S' | 'T' | 'U'
+            | 'V' | 'W' | 'X' | 'Y' => {
                 if self.in_header {
                     self.text()
                 } else if self.has_colon_ahead() {
@@ -224,6 +288,10 @@ impl<'a> Lexer<'a> {
             // Unknown character
             _ => TokenKind::Error,
         };
+        self.after_bar = matches!(
+            kind,
+            TokenKind::Bar | TokenKind::RepeatEnd | TokenKind::DoubleBar
+        );
 
         Token::new(
             kind,
@@ -252,11 +320,9 @@ impl<'a> Lexer<'a> {
         while let Some(c) = chars.next() {
             match c {
                 ':' => {
-                    // Check if this is ":| " (repeat end) - not a field label
-                    if chars.peek() == Some(&'|') {
-                        return false;
-                    }
-                    return true;
+                    // Check if this is ":| " (repeat end) or "::" (repeat end
+                    // and start) - not a field label
+                    return !matches!(chars.peek(), Some('|' | ':'));
                 }
                 ' ' | '\t' => continue,
                 _ => return false,
@@ -307,6 +373,29 @@ impl<'a> Lexer<'a> {
         false
     }
 
+    /// Check if the colon at `colon` follows an inline field label like "[K" or "[ K ".
+    fn follows_inline_label(&self, colon: usize) -> bool {
+        let before = self.source[..colon].trim_end_matches([' ', '\t']);
+        match before.strip_suffix(|c: char| c.is_ascii_uppercase()) {
+            Some(rest) => rest.trim_end_matches([' ', '\t']).ends_with('['),
+            None => false,
+        }
+    }
+
+    /// Whether the `:` at `colon` follows a `w` label that starts a line.
+    fn follows_lyrics_label(&self, colon: usize) -> bool {
+        let before = self.source[..colon].trim_end_matches([' ', '\t']);
+        match before.strip_suffix('w') {
+            Some(rest) => self.starts_line(rest.len()),
+            None => false,
+        }
+    }
+
+    /// Whether `position` is at the start of a line.
+    fn starts_line(&self, position: usize) -> bool {
+        self.source[..position].is_empty() || self.source[..position].ends_with(['\n', '\r'])
+    }
+
     fn advance(&mut self) -> char {
         let c = self.source[self.position..].chars().next().unwrap();
         self.position += c.len_utf8();
@@ -369,16 +458,38 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Lexes the rest of an ending after `[` or its first digit: numbers
+    /// separated by `,` or `-` (`[1`, `2`, `[1,3`, `[1-3`).
+    fn ending(&mut self) -> TokenKind {
+        loop {
+            while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                self.advance();
+            }
+            let mut rest = self.source[self.position..].chars();
+            let separator = matches!(rest.next(), Some(',' | '-'));
+            if !separator || !rest.next().is_some_and(|c| c.is_ascii_digit()) {
+                return TokenKind::Ending;
+            }
+            self.advance();
+        }
+    }
+
     fn text(&mut self) -> TokenKind {
         // Consume text until we hit a delimiter
         while let Some(c) = self.peek() {
             match c {
                 '\n' | '\r' | '%' => break,
                 // Stop at ] for inline fields
-                ']' => {
+                ']' if self.in_inline_field => {
                     self.in_header = false;
+                    self.in_inline_field = false;
                     break;
                 }
+                // Escapes such as \% don't end the text
+                '\\' => {
+                    self.advance();
+                    self.escaped_char();
+                }
                 _ => {
                     self.advance();
                 }
@@ -387,26 +498,47 @@ impl<'a> Lexer<'a> {
         TokenKind::Text
     }
 
+    /// Consumes the character following a backslash, unless it ends the line.
+    fn escaped_char(&mut self) {
+        if self.peek().is_some_and(|c| c != '\n' && c != '\r') {
+            self.advance();
+        }
+    }
+
     fn decoration(&mut self, delimiter: char) -> TokenKind {
-        // Consume decoration name until matching delimiter
+        // Consume decoration name until matching delimiter. Names have no
+        // escapes, and one containing '!' is written +name+ instead.
+        let mut first_word_end = None;
         while let Some(c) = self.peek() {
             if c == delimiter {
                 self.advance(); // consume closing delimiter
                 return TokenKind::Decoration;
             }
             if c == '\n' || c == '\r' {
-                // Unterminated decoration
-                return TokenKind::Error;
+                break;
+            }
+            if c.is_whitespace() && first_word_end.is_none() {
+                first_word_end = Some(self.position);
             }
             self.advance();
         }
-        // Unterminated decoration (reached EOF)
+        // Unterminated decoration (end of line or EOF): only the delimiter and
+        // the word after it are an error, so the music after them (or after
+        // a lone '!' line break) is still read
+        if let Some(end) = first_word_end {
+            self.position = end;
+        }
         TokenKind::Error
     }
 
     fn annotation(&mut self) -> TokenKind {
-        // Consume annotation text until closing double quote
+        // Consume annotation text until closing double quote (\" does not close it)
         while let Some(c) = self.peek() {
+            if c == '\\' {
+                self.advance();
+                self.escaped_char();
+                continue;
+            }
             if c == '"' {
                 self.advance(); // consume closing quote
                 return TokenKind::Annotation;
@@ -1,7 +1,9 @@
 mod cst_lexer;
+mod escape;
 mod lexer;
 mod token;
 
 pub use cst_lexer::tokenize_cst;
+pub use escape::{escape, unescape, EscapeError};
 pub use lexer::{token_text, Lexer};
 pub use token::{Token, TokenKind};
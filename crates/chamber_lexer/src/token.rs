@@ -56,10 +56,14 @@ pub enum TokenKind {
     RepeatStart,
     /// Repeat end (:|)
     RepeatEnd,
+    /// Repeat end and start (:: or :|:)
+    RepeatBoth,
     /// Thin-thick double bar (|])
     ThinThickBar,
     /// Thick-thin double bar ([|)
     ThickThinBar,
+    /// Start of an ending ([1, |2, [1,3)
+    Ending,
 
     // Grouping
     /// Left bracket ([) for chords
@@ -142,8 +146,10 @@ impl TokenKind {
             TokenKind::DoubleBar => SyntaxKind::DOUBLE_BAR,
             TokenKind::RepeatStart => SyntaxKind::REPEAT_START,
             TokenKind::RepeatEnd => SyntaxKind::REPEAT_END,
+            TokenKind::RepeatBoth => SyntaxKind::REPEAT_BOTH,
             TokenKind::ThinThickBar => SyntaxKind::THIN_THICK_BAR,
             TokenKind::ThickThinBar => SyntaxKind::THICK_THIN_BAR,
+            TokenKind::Ending => SyntaxKind::ENDING,
             TokenKind::LeftBracket => SyntaxKind::L_BRACKET,
             TokenKind::RightBracket => SyntaxKind::R_BRACKET,
             TokenKind::LeftParen => SyntaxKind::L_PAREN,
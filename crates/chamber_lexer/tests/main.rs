@@ -127,6 +127,15 @@ fn test_rest() {
     );
 }
 
+#[test]
+fn test_transcription_field_label() {
+    // `Z:` is a field label, `Z:|` a multi-measure rest before a repeat end
+    let tokens = tokenize_with_text("Z:me\nZ:|");
+    assert_eq!(tokens[0], (TokenKind::FieldLabel, "Z"));
+    assert_eq!(tokens[tokens.len() - 3], (TokenKind::Rest, "Z"));
+    assert_eq!(tokens[tokens.len() - 2], (TokenKind::RepeatEnd, ":|"));
+}
+
 #[test]
 fn test_octave_modifiers() {
     let tokens = tokenize("C'D,");
@@ -204,6 +213,38 @@ fn test_repeat_start_with_space() {
     assert_eq!(tokens, vec![TokenKind::RepeatStart, TokenKind::Eof]);
 }
 
+#[test]
+fn test_repeat_both() {
+    assert_eq!(tokenize("::"), vec![TokenKind::RepeatBoth, TokenKind::Eof]);
+    assert_eq!(tokenize(":|:"), vec![TokenKind::RepeatBoth, TokenKind::Eof]);
+}
+
+#[test]
+fn test_endings() {
+    assert_eq!(
+        tokenize_with_text("|1 C:|2 D|[1,3 E|[2-4 F"),
+        vec![
+            (TokenKind::Bar, "|"),
+            (TokenKind::Ending, "1"),
+            (TokenKind::Whitespace, " "),
+            (TokenKind::Note, "C"),
+            (TokenKind::RepeatEnd, ":|"),
+            (TokenKind::Ending, "2"),
+            (TokenKind::Whitespace, " "),
+            (TokenKind::Note, "D"),
+            (TokenKind::Bar, "|"),
+            (TokenKind::Ending, "[1,3"),
+            (TokenKind::Whitespace, " "),
+            (TokenKind::Note, "E"),
+            (TokenKind::Bar, "|"),
+            (TokenKind::Ending, "[2-4"),
+            (TokenKind::Whitespace, " "),
+            (TokenKind::Note, "F"),
+            (TokenKind::Eof, ""),
+        ]
+    );
+}
+
 #[test]
 fn test_simple_bar() {
     let tokens = tokenize("|C|D|");
@@ -386,6 +427,146 @@ fn test_full_tune_header() {
     );
 }
 
+#[test]
+fn test_unterminated_decoration() {
+    assert_eq!(
+        tokenize_with_text("C!trill D|"),
+        vec![
+            (TokenKind::Note, "C"),
+            (TokenKind::Error, "!trill"),
+            (TokenKind::Whitespace, " "),
+            (TokenKind::Note, "D"),
+            (TokenKind::Bar, "|"),
+            (TokenKind::Eof, ""),
+        ]
+    );
+    // A '!' at the end of a line (an old-style line break)
+    assert_eq!(
+        tokenize("C!\nD"),
+        vec![
+            TokenKind::Note,
+            TokenKind::Error,
+            TokenKind::Newline,
+            TokenKind::Note,
+            TokenKind::Eof
+        ]
+    );
+    // The first closing delimiter ends a decoration; '+' names may hold '!'
+    assert_eq!(
+        tokenize_with_text("!+!+f!+C"),
+        vec![
+            (TokenKind::Decoration, "!+!"),
+            (TokenKind::Decoration, "+f!+"),
+            (TokenKind::Note, "C"),
+            (TokenKind::Eof, ""),
+        ]
+    );
+}
+
+#[test]
+fn test_annotation_with_escaped_quote() {
+    let tokens = tokenize_with_text("\"a \\\"b\\\"\"C");
+    assert_eq!(
+        tokens,
+        vec![
+            (TokenKind::Annotation, "\"a \\\"b\\\"\""),
+            (TokenKind::Note, "C"),
+            (TokenKind::Eof, ""),
+        ]
+    );
+}
+
+#[test]
+fn test_header_text_escapes() {
+    // \% does not start a comment, and a leading escape is still text
+    let tokens = tokenize_with_text("T:\\'Ecole 100\\% % comment");
+    assert_eq!(
+        tokens,
+        vec![
+            (TokenKind::FieldLabel, "T"),
+            (TokenKind::Colon, ":"),
+            (TokenKind::Text, "\\'Ecole 100\\% "),
+            (TokenKind::Comment, "% comment"),
+            (TokenKind::Eof, ""),
+        ]
+    );
+}
+
+#[test]
+fn test_header_text_with_bracket() {
+    // Only inline fields end at ]
+    let tokens = tokenize_with_text("T:Reel [2]\n[K:G]A");
+    assert_eq!(
+        tokens,
+        vec![
+            (TokenKind::FieldLabel, "T"),
+            (TokenKind::Colon, ":"),
+            (TokenKind::Text, "Reel [2]"),
+            (TokenKind::Newline, "\n"),
+            (TokenKind::LeftBracket, "["),
+            (TokenKind::FieldLabel, "K"),
+            (TokenKind::Colon, ":"),
+            (TokenKind::Text, "G"),
+            (TokenKind::RightBracket, "]"),
+            (TokenKind::Note, "A"),
+            (TokenKind::Eof, ""),
+        ]
+    );
+}
+
+#[test]
+fn test_empty_inline_field() {
+    let tokens = tokenize("[M:]A");
+    assert_eq!(
+        tokens,
+        vec![
+            TokenKind::LeftBracket,
+            TokenKind::FieldLabel,
+            TokenKind::Colon,
+            TokenKind::RightBracket,
+            TokenKind::Note,
+            TokenKind::Eof
+        ]
+    );
+}
+
+#[test]
+fn test_lyrics_line() {
+    // `|`, `-` and `_` in lyrics are text, up to a comment
+    let tokens = tokenize_with_text("A2-A2|\nw:_ Hap-py|day % verse 1\nw");
+    assert_eq!(
+        tokens,
+        vec![
+            (TokenKind::Note, "A"),
+            (TokenKind::NoteLength, "2"),
+            (TokenKind::Tie, "-"),
+            (TokenKind::Note, "A"),
+            (TokenKind::NoteLength, "2"),
+            (TokenKind::Bar, "|"),
+            (TokenKind::Newline, "\n"),
+            (TokenKind::FieldLabel, "w"),
+            (TokenKind::Colon, ":"),
+            (TokenKind::Text, "_ Hap-py|day "),
+            (TokenKind::Comment, "% verse 1"),
+            (TokenKind::Newline, "\n"),
+            (TokenKind::Error, "w"),
+            (TokenKind::Eof, ""),
+        ]
+    );
+
+    // A bar at the start of the lyrics is not a repeat
+    let tokens = tokenize("w:|la");
+    assert_eq!(
+        tokens,
+        vec![
+            TokenKind::FieldLabel,
+            TokenKind::Colon,
+            TokenKind::Text,
+            TokenKind::Eof
+        ]
+    );
+}
+
 #[test]
 fn test_line_continuation() {
     let tokens = tokenize("C\\\nD");
@@ -55,7 +55,7 @@ impl<'a> CstParser<'a> {
     }
 
     fn check_any(&self, kinds: &[SyntaxKind]) -> bool {
-        self.current_kind().map_or(false, |k| kinds.contains(&k))
+        self.current_kind().is_some_and(|k| kinds.contains(&k))
     }
 
     fn eat(&mut self, kind: SyntaxKind) -> Option<CstToken> {
@@ -114,15 +114,9 @@ impl<'a> CstParser<'a> {
             children.push(CstChild::Token(colon));
         }
 
-        // Field value (TEXT tokens until newline)
-        while !self.is_at_end() {
-            // TEXT token contains the value
-            if let Some(text) = self.eat(SyntaxKind::TEXT) {
-                children.push(CstChild::Token(text));
-                break; // TEXT consumes until newline
-            } else {
-                break;
-            }
+        // Field value (a single TEXT token consumes until newline)
+        if let Some(text) = self.eat(SyntaxKind::TEXT) {
+            children.push(CstChild::Token(text));
         }
 
         CstNode::with_children(SyntaxKind::HEADER_FIELD, children)
@@ -162,9 +156,19 @@ impl<'a> CstParser<'a> {
             | SyntaxKind::DOUBLE_BAR
             | SyntaxKind::REPEAT_START
             | SyntaxKind::REPEAT_END
+            | SyntaxKind::REPEAT_BOTH
             | SyntaxKind::THIN_THICK_BAR
             | SyntaxKind::THICK_THIN_BAR => Some(CstChild::Node(self.parse_bar_line())),
 
+            // Ending
+            SyntaxKind::ENDING => {
+                let token = self.advance()?;
+                Some(CstChild::Node(CstNode::with_children(
+                    SyntaxKind::ENDING_NODE,
+                    vec![CstChild::Token(token)],
+                )))
+            }
+
             // Chord
             SyntaxKind::L_BRACKET => Some(CstChild::Node(self.parse_chord_or_inline_field())),
 
@@ -328,7 +332,7 @@ impl<'a> CstParser<'a> {
     fn parse_chord_or_inline_field(&mut self) -> CstNode {
         // Peek ahead to determine if this is an inline field [M:3/4] or chord [CEG]
         // For now, simplified: check if second token is FIELD_LABEL
-        let is_inline_field = self.tokens.get(self.position + 1).map_or(false, |t| {
+        let is_inline_field = self.tokens.get(self.position + 1).is_some_and(|t| {
             t.kind() == SyntaxKind::FIELD_LABEL || t.kind() == SyntaxKind::TEXT
         });
 
@@ -5,10 +5,11 @@
 
 use chamber_ast::{
     Accidental, Annotation, BarLine, BarLineKind, Body, BrokenRhythm, Chord, Decoration, Duration,
-    GraceNotes, Header, HeaderField, HeaderFieldKind, InlineField, MusicElement, Note, Pitch, Rest,
-    Slur, Tie, Tune, Tuplet,
+    Ending, GraceNotes, Header, HeaderField, HeaderFieldKind, InlineField, MusicElement, Note,
+    Pitch, Rest, Slur, Tie, Tune, Tuplet,
 };
 use chamber_cst::{CstChild, CstNode, CstToken};
+use chamber_lexer::unescape;
 use chamber_syntax::SyntaxKind;
 use chamber_text_size::TextRange;
 
@@ -60,7 +61,9 @@ fn convert_header_field(cst: &CstNode, source: &str) -> HeaderField {
         .map(|t| t.text(source).chars().next().unwrap_or('?'))
         .unwrap_or('?');
 
-    let value = text_token.map(|t| t.text(source).trim().to_string()).unwrap_or_default();
+    let value = text_token
+        .map(|t| unescape(t.text(source).trim()).0)
+        .unwrap_or_default();
 
     HeaderField {
         kind: HeaderFieldKind::from_char(label_char),
@@ -98,6 +101,7 @@ fn convert_music_element(child: &CstChild, source: &str) -> Option<MusicElement>
                 Some(MusicElement::BrokenRhythm(convert_broken_rhythm(node, source)))
             }
             SyntaxKind::TIE_NODE => Some(MusicElement::Tie(convert_tie(node))),
+            SyntaxKind::ENDING_NODE => Some(MusicElement::Ending(convert_ending(node, source))),
             SyntaxKind::INLINE_FIELD => {
                 Some(MusicElement::InlineField(convert_inline_field(node, source)))
             }
@@ -120,8 +124,8 @@ fn convert_note(cst: &CstNode, source: &str) -> Note {
             let text = t.text(source);
             // Remove the delimiters (! or +)
             let name = text
-                .trim_start_matches(|c| c == '!' || c == '+')
-                .trim_end_matches(|c| c == '!' || c == '+')
+                .trim_start_matches(['!', '+'])
+                .trim_end_matches(['!', '+'])
                 .to_string();
             Decoration::new(name, t.range())
         })
@@ -227,8 +231,8 @@ fn convert_rest(cst: &CstNode, source: &str) -> Rest {
         .map(|t| {
             let text = t.text(source);
             let name = text
-                .trim_start_matches(|c| c == '!' || c == '+')
-                .trim_end_matches(|c| c == '!' || c == '+')
+                .trim_start_matches(['!', '+'])
+                .trim_end_matches(['!', '+'])
                 .to_string();
             Decoration::new(name, t.range())
         })
@@ -259,8 +263,8 @@ fn convert_chord(cst: &CstNode, source: &str) -> Chord {
         .map(|t| {
             let text = t.text(source);
             let name = text
-                .trim_start_matches(|c| c == '!' || c == '+')
-                .trim_end_matches(|c| c == '!' || c == '+')
+                .trim_start_matches(['!', '+'])
+                .trim_end_matches(['!', '+'])
                 .to_string();
             Decoration::new(name, t.range())
         })
@@ -292,6 +296,7 @@ fn convert_bar_line(cst: &CstNode, _source: &str) -> BarLine {
             SyntaxKind::DOUBLE_BAR => BarLineKind::Double,
             SyntaxKind::REPEAT_START => BarLineKind::RepeatStart,
             SyntaxKind::REPEAT_END => BarLineKind::RepeatEnd,
+            SyntaxKind::REPEAT_BOTH => BarLineKind::RepeatBoth,
             SyntaxKind::THIN_THICK_BAR => BarLineKind::ThinThick,
             SyntaxKind::THICK_THIN_BAR => BarLineKind::ThickThin,
             _ => BarLineKind::Single,
@@ -371,6 +376,14 @@ fn convert_tie(cst: &CstNode) -> Tie {
     Tie { range: cst.range() }
 }
 
+fn convert_ending(cst: &CstNode, source: &str) -> Ending {
+    let text = cst.first_token().map(|t| t.text(source)).unwrap_or("");
+    Ending {
+        numbers: Ending::parse_numbers(text),
+        range: cst.range(),
+    }
+}
+
 fn convert_inline_field(cst: &CstNode, source: &str) -> InlineField {
     // Find field label token inside brackets
     let label = cst
@@ -383,7 +396,7 @@ fn convert_inline_field(cst: &CstNode, source: &str) -> InlineField {
     let value = cst
         .child_tokens()
         .find(|t| t.kind() == SyntaxKind::TEXT)
-        .map(|t| t.text(source).trim().to_string())
+        .map(|t| unescape(t.text(source).trim()).0)
         .unwrap_or_default();
 
     InlineField {
@@ -399,9 +412,9 @@ fn convert_annotation(cst: &CstNode, source: &str) -> Annotation {
         .map(|t| {
             let raw = t.text(source);
             // Remove surrounding double quotes
-            raw.trim_start_matches('"')
-                .trim_end_matches('"')
-                .to_string()
+            let raw = raw.strip_prefix('"').unwrap_or(raw);
+            let raw = raw.strip_suffix('"').unwrap_or(raw);
+            unescape(raw).0
         })
         .unwrap_or_default();
 
@@ -450,6 +463,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_convert_decodes_escapes() {
+        let source = "X:1\nT:Caf\\'e\nK:C\n\"a \\\"b\\\"\"C";
+        let cst = parse_cst(source);
+        let ast = cst_to_ast(&cst, source);
+
+        assert_eq!(ast.header.fields[1].value, "Café");
+        match &ast.body.elements[0] {
+            MusicElement::Annotation(ann) => assert_eq!(ann.text, "a \"b\""),
+            other => panic!("Expected Annotation at 0, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_convert_note_pitch() {
         let source = "X:1\nK:C\nC";
@@ -2,8 +2,10 @@ pub mod ast;
 mod cst_parser;
 mod cst_to_ast;
 mod parser;
+mod stream;
 
 pub use ast::*;
 pub use cst_parser::parse_cst;
 pub use cst_to_ast::cst_to_ast;
 pub use parser::{parse, parse_with_diagnostics, ParseResult, Parser};
+pub use stream::{parse_collection, StreamedTune, TuneSource, TuneSources, TuneStream};
@@ -1,5 +1,7 @@
-use chamber_diagnostics::{Diagnostic, DiagnosticBag, DiagnosticCode, DiagnosticSink};
-use chamber_lexer::{token_text, Lexer, Token, TokenKind};
+use chamber_diagnostics::{
+    Applicability, Diagnostic, DiagnosticBag, DiagnosticCode, DiagnosticSink, Fix, TextEdit,
+};
+use chamber_lexer::{token_text, unescape, Lexer, Token, TokenKind};
 use chamber_text_size::{TextRange, TextSize};
 use serde::{Deserialize, Serialize};
 
@@ -109,11 +111,18 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
 
         // H001: Missing X:
         if x_fields.is_empty() {
-            self.report(Diagnostic::error(
-                DiagnosticCode::MissingReferenceNumber,
-                header.range,
-                "missing reference number field (X:)",
-            ));
+            self.report(
+                Diagnostic::error(
+                    DiagnosticCode::MissingReferenceNumber,
+                    header.range,
+                    "missing reference number field (X:)",
+                )
+                .with_fix(Fix::edit(
+                    "insert 'X:1'",
+                    TextEdit::insert(header.range.start(), "X:1\n"),
+                    Applicability::MachineApplicable,
+                )),
+            );
         }
 
         // H003: Duplicate X:
@@ -132,11 +141,29 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
 
         // H002: Missing K:
         if k_field.is_none() {
-            self.report(Diagnostic::error(
-                DiagnosticCode::MissingKeyField,
-                header.range,
-                "missing key field (K:)",
-            ));
+            // K: ends the header, so it goes after the last field
+            let offset = match fields.last() {
+                Some(last) => self.line_end(last.range.start()),
+                None => header.range.start(),
+            };
+            let before = &self.source[..offset.raw() as usize];
+            let text = if before.is_empty() || before.ends_with('\n') {
+                "K:C\n"
+            } else {
+                "\nK:C\n"
+            };
+            self.report(
+                Diagnostic::error(
+                    DiagnosticCode::MissingKeyField,
+                    header.range,
+                    "missing key field (K:)",
+                )
+                .with_fix(Fix::edit(
+                    "insert 'K:C'",
+                    TextEdit::insert(offset, text),
+                    Applicability::MaybeIncorrect,
+                )),
+            );
         }
 
         // H009: Missing T: (warning)
@@ -154,15 +181,13 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
                 HeaderFieldKind::ReferenceNumber => {
                     self.validate_reference_number(field);
                 }
-                HeaderFieldKind::Title => {
-                    if field.value.trim().is_empty() {
-                        // H010: Empty T:
-                        self.report(Diagnostic::warning(
-                            DiagnosticCode::EmptyTitle,
-                            field.range,
-                            "empty title field",
-                        ));
-                    }
+                HeaderFieldKind::Title if field.value.trim().is_empty() => {
+                    // H010: Empty T:
+                    self.report(Diagnostic::warning(
+                        DiagnosticCode::EmptyTitle,
+                        field.range,
+                        "empty title field",
+                    ));
                 }
                 HeaderFieldKind::Meter => {
                     self.validate_meter(field);
@@ -183,11 +208,29 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
         // H004: Check field order (X: should be first)
         if let Some(first) = fields.first() {
             if first.kind != HeaderFieldKind::ReferenceNumber {
-                self.report(Diagnostic::warning(
+                let mut diagnostic = Diagnostic::warning(
                     DiagnosticCode::InvalidFieldOrder,
                     first.range,
                     "X: (reference number) should be the first field in the header",
-                ));
+                );
+                if let Some(x_field) = x_fields.first() {
+                    // Move the X: line in front of the first field
+                    let start = x_field.range.start();
+                    let line = TextRange::new(start, self.line_end(start));
+                    let mut text = self.range_text(line).to_string();
+                    if !text.ends_with('\n') {
+                        text.push('\n');
+                    }
+                    diagnostic = diagnostic.with_fix(Fix::new(
+                        "move X: to the start of the header",
+                        vec![
+                            TextEdit::insert(first.range.start(), text),
+                            TextEdit::delete(line),
+                        ],
+                        Applicability::MachineApplicable,
+                    ));
+                }
+                self.report(diagnostic);
             }
         }
     }
@@ -256,9 +299,9 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
         }
 
         // Remove quoted tempo name if present
-        let value = if value.starts_with('"') {
-            if let Some(end_quote) = value[1..].find('"') {
-                value[end_quote + 2..].trim()
+        let value = if let Some(quoted) = value.strip_prefix('"') {
+            if let Some(end_quote) = quoted.find('"') {
+                quoted[end_quote + 1..].trim()
             } else {
                 value // malformed, let it fail below
             }
@@ -424,10 +467,16 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
         while self.check(TokenKind::Error) {
             let token = self.advance().unwrap();
             let text = self.token_text(&token);
+            let message = match text.chars().next() {
+                Some(delimiter @ ('!' | '+')) => {
+                    format!("unterminated decoration, missing closing '{}'", delimiter)
+                }
+                c => format!("unexpected character '{}'", c.unwrap_or('?')),
+            };
             self.report(Diagnostic::error(
                 DiagnosticCode::UnexpectedCharacter,
                 token.range,
-                format!("unexpected character '{}'", text.chars().next().unwrap_or('?')),
+                message,
             ));
         }
     }
@@ -484,7 +533,9 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
 
         // Collect value until newline
         self.skip_whitespace_only();
+        let value_start = self.current_position();
         let value = self.collect_until_newline();
+        let value = self.unescape(&value, value_start);
 
         // Skip the newline
         self.skip_newlines();
@@ -505,6 +556,13 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
             self.skip_trivia();
             self.handle_error_tokens();
 
+            if self.check(TokenKind::FieldLabel) && self.peek_text() == "w" {
+                if let Some(lyrics) = self.parse_lyrics() {
+                    elements.push(MusicElement::Lyrics(lyrics));
+                }
+                continue;
+            }
+
             // S002: UnexpectedToken - field label in body (should be in header)
             if self.check(TokenKind::FieldLabel) {
                 let token = self.advance().unwrap();
@@ -595,8 +653,10 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
             | TokenKind::DoubleBar
             | TokenKind::RepeatStart
             | TokenKind::RepeatEnd
+            | TokenKind::RepeatBoth
             | TokenKind::ThinThickBar
             | TokenKind::ThickThinBar => self.parse_bar_line().map(MusicElement::BarLine),
+            TokenKind::Ending => self.parse_ending().map(MusicElement::Ending),
             TokenKind::LeftBracket => {
                 if self.is_inline_field() {
                     self.parse_inline_field().map(MusicElement::InlineField)
@@ -843,6 +903,7 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
             TokenKind::DoubleBar => BarLineKind::Double,
             TokenKind::RepeatStart => BarLineKind::RepeatStart,
             TokenKind::RepeatEnd => BarLineKind::RepeatEnd,
+            TokenKind::RepeatBoth => BarLineKind::RepeatBoth,
             TokenKind::ThinThickBar => BarLineKind::ThinThick,
             TokenKind::ThickThinBar => BarLineKind::ThickThin,
             _ => return None,
@@ -854,6 +915,18 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
         })
     }
 
+    fn parse_ending(&mut self) -> Option<Ending> {
+        let token = self.advance()?;
+        if token.kind != TokenKind::Ending {
+            return None;
+        }
+
+        Some(Ending {
+            numbers: Ending::parse_numbers(self.token_text(&token)),
+            range: token.range,
+        })
+    }
+
     fn parse_chord(&mut self) -> Option<Chord> {
         let start = self.current_position();
 
@@ -900,13 +973,19 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
             true
         } else {
             let end = self.current_position();
+            let close_at = notes.last().map_or(open_bracket_range.end(), |note| note.range.end());
             self.report(
                 Diagnostic::error(
                     DiagnosticCode::UnclosedChord,
                     TextRange::new(start, end),
                     "unclosed chord, missing ']'",
                 )
-                .with_label(open_bracket_range, "opening '[' here"),
+                .with_label(open_bracket_range, "opening '[' here")
+                .with_fix(Fix::edit(
+                    "insert ']'",
+                    TextEdit::insert(close_at, "]"),
+                    Applicability::MachineApplicable,
+                )),
             );
             false
         };
@@ -992,7 +1071,11 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
             self.advance();
         }
         let value_end = self.current_position();
-        let value = self.source[value_start.raw() as usize..value_end.raw() as usize].to_string();
+        let raw = &self.source[value_start.raw() as usize..value_end.raw() as usize];
+        let trimmed = raw.trim_start();
+        let offset = TextSize::new((raw.len() - trimmed.len()) as u32);
+        let trimmed = trimmed.trim_end().to_string();
+        let value = self.unescape(&trimmed, value_start + offset);
 
         // Consume ] or report error
         if self.check(TokenKind::RightBracket) {
@@ -1011,7 +1094,7 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
         let end = self.current_position();
         Some(InlineField {
             label,
-            value: value.trim().to_string(),
+            value,
             range: TextRange::new(start, end),
         })
     }
@@ -1034,6 +1117,15 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
         for _ in 0..ratio {
             self.skip_trivia();
             self.handle_error_tokens();
+            // A short tuplet ends at the next element that isn't a note,
+            // which parse_note would consume
+            let starts_note = matches!(
+                self.peek_past_decorations(),
+                Some(TokenKind::Note | TokenKind::Sharp | TokenKind::Flat | TokenKind::Natural)
+            );
+            if !starts_note {
+                break;
+            }
             if let Some(note) = self.parse_note() {
                 notes.push(note);
             } else {
@@ -1211,6 +1303,38 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
         })
     }
 
+    fn parse_lyrics(&mut self) -> Option<Lyrics> {
+        let label_token = self.advance()?;
+        self.skip_whitespace_only();
+        if !self.check(TokenKind::Colon) {
+            return None;
+        }
+        let mut end = self.advance()?.range.end();
+
+        self.skip_whitespace_only();
+        let mut text = String::new();
+        let mut syllables = Vec::new();
+        if self.check(TokenKind::Text) {
+            let token = self.advance()?;
+            text = self.token_text(&token).trim_end().to_string();
+            let start = token.range.start();
+            end = start + TextSize::new(text.len() as u32);
+            syllables = Syllable::parse_line(&text)
+                .into_iter()
+                .map(|syllable| Syllable {
+                    range: syllable.range + start,
+                    ..syllable
+                })
+                .collect();
+        }
+
+        Some(Lyrics {
+            text,
+            syllables,
+            range: TextRange::new(label_token.range.start(), end),
+        })
+    }
+
     fn parse_tie(&mut self) -> Option<Tie> {
         let token = self.advance()?;
         if token.kind != TokenKind::Tie {
@@ -1228,10 +1352,9 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
 
         let raw_text = self.token_text(&token);
         // Remove surrounding double quotes
-        let text = raw_text
-            .trim_start_matches('"')
-            .trim_end_matches('"')
-            .to_string();
+        let raw_text = raw_text.strip_prefix('"').unwrap_or(raw_text);
+        let raw_text = raw_text.strip_suffix('"').unwrap_or(raw_text).to_string();
+        let text = self.unescape(&raw_text, token.range.start() + TextSize::new(1));
 
         Some(Annotation {
             text,
@@ -1241,6 +1364,20 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
 
     // Helper methods
 
+    /// Decodes text escapes, reporting malformed ones (L002).
+    /// `start` is the source offset of `text`.
+    fn unescape(&mut self, text: &str, start: TextSize) -> String {
+        let (decoded, errors) = unescape(text);
+        for error in errors {
+            self.report(Diagnostic::warning(
+                DiagnosticCode::InvalidEscape,
+                error.range + start,
+                error.message,
+            ));
+        }
+        decoded
+    }
+
     /// Checks if we're at a recovery point (bar line or newline).
     fn is_recovery_point(&self) -> bool {
         matches!(
@@ -1250,6 +1387,7 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
                     | TokenKind::DoubleBar
                     | TokenKind::RepeatStart
                     | TokenKind::RepeatEnd
+                    | TokenKind::RepeatBoth
                     | TokenKind::Newline
                     | TokenKind::Eof
             )
@@ -1290,10 +1428,28 @@ impl<'a, S: DiagnosticSink> Parser<'a, S> {
         self.peek().map(|t| t.kind == kind).unwrap_or(false)
     }
 
+    fn peek_text(&self) -> &str {
+        self.peek()
+            .map_or("", |token| token_text(self.source, token))
+    }
+
     fn token_text(&self, token: &Token) -> &str {
         token_text(self.source, token)
     }
 
+    fn range_text(&self, range: TextRange) -> &str {
+        &self.source[range.start().raw() as usize..range.end().raw() as usize]
+    }
+
+    /// Returns the offset just past the end of the line containing `offset`.
+    fn line_end(&self, offset: TextSize) -> TextSize {
+        let start = offset.raw() as usize;
+        let end = self.source[start..]
+            .find('\n')
+            .map_or(self.source.len(), |i| start + i + 1);
+        TextSize::new(end as u32)
+    }
+
     fn skip_trivia(&mut self) {
         while let Some(token) = self.peek() {
             if token.kind.is_trivia() {
@@ -0,0 +1,300 @@
+//! Tune-by-tune parsing of ABC collections.
+//!
+//! A collection file holds many tunes separated by blank lines. Instead of
+//! tokenizing the whole file at once, [`TuneStream`] reads one tune at a
+//! time from a [`BufRead`] and parses it on its own, so memory use is
+//! bounded by the largest tune rather than the size of the file.
+//!
+//! The fields of a file header (such as `M:6/8` and `L:1/8` before the
+//! first tune) are the defaults of every tune in the file.
+
+use std::io::{self, BufRead};
+use std::sync::Arc;
+
+use chamber_diagnostics::Diagnostic;
+use chamber_text_size::TextRange;
+
+use crate::ast::{Header, HeaderField, HeaderFieldKind, Tune};
+use crate::parser::parse_with_diagnostics;
+
+/// A tune read from a collection.
+///
+/// Ranges in `tune` and `diagnostics` are relative to `source`, the text of
+/// this tune alone. Add `offset` to get a position in the whole collection.
+#[derive(Debug, Clone)]
+pub struct StreamedTune {
+    /// The parsed tune.
+    pub tune: Tune,
+    /// Diagnostics collected while parsing this tune.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Source text of this tune.
+    pub source: String,
+    /// Byte offset of `source` in the collection.
+    pub offset: usize,
+    /// Line number (0-based) of the first line of `source` in the collection.
+    pub line: u32,
+}
+
+/// The source text of one tune in a collection, not yet parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TuneSource {
+    /// Source text of this tune.
+    pub source: String,
+    /// Byte offset of `source` in the collection.
+    pub offset: usize,
+    /// Line number (0-based) of the first line of `source` in the collection.
+    pub line: u32,
+    /// The file header of the collection, whose fields the tune inherits.
+    pub file_header: Option<Arc<Header>>,
+}
+
+impl TuneSource {
+    /// Returns true if the source only has comments (e.g. a file header
+    /// made of `%abc-2.1` and `%%` directives).
+    fn is_comment_only(&self) -> bool {
+        self.source
+            .lines()
+            .all(|line| line.trim_start().starts_with('%'))
+    }
+
+    /// Returns the header of this section if it is a file header: header
+    /// fields without `X:`, `K:` or music.
+    fn file_header(&self) -> Option<Header> {
+        let starts_tune = self
+            .source
+            .lines()
+            .any(|line| line.starts_with("X:") || line.starts_with("K:"));
+        if starts_tune {
+            return None;
+        }
+        let tune = parse_with_diagnostics(&self.source).tune;
+        let is_header = !tune.header.fields.is_empty() && tune.body.elements.is_empty();
+        is_header.then_some(tune.header)
+    }
+
+    /// Adds the fields of the file header that `tune` doesn't set itself to
+    /// its header, after `X:`. Their ranges are empty, at the start of the
+    /// tune.
+    pub fn inherit(&self, tune: &mut Tune) {
+        let Some(file_header) = &self.file_header else {
+            return;
+        };
+        let fields = &tune.header.fields;
+        let inherited: Vec<_> = file_header
+            .fields
+            .iter()
+            .filter(|field| !fields.iter().any(|f| f.kind == field.kind))
+            .map(|field| HeaderField {
+                range: TextRange::default(),
+                ..field.clone()
+            })
+            .collect();
+        let at = usize::from(
+            fields
+                .first()
+                .is_some_and(|f| f.kind == HeaderFieldKind::ReferenceNumber),
+        );
+        tune.header.fields.splice(at..at, inherited);
+    }
+
+    /// Parses this tune, with the fields it inherits from the file header.
+    pub fn parse(self) -> StreamedTune {
+        let mut result = parse_with_diagnostics(&self.source);
+        self.inherit(&mut result.tune);
+        StreamedTune {
+            tune: result.tune,
+            diagnostics: result.diagnostics,
+            source: self.source,
+            offset: self.offset,
+            line: self.line,
+        }
+    }
+}
+
+/// Iterator over the source text of each tune in a collection.
+///
+/// Tunes are separated by blank lines. An `X:` line after a `K:` line also
+/// starts a new tune, so collections without blank lines still split.
+/// Sections made only of comments are skipped, unless the input has nothing
+/// else (an empty or comment-only input yields one empty tune, matching
+/// [`parse_with_diagnostics`]).
+///
+/// A leading section of header fields without `X:`, `K:` or music is the
+/// file header. It is not yielded either (unless it is all there is); the
+/// tunes after it carry it in [`TuneSource::file_header`] and inherit its
+/// fields when parsed.
+///
+/// Use this instead of [`TuneStream`] to parse the tunes elsewhere, for
+/// example on a thread pool.
+pub struct TuneSources<R> {
+    reader: R,
+    /// Line buffer reused between reads.
+    buffer: String,
+    /// An `X:` line that ended the previous tune and starts the next one.
+    carry: Option<TuneSource>,
+    /// The last comment-only section or the file header, kept in case the
+    /// input has no tunes.
+    skipped: Option<TuneSource>,
+    /// The file header, once read.
+    file_header: Option<Arc<Header>>,
+    /// Byte offset of the next line to read.
+    offset: usize,
+    /// Line number of the next line to read.
+    line: u32,
+    yielded: bool,
+    done: bool,
+}
+
+impl<R: BufRead> TuneSources<R> {
+    /// Creates an iterator reading tunes from `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: String::new(),
+            carry: None,
+            skipped: None,
+            file_header: None,
+            offset: 0,
+            line: 0,
+            yielded: false,
+            done: false,
+        }
+    }
+
+    /// Reads the lines of the next tune, or `None` at the end of input.
+    fn read_tune(&mut self) -> io::Result<Option<TuneSource>> {
+        let mut tune = self.carry.take();
+        let mut has_key = false;
+
+        loop {
+            self.buffer.clear();
+            let len = self.reader.read_line(&mut self.buffer)?;
+            if len == 0 {
+                self.done = true;
+                return Ok(tune);
+            }
+            let (offset, line) = (self.offset, self.line);
+            self.offset += len;
+            self.line += 1;
+
+            if self.buffer.trim().is_empty() {
+                if tune.is_some() {
+                    return Ok(tune);
+                }
+                continue;
+            }
+
+            let next = TuneSource {
+                source: self.buffer.clone(),
+                offset,
+                line,
+                file_header: self.file_header.clone(),
+            };
+            if self.buffer.starts_with("X:") && has_key {
+                self.carry = Some(next);
+                return Ok(tune);
+            }
+            if self.buffer.starts_with("K:") {
+                has_key = true;
+            }
+            match tune {
+                Some(ref mut tune) => tune.source.push_str(&self.buffer),
+                None => tune = Some(next),
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for TuneSources<R> {
+    type Item = io::Result<TuneSource>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done || self.carry.is_some() {
+            match self.read_tune() {
+                Ok(Some(tune)) if tune.is_comment_only() => {
+                    if self.file_header.is_none() {
+                        self.skipped = Some(tune);
+                    }
+                }
+                Ok(Some(tune)) if !self.yielded && self.file_header.is_none() => {
+                    match tune.file_header() {
+                        Some(header) => {
+                            self.file_header = Some(Arc::new(header));
+                            self.skipped = Some(tune);
+                        }
+                        None => {
+                            self.yielded = true;
+                            return Some(Ok(tune));
+                        }
+                    }
+                }
+                Ok(Some(tune)) => {
+                    self.yielded = true;
+                    return Some(Ok(tune));
+                }
+                Ok(None) => {}
+                Err(error) => {
+                    // Stop here rather than yielding a partial collection
+                    self.done = true;
+                    self.carry = None;
+                    self.yielded = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+
+        if self.yielded {
+            return None;
+        }
+        self.yielded = true;
+        Some(Ok(self.skipped.take().unwrap_or(TuneSource {
+            source: String::new(),
+            offset: 0,
+            line: 0,
+            file_header: None,
+        })))
+    }
+}
+
+/// Iterator over the tunes of a collection, parsing each one as it is read.
+///
+/// See [`TuneSources`] for how the collection is split.
+///
+/// # Example
+///
+/// ```
+/// use chamber_parser::TuneStream;
+///
+/// let collection = "X:1\nT:First\nK:C\nCDEF|\n\nX:2\nT:Second\nK:G\nGABc|\n";
+/// let titles: Vec<_> = TuneStream::new(collection.as_bytes())
+///     .map(|tune| tune.unwrap().tune.header.fields[1].value.clone())
+///     .collect();
+/// assert_eq!(titles, ["First", "Second"]);
+/// ```
+pub struct TuneStream<R> {
+    sources: TuneSources<R>,
+}
+
+impl<R: BufRead> TuneStream<R> {
+    /// Creates a stream reading tunes from `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            sources: TuneSources::new(reader),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for TuneStream<R> {
+    type Item = io::Result<StreamedTune>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.sources.next().map(|tune| tune.map(TuneSource::parse))
+    }
+}
+
+/// Parses every tune of a collection held in memory.
+///
+/// See [`TuneSources`] for how the collection is split.
+pub fn parse_collection(source: &str) -> impl Iterator<Item = StreamedTune> + '_ {
+    TuneStream::new(source.as_bytes()).map(|tune| tune.expect("reading from a string cannot fail"))
+}
@@ -0,0 +1,99 @@
+//! Tests for the fixes attached to parser diagnostics.
+
+use chamber_diagnostics::{apply_fixes, fix_until_stable, Applicability, DiagnosticCode};
+use chamber_parser::parse_with_diagnostics;
+
+/// Applies the fixes for diagnostics with `code` in one pass.
+fn fix(source: &str, code: DiagnosticCode, applicability: Applicability) -> String {
+    let diagnostics: Vec<_> = parse_with_diagnostics(source)
+        .diagnostics
+        .into_iter()
+        .filter(|d| d.code == code)
+        .collect();
+    assert!(!diagnostics.is_empty(), "no {} in {:?}", code, source);
+    apply_fixes(source, &diagnostics, applicability).source
+}
+
+#[test]
+fn missing_reference_number() {
+    let fixed = fix(
+        "T:Tune\nK:C\nCDEF|",
+        DiagnosticCode::MissingReferenceNumber,
+        Applicability::MachineApplicable,
+    );
+    assert_eq!(fixed, "X:1\nT:Tune\nK:C\nCDEF|");
+}
+
+#[test]
+fn missing_key_is_a_guess() {
+    let source = "X:1\nT:Tune\nCDEF|";
+    let fixed = fix(
+        source,
+        DiagnosticCode::MissingKeyField,
+        Applicability::MachineApplicable,
+    );
+    assert_eq!(fixed, source);
+
+    let fixed = fix(
+        source,
+        DiagnosticCode::MissingKeyField,
+        Applicability::MaybeIncorrect,
+    );
+    assert_eq!(fixed, "X:1\nT:Tune\nK:C\nCDEF|");
+}
+
+#[test]
+fn missing_key_at_end_of_input() {
+    let fixed = fix(
+        "X:1\nT:Tune",
+        DiagnosticCode::MissingKeyField,
+        Applicability::MaybeIncorrect,
+    );
+    assert_eq!(fixed, "X:1\nT:Tune\nK:C\n");
+}
+
+#[test]
+fn field_order() {
+    let fixed = fix(
+        "T:Tune\nM:4/4\nX:1\nK:C\nCDEF|",
+        DiagnosticCode::InvalidFieldOrder,
+        Applicability::MachineApplicable,
+    );
+    assert_eq!(fixed, "X:1\nT:Tune\nM:4/4\nK:C\nCDEF|");
+}
+
+#[test]
+fn field_order_without_reference_number_has_no_fix() {
+    let result = parse_with_diagnostics("T:Tune\nK:C\nCDEF|");
+    let diagnostic = result
+        .diagnostics
+        .iter()
+        .find(|d| d.code == DiagnosticCode::InvalidFieldOrder)
+        .unwrap();
+    assert!(diagnostic.fixes.is_empty());
+}
+
+#[test]
+fn unclosed_chord() {
+    let fixed = fix(
+        "X:1\nK:C\n[CEG |",
+        DiagnosticCode::UnclosedChord,
+        Applicability::MachineApplicable,
+    );
+    assert_eq!(fixed, "X:1\nK:C\n[CEG] |");
+}
+
+#[test]
+fn fixes_reach_a_fixed_point() {
+    // Missing X: and K: both insert at the start, so they take two passes
+    let fixed = fix_until_stable("CDEF|", Applicability::MaybeIncorrect, |source| {
+        parse_with_diagnostics(source).diagnostics
+    });
+    assert_eq!(fixed.source, "X:1\nK:C\nCDEF|");
+    assert_eq!(fixed.applied, 2);
+    assert!(!fixed
+        .diagnostics
+        .iter()
+        .any(|d| d.code == DiagnosticCode::MissingReferenceNumber
+            || d.code == DiagnosticCode::MissingKeyField));
+}
@@ -172,6 +172,34 @@ fn test_bar_lines() {
     assert_eq!(bar_count, 5);
 }
 
+#[test]
+fn test_repeat_both_and_endings() {
+    let tune = parse("X:1\nK:C\nC::D|1 E:|2,3 F|[4-5 G|]");
+    let elements: Vec<_> = tune
+        .body
+        .elements
+        .iter()
+        .filter_map(|element| match element {
+            MusicElement::BarLine(bar) => Some(format!("{:?}", bar.kind)),
+            MusicElement::Ending(ending) => Some(format!("{:?}", ending.numbers)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        elements,
+        [
+            "RepeatBoth",
+            "Single",
+            "[1]",
+            "RepeatEnd",
+            "[2, 3]",
+            "Single",
+            "[4, 5]",
+            "ThinThick"
+        ]
+    );
+}
+
 #[test]
 fn test_chord() {
     let tune = parse("X:1\nK:C\n[CEG]2");
@@ -317,7 +345,7 @@ CCGG|AAG2|FFEE|DDC2|
 // Diagnostic tests
 // ============================================
 
-use chamber_diagnostics::DiagnosticCode;
+use chamber_diagnostics::{DiagnosticCode, Severity};
 
 #[test]
 fn test_parse_with_diagnostics_no_errors() {
@@ -442,6 +470,23 @@ fn test_tuplet_note_mismatch_warning() {
         .diagnostics
         .iter()
         .any(|d| d.code == DiagnosticCode::TupletNoteMismatch));
+    // The bar line after a short tuplet is kept
+    assert!(matches!(
+        result.tune.body.elements[1],
+        MusicElement::BarLine(_)
+    ));
+}
+
+#[test]
+fn test_unterminated_decoration_recovery() {
+    let result = parse_with_diagnostics("X:1\nK:C\nC!trill DE|F");
+
+    assert!(result.diagnostics.iter().any(|d| {
+        d.code == DiagnosticCode::UnexpectedCharacter
+            && d.message == "unterminated decoration, missing closing '!'"
+    }));
+    // The notes and bar line after the decoration are kept
+    assert_eq!(result.tune.body.elements.len(), 5);
 }
 
 #[test]
@@ -564,6 +609,20 @@ fn test_inline_field_unit_note_length() {
     }
 }
 
+#[test]
+fn test_inline_field_escapes() {
+    let tune = parse("X:1\nK:C\n[P:A\\] 100\\%] CDEF|");
+
+    match &tune.body.elements[0] {
+        MusicElement::InlineField(f) => {
+            assert_eq!(f.label, 'P');
+            assert_eq!(f.value, "A] 100%");
+        }
+        other => panic!("Expected InlineField, got {:?}", other),
+    }
+    assert_eq!(tune.body.elements.len(), 6);
+}
+
 #[test]
 fn test_chord_still_works() {
     let tune = parse("X:1\nK:C\n[CEG]|");
@@ -840,6 +899,55 @@ fn test_multiple_annotations() {
     }
 }
 
+#[test]
+fn test_annotation_with_escaped_quote() {
+    let tune = parse("X:1\nK:C\n\"say \\\"hi\\\"\"C");
+    match &tune.body.elements[0] {
+        MusicElement::Annotation(ann) => {
+            assert_eq!(ann.text, "say \"hi\"");
+        }
+        other => panic!("Expected Annotation, got {:?}", other),
+    }
+    assert!(matches!(tune.body.elements[1], MusicElement::Note(_)));
+}
+
+#[test]
+fn test_text_escapes_decoded() {
+    let tune = parse("X:1\nT:Caf\\'e &amp; cr\\^epes \\u00e0 100\\%\nK:C\n\"&Eacute;t\\'e\"C");
+    assert_eq!(tune.header.fields[1].value, "Café & crêpes à 100%");
+    match &tune.body.elements[0] {
+        MusicElement::Annotation(ann) => assert_eq!(ann.text, "Été"),
+        other => panic!("Expected Annotation, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_invalid_escape_diagnostic() {
+    let source = "X:1\nT:Caf\\'q\nK:C\n\"&bogus;\"C";
+    let result = parse_with_diagnostics(source);
+    let escapes: Vec<_> = result
+        .diagnostics
+        .iter()
+        .filter(|d| d.code == DiagnosticCode::InvalidEscape)
+        .collect();
+    assert_eq!(escapes.len(), 2);
+    assert_eq!(escapes[0].severity, Severity::Warning);
+
+    let range = escapes[0].range;
+    assert_eq!(
+        &source[range.start().raw() as usize..range.end().raw() as usize],
+        "\\'q"
+    );
+    let range = escapes[1].range;
+    assert_eq!(
+        &source[range.start().raw() as usize..range.end().raw() as usize],
+        "&bogus;"
+    );
+
+    // Malformed escapes are kept as written
+    assert_eq!(result.tune.header.fields[1].value, "Caf\\'q");
+}
+
 #[test]
 fn test_annotation_no_error() {
     let result = parse_with_diagnostics("X:1\nT:Test\nK:C\n\"CM7\" C E G B |");
@@ -847,3 +955,81 @@ fn test_annotation_no_error() {
     // Should not have any errors about unexpected character
     assert!(!result.has_errors());
 }
+
+// ============================================
+// Lyrics
+// ============================================
+
+fn syllables(line: &str) -> Vec<(SyllableKind, String)> {
+    Syllable::parse_line(line)
+        .into_iter()
+        .map(|syllable| (syllable.kind, syllable.text))
+        .collect()
+}
+
+#[test]
+fn test_lyrics_line() {
+    let source = "X:1\nT:Song\nK:C\nC2 D2-D2|E4|\nw: Hap-py _|day % verse 1\nF";
+    let result = parse_with_diagnostics(source);
+    assert!(result.diagnostics.is_empty());
+
+    let tune = result.tune;
+    assert_eq!(tune.body.elements.len(), 9);
+    match &tune.body.elements[7] {
+        MusicElement::Lyrics(lyrics) => {
+            assert_eq!(lyrics.text, "Hap-py _|day");
+            let kinds: Vec<_> = lyrics.syllables.iter().map(|s| s.kind).collect();
+            assert_eq!(
+                kinds,
+                [
+                    SyllableKind::Syllable,
+                    SyllableKind::Syllable,
+                    SyllableKind::Hold,
+                    SyllableKind::BarLine,
+                    SyllableKind::Syllable,
+                ]
+            );
+            let range = lyrics.syllables[1].range;
+            assert_eq!(
+                &source[range.start().raw() as usize..range.end().raw() as usize],
+                "py"
+            );
+            let range = lyrics.range;
+            assert_eq!(
+                &source[range.start().raw() as usize..range.end().raw() as usize],
+                "w: Hap-py _|day"
+            );
+        }
+        other => panic!("Expected Lyrics, got {:?}", other),
+    }
+    assert!(matches!(tune.body.elements[8], MusicElement::Note(_)));
+}
+
+#[test]
+fn test_lyrics_syllables() {
+    use SyllableKind::*;
+    assert_eq!(
+        syllables("birth-day~to you"),
+        [
+            (Syllable, "birth".to_string()),
+            (Syllable, "day to".to_string()),
+            (Syllable, "you".to_string()),
+        ]
+    );
+    // A hyphen after a space or another hyphen takes a note of its own
+    assert_eq!(
+        syllables("ha--ppy * -"),
+        [
+            (Syllable, "ha".to_string()),
+            (Hyphen, String::new()),
+            (Syllable, "ppy".to_string()),
+            (Skip, String::new()),
+            (Hyphen, String::new()),
+        ]
+    );
+    assert_eq!(syllables("x\\-ray"), [(Syllable, "x-ray".to_string())]);
+    assert_eq!(
+        syllables("|_"),
+        [(BarLine, String::new()), (Hold, String::new())]
+    );
+}
@@ -0,0 +1,212 @@
+//! Integration tests for tune-by-tune parsing of collections
+
+use std::io::{self, BufRead, BufReader, Read};
+
+use chamber_diagnostics::DiagnosticCode;
+use chamber_parser::{parse_collection, HeaderFieldKind, StreamedTune, TuneStream};
+use chamber_text_size::TextRange;
+
+fn titles(tunes: &[StreamedTune]) -> Vec<&str> {
+    tunes
+        .iter()
+        .map(|t| {
+            t.tune
+                .header
+                .fields
+                .iter()
+                .find(|f| f.kind == HeaderFieldKind::Title)
+                .map_or("", |f| f.value.as_str())
+        })
+        .collect()
+}
+
+// ============================================
+// Splitting
+// ============================================
+
+#[test]
+fn splits_on_blank_lines() {
+    let source = "X:1\nT:One\nK:C\nCDEF|\n\n\nX:2\nT:Two\nK:G\nGABc|\n";
+    let tunes: Vec<_> = parse_collection(source).collect();
+
+    assert_eq!(titles(&tunes), ["One", "Two"]);
+    assert_eq!(tunes[1].source, "X:2\nT:Two\nK:G\nGABc|\n");
+    assert!(tunes.iter().all(|t| t.diagnostics.is_empty()));
+}
+
+#[test]
+fn splits_on_reference_number_after_key() {
+    let source = "X:1\nT:One\nK:C\nCDEF|\nX:2\nT:Two\nK:G\nGABc|\n";
+    let tunes: Vec<_> = parse_collection(source).collect();
+
+    assert_eq!(titles(&tunes), ["One", "Two"]);
+}
+
+#[test]
+fn duplicate_reference_number_in_header_stays_one_tune() {
+    let tunes: Vec<_> = parse_collection("X:1\nX:2\nT:Dup\nK:C\nC|\n").collect();
+
+    assert_eq!(tunes.len(), 1);
+    assert_eq!(
+        tunes[0].diagnostics[0].code,
+        DiagnosticCode::DuplicateReferenceNumber
+    );
+}
+
+#[test]
+fn skips_comment_only_sections() {
+    let source = "%abc-2.1\n%%pagewidth 21cm\n\nX:1\nT:One\nK:C\nC|\n\n% trailing\n";
+    let tunes: Vec<_> = parse_collection(source).collect();
+
+    assert_eq!(titles(&tunes), ["One"]);
+}
+
+#[test]
+fn file_header_fields_are_inherited() {
+    let source =
+        "%abc-2.1\nM:6/8\nL:1/8\nR:jig\n\nX:1\nT:One\nK:G\nGAG|\n\nX:2\nT:Two\nM:9/8\nK:D\nDFA|\n";
+    let tunes: Vec<_> = parse_collection(source).collect();
+
+    // The file header is not a tune of its own
+    assert_eq!(titles(&tunes), ["One", "Two"]);
+    assert!(tunes.iter().all(|t| t.diagnostics.is_empty()));
+
+    let fields = |tune: &StreamedTune| -> Vec<(HeaderFieldKind, String)> {
+        let fields = &tune.tune.header.fields;
+        fields.iter().map(|f| (f.kind, f.value.clone())).collect()
+    };
+    assert_eq!(
+        fields(&tunes[0]),
+        [
+            (HeaderFieldKind::ReferenceNumber, "1".to_string()),
+            (HeaderFieldKind::Meter, "6/8".to_string()),
+            (HeaderFieldKind::UnitNoteLength, "1/8".to_string()),
+            (HeaderFieldKind::Other('R'), "jig".to_string()),
+            (HeaderFieldKind::Title, "One".to_string()),
+            (HeaderFieldKind::Key, "G".to_string()),
+        ]
+    );
+    // A field the tune sets itself is not inherited
+    let meters: Vec<_> = fields(&tunes[1])
+        .into_iter()
+        .filter(|(kind, _)| *kind == HeaderFieldKind::Meter)
+        .collect();
+    assert_eq!(meters, [(HeaderFieldKind::Meter, "9/8".to_string())]);
+    // Inherited fields have empty ranges at the start of the tune
+    assert_eq!(tunes[0].tune.header.fields[1].range, TextRange::default());
+}
+
+#[test]
+fn file_header_alone_is_a_tune() {
+    let tunes: Vec<_> = parse_collection("M:6/8\nL:1/8\n").collect();
+
+    assert_eq!(tunes.len(), 1);
+    assert!(tunes[0]
+        .diagnostics
+        .iter()
+        .any(|d| d.code == DiagnosticCode::MissingReferenceNumber));
+}
+
+#[test]
+fn leading_tune_without_reference_number_is_not_a_file_header() {
+    let source = "T:Scale\nK:C\nCDEF|\n\nX:2\nT:Two\nK:C\nD|\n";
+    let tunes: Vec<_> = parse_collection(source).collect();
+
+    assert_eq!(titles(&tunes), ["Scale", "Two"]);
+    assert!(tunes[1]
+        .tune
+        .header
+        .fields
+        .iter()
+        .all(|f| !f.range.is_empty()));
+}
+
+#[test]
+fn empty_input_yields_empty_tune() {
+    for source in ["", "\n\n", "% nothing here\n"] {
+        let tunes: Vec<_> = parse_collection(source).collect();
+        assert_eq!(tunes.len(), 1, "for {:?}", source);
+        assert!(tunes[0]
+            .diagnostics
+            .iter()
+            .any(|d| d.code == DiagnosticCode::EmptyTune));
+    }
+}
+
+// ============================================
+// Positions
+// ============================================
+
+#[test]
+fn ranges_are_relative_to_tune() {
+    let source = "X:1\nT:One\nK:C\nC|\n\nX:2\nT:Two\nK:C\n[CEG\n";
+    let tunes: Vec<_> = parse_collection(source).collect();
+
+    let second = &tunes[1];
+    assert_eq!(second.offset, 18);
+    assert_eq!(second.line, 5);
+
+    let diag = &second.diagnostics[0];
+    assert_eq!(diag.code, DiagnosticCode::UnclosedChord);
+    let start = second.offset + u32::from(diag.range.start()) as usize;
+    assert!(source[start..].starts_with("[CEG"));
+}
+
+#[test]
+fn crlf_line_endings() {
+    let source = "X:1\r\nT:One\r\nK:C\r\nC|\r\n\r\nX:2\r\nT:Two\r\nK:C\r\nD|\r\n";
+    let tunes: Vec<_> = parse_collection(source).collect();
+
+    assert_eq!(titles(&tunes), ["One", "Two"]);
+    assert_eq!(tunes[1].offset, source.find("X:2").unwrap());
+}
+
+// ============================================
+// Reading
+// ============================================
+
+#[test]
+fn reads_from_buffered_reader() {
+    let source = "X:1\nT:One\nK:C\nC|\n\nX:2\nT:Two\nK:C\nD|\n";
+    // A tiny buffer forces lines to be read in several pieces
+    let reader = BufReader::with_capacity(3, source.as_bytes());
+    let tunes: Vec<_> = TuneStream::new(reader).collect::<io::Result<_>>().unwrap();
+
+    assert_eq!(titles(&tunes), ["One", "Two"]);
+}
+
+struct FailingReader;
+
+impl Read for FailingReader {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::other("disk on fire"))
+    }
+}
+
+#[test]
+fn read_error_ends_stream() {
+    let mut stream = TuneStream::new(BufReader::new(FailingReader));
+
+    assert!(stream.next().unwrap().is_err());
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn invalid_utf8_is_an_error() {
+    let bytes: &[u8] = b"X:1\nT:\xff\nK:C\n";
+    let result: io::Result<Vec<_>> = TuneStream::new(bytes).collect();
+
+    assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn reader_is_consumed_lazily() {
+    let source = "X:1\nK:C\nC|\n\nX:2\nK:C\nD|\n";
+    let mut reader = source.as_bytes();
+    let mut stream = TuneStream::new(&mut reader);
+
+    stream.next().unwrap().unwrap();
+    drop(stream);
+    // Only the first tune and its blank line were read
+    assert_eq!(reader.fill_buf().unwrap(), b"X:2\nK:C\nD|\n");
+}
@@ -55,10 +55,14 @@ pub enum SyntaxKind {
     REPEAT_START,
     /// Repeat end (:|)
     REPEAT_END,
+    /// Repeat end and start (:: or :|:)
+    REPEAT_BOTH,
     /// Thin-thick bar (|])
     THIN_THICK_BAR,
     /// Thick-thin bar ([|)
     THICK_THIN_BAR,
+    /// Start of an ending ([1, |2)
+    ENDING,
 
     // --- Delimiters ---
     /// Left bracket ([)
@@ -135,6 +139,8 @@ pub enum SyntaxKind {
     BROKEN_RHYTHM_NODE,
     /// Tie element
     TIE_NODE,
+    /// Start of an ending ([1, [2)
+    ENDING_NODE,
     /// Inline field ([M:3/4])
     INLINE_FIELD,
 
@@ -180,6 +186,7 @@ impl SyntaxKind {
                 | Self::DOUBLE_BAR
                 | Self::REPEAT_START
                 | Self::REPEAT_END
+                | Self::REPEAT_BOTH
                 | Self::THIN_THICK_BAR
                 | Self::THICK_THIN_BAR
         )
@@ -1,3 +1,5 @@
+use std::ops::Add;
+
 use serde::{Deserialize, Serialize};
 
 use crate::TextSize;
@@ -93,3 +95,15 @@ impl TextRange {
         }
     }
 }
+
+/// Shifts a range by an offset.
+impl Add<TextSize> for TextRange {
+    type Output = Self;
+
+    fn add(self, offset: TextSize) -> Self::Output {
+        Self {
+            start: self.start + offset,
+            end: self.end + offset,
+        }
+    }
+}
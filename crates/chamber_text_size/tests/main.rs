@@ -60,6 +60,13 @@ fn cover_offset() {
     assert_eq!(range(1..3).cover_offset(size(4)), range(1..4));
 }
 
+#[test]
+fn shift() {
+    assert_eq!(range(1..3) + size(0), range(1..3));
+    assert_eq!(range(1..3) + size(5), range(6..8));
+    assert_eq!(range(0..0) + size(2), range(2..2));
+}
+
 #[test]
 #[rustfmt::skip]
 fn contains_point() {
@@ -3,6 +3,7 @@
 //! This crate provides JavaScript/TypeScript bindings for the Chamber
 //! parser, analyzer, and formatter via WebAssembly.
 
+use serde::Serialize;
 use wasm_bindgen::prelude::*;
 
 /// Parse ABC notation source code.
@@ -27,13 +28,179 @@ pub fn analyze(tune_js: JsValue) -> JsValue {
     serde_wasm_bindgen::to_value(&result).unwrap_or(JsValue::NULL)
 }
 
-/// Format ABC notation source code with custom configuration.
+/// Analyze a tune with per-rule configuration.
+///
+/// `config_js` is an object like
+/// `{ rules: { unusualOctave: "off" }, options: { barLength: { mode: "strict" } } }`.
+/// House rules are declared in `options.customRules` (see the README).
+/// Returns null if the tune or configuration is invalid.
 #[wasm_bindgen]
-pub fn format(source: &str, config_js: JsValue) -> String {
-    let config: chamber_formatter::FormatterConfig = match serde_wasm_bindgen::from_value(config_js) {
+pub fn analyze_with_config(tune_js: JsValue, config_js: JsValue) -> JsValue {
+    let tune: chamber_ast::Tune = match serde_wasm_bindgen::from_value(tune_js) {
+        Ok(t) => t,
+        Err(_) => return JsValue::NULL,
+    };
+    let config: chamber_analyzer::AnalyzerConfig = match serde_wasm_bindgen::from_value(config_js) {
         Ok(c) => c,
-        Err(_) => chamber_formatter::FormatterConfig::default(),
+        Err(_) => return JsValue::NULL,
+    };
+    let analyzer = match chamber_analyzer::Analyzer::new().with_config(config) {
+        Ok(a) => a,
+        Err(_) => return JsValue::NULL,
     };
+    let result = analyzer.analyze(&tune);
+    serde_wasm_bindgen::to_value(&result).unwrap_or(JsValue::NULL)
+}
+
+/// Run the collection rules (duplicate `X:` numbers, titles and melodies,
+/// inconsistent `%%` directives) over the tunes of a multi-tune source.
+///
+/// `config_js` is a configuration like for [`analyze_with_config`], or
+/// `undefined` for the defaults. Diagnostic ranges are offsets in `source`.
+/// Returns null if the configuration is invalid.
+#[wasm_bindgen]
+pub fn analyze_collection(source: &str, config_js: JsValue) -> JsValue {
+    let config: chamber_analyzer::AnalyzerConfig = if config_js.is_undefined() {
+        chamber_analyzer::AnalyzerConfig::default()
+    } else {
+        match serde_wasm_bindgen::from_value(config_js) {
+            Ok(c) => c,
+            Err(_) => return JsValue::NULL,
+        }
+    };
+    let analyzer = match chamber_analyzer::Analyzer::new().with_config(config) {
+        Ok(a) => a,
+        Err(_) => return JsValue::NULL,
+    };
+    let mut collection = analyzer.collection();
+    for tune in chamber_parser::TuneStream::new(source.as_bytes()).flatten() {
+        let offset = chamber_text_size::TextSize::new(tune.offset as u32);
+        collection.add_tune(&tune.source, &tune.tune, offset);
+    }
+    serde_wasm_bindgen::to_value(&collection.finish()).unwrap_or(JsValue::NULL)
+}
+
+/// Checks whole documents, re-analyzing only the tunes that changed since
+/// the previous check (for editors that check on every keystroke or save).
+///
+/// ```js
+/// const checker = new Checker({ rules: { barLength: "error" } });
+/// const { tunes, collection } = checker.check(source);
+/// ```
+#[wasm_bindgen]
+pub struct Checker {
+    analyzer: chamber_analyzer::Analyzer,
+    cache: chamber_analyzer::AnalysisCache,
+}
+
+/// One tune in the output of [`Checker::check`].
+#[derive(Serialize)]
+struct CheckedTune {
+    /// Byte offset of the tune in the document.
+    offset: usize,
+    /// 0-based line of the tune in the document.
+    line: u32,
+    /// Parser and rule diagnostics, with ranges relative to the tune.
+    diagnostics: Vec<chamber_diagnostics::Diagnostic>,
+}
+
+#[derive(Serialize)]
+struct CheckResult {
+    tunes: Vec<CheckedTune>,
+    /// Collection rule diagnostics, with ranges in the whole document.
+    collection: Vec<chamber_diagnostics::Diagnostic>,
+}
+
+#[wasm_bindgen]
+impl Checker {
+    /// Creates a checker with a configuration like for
+    /// [`analyze_with_config`], or `undefined` for the defaults.
+    #[wasm_bindgen(constructor)]
+    pub fn new(config_js: JsValue) -> Result<Checker, JsValue> {
+        let config: chamber_analyzer::AnalyzerConfig = if config_js.is_undefined() {
+            chamber_analyzer::AnalyzerConfig::default()
+        } else {
+            serde_wasm_bindgen::from_value(config_js)?
+        };
+        let analyzer = chamber_analyzer::Analyzer::new()
+            .with_config(config)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let cache = chamber_analyzer::AnalysisCache::new(&analyzer);
+        Ok(Checker { analyzer, cache })
+    }
+
+    /// Checks every tune of `source`, returning
+    /// `{ tunes: [{ offset, line, diagnostics }], collection }`.
+    ///
+    /// Results of tunes that are no longer in the document are forgotten.
+    pub fn check(&self, source: &str) -> JsValue {
+        let mut tunes = Vec::new();
+        let mut collection = self.analyzer.collection();
+        for mut tune in chamber_parser::TuneStream::new(source.as_bytes()).flatten() {
+            let parsed = std::mem::take(&mut tune.diagnostics);
+            let diagnostics =
+                self.analyzer
+                    .analyze_cached(&self.cache, &tune.source, &tune.tune, parsed);
+            let offset = chamber_text_size::TextSize::new(tune.offset as u32);
+            collection.add_tune(&tune.source, &tune.tune, offset);
+            tunes.push(CheckedTune {
+                offset: tune.offset,
+                line: tune.line,
+                diagnostics,
+            });
+        }
+        self.cache.prune();
+
+        let result = CheckResult {
+            tunes,
+            collection: collection.finish().diagnostics,
+        };
+        serde_wasm_bindgen::to_value(&result).unwrap_or(JsValue::NULL)
+    }
+}
+
+/// A rule in the output of [`list_rules`].
+#[derive(Serialize)]
+struct RuleListing {
+    #[serde(flatten)]
+    info: chamber_analyzer::RuleInfo,
+    options: serde_json::Value,
+}
+
+/// List the available lint rules.
+///
+/// Returns an array of `{ name, code, severity, category, docs, options }`,
+/// where `options` are the rule's default options.
+#[wasm_bindgen]
+pub fn list_rules() -> JsValue {
+    let rules: Vec<_> = chamber_analyzer::RuleRegistry::builtin()
+        .iter()
+        .map(|rule| RuleListing {
+            info: rule.info,
+            options: rule.default_options(),
+        })
+        .collect();
+    // Plain objects rather than `Map`s for the flattened fields and options
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    rules.serialize(&serializer).unwrap_or(JsValue::NULL)
+}
+
+/// Explain a diagnostic code (`"W003"`) or the code of a rule (`"barLength"`).
+///
+/// Returns Markdown with the rationale, a bad and a corrected example, and
+/// related codes, or undefined if there is no such code or rule.
+#[wasm_bindgen]
+pub fn explain(target: &str) -> Option<String> {
+    let analyzer = chamber_analyzer::Analyzer::new();
+    let code = analyzer.registry().resolve_code(target)?;
+    Some(analyzer.explain(code))
+}
+
+/// Format ABC notation source code with custom configuration.
+#[wasm_bindgen]
+pub fn format(source: &str, config_js: JsValue) -> String {
+    let config: chamber_formatter::FormatterConfig =
+        serde_wasm_bindgen::from_value(config_js).unwrap_or_default();
     chamber_formatter::format(source, &config)
 }
 